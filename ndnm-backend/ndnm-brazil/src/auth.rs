@@ -0,0 +1,757 @@
+//! Authentication and role-based authorization
+//!
+//! Auth is configured in the `auth` section of Brazil's config file
+//! (`BRAZIL_CONFIG`, default `config.yaml`). Without that section, or with
+//! `enabled: false`, every caller is treated as an admin.
+//!
+//! Two kinds of credentials are accepted:
+//!
+//! - Local users with Argon2 password hashes (`ndnm-brazil hash-password`),
+//!   who exchange their password for a JWT with `POST /auth/login`
+//! - Static API tokens, configured as the SHA-256 digest of the token and
+//!   sent as-is
+//!
+//! Both are sent as `Authorization: Bearer <token>`. Browsers cannot set
+//! headers on a WebSocket upgrade, so `/ws` also accepts
+//! `?access_token=<token>`.
+//!
+//! Roles are ordered, each one including the ones before it:
+//!
+//! - `viewer` - read the registry and workspaces, join workspace rooms
+//! - `editor` - save, rename, delete and restore workspaces, edit in rooms,
+//!   upload blobs
+//! - `operator` - run and cancel graphs
+//! - `admin` - maintenance, such as clearing the logs
+//!
+//! ```yaml
+//! auth:
+//!   enabled: true
+//!   jwt_secret: "at least 32 bytes, or set BRAZIL_JWT_SECRET"
+//!   token_ttl_secs: 3600
+//!   users:
+//!     - username: alice
+//!       password_hash: "$argon2id$v=19$m=19456,t=2,p=1$..."
+//!       role: editor
+//!   api_tokens:
+//!     - name: ci
+//!       sha256: "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+//!       role: operator
+//! ```
+
+use crate::config::BrazilConfig;
+use crate::AppState;
+use argon2::password_hash::{rand_core::OsRng, SaltString};
+use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use axum::{
+    extract::{MatchedPath, Query, Request, State},
+    http::{header, Method},
+    middleware::Next,
+    response::Response,
+    Extension, Json,
+};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use ndnm_libs::AppError;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+/// Issuer of the JWTs, checked when they come back
+const TOKEN_ISSUER: &str = "ndnm-brazil";
+
+/// Shortest accepted JWT signing secret, in bytes
+const MIN_SECRET_LEN: usize = 32;
+
+/// Default lifetime of issued JWTs
+const DEFAULT_TOKEN_TTL_SECS: u64 = 3600;
+
+/// What a caller is allowed to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Read the registry and workspaces
+    Viewer,
+
+    /// Save and manage workspaces
+    Editor,
+
+    /// Run and cancel graphs
+    Operator,
+
+    /// Maintenance
+    Admin,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Role::Viewer => "viewer",
+            Role::Editor => "editor",
+            Role::Operator => "operator",
+            Role::Admin => "admin",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// An authenticated caller
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Principal {
+    /// Username, or `token:<name>` for API tokens
+    pub subject: String,
+
+    /// Role of the caller
+    pub role: Role,
+}
+
+impl Principal {
+    /// Caller used when authentication is disabled
+    pub fn anonymous() -> Self {
+        Self {
+            subject: "anonymous".to_string(),
+            role: Role::Admin,
+        }
+    }
+
+    /// Check that the caller has at least the given role
+    ///
+    /// # Arguments
+    ///
+    /// * `role` - Required role
+    /// * `action` - What the caller is trying to do, for the error message
+    pub fn require(&self, role: Role, action: &str) -> Result<(), AppError> {
+        if self.role >= role {
+            Ok(())
+        } else {
+            Err(AppError::Forbidden(format!(
+                "{} requires the {} role (you are {})",
+                action, role, self.role
+            )))
+        }
+    }
+}
+
+/// The `auth` section of the config file
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct AuthConfig {
+    /// Whether requests must be authenticated
+    pub enabled: bool,
+
+    /// Secret used to sign JWTs (overridden by `BRAZIL_JWT_SECRET`)
+    pub jwt_secret: Option<String>,
+
+    /// Lifetime of issued JWTs, in seconds
+    pub token_ttl_secs: u64,
+
+    /// Local user accounts
+    pub users: Vec<UserConfig>,
+
+    /// Static API tokens
+    pub api_tokens: Vec<ApiTokenConfig>,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            jwt_secret: None,
+            token_ttl_secs: DEFAULT_TOKEN_TTL_SECS,
+            users: Vec::new(),
+            api_tokens: Vec::new(),
+        }
+    }
+}
+
+/// A local user account
+#[derive(Debug, Clone, Deserialize)]
+pub struct UserConfig {
+    /// Login name
+    pub username: String,
+
+    /// Argon2 hash in PHC format
+    pub password_hash: String,
+
+    /// Role of the user
+    pub role: Role,
+}
+
+/// A static API token
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApiTokenConfig {
+    /// Name of the token, shown as the caller's subject
+    pub name: String,
+
+    /// Hex SHA-256 digest of the token
+    pub sha256: String,
+
+    /// Role granted by the token
+    pub role: Role,
+}
+
+/// Claims of the JWTs issued by `/auth/login`
+#[derive(Debug, Serialize, Deserialize)]
+struct Claims {
+    sub: String,
+    role: Role,
+    iss: String,
+    iat: u64,
+    exp: u64,
+}
+
+/// Request body for `POST /auth/login`
+#[derive(Debug, Deserialize)]
+pub struct LoginRequest {
+    pub username: String,
+    pub password: String,
+}
+
+/// Response body for `POST /auth/login`
+#[derive(Debug, Serialize)]
+pub struct LoginResponse {
+    /// JWT to send as a bearer token
+    pub access_token: String,
+
+    /// Always `Bearer`
+    pub token_type: &'static str,
+
+    /// Lifetime of the token, in seconds
+    pub expires_in: u64,
+
+    /// Role of the user
+    pub role: Role,
+}
+
+/// Credentials and keys of an enabled configuration
+struct AuthInner {
+    /// Users by username
+    users: HashMap<String, UserConfig>,
+
+    /// API tokens by digest
+    api_tokens: HashMap<String, ApiTokenConfig>,
+
+    /// Hash checked when the username is unknown, so that logins take as
+    /// long whether or not the user exists
+    dummy_hash: String,
+
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    validation: Validation,
+    token_ttl: Duration,
+}
+
+/// Authentication settings, shared by all handlers
+///
+/// The default is disabled authentication.
+#[derive(Clone, Default)]
+pub struct Auth {
+    inner: Option<Arc<AuthInner>>,
+}
+
+impl Auth {
+    /// Load the `auth` section of Brazil's config file (see
+    /// [`crate::config`]); `BRAZIL_JWT_SECRET` overrides its secret
+    pub fn from_env() -> Result<Self, AppError> {
+        let mut config = BrazilConfig::from_env()?.auth;
+
+        if let Ok(secret) = std::env::var("BRAZIL_JWT_SECRET") {
+            config.jwt_secret = Some(secret);
+        }
+
+        Self::from_config(config)
+    }
+
+    /// Build the settings from a parsed `auth` section
+    ///
+    /// # Returns
+    ///
+    /// * `Err(AppError::ConfigError)` - Auth is enabled but the secret,
+    ///   a password hash or a token digest is invalid
+    pub fn from_config(config: AuthConfig) -> Result<Self, AppError> {
+        if !config.enabled {
+            warn!("Authentication is disabled; every caller is treated as admin");
+            return Ok(Self::default());
+        }
+
+        let secret = config.jwt_secret.unwrap_or_default();
+        if secret.len() < MIN_SECRET_LEN {
+            return Err(AppError::ConfigError(format!(
+                "auth.jwt_secret must be at least {} bytes",
+                MIN_SECRET_LEN
+            )));
+        }
+
+        let mut users = HashMap::new();
+        for user in config.users {
+            PasswordHash::new(&user.password_hash).map_err(|e| {
+                AppError::ConfigError(format!(
+                    "Invalid password hash for user '{}': {}",
+                    user.username, e
+                ))
+            })?;
+            users.insert(user.username.clone(), user);
+        }
+
+        let mut api_tokens = HashMap::new();
+        for token in config.api_tokens {
+            let digest = token.sha256.to_ascii_lowercase();
+            if digest.len() != 64 || !digest.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(AppError::ConfigError(format!(
+                    "API token '{}' must be a hex SHA-256 digest",
+                    token.name
+                )));
+            }
+            api_tokens.insert(digest, token);
+        }
+
+        if users.is_empty() && api_tokens.is_empty() {
+            warn!("Authentication is enabled but no users or API tokens are configured");
+        }
+        info!(
+            "Authentication enabled with {} user(s) and {} API token(s)",
+            users.len(),
+            api_tokens.len()
+        );
+
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.set_issuer(&[TOKEN_ISSUER]);
+
+        Ok(Self {
+            inner: Some(Arc::new(AuthInner {
+                users,
+                api_tokens,
+                dummy_hash: hash_password(&uuid::Uuid::new_v4().to_string())?,
+                encoding_key: EncodingKey::from_secret(secret.as_bytes()),
+                decoding_key: DecodingKey::from_secret(secret.as_bytes()),
+                validation,
+                token_ttl: Duration::from_secs(config.token_ttl_secs),
+            })),
+        })
+    }
+
+    /// Whether requests must be authenticated
+    pub fn is_enabled(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    /// Check a user's password and issue a JWT
+    pub async fn login(&self, username: &str, password: &str) -> Result<LoginResponse, AppError> {
+        let Some(inner) = &self.inner else {
+            return Err(AppError::BadRequest(
+                "Authentication is disabled".to_string(),
+            ));
+        };
+
+        let invalid = || AppError::Unauthorized("Invalid username or password".to_string());
+
+        let user = inner.users.get(username);
+        let hash = user.map_or(&inner.dummy_hash, |user| &user.password_hash).clone();
+        let password = password.to_string();
+
+        // Argon2 takes tens of milliseconds of CPU, too long for a runtime worker
+        let verified = tokio::task::spawn_blocking(move || {
+            PasswordHash::new(&hash).is_ok_and(|hash| {
+                Argon2::default()
+                    .verify_password(password.as_bytes(), &hash)
+                    .is_ok()
+            })
+        })
+        .await
+        .map_err(|e| AppError::Internal(format!("Password check failed: {}", e)))?;
+        let user = user.filter(|_| verified).ok_or_else(invalid)?;
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let claims = Claims {
+            sub: user.username.clone(),
+            role: user.role,
+            iss: TOKEN_ISSUER.to_string(),
+            iat: now,
+            exp: now + inner.token_ttl.as_secs(),
+        };
+
+        let access_token =
+            jsonwebtoken::encode(&Header::default(), &claims, &inner.encoding_key)
+                .map_err(|e| AppError::Internal(format!("Failed to issue token: {}", e)))?;
+
+        info!("User '{}' logged in as {}", user.username, user.role);
+
+        Ok(LoginResponse {
+            access_token,
+            token_type: "Bearer",
+            expires_in: inner.token_ttl.as_secs(),
+            role: user.role,
+        })
+    }
+
+    /// Identify the caller of a request from its bearer token
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Principal)` - The caller; an anonymous admin when auth is disabled
+    /// * `Err(AppError::Unauthorized)` - Missing, invalid or expired token
+    pub fn authenticate(&self, token: Option<&str>) -> Result<Principal, AppError> {
+        let Some(inner) = &self.inner else {
+            return Ok(Principal::anonymous());
+        };
+
+        let token =
+            token.ok_or_else(|| AppError::Unauthorized("Missing bearer token".to_string()))?;
+
+        if let Some(api_token) = inner.api_tokens.get(&sha256_hex(token)) {
+            return Ok(Principal {
+                subject: format!("token:{}", api_token.name),
+                role: api_token.role,
+            });
+        }
+
+        let claims = jsonwebtoken::decode::<Claims>(token, &inner.decoding_key, &inner.validation)
+            .map_err(|_| AppError::Unauthorized("Invalid or expired token".to_string()))?
+            .claims;
+
+        Ok(Principal {
+            subject: claims.sub,
+            role: claims.role,
+        })
+    }
+}
+
+/// Hash a password for the `password_hash` of a user
+pub fn hash_password(password: &str) -> Result<String, AppError> {
+    let salt = SaltString::generate(&mut OsRng);
+
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| AppError::Internal(format!("Failed to hash password: {}", e)))
+}
+
+/// Hex SHA-256 digest of an API token
+fn sha256_hex(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Role required for a route, or `None` for public routes
+///
+/// Reading is open to viewers; anything not listed requires admin.
+///
+/// # Arguments
+///
+/// * `method` - Request method
+/// * `path` - Route pattern (e.g. `/nexus/:name/run`)
+pub fn required_role(method: &Method, path: &str) -> Option<Role> {
+    if path == "/health" || (method == Method::POST && path == "/auth/login") {
+        return None;
+    }
+
+    let role = if method == Method::GET || method == Method::HEAD {
+        Role::Viewer
+    } else if method == Method::POST {
+        match path {
+            "/graphs/translate" => Role::Viewer,
+            "/graphs/run" | "/executions/:execution_id/cancel" | "/nexus/:name/run" => {
+                Role::Operator
+            }
+            "/blobs"
+            | "/nexus/save"
+            | "/nexus/migrate"
+            | "/nexus/import"
+            | "/nexus/:name/rename"
+            | "/nexus/:name/duplicate"
+            | "/nexus/:name/instantiate"
+            | "/nexus/:name/revisions/:revision/restore"
+            | "/trash/:trash_id/restore" => Role::Editor,
+            _ => Role::Admin,
+        }
+    } else if method == Method::PUT && path == "/blobs" {
+        Role::Editor
+    } else if method == Method::DELETE {
+        match path {
+            "/nexus/:name" | "/trash" | "/trash/:trash_id" => Role::Editor,
+            _ => Role::Admin,
+        }
+    } else {
+        Role::Admin
+    };
+
+    Some(role)
+}
+
+/// Query parameter carrying the token on WebSocket upgrades
+#[derive(Debug, Deserialize)]
+struct AccessToken {
+    access_token: Option<String>,
+}
+
+/// Middleware authenticating requests and checking their route's role
+///
+/// The caller is stored as a [`Principal`] request extension.
+pub async fn authorize(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let path = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| request.uri().path().to_string());
+
+    let Some(role) = required_role(request.method(), &path) else {
+        return Ok(next.run(request).await);
+    };
+
+    let header_token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::to_string);
+
+    // Only the WebSocket upgrade takes the token from the URL, which ends
+    // up in logs
+    let token = header_token.or_else(|| {
+        (path == "/ws")
+            .then(|| Query::<AccessToken>::try_from_uri(request.uri()).ok())
+            .flatten()
+            .and_then(|query| query.0.access_token)
+    });
+
+    let principal = state.auth.authenticate(token.as_deref())?;
+    principal.require(role, &format!("{} {}", request.method(), path))?;
+
+    request.extensions_mut().insert(principal);
+    Ok(next.run(request).await)
+}
+
+/// Handler for POST /auth/login - Exchange a username and password for a JWT
+pub async fn login(
+    State(state): State<AppState>,
+    Json(request): Json<LoginRequest>,
+) -> Result<Json<LoginResponse>, AppError> {
+    state
+        .auth
+        .login(&request.username, &request.password)
+        .await
+        .map(Json)
+}
+
+/// Handler for GET /auth/me - The authenticated caller
+pub async fn me(Extension(principal): Extension<Principal>) -> Json<Principal> {
+    Json(principal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{offline_state, serve_brazil};
+
+    const SECRET: &str = "0123456789abcdef0123456789abcdef";
+
+    fn config() -> AuthConfig {
+        AuthConfig {
+            enabled: true,
+            jwt_secret: Some(SECRET.to_string()),
+            users: vec![UserConfig {
+                username: "alice".to_string(),
+                password_hash: hash_password("wonderland").unwrap(),
+                role: Role::Editor,
+            }],
+            api_tokens: vec![ApiTokenConfig {
+                name: "ci".to_string(),
+                sha256: sha256_hex("ci-token"),
+                role: Role::Operator,
+            }],
+            ..AuthConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_login_and_authenticate() {
+        let auth = Auth::from_config(config()).unwrap();
+
+        let login = auth.login("alice", "wonderland").await.unwrap();
+        assert_eq!(login.role, Role::Editor);
+        assert_eq!(
+            auth.authenticate(Some(&login.access_token)).unwrap(),
+            Principal {
+                subject: "alice".to_string(),
+                role: Role::Editor
+            }
+        );
+
+        assert!(matches!(
+            auth.login("alice", "looking-glass").await,
+            Err(AppError::Unauthorized(_))
+        ));
+        assert!(matches!(
+            auth.login("bob", "wonderland").await,
+            Err(AppError::Unauthorized(_))
+        ));
+
+        let ci = auth.authenticate(Some("ci-token")).unwrap();
+        assert_eq!(ci.subject, "token:ci");
+        assert_eq!(ci.role, Role::Operator);
+
+        assert!(matches!(
+            auth.authenticate(None),
+            Err(AppError::Unauthorized(_))
+        ));
+        assert!(matches!(
+            auth.authenticate(Some("forged")),
+            Err(AppError::Unauthorized(_))
+        ));
+
+        // Tokens signed with another secret are rejected
+        let other = Auth::from_config(AuthConfig {
+            jwt_secret: Some("another secret that is long enough".to_string()),
+            ..config()
+        })
+        .unwrap();
+        let foreign = other.login("alice", "wonderland").await.unwrap();
+        assert!(auth.authenticate(Some(&foreign.access_token)).is_err());
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let short_secret = AuthConfig {
+            jwt_secret: Some("short".to_string()),
+            ..config()
+        };
+        assert!(matches!(
+            Auth::from_config(short_secret),
+            Err(AppError::ConfigError(_))
+        ));
+
+        let mut bad_hash = config();
+        bad_hash.users[0].password_hash = "wonderland".to_string();
+        assert!(Auth::from_config(bad_hash).is_err());
+
+        // Disabled auth ignores everything else and lets everyone in
+        let disabled = Auth::from_config(AuthConfig::default()).unwrap();
+        assert!(!disabled.is_enabled());
+        assert_eq!(disabled.authenticate(None).unwrap(), Principal::anonymous());
+    }
+
+    #[test]
+    fn test_required_role() {
+        assert_eq!(required_role(&Method::GET, "/health"), None);
+        assert_eq!(required_role(&Method::POST, "/auth/login"), None);
+        assert_eq!(required_role(&Method::GET, "/ws"), Some(Role::Viewer));
+        assert_eq!(
+            required_role(&Method::GET, "/nexus/list"),
+            Some(Role::Viewer)
+        );
+        assert_eq!(
+            required_role(&Method::POST, "/nexus/save"),
+            Some(Role::Editor)
+        );
+        assert_eq!(
+            required_role(&Method::DELETE, "/nexus/:name"),
+            Some(Role::Editor)
+        );
+        assert_eq!(
+            required_role(&Method::POST, "/graphs/run"),
+            Some(Role::Operator)
+        );
+        assert_eq!(
+            required_role(&Method::POST, "/nexus/:name/revisions/:revision/restore"),
+            Some(Role::Editor)
+        );
+        assert_eq!(required_role(&Method::DELETE, "/logs"), Some(Role::Admin));
+        assert_eq!(required_role(&Method::PUT, "/blobs"), Some(Role::Editor));
+        assert_eq!(required_role(&Method::PUT, "/anything"), Some(Role::Admin));
+
+        let viewer = Principal {
+            subject: "v".to_string(),
+            role: Role::Viewer,
+        };
+        assert!(viewer.require(Role::Viewer, "Reading").is_ok());
+        assert!(matches!(
+            viewer.require(Role::Operator, "Running graphs"),
+            Err(AppError::Forbidden(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_routes_require_roles() {
+        let auth = Auth::from_config(config()).unwrap();
+        let editor = auth.login("alice", "wonderland").await.unwrap().access_token;
+
+        let base = serve_brazil(offline_state().with_auth(auth)).await;
+
+        let client = reqwest::Client::new();
+        let url = |path: &str| format!("{}{}", base, path);
+
+        let health = client.get(url("/health")).send().await.unwrap();
+        assert_eq!(health.status(), 200);
+
+        let anonymous = client.get(url("/auth/me")).send().await.unwrap();
+        assert_eq!(anonymous.status(), 401);
+
+        let me: serde_json::Value = client
+            .get(url("/auth/me"))
+            .bearer_auth(&editor)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(me["subject"], "alice");
+        assert_eq!(me["role"], "editor");
+
+        let run = client
+            .post(url("/graphs/run"))
+            .bearer_auth(&editor)
+            .json(&serde_json::json!({ "nodes": [], "connections": [] }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(run.status(), 403);
+        let body: serde_json::Value = run.json().await.unwrap();
+        assert!(body["error"].as_str().unwrap().contains("operator"));
+
+        // The operator token gets past auth; Hermes is down
+        let run = client
+            .post(url("/executions/e-1/cancel"))
+            .bearer_auth("ci-token")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(run.status(), 502);
+
+        // WebSocket upgrades take the token from the query string
+        let ws = url("/ws").replacen("http", "ws", 1);
+        assert!(tokio_tungstenite::connect_async(&ws).await.is_err());
+        let ws = format!("{}?access_token={}", ws, editor);
+        assert!(tokio_tungstenite::connect_async(&ws).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_cors_allows_configured_origins_only() {
+        let frontend = axum::http::HeaderValue::from_static("http://localhost:5173");
+        let state = offline_state().with_cors_origins(vec![frontend.clone()]);
+        let base = serve_brazil(state).await;
+
+        let client = reqwest::Client::new();
+        let origins = [(frontend.to_str().unwrap(), true), ("https://evil.example", false)];
+        for (origin, allowed) in origins {
+            let preflight = client
+                .request(reqwest::Method::OPTIONS, format!("{}/auth/me", base))
+                .header(header::ORIGIN, origin)
+                .header(header::ACCESS_CONTROL_REQUEST_METHOD, "GET")
+                .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "authorization")
+                .send()
+                .await
+                .unwrap();
+            let allow_origin = preflight.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN);
+            assert_eq!(allow_origin, allowed.then_some(&frontend), "{}", origin);
+        }
+    }
+}
@@ -0,0 +1,917 @@
+//! Content-addressed blob store
+//!
+//! Files too large to embed in a graph as JSON strings are uploaded to
+//! Brazil once and referenced by their SHA-256:
+//!
+//! - `PUT /blobs?filename=<name>` - stream the request body into a blob
+//! - `POST /blobs` - store every part of a `multipart/form-data` upload
+//! - `GET /blobs/:sha256` - download a blob, with `Range` support
+//!
+//! Uploads answer with the blob's metadata and a reference,
+//! `{"$blob": "sha256:<hex>"}`, that frontend graphs place in a node's
+//! `inputs` under the name of a `FILE_CONTENT` or `BLOB` input handle.
+//! When Brazil sends the graph to Hermes, it hands each referenced blob to
+//! Hermes' artifact store and replaces the reference with the artifact's
+//! handle, `{"$ref": "sha256:<hex>"}` (`{filename, content: <handle>}` when
+//! the upload was named). Nodes fetch the content themselves (see
+//! `ndnm_libs::artifact`), so large files never travel in `/run` bodies.
+//! Saved workspaces keep the reference; Hermes runs them itself through
+//! `/nexus/:name/run`, which Brazil refuses for workspaces that reference
+//! blobs: they are run by loading them and posting their graph to
+//! `/graphs/run`.
+//!
+//! Node outputs longer than the inline limit are moved into the store when
+//! a run finishes and replaced by a reference, so clients stream them from
+//! `/blobs/:sha256` instead of receiving them in the run response.
+//!
+//! Blobs live under `BLOB_DIR` (default `./blobs`) and are limited to
+//! `BLOB_MAX_BYTES` each (default 512 MiB); outputs are offloaded above
+//! `BLOB_INLINE_MAX_BYTES` (default 1 MiB). Blobs used as inputs are also
+//! bound by Hermes' artifact size limit (`ARTIFACT_MAX_BYTES`).
+
+use crate::hermes::{HermesClient, HermesError};
+use crate::AppState;
+use axum::{
+    body::{Body, Bytes},
+    extract::{Multipart, Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures_util::{Stream, StreamExt};
+use ndnm_libs::range::{content_range, parse_range, RangeNotSatisfiable};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::path::{Path as FsPath, PathBuf};
+use thiserror::Error;
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio_util::io::ReaderStream;
+use tracing::{info, warn};
+
+/// Default directory of the store
+pub const DEFAULT_DIR: &str = "blobs";
+
+/// Default size limit of one blob
+pub const DEFAULT_MAX_SIZE: u64 = 512 * 1024 * 1024;
+
+/// Default size above which node outputs are offloaded to the store
+pub const DEFAULT_INLINE_LIMIT: usize = 1024 * 1024;
+
+/// Key of a blob reference object
+pub const REF_KEY: &str = "$blob";
+
+/// Prefix of the hash in a blob reference
+const REF_PREFIX: &str = "sha256:";
+
+/// Errors of the blob store and its routes
+#[derive(Debug, Error)]
+pub enum BlobError {
+    /// No blob has this hash
+    #[error("Blob '{0}' not found")]
+    NotFound(String),
+
+    /// A hash or reference is malformed
+    #[error("Invalid blob reference: {0}")]
+    InvalidRef(String),
+
+    /// The upload exceeds the size limit
+    #[error("Blob exceeds the limit of {limit} bytes")]
+    TooLarge {
+        /// Size limit in bytes
+        limit: u64,
+    },
+
+    /// The upload could not be read
+    #[error("Failed to read upload: {0}")]
+    Upload(String),
+
+    /// The requested range lies outside the blob
+    #[error("Range not satisfiable for a blob of {size} bytes")]
+    RangeNotSatisfiable {
+        /// Size of the blob
+        size: u64,
+    },
+
+    /// The store could not be read or written
+    #[error("Blob store error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// Hermes did not take the content of a blob
+    #[error(transparent)]
+    Hermes(#[from] HermesError),
+}
+
+impl BlobError {
+    /// Status Brazil answers with for this error
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::InvalidRef(_) | Self::Upload(_) => StatusCode::BAD_REQUEST,
+            Self::TooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::RangeNotSatisfiable { .. } => StatusCode::RANGE_NOT_SATISFIABLE,
+            Self::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Hermes(e) => e.status(),
+        }
+    }
+}
+
+impl From<RangeNotSatisfiable> for BlobError {
+    fn from(e: RangeNotSatisfiable) -> Self {
+        Self::RangeNotSatisfiable { size: e.size }
+    }
+}
+
+impl IntoResponse for BlobError {
+    fn into_response(self) -> Response {
+        let mut response =
+            (self.status(), Json(json!({ "error": self.to_string() }))).into_response();
+        if let Self::RangeNotSatisfiable { size } = self
+            && let Ok(value) = HeaderValue::from_str(&RangeNotSatisfiable { size }.content_range())
+        {
+            response.headers_mut().insert(header::CONTENT_RANGE, value);
+        }
+        response
+    }
+}
+
+/// Metadata of a stored blob
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlobInfo {
+    /// Hex SHA-256 of the content
+    pub sha256: String,
+
+    /// Size in bytes
+    pub size: u64,
+
+    /// Content type given when the blob was first uploaded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+
+    /// File name given when the blob was first uploaded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
+}
+
+impl BlobInfo {
+    /// Reference to this blob, for node inputs and outputs
+    pub fn reference(&self) -> Value {
+        json!({ REF_KEY: format!("{}{}", REF_PREFIX, self.sha256) })
+    }
+}
+
+/// Response to an upload
+#[derive(Debug, Serialize)]
+pub struct UploadedBlob {
+    /// Form field the blob came from, for multipart uploads
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+
+    /// Stored blob
+    #[serde(flatten)]
+    pub info: BlobInfo,
+
+    /// Reference to place in a node's `inputs`
+    #[serde(rename = "ref")]
+    pub reference: Value,
+}
+
+impl From<BlobInfo> for UploadedBlob {
+    fn from(info: BlobInfo) -> Self {
+        Self {
+            field: None,
+            reference: info.reference(),
+            info,
+        }
+    }
+}
+
+/// Hash of a blob reference (`{"$blob": "sha256:<hex>"}`), if the value is
+/// one
+pub fn referenced_hash(value: &Value) -> Option<&str> {
+    let object = value.as_object()?;
+    object.get(REF_KEY)?.as_str()
+}
+
+/// Whether the `inputs` of a Hermes graph's nodes reference blobs
+pub fn references_blobs(graph: &Value) -> bool {
+    graph
+        .get("nodes")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|node| node.get("inputs").and_then(Value::as_object))
+        .flat_map(|inputs| inputs.values())
+        .any(|value| referenced_hash(value).is_some())
+}
+
+/// Validate a hash, with or without the `sha256:` prefix
+fn parse_hash(hash: &str) -> Result<&str, BlobError> {
+    let hex = hash.strip_prefix(REF_PREFIX).unwrap_or(hash);
+    let valid = hex.len() == 64 && hex.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'));
+
+    if valid {
+        Ok(hex)
+    } else {
+        Err(BlobError::InvalidRef(hash.to_string()))
+    }
+}
+
+/// Content-addressed store on the local filesystem
+#[derive(Debug, Clone)]
+pub struct BlobStore {
+    /// Root directory
+    dir: PathBuf,
+    /// Size limit of one blob
+    max_size: u64,
+    /// Size above which node outputs are offloaded
+    inline_limit: usize,
+}
+
+impl Default for BlobStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_DIR)
+    }
+}
+
+impl BlobStore {
+    /// Create a store in the given directory with the default limits
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            max_size: DEFAULT_MAX_SIZE,
+            inline_limit: DEFAULT_INLINE_LIMIT,
+        }
+    }
+
+    /// Create a store from `BLOB_DIR`, `BLOB_MAX_BYTES` and
+    /// `BLOB_INLINE_MAX_BYTES`
+    pub fn from_env() -> Self {
+        let dir = std::env::var("BLOB_DIR").unwrap_or_else(|_| DEFAULT_DIR.to_string());
+        let bytes = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .filter(|n| *n > 0)
+        };
+
+        let mut store = Self::new(dir);
+        if let Some(max_size) = bytes("BLOB_MAX_BYTES") {
+            store = store.with_max_size(max_size);
+        }
+        if let Some(limit) = bytes("BLOB_INLINE_MAX_BYTES") {
+            store = store.with_inline_limit(limit as usize);
+        }
+        store
+    }
+
+    /// Use the given size limit for one blob
+    pub fn with_max_size(mut self, max_size: u64) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Offload node outputs longer than the given number of bytes
+    pub fn with_inline_limit(mut self, inline_limit: usize) -> Self {
+        self.inline_limit = inline_limit;
+        self
+    }
+
+    /// Path of a blob's content; blobs are spread over 256 directories
+    fn content_path(&self, hex: &str) -> PathBuf {
+        self.dir.join(&hex[..2]).join(hex)
+    }
+
+    /// Path of a blob's metadata
+    fn info_path(&self, hex: &str) -> PathBuf {
+        self.dir.join(&hex[..2]).join(format!("{}.json", hex))
+    }
+
+    /// Store a stream of bytes
+    ///
+    /// The content is hashed while it is written to a temporary file, which
+    /// is then moved to its hash. Content that is already stored keeps its
+    /// first content type and file name.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(BlobInfo)` - The stored blob
+    /// * `Err(BlobError::TooLarge)` - The stream exceeds the size limit
+    pub async fn put<S, B, E>(
+        &self,
+        stream: S,
+        content_type: Option<String>,
+        filename: Option<String>,
+    ) -> Result<BlobInfo, BlobError>
+    where
+        S: Stream<Item = Result<B, E>>,
+        B: AsRef<[u8]>,
+        E: fmt::Display,
+    {
+        let tmp_dir = self.dir.join("tmp");
+        fs::create_dir_all(&tmp_dir).await?;
+        let tmp_path = tmp_dir.join(uuid::Uuid::new_v4().to_string());
+
+        let result = self.write_temp(stream, &tmp_path).await;
+        let (hex, size) = match result {
+            Ok(written) => written,
+            Err(e) => {
+                let _ = fs::remove_file(&tmp_path).await;
+                return Err(e);
+            }
+        };
+
+        let path = self.content_path(&hex);
+        if fs::try_exists(&path).await? {
+            fs::remove_file(&tmp_path).await?;
+            return self.info(&hex).await;
+        }
+
+        fs::create_dir_all(path.parent().unwrap_or(&self.dir)).await?;
+        let info = BlobInfo {
+            sha256: hex.clone(),
+            size,
+            content_type,
+            filename,
+        };
+        let info_json = serde_json::to_vec_pretty(&info).map_err(std::io::Error::other)?;
+        fs::write(self.info_path(&hex), info_json).await?;
+        fs::rename(&tmp_path, &path).await?;
+
+        info!("Stored blob {} ({} bytes)", hex, size);
+        Ok(info)
+    }
+
+    /// Write a stream to a temporary file, returning its hash and size
+    async fn write_temp<S, B, E>(
+        &self,
+        stream: S,
+        path: &FsPath,
+    ) -> Result<(String, u64), BlobError>
+    where
+        S: Stream<Item = Result<B, E>>,
+        B: AsRef<[u8]>,
+        E: fmt::Display,
+    {
+        let mut file = fs::File::create(path).await?;
+        let mut hasher = Sha256::new();
+        let mut size = 0u64;
+
+        let mut stream = std::pin::pin!(stream);
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|e| BlobError::Upload(e.to_string()))?;
+            let chunk = chunk.as_ref();
+
+            size += chunk.len() as u64;
+            if size > self.max_size {
+                return Err(BlobError::TooLarge {
+                    limit: self.max_size,
+                });
+            }
+
+            hasher.update(chunk);
+            file.write_all(chunk).await?;
+        }
+
+        file.flush().await?;
+        let hex = hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+
+        Ok((hex, size))
+    }
+
+    /// Metadata of a blob
+    pub async fn info(&self, hash: &str) -> Result<BlobInfo, BlobError> {
+        let hex = parse_hash(hash)?;
+
+        match fs::read(self.info_path(hex)).await {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(|e| {
+                BlobError::Io(std::io::Error::other(format!(
+                    "Corrupt metadata of blob {}: {}",
+                    hex, e
+                )))
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Err(BlobError::NotFound(hex.to_string()))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Open a blob for reading, positioned at the start of `range`
+    async fn open(&self, info: &BlobInfo, range: &Range<u64>) -> Result<fs::File, BlobError> {
+        let mut file = fs::File::open(self.content_path(&info.sha256)).await?;
+        file.seek(std::io::SeekFrom::Start(range.start)).await?;
+        Ok(file)
+    }
+
+    /// Hand the blobs referenced in the `inputs` of a Hermes graph's nodes
+    /// to Hermes' artifact store, replacing each reference with the
+    /// artifact's handle
+    ///
+    /// Each blob is uploaded once per graph. Uploads are signed, which
+    /// takes the whole content, so a blob is read into memory while it is
+    /// uploaded.
+    ///
+    /// # Returns
+    ///
+    /// * `Err(BlobError::NotFound)` - A referenced blob is not stored
+    /// * `Err(BlobError::Hermes)` - Hermes did not take a blob's content
+    pub async fn resolve_inputs(
+        &self,
+        graph: &mut Value,
+        hermes: &HermesClient,
+    ) -> Result<(), BlobError> {
+        let Some(nodes) = graph.get_mut("nodes").and_then(Value::as_array_mut) else {
+            return Ok(());
+        };
+        let mut handles: HashMap<String, Value> = HashMap::new();
+
+        for node in nodes {
+            let Some(inputs) = node.get_mut("inputs").and_then(Value::as_object_mut) else {
+                continue;
+            };
+
+            for value in inputs.values_mut() {
+                let Some(hash) = referenced_hash(value) else {
+                    continue;
+                };
+                let info = self.info(hash).await?;
+
+                let handle = match handles.get(&info.sha256) {
+                    Some(handle) => handle.clone(),
+                    None => {
+                        let content = fs::read(self.content_path(&info.sha256)).await?;
+                        let handle = hermes.put_artifact(content.into()).await?.to_value();
+                        handles.insert(info.sha256.clone(), handle.clone());
+                        handle
+                    }
+                };
+
+                *value = match info.filename {
+                    Some(filename) => json!({ "filename": filename, "content": handle }),
+                    None => handle,
+                };
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Move node outputs longer than the inline limit into the store
+    ///
+    /// Strings, and the `content` of `{filename, content}` objects, are
+    /// replaced by a reference carrying the blob's size. Outputs that cannot
+    /// be stored are left inline.
+    pub async fn offload_outputs(&self, execution: &mut Value) {
+        let Some(results) = execution
+            .get_mut("node_results")
+            .and_then(Value::as_object_mut)
+        else {
+            return;
+        };
+
+        for result in results.values_mut() {
+            let Some(outputs) = result.get_mut("outputs").and_then(Value::as_object_mut) else {
+                continue;
+            };
+
+            for output in outputs.values_mut() {
+                let filename = output
+                    .get("filename")
+                    .and_then(Value::as_str)
+                    .map(str::to_string);
+                let content = match output {
+                    Value::String(_) => output,
+                    Value::Object(object) => match object.get_mut("content") {
+                        Some(content) => content,
+                        None => continue,
+                    },
+                    _ => continue,
+                };
+
+                let Some(text) = content.as_str().filter(|s| s.len() > self.inline_limit) else {
+                    continue;
+                };
+
+                let chunk = futures_util::stream::once(async {
+                    Ok::<_, std::convert::Infallible>(Bytes::copy_from_slice(text.as_bytes()))
+                });
+                let content_type = Some("text/plain; charset=utf-8".to_string());
+
+                match self.put(chunk, content_type, filename).await {
+                    Ok(info) => {
+                        let mut reference = info.reference();
+                        reference["size"] = json!(info.size);
+                        *content = reference;
+                    }
+                    Err(e) => warn!("Failed to offload node output: {}", e),
+                }
+            }
+        }
+    }
+}
+
+/// Query of `PUT /blobs`
+#[derive(Debug, Deserialize)]
+pub struct UploadQuery {
+    /// File name of the upload
+    filename: Option<String>,
+}
+
+/// Content type of a request, if it has one
+fn content_type(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+}
+
+/// Handler for PUT /blobs - Store the request body
+pub async fn upload(
+    State(state): State<AppState>,
+    Query(query): Query<UploadQuery>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<(StatusCode, Json<UploadedBlob>), BlobError> {
+    let info = state
+        .blobs
+        .put(
+            body.into_data_stream(),
+            content_type(&headers),
+            query.filename,
+        )
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(info.into())))
+}
+
+/// Handler for POST /blobs - Store every part of a multipart upload
+pub async fn upload_multipart(
+    State(state): State<AppState>,
+    mut multipart: Multipart,
+) -> Result<(StatusCode, Json<Value>), BlobError> {
+    let mut blobs = Vec::new();
+
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| BlobError::Upload(e.to_string()))?
+    {
+        let name = field.name().map(str::to_string);
+        let filename = field.file_name().map(str::to_string);
+        let content_type = field.content_type().map(str::to_string);
+
+        let info = state.blobs.put(field, content_type, filename).await?;
+        blobs.push(UploadedBlob {
+            field: name,
+            ..info.into()
+        });
+    }
+
+    if blobs.is_empty() {
+        return Err(BlobError::Upload("No parts in the upload".to_string()));
+    }
+
+    Ok((StatusCode::CREATED, Json(json!({ "blobs": blobs }))))
+}
+
+/// Handler for GET /blobs/:sha256 - Stream a blob
+///
+/// Supports single byte ranges (`206 Partial Content`) and
+/// `If-None-Match` against the blob's hash. Blobs are always served as
+/// attachments with `nosniff`, so uploaded HTML or scripts are never
+/// rendered on Brazil's origin.
+pub async fn download(
+    State(state): State<AppState>,
+    Path(hash): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, BlobError> {
+    let info = state.blobs.info(&hash).await?;
+    let etag = format!("\"{}\"", info.sha256);
+
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| {
+            v.split(',')
+                .any(|tag| tag.trim() == etag || tag.trim() == "*")
+        });
+    if not_modified {
+        return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response());
+    }
+
+    let requested = parse_range(headers.get(header::RANGE), info.size)?;
+    let range = requested.clone().unwrap_or(0..info.size);
+    let file = state.blobs.open(&info, &range).await?;
+    let body = Body::from_stream(ReaderStream::new(file.take(range.end - range.start)));
+
+    let content_type = info
+        .content_type
+        .clone()
+        .unwrap_or_else(|| "application/octet-stream".to_string());
+
+    let mut response = Response::builder()
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::CONTENT_LENGTH, range.end - range.start)
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::ETAG, etag)
+        .header(
+            header::CACHE_CONTROL,
+            "private, max-age=31536000, immutable",
+        )
+        .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff");
+
+    let disposition = match &info.filename {
+        Some(filename) => {
+            let filename = filename.replace(['"', '\\', '\r', '\n'], "_");
+            format!("attachment; filename=\"{}\"", filename)
+        }
+        None => "attachment".to_string(),
+    };
+    response = response.header(header::CONTENT_DISPOSITION, disposition);
+
+    response = match requested {
+        Some(range) => response
+            .status(StatusCode::PARTIAL_CONTENT)
+            .header(header::CONTENT_RANGE, content_range(&range, info.size)),
+        None => response.status(StatusCode::OK),
+    };
+
+    response
+        .body(body)
+        .map_err(|e| BlobError::Io(std::io::Error::other(e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{offline_state, serve, serve_brazil};
+
+    /// Store in a fresh temporary directory
+    fn temp_store() -> BlobStore {
+        let dir = std::env::temp_dir().join(format!("ndnm-blobs-{}", uuid::Uuid::new_v4()));
+        BlobStore::new(dir)
+    }
+
+    /// Stream of one chunk
+    fn chunk(bytes: &'static [u8]) -> impl Stream<Item = Result<&'static [u8], String>> {
+        futures_util::stream::once(async move { Ok(bytes) })
+    }
+
+    #[tokio::test]
+    async fn test_put_deduplicates_and_limits_size() {
+        let store = temp_store().with_max_size(8);
+
+        let info = store
+            .put(chunk(b"hello"), None, Some("a.txt".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(
+            info.sha256,
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        assert_eq!(info.size, 5);
+
+        // The same content keeps its first name
+        let again = store
+            .put(chunk(b"hello"), None, Some("b.txt".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(again.filename.as_deref(), Some("a.txt"));
+
+        let result = store.put(chunk(b"too long!"), None, None).await;
+        assert!(matches!(result, Err(BlobError::TooLarge { limit: 8 })));
+        assert!(matches!(
+            store.info(&"0".repeat(64)).await,
+            Err(BlobError::NotFound(_))
+        ));
+        assert!(matches!(
+            store.info("../etc/passwd").await,
+            Err(BlobError::InvalidRef(_))
+        ));
+
+        let _ = std::fs::remove_dir_all(&store.dir);
+    }
+
+    #[tokio::test]
+    async fn test_offload_outputs() {
+        let store = temp_store().with_inline_limit(4);
+        let text = store
+            .put(chunk(b"file body"), None, Some("in.txt".to_string()))
+            .await
+            .unwrap();
+
+        let mut execution = json!({
+            "node_results": {
+                "n1": {
+                    "outputs": {
+                        "short": "abc",
+                        "long": "file body",
+                        "file": { "filename": "in.txt", "content": "file body" }
+                    }
+                }
+            }
+        });
+        store.offload_outputs(&mut execution).await;
+
+        let outputs = &execution["node_results"]["n1"]["outputs"];
+        assert_eq!(outputs["short"], "abc");
+        assert_eq!(
+            referenced_hash(&outputs["long"]),
+            Some(&*format!("sha256:{}", text.sha256))
+        );
+        assert_eq!(outputs["long"]["size"], 9);
+        assert_eq!(outputs["file"]["filename"], "in.txt");
+        assert!(referenced_hash(&outputs["file"]["content"]).is_some());
+
+        let _ = std::fs::remove_dir_all(&store.dir);
+    }
+
+    #[tokio::test]
+    async fn test_graph_inputs_become_artifacts() {
+        use axum::{
+            extract::DefaultBodyLimit,
+            routing::{get, post, put},
+            Router,
+        };
+        use std::sync::{Arc, Mutex};
+
+        // A Hermes with an artifact store and the default body limit on runs
+        let artifacts: Arc<Mutex<HashMap<String, usize>>> = Arc::default();
+        let stored = artifacts.clone();
+        let hermes = Router::new()
+            .route(
+                "/artifacts",
+                put(move |body: Bytes| async move {
+                    let hex: String =
+                        Sha256::digest(&body).iter().map(|b| format!("{:02x}", b)).collect();
+                    stored.lock().unwrap().insert(hex.clone(), body.len());
+                    (StatusCode::CREATED, Json(json!({ "$ref": format!("sha256:{}", hex) })))
+                })
+                .layer(DefaultBodyLimit::disable()),
+            )
+            .route(
+                "/graphs/run",
+                post(|Json(request): Json<Value>| async move {
+                    Json(json!({ "execution_id": "exec-1", "graph": request["graph"] }))
+                }),
+            )
+            .route(
+                "/nexus/load/:name",
+                get(|| async {
+                    let reference = json!({ REF_KEY: format!("sha256:{}", "0".repeat(64)) });
+                    Json(json!({ "graph": { "nodes": [{ "inputs": { "file": reference } }] } }))
+                }),
+            );
+        let hermes_url = serve(hermes).await;
+
+        let store = temp_store();
+        let dir = store.dir.clone();
+        let large: &'static [u8] = vec![b'x'; 3 * 1024 * 1024].leak();
+        let blob = store
+            .put(chunk(large), None, Some("large.txt".to_string()))
+            .await
+            .unwrap();
+        let binary = store.put(chunk(&[0xff, 0x00]), None, None).await.unwrap();
+
+        let url = serve_brazil(AppState::new(hermes_url).with_blobs(store)).await;
+
+        let client = reqwest::Client::new();
+        let response = client
+            .post(format!("{}/graphs/run", url))
+            .json(&json!({
+                "graph": {
+                    "nodes": [{
+                        "instance_id": "n1",
+                        "inputs": {
+                            "copy_input_0": blob.reference(),
+                            "copy_input_1": binary.reference(),
+                            "copy_input_2": "inline"
+                        }
+                    }],
+                    "connections": []
+                }
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body: Value = response.json().await.unwrap();
+        let inputs = &body["graph"]["nodes"][0]["inputs"];
+        assert_eq!(
+            inputs["copy_input_0"],
+            json!({
+                "filename": "large.txt",
+                "content": { "$ref": format!("sha256:{}", blob.sha256) }
+            })
+        );
+        assert_eq!(
+            inputs["copy_input_1"],
+            json!({ "$ref": format!("sha256:{}", binary.sha256) })
+        );
+        assert_eq!(inputs["copy_input_2"], "inline");
+        assert_eq!(artifacts.lock().unwrap()[&blob.sha256], large.len());
+
+        // Saved workspaces referencing blobs cannot be run by Hermes
+        let response = client
+            .post(format!("{}/nexus/with-blobs/run", url))
+            .json(&json!({}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_upload_and_ranged_download() {
+        let store = temp_store();
+        let dir = store.dir.clone();
+        let url = serve_brazil(offline_state().with_blobs(store)).await;
+
+        let client = reqwest::Client::new();
+        let uploaded: Value = client
+            .put(format!("{}/blobs?filename=report.txt", url))
+            .header("content-type", "text/plain")
+            .body("0123456789")
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let sha256 = uploaded["sha256"].as_str().unwrap();
+        assert_eq!(
+            uploaded["ref"],
+            json!({ "$blob": format!("sha256:{}", sha256) })
+        );
+
+        let response = client
+            .get(format!("{}/blobs/{}", url, sha256))
+            .header("range", "bytes=2-5")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()["content-range"], "bytes 2-5/10");
+        assert_eq!(response.headers()["content-type"], "text/plain");
+        assert_eq!(response.text().await.unwrap(), "2345");
+
+        let response = client
+            .get(format!("{}/blobs/{}", url, sha256))
+            .header("range", "bytes=20-")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+
+        // Unnamed HTML is still downloaded, never rendered
+        let uploaded: Value = client
+            .put(format!("{}/blobs", url))
+            .header("content-type", "text/html")
+            .body("<script>alert(1)</script>")
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let response = client
+            .get(format!("{}/blobs/{}", url, uploaded["sha256"].as_str().unwrap()))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.headers()["content-disposition"], "attachment");
+        assert_eq!(response.headers()["x-content-type-options"], "nosniff");
+
+        let form = reqwest::multipart::Form::new()
+            .part(
+                "a",
+                reqwest::multipart::Part::text("first").file_name("a.txt"),
+            )
+            .part("b", reqwest::multipart::Part::bytes(vec![1, 2, 3]));
+        let response = client
+            .post(format!("{}/blobs", url))
+            .multipart(form)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["blobs"][0]["field"], "a");
+        assert_eq!(body["blobs"][0]["filename"], "a.txt");
+        assert_eq!(body["blobs"][1]["size"], 3);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,1046 @@
+//! Collaborative workspace editing
+//!
+//! Clients editing the same workspace join its room. Brazil keeps the
+//! authoritative state of every open room in memory and orders the graph
+//! operations sent by its members: each applied operation gets the next
+//! sequence number of the room and is sent to every member (the author
+//! included) as a `room_op` event on the `workspace:<name>` topic. Members
+//! apply `room_op` events in sequence order; an operation that no longer
+//! applies (e.g. it targets a node someone else removed) is rejected with
+//! an error reply.
+//!
+//! Dirty rooms are saved to Hermes every `COLLAB_PERSIST_SECS` seconds
+//! (default 10), when their last member leaves and when Brazil shuts down;
+//! saves of a room never overlap. A room is closed once it has no members
+//! and nothing left to save.
+//!
+//! Saves expect the revision the room was loaded from, so a save made
+//! outside the room is reported as a `room_persist_failed` event (with a
+//! `conflict` error) instead of being overwritten. The room then stops
+//! saving and keeps its state in memory, even without members, until a
+//! member sends `resolve_conflict`: `overwrite` saves the room over the
+//! outside save, `reload` replaces the room state with the saved workspace
+//! and sends it to the members as a `room_reloaded` event.
+//!
+//! Presence (who is in the room, their cursor and selection) is sent to
+//! the room as `presence` events; it is not sequenced.
+
+use crate::commands::{Command, CommandError, ErrorCode};
+use crate::translate::{HermesConnection, HermesGraph, HermesNode};
+use crate::websocket::Topic;
+use crate::AppState;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Interval between saves of dirty rooms when `COLLAB_PERSIST_SECS` is unset
+const DEFAULT_PERSIST_INTERVAL: Duration = Duration::from_secs(10);
+
+/// An operation on a workspace graph
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum GraphOp {
+    /// Add a node instance
+    AddNode {
+        /// The new node
+        node: HermesNode,
+    },
+
+    /// Move a node in the editor
+    MoveNode {
+        /// Node instance ID
+        instance_id: String,
+
+        /// New position (`{x, y}`)
+        position: Value,
+    },
+
+    /// Remove a node and every connection touching it
+    RemoveNode {
+        /// Node instance ID
+        instance_id: String,
+    },
+
+    /// Connect two handles
+    Connect {
+        /// The new connection
+        connection: HermesConnection,
+    },
+
+    /// Remove a connection
+    Disconnect {
+        /// The connection to remove
+        connection: HermesConnection,
+    },
+
+    /// Set an input field of a node (`null` clears it)
+    SetField {
+        /// Node instance ID
+        instance_id: String,
+
+        /// Input field name
+        field: String,
+
+        /// New value
+        value: Value,
+    },
+}
+
+/// What a member shows the rest of the room
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Presence {
+    /// Display name of the member
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+
+    /// Cursor position in the editor
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<Value>,
+
+    /// Selected node instance IDs
+    #[serde(default)]
+    pub selection: Vec<String>,
+}
+
+/// An open workspace room
+#[derive(Debug)]
+struct Room {
+    /// Hermes revision the room state is based on
+    revision: u64,
+
+    /// Authoritative graph
+    graph: HermesGraph,
+
+    /// Workspace data other than the graph (parameters, metadata, ...)
+    rest: Map<String, Value>,
+
+    /// Sequence number of the last applied operation
+    seq: u64,
+
+    /// Sequence number of the last operation saved to Hermes
+    persisted_seq: u64,
+
+    /// Whether a save was rejected because the workspace was saved outside
+    /// the room; no further saves are attempted until it is resolved
+    conflict: bool,
+
+    /// Held while the room is being saved
+    save_lock: Arc<Mutex<()>>,
+
+    /// Members and their presence
+    members: HashMap<Uuid, Presence>,
+}
+
+impl Room {
+    /// Build a room from a Hermes `/nexus/load` response
+    fn from_load(response: Value) -> Result<Self, String> {
+        let revision = response
+            .get("revision")
+            .and_then(|r| r.as_u64())
+            .ok_or("Load response has no revision")?;
+
+        let Some(Value::Object(mut rest)) = response.get("data").cloned() else {
+            return Err("Load response has no workspace data".to_string());
+        };
+
+        let graph = rest.remove("graph").ok_or("Workspace has no graph")?;
+        let graph = serde_json::from_value(graph).map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            revision,
+            graph,
+            rest,
+            seq: 0,
+            persisted_seq: 0,
+            conflict: false,
+            save_lock: Arc::default(),
+            members: HashMap::new(),
+        })
+    }
+
+    /// Apply an operation and return its sequence number
+    fn apply(&mut self, op: &GraphOp) -> Result<u64, String> {
+        let graph = &mut self.graph;
+
+        match op {
+            GraphOp::AddNode { node } => {
+                if graph
+                    .nodes
+                    .iter()
+                    .any(|n| n.instance_id == node.instance_id)
+                {
+                    return Err(format!("Node '{}' already exists", node.instance_id));
+                }
+                graph.nodes.push(node.clone());
+            }
+            GraphOp::MoveNode {
+                instance_id,
+                position,
+            } => {
+                find_node(graph, instance_id)?.position = Some(position.clone());
+            }
+            GraphOp::RemoveNode { instance_id } => {
+                find_node(graph, instance_id)?;
+                graph.nodes.retain(|n| &n.instance_id != instance_id);
+                graph
+                    .connections
+                    .retain(|c| &c.from_node != instance_id && &c.to_node != instance_id);
+            }
+            GraphOp::Connect { connection } => {
+                find_node(graph, &connection.from_node)?;
+                find_node(graph, &connection.to_node)?;
+                if graph.connections.contains(connection) {
+                    return Err("Connection already exists".to_string());
+                }
+                graph.connections.push(connection.clone());
+            }
+            GraphOp::Disconnect { connection } => {
+                let before = graph.connections.len();
+                graph.connections.retain(|c| c != connection);
+                if graph.connections.len() == before {
+                    return Err("Connection does not exist".to_string());
+                }
+            }
+            GraphOp::SetField {
+                instance_id,
+                field,
+                value,
+            } => {
+                let node = find_node(graph, instance_id)?;
+                if value.is_null() {
+                    node.input_values.remove(field);
+                } else {
+                    node.input_values.insert(field.clone(), value.clone());
+                }
+            }
+        }
+
+        self.seq += 1;
+        Ok(self.seq)
+    }
+
+    /// Full workspace data, as saved to Hermes
+    fn workspace_data(&self) -> Value {
+        let mut data = self.rest.clone();
+        data.insert("graph".to_string(), json!(self.graph));
+        Value::Object(data)
+    }
+
+    /// Members and their presence
+    fn members(&self) -> Vec<Value> {
+        let mut members: Vec<Value> = self
+            .members
+            .iter()
+            .map(|(client_id, presence)| {
+                let mut member = json!(presence);
+                member["client_id"] = json!(client_id);
+                member
+            })
+            .collect();
+        members.sort_by_key(|m| m["client_id"].as_str().unwrap_or_default().to_string());
+        members
+    }
+
+    /// Whether the room has operations not yet saved to Hermes
+    fn is_dirty(&self) -> bool {
+        self.seq > self.persisted_seq
+    }
+
+    /// Snapshot of the room sent to members
+    fn snapshot(&self, name: &str) -> Value {
+        json!({
+            "workspace": name,
+            "revision": self.revision,
+            "seq": self.seq,
+            "conflict": self.conflict,
+            "data": self.workspace_data(),
+            "members": self.members()
+        })
+    }
+}
+
+/// Find a node of the graph by instance ID
+fn find_node<'a>(
+    graph: &'a mut HermesGraph,
+    instance_id: &str,
+) -> Result<&'a mut HermesNode, String> {
+    graph
+        .nodes
+        .iter_mut()
+        .find(|n| n.instance_id == instance_id)
+        .ok_or_else(|| format!("Node '{}' does not exist", instance_id))
+}
+
+/// How to resolve a room whose save conflicted
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Resolution {
+    /// Save the room state over the workspace saved outside the room
+    Overwrite,
+
+    /// Drop the room's unsaved operations and reload the saved workspace
+    Reload,
+}
+
+/// Open rooms by workspace name
+#[derive(Clone, Default)]
+pub struct Rooms {
+    /// Rooms by workspace name
+    rooms: Arc<Mutex<HashMap<String, Room>>>,
+}
+
+/// Interval between saves of dirty rooms, from `COLLAB_PERSIST_SECS`
+pub fn persist_interval_from_env() -> Duration {
+    std::env::var("COLLAB_PERSIST_SECS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .filter(|&secs| secs > 0)
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_PERSIST_INTERVAL)
+}
+
+/// Execute a room command for a client
+///
+/// # Arguments
+///
+/// * `state` - Application state
+/// * `client_id` - Client sending the command
+/// * `command` - `join_workspace`, `leave_workspace`, `apply_op`, `update_presence`
+///   or `resolve_conflict`
+pub async fn execute(
+    state: &AppState,
+    client_id: Uuid,
+    command: Command,
+) -> Result<Value, CommandError> {
+    match command {
+        Command::JoinWorkspace { name, presence } => join(state, client_id, &name, presence).await,
+        Command::LeaveWorkspace { name } => leave(state, client_id, &name).await,
+        Command::ApplyOp { name, op } => apply_op(state, client_id, &name, op).await,
+        Command::UpdatePresence { name, presence } => {
+            update_presence(state, client_id, &name, presence).await
+        }
+        Command::ResolveConflict { name, resolution } => {
+            resolve_conflict(state, client_id, &name, resolution).await
+        }
+        other => Err(CommandError::new(
+            ErrorCode::Internal,
+            format!("'{}' is not a room command", other.name()),
+        )),
+    }
+}
+
+/// Join a workspace room, opening it if needed
+///
+/// # Returns
+///
+/// Snapshot of the room: workspace data, sequence number and members
+async fn join(
+    state: &AppState,
+    client_id: Uuid,
+    name: &str,
+    presence: Presence,
+) -> Result<Value, CommandError> {
+    let is_open = state.rooms.rooms.lock().await.contains_key(name);
+
+    // Load outside the lock; a concurrent join may open the room first
+    let loaded = if is_open {
+        None
+    } else {
+        let response = state.hermes.load_workspace(name).await?.body;
+        Some(Room::from_load(response).map_err(|e| {
+            CommandError::new(
+                ErrorCode::HermesError,
+                format!("Failed to open workspace '{}': {}", name, e),
+            )
+        })?)
+    };
+
+    let mut rooms = state.rooms.rooms.lock().await;
+
+    let room = match loaded {
+        Some(loaded) => rooms.entry(name.to_string()).or_insert_with(|| {
+            info!("Opened collaborative room for workspace '{}'", name);
+            loaded
+        }),
+        None => rooms.get_mut(name).ok_or_else(|| {
+            CommandError::new(
+                ErrorCode::Conflict,
+                format!("Room for workspace '{}' closed while joining", name),
+            )
+        })?,
+    };
+
+    room.members.insert(client_id, presence);
+    state
+        .ws_broadcaster
+        .subscribe(client_id, &[Topic::Workspace(name.to_string())]);
+
+    let snapshot = room.snapshot(name);
+
+    publish_presence(state, name, room).await;
+    Ok(snapshot)
+}
+
+/// Leave a workspace room, closing it when the last member leaves
+async fn leave(state: &AppState, client_id: Uuid, name: &str) -> Result<Value, CommandError> {
+    let mut rooms = state.rooms.rooms.lock().await;
+
+    let room = member_room(&mut rooms, client_id, name)?;
+    room.members.remove(&client_id);
+    state
+        .ws_broadcaster
+        .unsubscribe(client_id, &[Topic::Workspace(name.to_string())]);
+
+    if !room.members.is_empty() {
+        publish_presence(state, name, room).await;
+        return Ok(json!({ "workspace": name }));
+    }
+
+    drop(rooms);
+    close_if_idle(state, name).await;
+
+    Ok(json!({ "workspace": name }))
+}
+
+/// Leave every room a client is in (on disconnect)
+pub async fn leave_all(state: &AppState, client_id: Uuid) {
+    let names: Vec<String> = state
+        .rooms
+        .rooms
+        .lock()
+        .await
+        .iter()
+        .filter(|(_, room)| room.members.contains_key(&client_id))
+        .map(|(name, _)| name.clone())
+        .collect();
+
+    for name in names {
+        let _ = leave(state, client_id, &name).await;
+    }
+}
+
+/// Apply a graph operation and send it to the room
+async fn apply_op(
+    state: &AppState,
+    client_id: Uuid,
+    name: &str,
+    op: GraphOp,
+) -> Result<Value, CommandError> {
+    let mut rooms = state.rooms.rooms.lock().await;
+    let room = member_room(&mut rooms, client_id, name)?;
+
+    let seq = room
+        .apply(&op)
+        .map_err(|e| CommandError::new(ErrorCode::Conflict, e))?;
+
+    // Published under the lock so members receive operations in order
+    state
+        .ws_broadcaster
+        .publish(
+            &[Topic::Workspace(name.to_string())],
+            &json!({
+                "type": "room_op",
+                "workspace": name,
+                "seq": seq,
+                "client_id": client_id,
+                "op": op
+            }),
+        )
+        .await;
+
+    Ok(json!({ "workspace": name, "seq": seq }))
+}
+
+/// Update a member's presence and send it to the room
+async fn update_presence(
+    state: &AppState,
+    client_id: Uuid,
+    name: &str,
+    presence: Presence,
+) -> Result<Value, CommandError> {
+    let mut rooms = state.rooms.rooms.lock().await;
+    let room = member_room(&mut rooms, client_id, name)?;
+
+    room.members.insert(client_id, presence);
+    publish_presence(state, name, room).await;
+
+    Ok(json!({ "workspace": name }))
+}
+
+/// Resolve a room whose save conflicted with a save made outside the room
+async fn resolve_conflict(
+    state: &AppState,
+    client_id: Uuid,
+    name: &str,
+    resolution: Resolution,
+) -> Result<Value, CommandError> {
+    let save_lock = {
+        let mut rooms = state.rooms.rooms.lock().await;
+        member_room(&mut rooms, client_id, name)?.save_lock.clone()
+    };
+
+    // The conflict flag only changes while the room's save lock is held
+    let saving = save_lock.lock().await;
+    if !member_room(&mut *state.rooms.rooms.lock().await, client_id, name)?.conflict {
+        return Err(CommandError::new(
+            ErrorCode::BadRequest,
+            format!("Room of workspace '{}' has no conflict", name),
+        ));
+    }
+
+    let response = state.hermes.load_workspace(name).await?.body;
+    let saved = Room::from_load(response).map_err(|e| {
+        CommandError::new(
+            ErrorCode::HermesError,
+            format!("Failed to reload workspace '{}': {}", name, e),
+        )
+    })?;
+
+    let mut rooms = state.rooms.rooms.lock().await;
+    let room = member_room(&mut rooms, client_id, name)?;
+
+    room.revision = saved.revision;
+    room.conflict = false;
+
+    match resolution {
+        Resolution::Overwrite => {
+            drop(rooms);
+            drop(saving);
+            persist_room(state, name).await;
+        }
+        Resolution::Reload => {
+            info!("Reloaded collaborative room for workspace '{}'", name);
+            room.graph = saved.graph;
+            room.rest = saved.rest;
+
+            // Members replace their state; later operations follow this one
+            room.seq += 1;
+            room.persisted_seq = room.seq;
+
+            let mut event = room.snapshot(name);
+            event["type"] = json!("room_reloaded");
+            state
+                .ws_broadcaster
+                .publish(&[Topic::Workspace(name.to_string())], &event)
+                .await;
+        }
+    }
+
+    Ok(json!({ "workspace": name }))
+}
+
+/// The room of a workspace, if the client is one of its members
+fn member_room<'a>(
+    rooms: &'a mut HashMap<String, Room>,
+    client_id: Uuid,
+    name: &str,
+) -> Result<&'a mut Room, CommandError> {
+    rooms
+        .get_mut(name)
+        .filter(|room| room.members.contains_key(&client_id))
+        .ok_or_else(|| {
+            CommandError::new(
+                ErrorCode::BadRequest,
+                format!("Not in the room of workspace '{}'", name),
+            )
+        })
+}
+
+/// Send the member list of a room to its members
+async fn publish_presence(state: &AppState, name: &str, room: &Room) {
+    state
+        .ws_broadcaster
+        .publish(
+            &[Topic::Workspace(name.to_string())],
+            &json!({
+                "type": "presence",
+                "workspace": name,
+                "members": room.members()
+            }),
+        )
+        .await;
+}
+
+/// Save every dirty room to Hermes and close the idle ones
+pub async fn persist_dirty(state: &AppState) {
+    let names: Vec<String> = state
+        .rooms
+        .rooms
+        .lock()
+        .await
+        .iter()
+        .filter(|(_, room)| (room.is_dirty() && !room.conflict) || room.members.is_empty())
+        .map(|(name, _)| name.clone())
+        .collect();
+
+    for name in names {
+        close_if_idle(state, &name).await;
+    }
+}
+
+/// Save a room, then close it if it has no members and nothing left to save
+///
+/// A room whose save failed stays open so that no operation is lost.
+async fn close_if_idle(state: &AppState, name: &str) {
+    if !persist_room(state, name).await {
+        return;
+    }
+
+    let mut rooms = state.rooms.rooms.lock().await;
+    if rooms
+        .get(name)
+        .is_some_and(|room| room.members.is_empty() && !room.is_dirty())
+    {
+        rooms.remove(name);
+        info!("Closed collaborative room for workspace '{}'", name);
+    }
+}
+
+/// Save a room to Hermes if it is dirty and has no conflict
+///
+/// Saves of a room are serialized, so each expects the revision the
+/// previous one produced.
+///
+/// # Returns
+///
+/// Whether the room has nothing left to save
+async fn persist_room(state: &AppState, name: &str) -> bool {
+    let Some(save_lock) = state
+        .rooms
+        .rooms
+        .lock()
+        .await
+        .get(name)
+        .map(|room| room.save_lock.clone())
+    else {
+        return true;
+    };
+    let _saving = save_lock.lock().await;
+
+    let (data, revision, seq) = match state.rooms.rooms.lock().await.get(name) {
+        Some(room) if room.is_dirty() && !room.conflict => {
+            (room.workspace_data(), room.revision, room.seq)
+        }
+        Some(room) => return !room.is_dirty(),
+        None => return true,
+    };
+
+    let result = persist(state, name, data, revision, seq).await;
+
+    let mut rooms = state.rooms.rooms.lock().await;
+    let Some(room) = rooms.get_mut(name) else {
+        return true;
+    };
+
+    match result {
+        Ok(saved) => {
+            room.revision = saved;
+            room.persisted_seq = room.persisted_seq.max(seq);
+        }
+        Err(error) if error.code == ErrorCode::Conflict => {
+            warn!(
+                "Collaborative room '{}' conflicts with a save made outside it",
+                name
+            );
+            room.conflict = true;
+        }
+        Err(_) => {}
+    }
+
+    !room.is_dirty()
+}
+
+/// Save dirty rooms periodically until Brazil shuts down, then once more
+pub async fn persist_loop(state: AppState, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+
+    loop {
+        tokio::select! {
+            _ = state.shutdown.cancelled() => break,
+            _ = ticker.tick() => persist_dirty(&state).await,
+        }
+    }
+
+    persist_dirty(&state).await;
+}
+
+/// Save a room's workspace to Hermes
+///
+/// # Returns
+///
+/// The new revision, or the error (also reported to the room)
+async fn persist(
+    state: &AppState,
+    name: &str,
+    data: Value,
+    revision: u64,
+    seq: u64,
+) -> Result<u64, CommandError> {
+    let request = json!({
+        "name": name,
+        "data": data,
+        "message": format!("Collaborative edit up to op {}", seq),
+        "expected_revision": revision
+    });
+
+    let response = state.hermes.save_workspace::<Value>(&request, None).await;
+    let saved = response.map_err(CommandError::from).and_then(|r| {
+        let revision = r.body.get("revision").and_then(|r| r.as_u64());
+        let revision = revision.ok_or_else(|| {
+            CommandError::new(ErrorCode::HermesError, "Save response has no revision")
+        })?;
+        Ok((revision, r.body))
+    });
+
+    match saved {
+        Ok((revision, saved)) => {
+            info!("Saved collaborative room '{}' at op {}", name, seq);
+            state
+                .ws_broadcaster
+                .publish_workspace_saved(name, &saved)
+                .await;
+            Ok(revision)
+        }
+        Err(error) => {
+            warn!(
+                "Failed to save collaborative room '{}': {}",
+                name, error.message
+            );
+            state
+                .ws_broadcaster
+                .publish(
+                    &[Topic::Workspace(name.to_string())],
+                    &json!({
+                        "type": "room_persist_failed",
+                        "workspace": name,
+                        "seq": seq,
+                        "error": error
+                    }),
+                )
+                .await;
+            Err(error)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::serve;
+    use axum::{
+        http::StatusCode,
+        routing::{get, post},
+        Json, Router,
+    };
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex as StdMutex;
+
+    fn node(id: &str) -> HermesNode {
+        serde_json::from_value(json!({
+            "instance_id": id,
+            "node_type_id": "browser",
+            "input_values": {}
+        }))
+        .unwrap()
+    }
+
+    fn connection(from: &str, to: &str) -> HermesConnection {
+        HermesConnection {
+            from_node: from.to_string(),
+            from_handle: "out".to_string(),
+            to_node: to.to_string(),
+            to_handle: "in".to_string(),
+        }
+    }
+
+    /// Events of a type received so far
+    fn events(rx: &mut tokio::sync::mpsc::Receiver<String>, kind: &str) -> Vec<Value> {
+        let mut events = Vec::new();
+        while let Ok(msg) = rx.try_recv() {
+            let msg: Value = serde_json::from_str(&msg).unwrap();
+            if msg["type"] == kind {
+                events.push(msg);
+            }
+        }
+        events
+    }
+
+    fn load_response() -> Value {
+        json!({
+            "revision": 3,
+            "data": {
+                "schema_version": 2,
+                "graph": { "nodes": [], "connections": [] },
+                "metadata": { "description": "shared" }
+            }
+        })
+    }
+
+    #[test]
+    fn test_apply_ops() {
+        let mut room = Room::from_load(load_response()).unwrap();
+
+        assert_eq!(room.apply(&GraphOp::AddNode { node: node("a") }), Ok(1));
+        assert_eq!(room.apply(&GraphOp::AddNode { node: node("b") }), Ok(2));
+        assert!(room.apply(&GraphOp::AddNode { node: node("a") }).is_err());
+
+        let connect = GraphOp::Connect {
+            connection: connection("a", "b"),
+        };
+        assert_eq!(room.apply(&connect), Ok(3));
+        assert!(room.apply(&connect).is_err());
+
+        let set = GraphOp::SetField {
+            instance_id: "a".to_string(),
+            field: "target_directory".to_string(),
+            value: json!("./in"),
+        };
+        assert_eq!(room.apply(&set), Ok(4));
+
+        room.apply(&GraphOp::RemoveNode {
+            instance_id: "b".to_string(),
+        })
+        .unwrap();
+        assert!(room.graph.connections.is_empty());
+
+        // Rejected operations do not take a sequence number
+        assert_eq!(room.seq, 5);
+        assert!(room
+            .apply(&GraphOp::MoveNode {
+                instance_id: "b".to_string(),
+                position: json!({ "x": 1.0, "y": 2.0 })
+            })
+            .is_err());
+        assert_eq!(room.seq, 5);
+
+        let data = room.workspace_data();
+        assert_eq!(data["schema_version"], json!(2));
+        assert_eq!(data["metadata"]["description"], json!("shared"));
+        assert_eq!(
+            data["graph"]["nodes"][0]["input_values"]["target_directory"],
+            json!("./in")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_room_lifecycle() {
+        let saves = Arc::new(StdMutex::new(Vec::<Value>::new()));
+        let recorded = saves.clone();
+
+        let app = Router::new()
+            .route("/nexus/load/:name", get(|| async { Json(load_response()) }))
+            .route(
+                "/nexus/save",
+                post(move |Json(body): Json<Value>| {
+                    let recorded = recorded.clone();
+                    async move {
+                        recorded.lock().unwrap().push(body);
+                        Json(json!({ "revision": 4, "saved_at": "2026-01-01T00:00:00Z" }))
+                    }
+                }),
+            );
+        let state = AppState::new(serve(app).await);
+        let (alice, bob) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut alice_rx = state.ws_broadcaster.register(alice);
+        let _bob_rx = state.ws_broadcaster.register(bob);
+
+        join(&state, alice, "demo", Presence::default())
+            .await
+            .unwrap();
+        let snapshot = join(&state, bob, "demo", Presence::default())
+            .await
+            .unwrap();
+        assert_eq!(snapshot["revision"], json!(3));
+        assert_eq!(snapshot["members"].as_array().unwrap().len(), 2);
+
+        // Operations need membership
+        let outsider = Uuid::new_v4();
+        assert!(apply_op(
+            &state,
+            outsider,
+            "demo",
+            GraphOp::AddNode { node: node("x") }
+        )
+        .await
+        .is_err());
+
+        let reply = apply_op(&state, bob, "demo", GraphOp::AddNode { node: node("a") })
+            .await
+            .unwrap();
+        assert_eq!(reply["seq"], json!(1));
+
+        // Alice sees her presence events, then Bob's operation
+        let mut op = None;
+        while let Ok(msg) = alice_rx.try_recv() {
+            let msg: Value = serde_json::from_str(&msg).unwrap();
+            if msg["type"] == "room_op" {
+                op = Some(msg);
+            }
+        }
+        let op = op.expect("room_op delivered");
+        assert_eq!(op["seq"], json!(1));
+        assert_eq!(op["op"]["op"], json!("add_node"));
+
+        persist_dirty(&state).await;
+        {
+            let saves = saves.lock().unwrap();
+            assert_eq!(saves.len(), 1);
+            assert_eq!(saves[0]["expected_revision"], json!(3));
+            assert_eq!(
+                saves[0]["data"]["graph"]["nodes"][0]["instance_id"],
+                json!("a")
+            );
+        }
+
+        // Nothing new to save; the last member leaving closes the room
+        persist_dirty(&state).await;
+        leave(&state, alice, "demo").await.unwrap();
+        leave(&state, bob, "demo").await.unwrap();
+        assert_eq!(saves.lock().unwrap().len(), 1);
+        assert!(state.rooms.rooms.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_conflicted_room() {
+        // Hermes' current revision and the revisions saves expected
+        let revision = Arc::new(StdMutex::new(3u64));
+        let expected = Arc::new(StdMutex::new(Vec::<Value>::new()));
+
+        let current = revision.clone();
+        let saved = revision.clone();
+        let recorded = expected.clone();
+        let app = Router::new()
+            .route(
+                "/nexus/load/:name",
+                get(move || {
+                    let mut response = load_response();
+                    response["revision"] = json!(*current.lock().unwrap());
+                    async move { Json(response) }
+                }),
+            )
+            .route(
+                "/nexus/save",
+                post(move |Json(body): Json<Value>| {
+                    let mut revision = saved.lock().unwrap();
+                    recorded.lock().unwrap().push(body["expected_revision"].clone());
+                    let response = if body["expected_revision"] == json!(*revision) {
+                        *revision += 1;
+                        (StatusCode::OK, Json(json!({ "revision": *revision })))
+                    } else {
+                        (StatusCode::CONFLICT, Json(json!({ "error": "Stale revision" })))
+                    };
+                    async move { response }
+                }),
+            );
+        let state = AppState::new(serve(app).await);
+        let alice = Uuid::new_v4();
+        let mut rx = state.ws_broadcaster.register(alice);
+
+        join(&state, alice, "demo", Presence::default())
+            .await
+            .unwrap();
+        apply_op(&state, alice, "demo", GraphOp::AddNode { node: node("a") })
+            .await
+            .unwrap();
+
+        // Someone saves the workspace outside the room
+        *revision.lock().unwrap() = 5;
+
+        // The conflict is reported once and the save is not retried
+        persist_dirty(&state).await;
+        persist_dirty(&state).await;
+        assert_eq!(*expected.lock().unwrap(), vec![json!(3)]);
+        let failures = events(&mut rx, "room_persist_failed");
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0]["error"]["code"], json!("conflict"));
+
+        // The room outlives its last member with its unsaved operations
+        leave(&state, alice, "demo").await.unwrap();
+        persist_dirty(&state).await;
+        assert_eq!(expected.lock().unwrap().len(), 1);
+        let snapshot = join(&state, alice, "demo", Presence::default())
+            .await
+            .unwrap();
+        assert_eq!(snapshot["conflict"], json!(true));
+        assert_eq!(snapshot["data"]["graph"]["nodes"][0]["instance_id"], json!("a"));
+
+        // Overwriting saves the room on top of the outside save
+        resolve_conflict(&state, alice, "demo", Resolution::Overwrite)
+            .await
+            .unwrap();
+        assert_eq!(*expected.lock().unwrap(), vec![json!(3), json!(5)]);
+        assert!(resolve_conflict(&state, alice, "demo", Resolution::Overwrite)
+            .await
+            .is_err());
+
+        // Reloading drops the room's unsaved operations
+        apply_op(&state, alice, "demo", GraphOp::AddNode { node: node("b") })
+            .await
+            .unwrap();
+        *revision.lock().unwrap() = 8;
+        persist_dirty(&state).await;
+        resolve_conflict(&state, alice, "demo", Resolution::Reload)
+            .await
+            .unwrap();
+        let reloaded = events(&mut rx, "room_reloaded");
+        assert_eq!(reloaded.len(), 1);
+        assert_eq!(reloaded[0]["revision"], json!(8));
+        assert_eq!(reloaded[0]["seq"], json!(3));
+        assert_eq!(reloaded[0]["data"]["graph"]["nodes"], json!([]));
+
+        // Nothing is left to save, so leaving closes the room
+        leave(&state, alice, "demo").await.unwrap();
+        assert_eq!(expected.lock().unwrap().len(), 3);
+        assert!(state.rooms.rooms.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_saves_do_not_overlap() {
+        let revision = Arc::new(StdMutex::new(3u64));
+        let saves = Arc::new(AtomicUsize::new(0));
+        let saved = revision.clone();
+        let counted = saves.clone();
+        let app = Router::new()
+            .route("/nexus/load/:name", get(|| async { Json(load_response()) }))
+            .route(
+                "/nexus/save",
+                post(move |Json(body): Json<Value>| {
+                    let saved = saved.clone();
+                    counted.fetch_add(1, Ordering::SeqCst);
+                    async move {
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                        let mut revision = saved.lock().unwrap();
+                        if body["expected_revision"] != json!(*revision) {
+                            return (StatusCode::CONFLICT, Json(json!({})));
+                        }
+                        *revision += 1;
+                        (StatusCode::OK, Json(json!({ "revision": *revision })))
+                    }
+                }),
+            );
+        let state = AppState::new(serve(app).await);
+        let alice = Uuid::new_v4();
+        let _rx = state.ws_broadcaster.register(alice);
+        join(&state, alice, "demo", Presence::default())
+            .await
+            .unwrap();
+        apply_op(&state, alice, "demo", GraphOp::AddNode { node: node("a") })
+            .await
+            .unwrap();
+
+        // The periodic save and the last member leaving race for the room
+        let (_, left) = tokio::join!(persist_dirty(&state), leave(&state, alice, "demo"));
+        left.unwrap();
+
+        // One save, not a second one expecting the same revision
+        assert_eq!(*revision.lock().unwrap(), 4);
+        assert_eq!(saves.load(Ordering::SeqCst), 1);
+        assert!(state.rooms.rooms.lock().await.is_empty());
+    }
+}
@@ -0,0 +1,670 @@
+//! WebSocket command protocol
+//!
+//! Clients send commands as JSON messages carrying a correlation `id`, a
+//! `type` and an optional `payload`:
+//!
+//! ```json
+//! { "id": "c-1", "type": "load_workspace", "payload": { "name": "demo" } }
+//! ```
+//!
+//! Every command is answered to the calling client only, echoing its `id`:
+//!
+//! ```json
+//! { "type": "response", "id": "c-1", "command": "load_workspace", "data": { ... } }
+//! { "type": "error", "id": "c-1", "command": "load_workspace",
+//!   "error": { "code": "bad_request", "message": "...", "status": 400 } }
+//! ```
+//!
+//! `run_graph` is acknowledged with an `ack` reply carrying the execution
+//! ID as soon as it starts, so the client can `cancel_execution` it before
+//! the final `response` arrives. Meanwhile, the client gets the nodes'
+//! `node_progress` and `node_partial` events on the execution's topic.
+//!
+//! When authentication is enabled, commands need the role given by
+//! [`Command::required_role`]; others are answered with a `forbidden` error.
+//!
+//! Brazil sends a `ping` with an `id` every [`HEARTBEAT_INTERVAL`]; clients
+//! answer with a `pong` command carrying the same `id`.
+
+use crate::auth::Role;
+use crate::collab::{GraphOp, Presence, Resolution};
+use crate::hermes::HermesError;
+use crate::rate_limit::RateLimited;
+use crate::{translate::TranslationError, websocket::Topic, AppState, TranslateFailure};
+use axum::http::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Interval between heartbeat pings sent to each client
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A command sent by a client, with its correlation ID
+#[derive(Debug, Deserialize)]
+pub struct CommandEnvelope {
+    /// Correlation ID echoed in the reply
+    #[serde(default)]
+    pub id: Option<String>,
+
+    /// The command itself
+    #[serde(flatten)]
+    pub command: Command,
+}
+
+/// Commands understood over the WebSocket
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", content = "payload", rename_all = "snake_case")]
+pub enum Command {
+    /// Run a graph, in the frontend or Hermes format (same body as `POST /graphs/run`)
+    RunGraph(Value),
+
+    /// Cancel an in-flight execution
+    CancelExecution {
+        /// ID of the execution to cancel
+        execution_id: String,
+    },
+
+    /// Save a workspace (same body as `POST /nexus/save`)
+    SaveWorkspace(Value),
+
+    /// Load a workspace
+    LoadWorkspace {
+        /// Workspace name
+        name: String,
+    },
+
+    /// List workspaces, optionally filtered like `GET /nexus/list`
+    ListWorkspaces(Option<serde_json::Map<String, Value>>),
+
+    /// Get the node registry
+    GetRegistry,
+
+    /// Subscribe to topics (`execution:<id>`, `workspace:<name>`, `registry`, `logs`)
+    Subscribe {
+        /// Topics to add
+        topics: Vec<Topic>,
+    },
+
+    /// Unsubscribe from topics
+    Unsubscribe {
+        /// Topics to remove
+        topics: Vec<Topic>,
+    },
+
+    /// Join the collaborative room of a workspace
+    JoinWorkspace {
+        /// Workspace name
+        name: String,
+
+        /// Initial presence of the member
+        #[serde(default)]
+        presence: Presence,
+    },
+
+    /// Leave the collaborative room of a workspace
+    LeaveWorkspace {
+        /// Workspace name
+        name: String,
+    },
+
+    /// Apply a graph operation in a workspace room
+    ApplyOp {
+        /// Workspace name
+        name: String,
+
+        /// The operation
+        op: GraphOp,
+    },
+
+    /// Update the member's presence in a workspace room
+    UpdatePresence {
+        /// Workspace name
+        name: String,
+
+        /// New presence
+        presence: Presence,
+    },
+
+    /// Resolve a workspace room whose save conflicted with a save made
+    /// outside the room
+    ResolveConflict {
+        /// Workspace name
+        name: String,
+
+        /// Whether to keep the room's state or the saved workspace
+        resolution: Resolution,
+    },
+
+    /// Check the connection; answered with a `pong` reply
+    Ping,
+
+    /// Answer to a heartbeat `ping` from Brazil
+    Pong,
+}
+
+impl Command {
+    /// Name of the command, as sent in `type`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::RunGraph(_) => "run_graph",
+            Command::CancelExecution { .. } => "cancel_execution",
+            Command::SaveWorkspace(_) => "save_workspace",
+            Command::LoadWorkspace { .. } => "load_workspace",
+            Command::ListWorkspaces(_) => "list_workspaces",
+            Command::GetRegistry => "get_registry",
+            Command::Subscribe { .. } => "subscribe",
+            Command::Unsubscribe { .. } => "unsubscribe",
+            Command::JoinWorkspace { .. } => "join_workspace",
+            Command::LeaveWorkspace { .. } => "leave_workspace",
+            Command::ApplyOp { .. } => "apply_op",
+            Command::UpdatePresence { .. } => "update_presence",
+            Command::ResolveConflict { .. } => "resolve_conflict",
+            Command::Ping => "ping",
+            Command::Pong => "pong",
+        }
+    }
+
+    /// Role needed to send the command (see [`crate::auth`])
+    pub fn required_role(&self) -> Role {
+        match self {
+            Command::RunGraph(_) | Command::CancelExecution { .. } => Role::Operator,
+            Command::SaveWorkspace(_)
+            | Command::ApplyOp { .. }
+            | Command::ResolveConflict { .. } => Role::Editor,
+            _ => Role::Viewer,
+        }
+    }
+
+    /// Whether the command belongs to a collaborative room (see [`crate::collab`])
+    pub fn is_room_command(&self) -> bool {
+        matches!(
+            self,
+            Command::JoinWorkspace { .. }
+                | Command::LeaveWorkspace { .. }
+                | Command::ApplyOp { .. }
+                | Command::UpdatePresence { .. }
+                | Command::ResolveConflict { .. }
+        )
+    }
+}
+
+/// Message sent to a single client
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Reply {
+    /// A command succeeded
+    Response {
+        /// Correlation ID of the command
+        id: Option<String>,
+
+        /// Command name
+        command: &'static str,
+
+        /// Command result
+        data: Value,
+    },
+
+    /// A long-running command was accepted
+    Ack {
+        /// Correlation ID of the command
+        id: Option<String>,
+
+        /// Command name
+        command: &'static str,
+
+        /// What the client needs to follow the command (e.g. execution ID)
+        data: Value,
+    },
+
+    /// A command failed
+    Error {
+        /// Correlation ID of the command, if it could be read
+        id: Option<String>,
+
+        /// Command name, if it could be read
+        command: Option<String>,
+
+        /// What went wrong
+        error: CommandError,
+    },
+
+    /// Answer to a client `ping`
+    Pong {
+        /// Correlation ID of the ping
+        id: Option<String>,
+    },
+
+    /// Heartbeat sent by Brazil, to be answered with a `pong` command
+    Ping {
+        /// ID to echo in the `pong`
+        id: String,
+    },
+}
+
+impl Reply {
+    /// Reply for the outcome of a command
+    pub fn from_result(
+        id: Option<String>,
+        command: &'static str,
+        result: Result<Value, CommandError>,
+    ) -> Self {
+        match result {
+            Ok(data) => Reply::Response { id, command, data },
+            Err(error) => Reply::Error {
+                id,
+                command: Some(command.to_string()),
+                error,
+            },
+        }
+    }
+
+    /// Serialize the reply for sending
+    pub fn to_text(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|e| {
+            warn!("Failed to serialize reply: {}", e);
+            r#"{"type":"error","id":null,"command":null,"error":{"code":"internal","message":"Failed to serialize reply"}}"#.to_string()
+        })
+    }
+}
+
+/// Category of a command error
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The message is not valid JSON or not a known command
+    InvalidMessage,
+
+    /// Hermes rejected the request
+    BadRequest,
+
+    /// The client's role does not allow the command
+    Forbidden,
+
+    /// The request conflicts with the current state (e.g. stale save)
+    Conflict,
+
+    /// The graph could not be translated to Hermes' format
+    TranslationFailed,
+
+    /// A blob referenced by the graph is missing or invalid (see
+    /// [`crate::blobs`])
+    InvalidBlob,
+
+    /// Hermes could not be reached
+    HermesUnavailable,
+
+    /// Hermes failed to handle the request
+    HermesError,
+
+    /// The client sent too many commands (see [`crate::rate_limit`])
+    RateLimited,
+
+    /// Brazil failed to handle the request
+    Internal,
+}
+
+/// Structured error returned for a failed command
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandError {
+    /// Error category
+    pub code: ErrorCode,
+
+    /// Human-readable message
+    pub message: String,
+
+    /// HTTP status returned by Hermes, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+
+    /// Field-level translation errors
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<TranslationError>,
+
+    /// Full error body from Hermes (e.g. the current workspace on conflicts)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
+}
+
+impl CommandError {
+    /// Create an error without status or details
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            status: None,
+            errors: Vec::new(),
+            details: None,
+        }
+    }
+}
+
+impl From<HermesError> for CommandError {
+    fn from(error: HermesError) -> Self {
+        let code = match &error {
+            HermesError::Unavailable(_) | HermesError::Timeout(_) => ErrorCode::HermesUnavailable,
+            HermesError::InvalidResponse(_) => ErrorCode::HermesError,
+            HermesError::InvalidPath(_) => ErrorCode::BadRequest,
+            HermesError::Status { status, .. } => match *status {
+                StatusCode::BAD_REQUEST => ErrorCode::BadRequest,
+                StatusCode::CONFLICT => ErrorCode::Conflict,
+                StatusCode::SERVICE_UNAVAILABLE => ErrorCode::HermesUnavailable,
+                _ => ErrorCode::HermesError,
+            },
+        };
+
+        let message = error.to_string();
+        let (status, details) = match error {
+            HermesError::Status { status, body } => (Some(status.as_u16()), Some(body)),
+            _ => (None, None),
+        };
+
+        Self {
+            code,
+            message,
+            status,
+            errors: Vec::new(),
+            details,
+        }
+    }
+}
+
+impl From<RateLimited> for CommandError {
+    fn from(limited: RateLimited) -> Self {
+        Self {
+            details: Some(serde_json::json!({ "retry_after_secs": limited.retry_after_secs })),
+            ..Self::new(ErrorCode::RateLimited, limited.to_string())
+        }
+    }
+}
+
+impl From<TranslateFailure> for CommandError {
+    fn from(failure: TranslateFailure) -> Self {
+        match failure {
+            TranslateFailure::Invalid(errors) => Self {
+                errors,
+                ..Self::new(
+                    ErrorCode::TranslationFailed,
+                    "Graph could not be translated",
+                )
+            },
+            TranslateFailure::Hermes(error) => Self {
+                message: format!("Failed to fetch the node registry from Hermes: {}", error),
+                ..Self::from(error)
+            },
+            TranslateFailure::Blob(error) => {
+                Self::new(ErrorCode::InvalidBlob, error.to_string())
+            }
+            TranslateFailure::Internal(message) => Self::new(ErrorCode::Internal, message),
+        }
+    }
+}
+
+/// Heartbeat state of one connection
+#[derive(Debug)]
+pub struct Heartbeat {
+    /// ID and send time of the last ping
+    last_ping: Option<(String, Instant)>,
+
+    /// When the client last answered a ping (or connected)
+    last_pong: Instant,
+}
+
+impl Heartbeat {
+    /// Heartbeat state for a freshly connected client
+    pub fn new() -> Self {
+        Self {
+            last_ping: None,
+            last_pong: Instant::now(),
+        }
+    }
+
+    /// Record a new ping and return its ID
+    pub fn ping(&mut self) -> String {
+        let id = uuid::Uuid::new_v4().to_string();
+        self.last_ping = Some((id.clone(), Instant::now()));
+        id
+    }
+
+    /// Record a pong and return the round trip, if it answers the last ping
+    pub fn pong(&mut self, id: Option<&str>) -> Option<Duration> {
+        let (ping_id, sent_at) = self.last_ping.as_ref()?;
+        if id != Some(ping_id.as_str()) {
+            return None;
+        }
+
+        let round_trip = sent_at.elapsed();
+        self.last_ping = None;
+        self.last_pong = Instant::now();
+        Some(round_trip)
+    }
+
+    /// Whether the client missed two heartbeats in a row
+    pub fn is_stale(&self) -> bool {
+        self.last_pong.elapsed() > HEARTBEAT_INTERVAL * 2
+    }
+}
+
+/// Parse a text message into a command
+///
+/// # Returns
+///
+/// * `Ok(CommandEnvelope)` - The command and its correlation ID
+/// * `Err(Reply)` - Error reply to send back to the client
+pub fn parse_command(text: &str) -> Result<CommandEnvelope, Box<Reply>> {
+    let value: Value = serde_json::from_str(text).map_err(|e| {
+        Box::new(Reply::Error {
+            id: None,
+            command: None,
+            error: CommandError::new(ErrorCode::InvalidMessage, format!("Invalid JSON: {}", e)),
+        })
+    })?;
+
+    let id = value.get("id").and_then(|v| v.as_str()).map(str::to_string);
+    let command = value
+        .get("type")
+        .and_then(|v| v.as_str())
+        .map(str::to_string);
+
+    serde_json::from_value(value).map_err(|e| {
+        Box::new(Reply::Error {
+            id,
+            command,
+            error: CommandError::new(ErrorCode::InvalidMessage, format!("Invalid command: {}", e)),
+        })
+    })
+}
+
+/// Execute a command against Hermes
+///
+/// `ping`, `pong`, `subscribe` and `unsubscribe` only concern the
+/// connection and are handled by it, room commands go to
+/// [`crate::collab::execute`]; `run_graph` is acknowledged by the
+/// connection before being executed here.
+///
+/// # Arguments
+///
+/// * `state` - Application state
+/// * `command` - Command to execute
+///
+/// # Returns
+///
+/// * `Ok(Value)` - Data for the `response` reply
+/// * `Err(CommandError)` - Error for the `error` reply
+pub async fn execute(state: &AppState, command: Command) -> Result<Value, CommandError> {
+    match command {
+        Command::RunGraph(body) => {
+            let request = crate::prepare_graph_request(state, &state.hermes, body).await?;
+            let request = serde_json::to_value(&request).map_err(|e| {
+                CommandError::new(
+                    ErrorCode::Internal,
+                    format!("Failed to serialize run request: {}", e),
+                )
+            })?;
+            // The execution ID is fixed before the command runs (see websocket)
+            let relay = match request.get("execution_id").and_then(|id| id.as_str()) {
+                Some(execution_id) => {
+                    crate::relay_execution_events(state, &state.hermes, execution_id).await
+                }
+                None => None,
+            };
+
+            let response = state.hermes.run_graph::<Value>(&request).await;
+            crate::finish_relay(relay).await;
+            let mut data = response?.body;
+            state.blobs.offload_outputs(&mut data).await;
+
+            let workspace = request.get("workspace").and_then(|w| w.as_str());
+            state
+                .ws_broadcaster
+                .publish_execution_complete(&data, workspace)
+                .await;
+
+            Ok(data)
+        }
+        Command::CancelExecution { execution_id } => {
+            Ok(state.hermes.cancel_execution(&execution_id).await?.body)
+        }
+        Command::SaveWorkspace(body) => {
+            Ok(state.hermes.save_workspace(&body, None).await?.body)
+        }
+        Command::LoadWorkspace { name } => {
+            Ok(state.hermes.load_workspace(&name).await?.body)
+        }
+        Command::ListWorkspaces(filters) => {
+            let query: Vec<(String, String)> = filters
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|(key, value)| match value {
+                    Value::String(s) => Some((key, s)),
+                    Value::Null => None,
+                    other => Some((key, other.to_string())),
+                })
+                .collect();
+
+            Ok(state.hermes.list_workspaces(&query).await?.body)
+        }
+        Command::GetRegistry => {
+            let registry = state.registry.get(&state.hermes, &state.ws_broadcaster).await?;
+            Ok(registry.body.clone())
+        }
+        Command::Ping
+        | Command::Pong
+        | Command::Subscribe { .. }
+        | Command::Unsubscribe { .. }
+        | Command::JoinWorkspace { .. }
+        | Command::LeaveWorkspace { .. }
+        | Command::ApplyOp { .. }
+        | Command::UpdatePresence { .. }
+        | Command::ResolveConflict { .. } => Ok(Value::Null),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fake_hermes;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_command() {
+        let envelope =
+            parse_command(r#"{"id":"c-1","type":"load_workspace","payload":{"name":"demo"}}"#)
+                .unwrap();
+        assert_eq!(envelope.id.as_deref(), Some("c-1"));
+        assert!(matches!(envelope.command, Command::LoadWorkspace { ref name } if name == "demo"));
+
+        let envelope = parse_command(r#"{"id":"c-2","type":"list_workspaces"}"#).unwrap();
+        assert!(matches!(envelope.command, Command::ListWorkspaces(None)));
+
+        let envelope = parse_command(
+            r#"{"type":"subscribe","payload":{"topics":["execution:e-1","registry"]}}"#,
+        )
+        .unwrap();
+        assert!(matches!(envelope.command, Command::Subscribe { ref topics } if topics.len() == 2));
+        assert!(parse_command(r#"{"type":"subscribe","payload":{"topics":["weather"]}}"#).is_err());
+
+        let reply = parse_command(r#"{"id":"c-3","type":"launch_rockets"}"#).unwrap_err();
+        let Reply::Error { id, command, error } = *reply else {
+            panic!("unknown command accepted");
+        };
+        assert_eq!(id.as_deref(), Some("c-3"));
+        assert_eq!(command.as_deref(), Some("launch_rockets"));
+        assert_eq!(error.code, ErrorCode::InvalidMessage);
+
+        assert!(parse_command("not json").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_execute_preserves_hermes_errors() {
+        let state = AppState::new(fake_hermes().await.url);
+
+        let data = execute(
+            &state,
+            Command::LoadWorkspace {
+                name: "demo".to_string(),
+            },
+        )
+        .await
+        .unwrap();
+        assert!(data.get("graph").is_some());
+
+        let error = execute(
+            &state,
+            Command::LoadWorkspace {
+                name: "missing".to_string(),
+            },
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error.code, ErrorCode::BadRequest);
+        assert_eq!(error.status, Some(400));
+        assert_eq!(error.message, "Workspace 'missing' not found");
+
+        let data = execute(
+            &state,
+            Command::CancelExecution {
+                execution_id: "exec-1".to_string(),
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(data["cancelled"], json!(true));
+    }
+
+    #[tokio::test]
+    async fn test_run_graph_relays_progress() {
+        let state = AppState::new(fake_hermes().await.url);
+        let client = uuid::Uuid::new_v4();
+        let mut messages = state.ws_broadcaster.register(client);
+        state
+            .ws_broadcaster
+            .subscribe(client, &[Topic::Execution("exec-1".to_string())]);
+
+        let body = json!({
+            "execution_id": "exec-1",
+            "graph": { "nodes": [], "connections": [] }
+        });
+        let data = execute(&state, Command::RunGraph(body)).await.unwrap();
+        assert_eq!(data["status"], "success");
+
+        let progress: Value = serde_json::from_str(&messages.recv().await.unwrap()).unwrap();
+        assert_eq!(progress["type"], "node_progress");
+        assert_eq!(progress["instance_id"], "scan");
+        assert_eq!(progress["data"]["percent"], 50.0);
+
+        let complete: Value = serde_json::from_str(&messages.recv().await.unwrap()).unwrap();
+        assert_eq!(complete["type"], "graph_execution_complete");
+    }
+
+    #[test]
+    fn test_heartbeat() {
+        let mut heartbeat = Heartbeat::new();
+        assert!(heartbeat.pong(Some("unexpected")).is_none());
+
+        let id = heartbeat.ping();
+        assert!(heartbeat.pong(Some("other")).is_none());
+        assert!(heartbeat.pong(Some(&id)).is_some());
+        assert!(!heartbeat.is_stale());
+    }
+}
@@ -0,0 +1,44 @@
+//! Brazil's config file
+//!
+//! Read from the file named by `BRAZIL_CONFIG` (default `config.yaml`). A
+//! missing or empty file, or a missing section, gives that section's
+//! defaults.
+
+use crate::auth::AuthConfig;
+use crate::rate_limit::RateLimitConfig;
+use ndnm_libs::AppError;
+use serde::Deserialize;
+use std::path::Path;
+
+/// Contents of Brazil's config file
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct BrazilConfig {
+    /// Authentication settings
+    pub auth: AuthConfig,
+
+    /// Rate limits
+    pub rate_limits: RateLimitConfig,
+}
+
+impl BrazilConfig {
+    /// Read the file named by `BRAZIL_CONFIG` (default `config.yaml`)
+    pub fn from_env() -> Result<Self, AppError> {
+        let path = std::env::var("BRAZIL_CONFIG").unwrap_or_else(|_| "config.yaml".to_string());
+        Self::read(Path::new(&path))
+    }
+
+    /// Read a config file
+    pub fn read(path: &Path) -> Result<Self, AppError> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = std::fs::read_to_string(path)?;
+        if contents.trim().is_empty() {
+            return Ok(Self::default());
+        }
+
+        Ok(serde_yaml::from_str(&contents)?)
+    }
+}
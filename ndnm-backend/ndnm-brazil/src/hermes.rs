@@ -0,0 +1,998 @@
+//! Typed client for the Hermes API
+//!
+//! [`HermesClient`] has a method for every Hermes endpoint. Responses are
+//! decoded into the type the caller asks for (`serde_json::Value` when a
+//! handler only relays them), and failures keep what Hermes said: a
+//! non-success status and its `AppError` body (`{"error": "..."}`) come
+//! back as [`HermesError::Status`] and are answered unchanged by Brazil's
+//! handlers.
+//!
+//! Every call:
+//!
+//! - carries an `x-request-id`, the one of the HTTP request being handled
+//!   or a fresh one for calls made from WebSocket commands and background
+//!   tasks
+//! - is signed when a service secret is configured (see
+//!   `ndnm_libs::signing`)
+//! - is bounded by the timeout of its route class: graph runs and their
+//!   event streams (`HERMES_RUN_TIMEOUT_SECS`, default 10 minutes), bundle
+//!   import and export and artifact uploads (`HERMES_BUNDLE_TIMEOUT_SECS`,
+//!   default 2 minutes) and everything else (`HERMES_TIMEOUT_SECS`,
+//!   default 30 seconds)
+
+use crate::AppState;
+use axum::{
+    async_trait,
+    body::{Body, Bytes},
+    extract::FromRequestParts,
+    http::{header, request::Parts, HeaderMap, HeaderValue, Method, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use ndnm_libs::progress::EventDecoder;
+use ndnm_libs::signing::SendError;
+use ndnm_libs::{signing, ArtifactRef, Signer, StreamFormat};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use thiserror::Error;
+use tracing::warn;
+use uuid::Uuid;
+
+/// Header carrying the request ID between Brazil, its clients and Hermes
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Header carrying the registry's `ETag` on every Hermes response
+pub const REGISTRY_ETAG_HEADER: &str = "x-registry-etag";
+
+/// Default timeout for Hermes calls
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default timeout for graph runs
+const DEFAULT_RUN_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Default timeout for workspace bundle import and export
+const DEFAULT_BUNDLE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Group of Hermes routes sharing a timeout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteClass {
+    /// Reads and small writes
+    Default,
+    /// Graph and workspace runs, which last as long as their nodes
+    Run,
+    /// Workspace bundle import and export, artifact transfers
+    Bundle,
+}
+
+/// Timeouts of Hermes calls by route class
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    /// Timeout for [`RouteClass::Default`]
+    pub default: Duration,
+    /// Timeout for [`RouteClass::Run`]
+    pub run: Duration,
+    /// Timeout for [`RouteClass::Bundle`]
+    pub bundle: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            default: DEFAULT_TIMEOUT,
+            run: DEFAULT_RUN_TIMEOUT,
+            bundle: DEFAULT_BUNDLE_TIMEOUT,
+        }
+    }
+}
+
+impl Timeouts {
+    /// Read the timeouts from `HERMES_TIMEOUT_SECS`,
+    /// `HERMES_RUN_TIMEOUT_SECS` and `HERMES_BUNDLE_TIMEOUT_SECS`
+    pub fn from_env() -> Self {
+        let secs = |name: &str, default: Duration| {
+            std::env::var(name)
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .filter(|&secs| secs > 0)
+                .map(Duration::from_secs)
+                .unwrap_or(default)
+        };
+
+        Self {
+            default: secs("HERMES_TIMEOUT_SECS", DEFAULT_TIMEOUT),
+            run: secs("HERMES_RUN_TIMEOUT_SECS", DEFAULT_RUN_TIMEOUT),
+            bundle: secs("HERMES_BUNDLE_TIMEOUT_SECS", DEFAULT_BUNDLE_TIMEOUT),
+        }
+    }
+
+    /// Timeout for a route class
+    pub fn get(&self, class: RouteClass) -> Duration {
+        match class {
+            RouteClass::Default => self.default,
+            RouteClass::Run => self.run,
+            RouteClass::Bundle => self.bundle,
+        }
+    }
+}
+
+/// Why a Hermes call failed
+#[derive(Debug, Error)]
+pub enum HermesError {
+    /// Hermes could not be reached
+    #[error("Failed to connect to Hermes: {0}")]
+    Unavailable(String),
+
+    /// Hermes did not answer within the route's timeout
+    #[error("Timed out waiting for Hermes: {0}")]
+    Timeout(String),
+
+    /// Hermes answered with a non-success status
+    #[error("{}", status_message(*.status, .body))]
+    Status {
+        /// Status returned by Hermes
+        status: StatusCode,
+        /// Response body, usually `{"error": "..."}`
+        body: Value,
+    },
+
+    /// Hermes answered with a body that could not be decoded
+    #[error("Failed to parse Hermes response: {0}")]
+    InvalidResponse(String),
+
+    /// A path parameter cannot be sent to Hermes (e.g. `..`)
+    #[error("Invalid path parameter: {0:?}")]
+    InvalidPath(String),
+}
+
+/// Message of a non-success Hermes response
+fn status_message(status: StatusCode, body: &Value) -> String {
+    body.get("error")
+        .and_then(|e| e.as_str())
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("Hermes returned status {}", status))
+}
+
+impl HermesError {
+    /// Classify a transport error
+    fn from_reqwest(error: reqwest::Error) -> Self {
+        if error.is_timeout() {
+            Self::Timeout(error.to_string())
+        } else if error.is_decode() {
+            Self::InvalidResponse(error.to_string())
+        } else {
+            Self::Unavailable(error.to_string())
+        }
+    }
+
+    /// Classify an error sending a signed request
+    fn from_send(error: SendError) -> Self {
+        match error {
+            SendError::Http(e) => Self::from_reqwest(e),
+            e @ SendError::StreamingBody => Self::Unavailable(e.to_string()),
+        }
+    }
+
+    /// Status Brazil answers with for this error
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::Unavailable(_) | Self::InvalidResponse(_) => StatusCode::BAD_GATEWAY,
+            Self::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            Self::Status { status, .. } => *status,
+            Self::InvalidPath(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+/// Characters escaped in path segments sent to Hermes: all but unreserved
+const SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// Percent-encode a path parameter as a single path segment
+///
+/// Path parameters reach Brazil already decoded, so `/`, `?` or `#` in them
+/// would otherwise change the Hermes route being called. Dot segments are
+/// rejected since URLs resolve them even when encoded.
+fn segment(value: &str) -> Result<String, HermesError> {
+    if matches!(value, "" | "." | "..") {
+        return Err(HermesError::InvalidPath(value.to_string()));
+    }
+    Ok(utf8_percent_encode(value, SEGMENT).to_string())
+}
+
+impl IntoResponse for HermesError {
+    fn into_response(self) -> Response {
+        match self {
+            Self::Status { status, body } => (status, Json(body)).into_response(),
+            other => (other.status(), Json(json!({ "error": other.to_string() }))).into_response(),
+        }
+    }
+}
+
+/// A successful Hermes response
+#[derive(Debug)]
+pub struct HermesResponse<T> {
+    /// Status returned by Hermes
+    pub status: StatusCode,
+    /// `ETag` returned by Hermes, for workspace loads and saves
+    pub etag: Option<HeaderValue>,
+    /// Decoded body
+    pub body: T,
+}
+
+impl<T: Serialize> IntoResponse for HermesResponse<T> {
+    fn into_response(self) -> Response {
+        let mut response = (self.status, Json(self.body)).into_response();
+        if let Some(etag) = self.etag {
+            response.headers_mut().insert(header::ETAG, etag);
+        }
+        response
+    }
+}
+
+/// A workspace bundle exported by Hermes, streamed to the client
+#[derive(Debug)]
+pub struct Bundle {
+    /// The `application/gzip` response
+    response: reqwest::Response,
+}
+
+impl IntoResponse for Bundle {
+    fn into_response(self) -> Response {
+        let disposition = self
+            .response
+            .headers()
+            .get(header::CONTENT_DISPOSITION)
+            .cloned()
+            .unwrap_or(HeaderValue::from_static("attachment"));
+
+        (
+            [
+                (header::CONTENT_TYPE, HeaderValue::from_static("application/gzip")),
+                (header::CONTENT_DISPOSITION, disposition),
+            ],
+            Body::from_stream(self.response.bytes_stream()),
+        )
+            .into_response()
+    }
+}
+
+/// An artifact fetched from Hermes, streamed to the client
+///
+/// Artifacts are arbitrary node output, so they are always served as an
+/// opaque download the browser must not sniff or render.
+#[derive(Debug)]
+pub struct Artifact {
+    /// Hermes' response, possibly `206 Partial Content` or `416`
+    response: reqwest::Response,
+}
+
+impl IntoResponse for Artifact {
+    fn into_response(self) -> Response {
+        let mut headers = HeaderMap::new();
+        for name in [
+            header::CONTENT_LENGTH,
+            header::CONTENT_RANGE,
+            header::ACCEPT_RANGES,
+            header::ETAG,
+        ] {
+            if let Some(value) = self.response.headers().get(&name) {
+                headers.insert(name, value.clone());
+            }
+        }
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/octet-stream"),
+        );
+        headers.insert(
+            header::X_CONTENT_TYPE_OPTIONS,
+            HeaderValue::from_static("nosniff"),
+        );
+        headers.insert(
+            header::CONTENT_DISPOSITION,
+            HeaderValue::from_static("attachment"),
+        );
+
+        let status = self.response.status();
+        let body = Body::from_stream(self.response.bytes_stream());
+        (status, headers, body).into_response()
+    }
+}
+
+/// Progress events of an execution, streamed by Hermes
+pub struct ExecutionEvents {
+    /// The `text/event-stream` response
+    response: reqwest::Response,
+    /// Splits the stream into events
+    decoder: EventDecoder,
+    /// Events decoded but not yet returned
+    pending: VecDeque<Value>,
+}
+
+impl ExecutionEvents {
+    /// The next event, or `None` once the execution has ended
+    pub async fn next(&mut self) -> Option<Value> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(event);
+            }
+
+            let chunk = match self.response.chunk().await {
+                Ok(chunk) => chunk?,
+                Err(e) => {
+                    warn!("Execution event stream broke off: {}", e);
+                    return None;
+                }
+            };
+            match self.decoder.push(&chunk) {
+                Ok(events) => self.pending.extend(events),
+                Err(e) => {
+                    warn!("Invalid execution event from Hermes: {}", e);
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+/// Client for the Hermes API
+#[derive(Clone)]
+pub struct HermesClient {
+    /// HTTP client
+    http: reqwest::Client,
+    /// Base URL of the Hermes API
+    base_url: String,
+    /// Signs requests, when a service secret is configured
+    signer: Option<Signer>,
+    /// Timeouts by route class
+    timeouts: Timeouts,
+    /// ID of the request being handled, forwarded to Hermes
+    request_id: Option<HeaderValue>,
+    /// Last registry `ETag` announced by Hermes, shared by all clones
+    registry_etag: Arc<Mutex<Option<HeaderValue>>>,
+}
+
+impl HermesClient {
+    /// Create a client for the Hermes API at `base_url`
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.into(),
+            signer: None,
+            timeouts: Timeouts::default(),
+            request_id: None,
+            registry_etag: Arc::default(),
+        }
+    }
+
+    /// Sign requests with the given signer
+    pub fn with_signer(mut self, signer: Option<Signer>) -> Self {
+        self.signer = signer;
+        self
+    }
+
+    /// Use the given timeouts
+    pub fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Forward the given request ID with every call
+    pub fn with_request_id(mut self, request_id: HeaderValue) -> Self {
+        self.request_id = Some(request_id);
+        self
+    }
+
+    /// Send a request and return the response if it succeeded
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - HTTP method
+    /// * `path` - Path and query, starting with `/`
+    /// * `class` - Route class selecting the timeout
+    /// * `build` - Adds the body and headers of the request
+    async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        class: RouteClass,
+        build: impl FnOnce(reqwest::RequestBuilder) -> reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, HermesError> {
+        let request_id = self.request_id.clone().unwrap_or_else(|| {
+            HeaderValue::from_str(&Uuid::new_v4().to_string()).expect("UUIDs are valid headers")
+        });
+
+        let request = self
+            .http
+            .request(method.clone(), format!("{}{}", self.base_url, path))
+            .timeout(self.timeouts.get(class))
+            .header(REQUEST_ID_HEADER, request_id.clone());
+
+        let response = signing::send(build(request), self.signer.as_ref())
+            .await
+            .map_err(|e| {
+                let error = HermesError::from_send(e);
+                warn!("{} {} (request {:?}): {}", method, path, request_id, error);
+                error
+            })?;
+
+        if let Some(etag) = response.headers().get(REGISTRY_ETAG_HEADER) {
+            *self.registry_etag.lock().unwrap() = Some(etag.clone());
+        }
+
+        // A 416 answers a forwarded `Range` and is relayed as is
+        let status = response.status();
+        if status.is_success()
+            || status == StatusCode::NOT_MODIFIED
+            || status == StatusCode::RANGE_NOT_SATISFIABLE
+        {
+            return Ok(response);
+        }
+
+        let body = response.bytes().await.map_err(HermesError::from_reqwest)?;
+        let body = serde_json::from_slice(&body).unwrap_or_else(
+            |_| json!({ "error": String::from_utf8_lossy(&body).trim().to_string() }),
+        );
+
+        warn!(
+            "{} {} (request {:?}): Hermes returned status {}",
+            method, path, request_id, status
+        );
+        Err(HermesError::Status { status, body })
+    }
+
+    /// Send a request and decode its JSON response
+    async fn call<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        class: RouteClass,
+        build: impl FnOnce(reqwest::RequestBuilder) -> reqwest::RequestBuilder,
+    ) -> Result<HermesResponse<T>, HermesError> {
+        let response = self.send(method, path, class, build).await?;
+
+        let status = response.status();
+        let etag = response.headers().get(header::ETAG).cloned();
+        let body = response
+            .json::<T>()
+            .await
+            .map_err(HermesError::from_reqwest)?;
+
+        Ok(HermesResponse { status, etag, body })
+    }
+
+    /// GET a path
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<HermesResponse<T>, HermesError> {
+        self.call(Method::GET, path, RouteClass::Default, |r| r)
+            .await
+    }
+
+    /// POST a JSON body to a path
+    async fn post<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &impl Serialize,
+    ) -> Result<HermesResponse<T>, HermesError> {
+        self.call(Method::POST, path, RouteClass::Default, |r| r.json(body))
+            .await
+    }
+
+    // === Health ===
+
+    /// Whether Hermes answers `GET /health`
+    pub async fn is_healthy(&self) -> bool {
+        self.send(Method::GET, "/health", RouteClass::Default, |r| r)
+            .await
+            .is_ok()
+    }
+
+    /// `GET /health/all` - Health of Hermes and every node
+    pub async fn health_all<T: DeserializeOwned>(&self) -> Result<HermesResponse<T>, HermesError> {
+        self.get("/health/all").await
+    }
+
+    // === Nodes ===
+
+    /// `GET /nodes/registry` - All registered nodes
+    ///
+    /// With `if_none_match`, `None` is returned when the registry still has
+    /// that `ETag`.
+    pub async fn registry<T: DeserializeOwned>(
+        &self,
+        if_none_match: Option<&HeaderValue>,
+    ) -> Result<Option<HermesResponse<T>>, HermesError> {
+        let response = self
+            .send(Method::GET, "/nodes/registry", RouteClass::Default, |r| {
+                match if_none_match {
+                    Some(etag) => r.header(header::IF_NONE_MATCH, etag.clone()),
+                    None => r,
+                }
+            })
+            .await?;
+
+        let status = response.status();
+        if status == StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+
+        let etag = response.headers().get(header::ETAG).cloned();
+        let body = response
+            .json::<T>()
+            .await
+            .map_err(HermesError::from_reqwest)?;
+
+        Ok(Some(HermesResponse { status, etag, body }))
+    }
+
+    /// Last registry `ETag` seen on a Hermes response
+    pub fn registry_etag(&self) -> Option<HeaderValue> {
+        self.registry_etag.lock().unwrap().clone()
+    }
+
+    /// `GET /nodes/:node_id` - One registered node
+    pub async fn node<T: DeserializeOwned>(
+        &self,
+        node_id: &str,
+    ) -> Result<HermesResponse<T>, HermesError> {
+        self.get(&format!("/nodes/{}", segment(node_id)?)).await
+    }
+
+    // === Executions ===
+
+    /// `POST /graphs/run` - Execute a graph
+    pub async fn run_graph<T: DeserializeOwned>(
+        &self,
+        request: &impl Serialize,
+    ) -> Result<HermesResponse<T>, HermesError> {
+        self.call(Method::POST, "/graphs/run", RouteClass::Run, |r| {
+            r.json(request)
+        })
+        .await
+    }
+
+    /// `POST /executions/:execution_id/cancel` - Cancel a running execution
+    pub async fn cancel_execution<T: DeserializeOwned>(
+        &self,
+        execution_id: &str,
+    ) -> Result<HermesResponse<T>, HermesError> {
+        let path = format!("/executions/{}/cancel", segment(execution_id)?);
+        self.call(Method::POST, &path, RouteClass::Default, |r| r)
+            .await
+    }
+
+    /// `GET /executions/:execution_id/events` - Follow the progress events
+    /// of an execution, which need not have started yet
+    pub async fn execution_events(
+        &self,
+        execution_id: &str,
+    ) -> Result<ExecutionEvents, HermesError> {
+        let path = format!("/executions/{}/events", segment(execution_id)?);
+        let response = self
+            .send(Method::GET, &path, RouteClass::Run, |r| r)
+            .await?;
+
+        Ok(ExecutionEvents {
+            response,
+            decoder: EventDecoder::new(StreamFormat::Sse),
+            pending: VecDeque::new(),
+        })
+    }
+
+    // === Workspaces ===
+
+    /// `POST /nexus/save` - Save a workspace, optionally only if it still
+    /// matches `if_match`
+    pub async fn save_workspace<T: DeserializeOwned>(
+        &self,
+        request: &impl Serialize,
+        if_match: Option<&HeaderValue>,
+    ) -> Result<HermesResponse<T>, HermesError> {
+        self.call(Method::POST, "/nexus/save", RouteClass::Default, |r| {
+            let r = r.json(request);
+            match if_match {
+                Some(if_match) => r.header(header::IF_MATCH, if_match.clone()),
+                None => r,
+            }
+        })
+        .await
+    }
+
+    /// `GET /nexus/load/:name` - Load a workspace
+    pub async fn load_workspace<T: DeserializeOwned>(
+        &self,
+        name: &str,
+    ) -> Result<HermesResponse<T>, HermesError> {
+        self.get(&format!("/nexus/load/{}", segment(name)?)).await
+    }
+
+    /// `GET /nexus/list` - List workspaces matching filters (`tag`,
+    /// `node_type`, `q`, `sort`)
+    pub async fn list_workspaces<T: DeserializeOwned>(
+        &self,
+        filters: &[(String, String)],
+    ) -> Result<HermesResponse<T>, HermesError> {
+        self.call(Method::GET, "/nexus/list", RouteClass::Default, |r| {
+            r.query(filters)
+        })
+        .await
+    }
+
+    /// `POST /nexus/migrate` - Migrate workspaces to the current format
+    pub async fn migrate_workspaces<T: DeserializeOwned>(
+        &self,
+        request: &impl Serialize,
+    ) -> Result<HermesResponse<T>, HermesError> {
+        self.post("/nexus/migrate", request).await
+    }
+
+    /// `POST /nexus/import` - Import a gzipped workspace bundle, with
+    /// options (`name`, `dry_run`, `overwrite`)
+    pub async fn import_workspace<T: DeserializeOwned>(
+        &self,
+        options: &[(String, String)],
+        archive: Bytes,
+    ) -> Result<HermesResponse<T>, HermesError> {
+        self.call(Method::POST, "/nexus/import", RouteClass::Bundle, |r| {
+            r.query(options)
+                .header(header::CONTENT_TYPE, "application/gzip")
+                .body(archive)
+        })
+        .await
+    }
+
+    /// `GET /nexus/:name/export` - Export a workspace as a gzipped bundle
+    pub async fn export_workspace(&self, name: &str) -> Result<Bundle, HermesError> {
+        let path = format!("/nexus/{}/export", segment(name)?);
+        let response = self
+            .send(Method::GET, &path, RouteClass::Bundle, |r| r)
+            .await?;
+
+        Ok(Bundle { response })
+    }
+
+    /// `GET /nexus/:name/revisions` - List the revisions of a workspace
+    pub async fn list_revisions<T: DeserializeOwned>(
+        &self,
+        name: &str,
+    ) -> Result<HermesResponse<T>, HermesError> {
+        self.get(&format!("/nexus/{}/revisions", segment(name)?))
+            .await
+    }
+
+    /// `GET /nexus/:name/revisions/:revision` - Load one revision
+    pub async fn load_revision<T: DeserializeOwned>(
+        &self,
+        name: &str,
+        revision: &str,
+    ) -> Result<HermesResponse<T>, HermesError> {
+        let path = format!("/nexus/{}/revisions/{}", segment(name)?, segment(revision)?);
+        self.get(&path).await
+    }
+
+    /// `POST /nexus/:name/revisions/:revision/restore` - Make an old
+    /// revision current
+    pub async fn restore_revision<T: DeserializeOwned>(
+        &self,
+        name: &str,
+        revision: &str,
+    ) -> Result<HermesResponse<T>, HermesError> {
+        let path = format!(
+            "/nexus/{}/revisions/{}/restore",
+            segment(name)?,
+            segment(revision)?
+        );
+        self.call(Method::POST, &path, RouteClass::Default, |r| r)
+            .await
+    }
+
+    /// `GET /nexus/:name/diff` - Compare two revisions (`from`, `to`)
+    pub async fn diff_revisions<T: DeserializeOwned>(
+        &self,
+        name: &str,
+        query: &[(String, String)],
+    ) -> Result<HermesResponse<T>, HermesError> {
+        let path = format!("/nexus/{}/diff", segment(name)?);
+        self.call(Method::GET, &path, RouteClass::Default, |r| r.query(query))
+            .await
+    }
+
+    /// `DELETE /nexus/:name` - Move a workspace to the trash
+    pub async fn delete_workspace<T: DeserializeOwned>(
+        &self,
+        name: &str,
+    ) -> Result<HermesResponse<T>, HermesError> {
+        let path = format!("/nexus/{}", segment(name)?);
+        self.call(Method::DELETE, &path, RouteClass::Default, |r| r)
+            .await
+    }
+
+    /// `POST /nexus/:name/rename` - Rename a workspace
+    pub async fn rename_workspace(
+        &self,
+        name: &str,
+        request: &impl Serialize,
+    ) -> Result<StatusCode, HermesError> {
+        let path = format!("/nexus/{}/rename", segment(name)?);
+        self.send(Method::POST, &path, RouteClass::Default, |r| {
+            r.json(request)
+        })
+        .await
+        .map(|response| response.status())
+    }
+
+    /// `POST /nexus/:name/duplicate` - Copy a workspace under a new name
+    pub async fn duplicate_workspace<T: DeserializeOwned>(
+        &self,
+        name: &str,
+        request: &impl Serialize,
+    ) -> Result<HermesResponse<T>, HermesError> {
+        let path = format!("/nexus/{}/duplicate", segment(name)?);
+        self.post(&path, request).await
+    }
+
+    /// `POST /nexus/:name/run` - Run a saved workspace with parameters
+    pub async fn run_workspace<T: DeserializeOwned>(
+        &self,
+        name: &str,
+        request: &impl Serialize,
+    ) -> Result<HermesResponse<T>, HermesError> {
+        let path = format!("/nexus/{}/run", segment(name)?);
+        self.call(Method::POST, &path, RouteClass::Run, |r| r.json(request))
+            .await
+    }
+
+    /// `POST /nexus/:name/instantiate` - Create a workspace from a template
+    pub async fn instantiate_template<T: DeserializeOwned>(
+        &self,
+        name: &str,
+        request: &impl Serialize,
+    ) -> Result<HermesResponse<T>, HermesError> {
+        let path = format!("/nexus/{}/instantiate", segment(name)?);
+        self.post(&path, request).await
+    }
+
+    // === Trash ===
+
+    /// `GET /trash` - List deleted workspaces
+    pub async fn list_trash<T: DeserializeOwned>(&self) -> Result<HermesResponse<T>, HermesError> {
+        self.get("/trash").await
+    }
+
+    /// `POST /trash/:trash_id/restore` - Restore a deleted workspace
+    pub async fn restore_from_trash<T: DeserializeOwned>(
+        &self,
+        trash_id: &str,
+    ) -> Result<HermesResponse<T>, HermesError> {
+        let path = format!("/trash/{}/restore", segment(trash_id)?);
+        self.call(Method::POST, &path, RouteClass::Default, |r| r)
+            .await
+    }
+
+    /// `DELETE /trash` or `DELETE /trash/:trash_id` - Purge the whole trash
+    /// or one entry
+    pub async fn purge_trash<T: DeserializeOwned>(
+        &self,
+        trash_id: Option<&str>,
+    ) -> Result<HermesResponse<T>, HermesError> {
+        let path = match trash_id {
+            Some(trash_id) => format!("/trash/{}", segment(trash_id)?),
+            None => "/trash".to_string(),
+        };
+        self.call(Method::DELETE, &path, RouteClass::Default, |r| r)
+            .await
+    }
+
+    // === Artifacts ===
+
+    /// `PUT /artifacts` - Store content in the artifact store and return its
+    /// handle
+    pub async fn put_artifact(&self, content: Bytes) -> Result<ArtifactRef, HermesError> {
+        let response = self
+            .call::<Value>(Method::PUT, "/artifacts", RouteClass::Bundle, |r| {
+                r.header(header::CONTENT_TYPE, "application/octet-stream")
+                    .body(content)
+            })
+            .await?;
+
+        ArtifactRef::from_value(&response.body).ok_or_else(|| {
+            HermesError::InvalidResponse(format!("Not an artifact handle: {}", response.body))
+        })
+    }
+
+    /// `GET /artifacts/:sha256` - Fetch the content of an artifact produced
+    /// by a node, forwarding the client's `Range` header
+    pub async fn fetch_artifact(
+        &self,
+        sha256: &str,
+        range: Option<&HeaderValue>,
+    ) -> Result<Artifact, HermesError> {
+        let path = format!("/artifacts/{}", segment(sha256)?);
+        let response = self
+            .send(Method::GET, &path, RouteClass::Bundle, |r| match range {
+                Some(range) => r.header(header::RANGE, range),
+                None => r,
+            })
+            .await?;
+
+        Ok(Artifact { response })
+    }
+}
+
+/// Extracts the Hermes client bound to the request's `x-request-id`
+#[async_trait]
+impl FromRequestParts<AppState> for HermesClient {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let hermes = state.hermes.clone();
+
+        Ok(match parts.headers.get(REQUEST_ID_HEADER) {
+            Some(request_id) => hermes.with_request_id(request_id.clone()),
+            None => hermes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{fake_hermes, serve_brazil, ARTIFACT};
+
+    #[tokio::test]
+    async fn test_preserves_status_and_body() {
+        let hermes = HermesClient::new(fake_hermes().await.url);
+
+        let error = hermes.node::<Value>("missing").await.unwrap_err();
+        assert_eq!(error.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(error.to_string(), "Node 'missing' not found");
+
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body, json!({ "error": "Node 'missing' not found" }));
+
+        let error = hermes.list_trash::<Value>().await.unwrap_err();
+        assert!(matches!(error, HermesError::InvalidResponse(_)));
+        assert_eq!(error.status(), StatusCode::BAD_GATEWAY);
+
+        let error = HermesClient::new("http://127.0.0.1:9")
+            .registry::<Value>(None)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, HermesError::Unavailable(_)));
+    }
+
+    #[tokio::test]
+    async fn test_path_parameters_stay_in_their_segment() {
+        let hermes = HermesClient::new(fake_hermes().await.url);
+
+        // Decoded by Brazil's router, e.g. from `/nexus/..%2F..%2Fgraphs%2Frun%3F/duplicate`
+        let name = "../../graphs/run?";
+        let response = hermes.duplicate_workspace::<Value>(name, &json!({})).await.unwrap();
+        assert_eq!(response.body, json!({ "name": name }));
+
+        let error = hermes.duplicate_workspace::<Value>("..", &json!({})).await.unwrap_err();
+        assert!(matches!(error, HermesError::InvalidPath(_)));
+        assert_eq!(error.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_forwards_request_id() {
+        let hermes = HermesClient::new(fake_hermes().await.url);
+
+        let response = hermes
+            .clone()
+            .with_request_id(HeaderValue::from_static("req-1"))
+            .node::<Value>("file-browser")
+            .await
+            .unwrap();
+        assert_eq!(response.body["request_id"], json!("req-1"));
+
+        // Calls outside a request get a fresh ID
+        let response = hermes.node::<Value>("file-browser").await.unwrap();
+        assert!(response.body["request_id"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_route_timeouts() {
+        let hermes = HermesClient::new(fake_hermes().await.url).with_timeouts(Timeouts {
+            run: Duration::from_millis(100),
+            ..Timeouts::default()
+        });
+
+        let slow = json!({ "delay_ms": 5000 });
+        let error = hermes.run_graph::<Value>(&slow).await.unwrap_err();
+        assert!(matches!(error, HermesError::Timeout(_)));
+        assert_eq!(error.status(), StatusCode::GATEWAY_TIMEOUT);
+
+        // Other routes keep the default timeout
+        assert!(hermes.node::<Value>("file-browser").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_router_passes_hermes_responses_through() {
+        let url = serve_brazil(AppState::new(fake_hermes().await.url)).await;
+
+        let client = reqwest::Client::new();
+        let response = client
+            .get(format!("{}/nodes/file-browser", url))
+            .header(REQUEST_ID_HEADER, "req-2")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[REQUEST_ID_HEADER], "req-2");
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["request_id"], json!("req-2"));
+
+        let response = client
+            .get(format!("{}/nodes/missing", url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(response.headers().contains_key(REQUEST_ID_HEADER));
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body, json!({ "error": "Node 'missing' not found" }));
+    }
+
+    #[tokio::test]
+    async fn test_router_streams_artifacts() {
+        let url = serve_brazil(AppState::new(fake_hermes().await.url)).await;
+        let artifact = format!("{}/artifacts/{}", url, "ab".repeat(32));
+        let client = reqwest::Client::new();
+
+        let response = client.get(&artifact).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_DISPOSITION], "attachment");
+        assert_eq!(response.headers()[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+        assert_eq!(response.headers()[header::ACCEPT_RANGES], "bytes");
+        assert_eq!(response.bytes().await.unwrap(), ARTIFACT);
+
+        let response = client
+            .get(&artifact)
+            .header(header::RANGE, "bytes=2-4")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes 2-4/10");
+        assert_eq!(response.bytes().await.unwrap(), "234");
+
+        let response = client
+            .get(&artifact)
+            .header(header::RANGE, "bytes=10-")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes */10");
+    }
+
+    #[tokio::test]
+    async fn test_router_streams_bundles() {
+        let url = serve_brazil(AppState::new(fake_hermes().await.url)).await;
+
+        let response = reqwest::get(format!("{}/nexus/demo/export", url)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/gzip");
+        assert_eq!(
+            response.headers()[header::CONTENT_DISPOSITION],
+            "attachment; filename=\"demo.tar.gz\""
+        );
+        assert_eq!(response.bytes().await.unwrap(), "bundle");
+    }
+}
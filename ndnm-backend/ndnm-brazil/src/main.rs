@@ -10,42 +10,251 @@
 //! - Relay commands from frontend to Hermes
 //! - Broadcast state updates from Hermes to connected clients
 //! - Transform data structures between frontend and backend formats
-//! - (Future) Authentication and authorization
-
+//! - Authenticate callers and enforce their roles
+//!
+//! ## Graph Translation
+//!
+//! `/graphs/run` accepts graphs in the frontend format (`id`, `node_type`,
+//! index-based handles) and translates them to Hermes' `GraphDefinition`
+//! using the Hermes registry. Graphs already in Hermes' format are forwarded
+//! unchanged. Graphs that cannot be translated are answered with 422 and a
+//! list of field-level errors; `/graphs/translate` returns the translation
+//! without running it.
+//!
+//! ## WebSocket Commands
+//!
+//! Clients can drive Brazil over `/ws` instead of HTTP: `run_graph`,
+//! `cancel_execution`, `save_workspace`, `load_workspace`, `list_workspaces`
+//! and `get_registry` are answered to the calling client only, matched by
+//! correlation id (see `commands`). Events such as finished executions and
+//! saved workspaces go only to clients subscribed to their topic
+//! (`execution:<id>`, `workspace:<name>`, `registry`, `logs`).
+//!
+//! A client that loses its connection can reconnect with
+//! `/ws?session=<token>&last_seq=<n>` within a minute to keep its client ID
+//! and receive the messages it missed.
+//!
+//! ## Authentication
+//!
+//! When enabled in `config.yaml`, every route except `/health` and
+//! `/auth/login` needs a bearer token: a JWT from `/auth/login` or a static
+//! API token. Routes and WebSocket commands require the `viewer`, `editor`,
+//! `operator` or `admin` role (see `auth`).
+//!
+//! Browsers may only call Brazil from its own origin, unless other origins
+//! are listed in `CORS_ALLOWED_ORIGINS` (comma separated, e.g.
+//! `http://localhost:5173` for the frontend's dev server).
+//!
+//! ## Rate Limiting
+//!
+//! Each user (or client IP, without authentication) has token buckets for
+//! runs, saves, reads and WebSocket commands. Requests over the limit get
+//! 429 with `Retry-After`; the counters are reported to Exdoida (see
+//! `rate_limit`).
+//!
+//! ## Hermes API
+//!
+//! Every Hermes route is exposed under the same path, through the typed
+//! client in `hermes`. Hermes' status codes and error bodies are passed
+//! through unchanged; Brazil answers 502 when Hermes cannot be reached and
+//! 504 when it does not answer in time. Requests carry an `x-request-id`
+//! (the client's, or a new one) that is forwarded to Hermes and echoed in
+//! the response.
+//!
+//! The node registry is cached and revalidated with Hermes; changes are
+//! pushed to the `registry` topic (see `registry`).
+//!
+//! Calls to Hermes are signed with `NDNM_SERVICE_SECRET` when it is set
+//! (see `ndnm_libs::signing`).
+//!
+//! ## Blobs
+//!
+//! Large files are uploaded to `/blobs` (streamed or multipart) and stored
+//! by SHA-256. Frontend graphs reference them in a node's `inputs`, and
+//! Brazil hands them to Hermes' artifact store when the graph is sent to
+//! Hermes; large node outputs
+//! come back as references to download, with `Range` support, from
+//! `/blobs/:sha256` (see `blobs`). Artifacts that nodes store in Hermes
+//! (outputs of the form `{"$ref": "sha256:..."}`) are relayed from
+//! `/artifacts/:sha256`.
+//!
+//! ## Collaborative Editing
+//!
+//! Clients editing the same workspace join its room; Brazil orders their
+//! graph operations, keeps the authoritative state in memory and saves it
+//! to Hermes periodically (see `collab`).
+
+mod auth;
+mod blobs;
+mod collab;
+mod commands;
+mod config;
+mod hermes;
+mod rate_limit;
+mod registry;
+mod translate;
 mod websocket;
+#[cfg(test)]
+mod test_support;
 
 use anyhow::Result;
 use axum::{
-    extract::State,
-    http::StatusCode,
-    routing::{get, post},
+    body::Bytes,
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    middleware,
+    routing::{delete, get, post, put},
     Json, Router,
 };
+use hermes::{Artifact, Bundle, HermesClient, HermesError, HermesResponse};
+use ndnm_libs::{AppError, Signer};
 use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::time::Duration;
 use tokio::net::TcpListener;
-use tower_http::{cors::CorsLayer, trace::TraceLayer};
+use tokio::task::JoinHandle;
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+use tower_http::{
+    cors::{AllowOrigin, Any, CorsLayer},
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::TraceLayer,
+};
 use tracing::{info, warn};
 
+/// Default URL of Exdoida, the log service
+const DEFAULT_EXDOIDA_URL: &str = "http://localhost:3003";
+
+/// Largest workspace bundle accepted for import (matches Hermes)
+const MAX_BUNDLE_SIZE: usize = 256 * 1024 * 1024;
+
+/// Environment variable listing the origins allowed to call Brazil from a
+/// browser
+const CORS_ORIGINS_ENV: &str = "CORS_ALLOWED_ORIGINS";
+
+/// How long a finished run's event relay may take to deliver its last events
+const RELAY_DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
 /// Main application state shared across handlers
 #[derive(Clone)]
 struct AppState {
-    /// HTTP client for communicating with Hermes
-    hermes_client: reqwest::Client,
-    /// Base URL for Hermes API
-    hermes_url: String,
+    /// Client for the Hermes API
+    hermes: HermesClient,
+    /// Cached Hermes node registry
+    registry: registry::RegistryCache,
+    /// Uploaded files and offloaded node outputs
+    blobs: blobs::BlobStore,
+    /// HTTP client for other services
+    http_client: reqwest::Client,
+    /// Base URL for the Exdoida API
+    exdoida_url: String,
+    /// Authentication settings
+    auth: auth::Auth,
+    /// Per-client rate limits
+    rate_limiter: rate_limit::RateLimiter,
     /// WebSocket broadcaster for sending updates to clients
     ws_broadcaster: websocket::Broadcaster,
+    /// Cancelled when Brazil starts shutting down
+    shutdown: CancellationToken,
+    /// Tracks live WebSocket connections so shutdown can wait for them
+    ws_tasks: TaskTracker,
+    /// Resumable WebSocket sessions
+    ws_sessions: websocket::Sessions,
+    /// Open collaborative workspace rooms
+    rooms: collab::Rooms,
+    /// Origins allowed to call Brazil from a browser, besides its own
+    cors_origins: Vec<HeaderValue>,
 }
 
 impl AppState {
     /// Create a new application state
     fn new(hermes_url: String) -> Self {
         Self {
-            hermes_client: reqwest::Client::new(),
-            hermes_url,
+            hermes: HermesClient::new(hermes_url),
+            registry: registry::RegistryCache::default(),
+            blobs: blobs::BlobStore::default(),
+            http_client: reqwest::Client::new(),
+            exdoida_url: DEFAULT_EXDOIDA_URL.to_string(),
+            auth: auth::Auth::default(),
+            rate_limiter: rate_limit::RateLimiter::default(),
             ws_broadcaster: websocket::Broadcaster::new(),
+            shutdown: CancellationToken::new(),
+            ws_tasks: TaskTracker::new(),
+            ws_sessions: websocket::Sessions::default(),
+            rooms: collab::Rooms::default(),
+            cors_origins: Vec::new(),
         }
     }
+
+    /// Use the given authentication settings
+    fn with_auth(mut self, auth: auth::Auth) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    /// Use the given Hermes client
+    fn with_hermes(mut self, hermes: HermesClient) -> Self {
+        self.hermes = hermes;
+        self
+    }
+
+    /// Use the given registry cache
+    fn with_registry(mut self, registry: registry::RegistryCache) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    /// Use the given blob store
+    fn with_blobs(mut self, blobs: blobs::BlobStore) -> Self {
+        self.blobs = blobs;
+        self
+    }
+
+    /// Use the given rate limiter
+    fn with_rate_limiter(mut self, rate_limiter: rate_limit::RateLimiter) -> Self {
+        self.rate_limiter = rate_limiter;
+        self
+    }
+
+    /// Use the given Exdoida URL
+    fn with_exdoida_url(mut self, exdoida_url: String) -> Self {
+        self.exdoida_url = exdoida_url;
+        self
+    }
+
+    /// Allow the given origins to call Brazil from a browser
+    fn with_cors_origins(mut self, origins: Vec<HeaderValue>) -> Self {
+        self.cors_origins = origins;
+        self
+    }
+}
+
+/// Read the origins allowed to call Brazil from `CORS_ALLOWED_ORIGINS`
+///
+/// Unset or empty means same-origin only.
+fn cors_origins_from_env() -> Result<Vec<HeaderValue>> {
+    let origins = std::env::var(CORS_ORIGINS_ENV).unwrap_or_default();
+
+    origins
+        .split(',')
+        .map(str::trim)
+        .filter(|origin| !origin.is_empty())
+        .map(|origin| {
+            HeaderValue::from_str(origin).map_err(|_| {
+                anyhow::anyhow!("Invalid origin '{}' in {}", origin, CORS_ORIGINS_ENV)
+            })
+        })
+        .collect()
+}
+
+/// CORS for the allowed origins; other origins get no CORS headers, so
+/// browsers block their cross-origin calls
+fn cors_layer(origins: &[HeaderValue]) -> CorsLayer {
+    CorsLayer::new()
+        .allow_origin(AllowOrigin::list(origins.iter().cloned()))
+        .allow_methods(Any)
+        .allow_headers(Any)
+        .expose_headers(Any)
 }
 
 // === API Handlers ===
@@ -56,171 +265,533 @@ struct HealthResponse {
     status: String,
     service: String,
     hermes_connected: bool,
+    /// Number of connected WebSocket clients
+    ws_clients: usize,
+    /// Whether callers must log in
+    auth_enabled: bool,
 }
 
 /// Handler for GET /health
-async fn health_check(State(state): State<AppState>) -> Json<HealthResponse> {
+async fn health_check(State(state): State<AppState>, hermes: HermesClient) -> Json<HealthResponse> {
     // Try to check if Hermes is accessible
-    let hermes_url = format!("{}/health", state.hermes_url);
-    let hermes_connected = state
-        .hermes_client
-        .get(&hermes_url)
-        .send()
-        .await
-        .map(|r| r.status().is_success())
-        .unwrap_or(false);
+    let hermes_connected = hermes.is_healthy().await;
 
     Json(HealthResponse {
         status: "healthy".to_string(),
         service: "ndnm-brazil".to_string(),
         hermes_connected,
+        ws_clients: state.ws_broadcaster.client_count(),
+        auth_enabled: state.auth.is_enabled(),
     })
 }
 
-/// Request to get node registry from Hermes
-async fn get_node_registry(State(state): State<AppState>) -> Result<Json<serde_json::Value>, StatusCode> {
-    let url = format!("{}/nodes/registry", state.hermes_url);
+/// Handler for GET /health/all - Health of Hermes and every node
+async fn health_check_all(
+    hermes: HermesClient,
+) -> Result<HermesResponse<serde_json::Value>, HermesError> {
+    hermes.health_all().await
+}
 
-    match state.hermes_client.get(&url).send().await {
-        Ok(response) => {
-            if response.status().is_success() {
-                match response.json::<serde_json::Value>().await {
-                    Ok(data) => Ok(Json(data)),
-                    Err(e) => {
-                        warn!("Failed to parse registry response: {}", e);
-                        Err(StatusCode::INTERNAL_SERVER_ERROR)
-                    }
-                }
-            } else {
-                warn!("Hermes returned status: {}", response.status());
-                Err(StatusCode::BAD_GATEWAY)
-            }
-        }
-        Err(e) => {
-            warn!("Failed to connect to Hermes: {}", e);
-            Err(StatusCode::BAD_GATEWAY)
-        }
+/// Handler for GET /nodes/registry
+///
+/// Served from the registry cache; clients sending its `ETag` in
+/// `If-None-Match` get 304 Not Modified.
+async fn get_node_registry(
+    State(state): State<AppState>,
+    hermes: HermesClient,
+    headers: HeaderMap,
+) -> Result<Response, HermesError> {
+    let registry = state.registry.get(&hermes, &state.ws_broadcaster).await?;
+    let etag = [(header::ETAG, registry.etag.clone())];
+
+    if registry.matches(&headers) {
+        return Ok((StatusCode::NOT_MODIFIED, etag).into_response());
     }
+
+    Ok((etag, Json(&registry.body)).into_response())
+}
+
+/// Handler for GET /nodes/:node_id
+async fn get_node_info(
+    hermes: HermesClient,
+    Path(node_id): Path<String>,
+) -> Result<HermesResponse<serde_json::Value>, HermesError> {
+    hermes.node(&node_id).await
 }
 
 /// Request to execute a graph
 #[derive(Debug, Serialize, Deserialize)]
 struct ExecuteGraphRequest {
+    /// Optional execution ID (generated by Hermes if not provided)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    execution_id: Option<String>,
     graph: serde_json::Value,
+    /// Workspace the graph belongs to, so Hermes can record its last run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    workspace: Option<String>,
 }
 
-/// Handler for POST /graphs/run
-async fn execute_graph(
-    State(state): State<AppState>,
-    Json(request): Json<ExecuteGraphRequest>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    info!("Received graph execution request via BFF");
+impl ExecuteGraphRequest {
+    /// Build a request from a `/graphs/run` body
+    ///
+    /// The body is either `{graph, workspace, execution_id}` or a bare
+    /// frontend graph.
+    fn from_body(mut body: serde_json::Value) -> Self {
+        let field = |body: &serde_json::Value, key: &str| {
+            body.get(key).and_then(|v| v.as_str()).map(str::to_string)
+        };
+
+        match body.get_mut("graph").map(serde_json::Value::take) {
+            Some(graph) => Self {
+                execution_id: field(&body, "execution_id"),
+                graph,
+                workspace: field(&body, "workspace"),
+            },
+            None => Self {
+                execution_id: None,
+                graph: body,
+                workspace: None,
+            },
+        }
+    }
+}
 
-    let url = format!("{}/graphs/run", state.hermes_url);
+/// Whether a graph is in the frontend format rather than Hermes' format
+fn is_frontend_graph(graph: &serde_json::Value) -> bool {
+    let has_key = |list: &str, key: &str| {
+        graph
+            .get(list)
+            .and_then(|v| v.as_array())
+            .is_some_and(|items| items.iter().any(|item| item.get(key).is_some()))
+    };
 
-    // Forward the request to Hermes
-    match state
-        .hermes_client
-        .post(&url)
-        .json(&request)
-        .send()
-        .await
-    {
-        Ok(response) => {
-            if response.status().is_success() {
-                match response.json::<serde_json::Value>().await {
-                    Ok(data) => {
-                        // Broadcast execution update to WebSocket clients
-                        state
-                            .ws_broadcaster
-                            .broadcast_json(&serde_json::json!({
-                                "type": "graph_execution_complete",
-                                "data": data
-                            }))
-                            .await;
-
-                        Ok(Json(data))
-                    }
-                    Err(e) => {
-                        warn!("Failed to parse execution response: {}", e);
-                        Err(StatusCode::INTERNAL_SERVER_ERROR)
-                    }
-                }
-            } else {
-                warn!("Hermes returned error status: {}", response.status());
-                Err(StatusCode::BAD_GATEWAY)
+    has_key("nodes", "node_type") || has_key("connections", "from_node_id")
+}
+
+/// Why a graph could not be prepared for Hermes
+enum TranslateFailure {
+    /// The frontend graph has field-level errors
+    Invalid(Vec<translate::TranslationError>),
+    /// Hermes could not provide the registry
+    Hermes(HermesError),
+    /// A referenced blob could not be read
+    Blob(blobs::BlobError),
+    /// The translated graph could not be serialized
+    Internal(String),
+}
+
+impl IntoResponse for TranslateFailure {
+    fn into_response(self) -> Response {
+        match self {
+            TranslateFailure::Invalid(errors) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(translate::TranslationErrorResponse::new(errors)),
+            )
+                .into_response(),
+            TranslateFailure::Hermes(error) => error.into_response(),
+            TranslateFailure::Blob(error) => error.into_response(),
+            TranslateFailure::Internal(message) => {
+                AppError::Internal(message).into_response()
             }
         }
+    }
+}
+
+/// Translate a frontend graph using the current Hermes registry
+async fn translate_frontend_graph(
+    state: &AppState,
+    hermes: &HermesClient,
+    graph: serde_json::Value,
+) -> Result<translate::TranslatedGraph, TranslateFailure> {
+    let graph = translate::parse_frontend_graph(graph).map_err(TranslateFailure::Invalid)?;
+    let registry = state
+        .registry
+        .get(hermes, &state.ws_broadcaster)
+        .await
+        .map_err(TranslateFailure::Hermes)?;
+
+    translate::translate_graph(&graph, &registry.nodes).map_err(TranslateFailure::Invalid)
+}
+
+/// Build the Hermes run request for a `/graphs/run` body
+///
+/// Frontend graphs are translated; Hermes graphs are passed unchanged. Blob
+/// references in node inputs are replaced with artifact handles.
+async fn prepare_graph_request(
+    state: &AppState,
+    hermes: &HermesClient,
+    body: serde_json::Value,
+) -> Result<ExecuteGraphRequest, TranslateFailure> {
+    let mut request = ExecuteGraphRequest::from_body(body);
+
+    if is_frontend_graph(&request.graph) {
+        let translated = translate_frontend_graph(state, hermes, request.graph).await?;
+
+        request.graph = serde_json::to_value(translated.graph).map_err(|e| {
+            warn!("Failed to serialize translated graph: {}", e);
+            TranslateFailure::Internal(format!("Failed to serialize translated graph: {}", e))
+        })?;
+    }
+
+    state
+        .blobs
+        .resolve_inputs(&mut request.graph, hermes)
+        .await
+        .map_err(TranslateFailure::Blob)?;
+
+    Ok(request)
+}
+
+/// Forward the progress events of an execution to its WebSocket subscribers
+///
+/// Subscribes before returning, so the run can start right after without
+/// missing events. Hermes ends the stream with the execution.
+///
+/// # Returns
+///
+/// * `Some(JoinHandle)` - The relay, to pass to `finish_relay`
+/// * `None` - Hermes did not accept the subscription
+async fn relay_execution_events(
+    state: &AppState,
+    hermes: &HermesClient,
+    execution_id: &str,
+) -> Option<JoinHandle<()>> {
+    let mut events = match hermes.execution_events(execution_id).await {
+        Ok(events) => events,
         Err(e) => {
-            warn!("Failed to connect to Hermes: {}", e);
-            Err(StatusCode::BAD_GATEWAY)
+            warn!("Not relaying events of execution {}: {}", execution_id, e);
+            return None;
         }
+    };
+
+    let broadcaster = state.ws_broadcaster.clone();
+    Some(tokio::spawn(async move {
+        while let Some(event) = events.next().await {
+            broadcaster.publish_execution_event(&event).await;
+        }
+    }))
+}
+
+/// Let a relay deliver the last events of a finished run, then stop it
+///
+/// The stream of an execution Hermes never started stays open, hence the
+/// bounded wait.
+async fn finish_relay(relay: Option<JoinHandle<()>>) {
+    if let Some(mut relay) = relay
+        && tokio::time::timeout(RELAY_DRAIN_TIMEOUT, &mut relay)
+            .await
+            .is_err()
+    {
+        relay.abort();
     }
 }
 
+/// Handler for POST /graphs/translate
+///
+/// Returns the Hermes graph and node ports for a frontend graph.
+async fn translate_graph(
+    State(state): State<AppState>,
+    hermes: HermesClient,
+    Json(body): Json<serde_json::Value>,
+) -> Response {
+    let request = ExecuteGraphRequest::from_body(body);
+
+    match translate_frontend_graph(&state, &hermes, request.graph).await {
+        Ok(translated) => Json(translated).into_response(),
+        Err(failure) => failure.into_response(),
+    }
+}
+
+/// Handler for POST /graphs/run
+///
+/// Frontend graphs are translated before being forwarded to Hermes.
+async fn execute_graph(
+    State(state): State<AppState>,
+    hermes: HermesClient,
+    Json(body): Json<serde_json::Value>,
+) -> Result<Response, HermesError> {
+    info!("Received graph execution request via BFF");
+
+    let request = match prepare_graph_request(&state, &hermes, body).await {
+        Ok(request) => request,
+        Err(failure) => return Ok(failure.into_response()),
+    };
+
+    // Relay progress to WebSocket clients following this execution
+    let relay = match &request.execution_id {
+        Some(execution_id) => relay_execution_events(&state, &hermes, execution_id).await,
+        None => None,
+    };
+
+    let response = hermes.run_graph::<serde_json::Value>(&request).await;
+    finish_relay(relay).await;
+    let mut response = response?;
+    state.blobs.offload_outputs(&mut response.body).await;
+
+    // Notify WebSocket clients following this execution
+    state
+        .ws_broadcaster
+        .publish_execution_complete(&response.body, request.workspace.as_deref())
+        .await;
+
+    Ok(response.into_response())
+}
+
+/// Handler for POST /executions/:execution_id/cancel
+///
+/// Executions that are not running are answered with Hermes' 400.
+async fn cancel_execution(
+    hermes: HermesClient,
+    Path(execution_id): Path<String>,
+) -> Result<HermesResponse<serde_json::Value>, HermesError> {
+    info!("Received cancel request for execution '{}' via BFF", execution_id);
+
+    hermes.cancel_execution(&execution_id).await
+}
+
+/// Handler for POST /nexus/:name/run
+///
+/// Runs a saved workspace with a parameter map. Invalid parameters are
+/// answered with Hermes' 400 and its error body, so the UI can show them.
+/// Workspaces referencing blobs are refused with 400: Hermes runs the saved
+/// graph itself, so their nodes would get the references unresolved.
+async fn run_workspace(
+    State(state): State<AppState>,
+    hermes: HermesClient,
+    Path(name): Path<String>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<Response, HermesError> {
+    info!("Received workspace run request for '{}' via BFF", name);
+
+    let workspace = hermes.load_workspace::<serde_json::Value>(&name).await?;
+    if blobs::references_blobs(&workspace.body["graph"]) {
+        return Ok(AppError::BadRequest(format!(
+            "Workspace '{}' references uploaded blobs; load it and run its graph \
+             through /graphs/run",
+            name
+        ))
+        .into_response());
+    }
+
+    let mut response = hermes.run_workspace(&name, &request).await?;
+    state.blobs.offload_outputs(&mut response.body).await;
+
+    // Notify WebSocket clients following this execution or workspace
+    state
+        .ws_broadcaster
+        .publish_execution_complete(&response.body, Some(&name))
+        .await;
+
+    Ok(response.into_response())
+}
+
 /// Handler for POST /nexus/save
+///
+/// Forwards `If-Match` to Hermes. A stale save is answered with 409 Conflict
+/// and Hermes' body, which carries the current version of the workspace.
 async fn save_workspace(
     State(state): State<AppState>,
+    hermes: HermesClient,
+    headers: HeaderMap,
     Json(request): Json<serde_json::Value>,
-) -> Result<StatusCode, StatusCode> {
-    let url = format!("{}/nexus/save", state.hermes_url);
-
-    match state
-        .hermes_client
-        .post(&url)
-        .json(&request)
-        .send()
-        .await
-    {
-        Ok(response) => {
-            if response.status().is_success() {
-                Ok(StatusCode::OK)
-            } else {
-                Err(StatusCode::BAD_GATEWAY)
-            }
-        }
-        Err(_) => Err(StatusCode::BAD_GATEWAY),
+) -> Result<HermesResponse<serde_json::Value>, HermesError> {
+    let response = hermes
+        .save_workspace(&request, headers.get(header::IF_MATCH))
+        .await?;
+
+    if let Some(name) = request.get("name").and_then(|n| n.as_str()) {
+        state
+            .ws_broadcaster
+            .publish_workspace_saved(name, &response.body)
+            .await;
     }
+
+    Ok(response)
 }
 
 /// Handler for GET /nexus/list
-async fn list_workspaces(State(state): State<AppState>) -> Result<Json<serde_json::Value>, StatusCode> {
-    let url = format!("{}/nexus/list", state.hermes_url);
-
-    match state.hermes_client.get(&url).send().await {
-        Ok(response) => {
-            if response.status().is_success() {
-                match response.json::<serde_json::Value>().await {
-                    Ok(data) => Ok(Json(data)),
-                    Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
-                }
-            } else {
-                Err(StatusCode::BAD_GATEWAY)
-            }
-        }
-        Err(_) => Err(StatusCode::BAD_GATEWAY),
-    }
+///
+/// Filters and sort order (`tag`, `node_type`, `q`, `sort`) are forwarded
+/// to Hermes as-is
+async fn list_workspaces(
+    hermes: HermesClient,
+    Query(query): Query<Vec<(String, String)>>,
+) -> Result<HermesResponse<serde_json::Value>, HermesError> {
+    hermes.list_workspaces(&query).await
 }
 
 /// Handler for GET /nexus/load/:name
 async fn load_workspace(
+    hermes: HermesClient,
+    Path(name): Path<String>,
+) -> Result<HermesResponse<serde_json::Value>, HermesError> {
+    hermes.load_workspace(&name).await
+}
+
+/// Handler for GET /nexus/:name/revisions
+async fn list_revisions(
+    hermes: HermesClient,
+    Path(name): Path<String>,
+) -> Result<HermesResponse<serde_json::Value>, HermesError> {
+    hermes.list_revisions(&name).await
+}
+
+/// Handler for GET /nexus/:name/revisions/:revision
+async fn load_revision(
+    hermes: HermesClient,
+    Path((name, revision)): Path<(String, String)>,
+) -> Result<HermesResponse<serde_json::Value>, HermesError> {
+    hermes.load_revision(&name, &revision).await
+}
+
+/// Handler for POST /nexus/:name/revisions/:revision/restore
+async fn restore_revision(
     State(state): State<AppState>,
-    axum::extract::Path(name): axum::extract::Path<String>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    let url = format!("{}/nexus/load/{}", state.hermes_url, name);
+    hermes: HermesClient,
+    Path((name, revision)): Path<(String, String)>,
+) -> Result<HermesResponse<serde_json::Value>, HermesError> {
+    let response = hermes.restore_revision(&name, &revision).await?;
+
+    state
+        .ws_broadcaster
+        .publish_workspace_saved(&name, &response.body)
+        .await;
+
+    Ok(response)
+}
+
+/// Handler for GET /nexus/:name/diff
+///
+/// The revisions to compare (`from`, `to`) are forwarded to Hermes as-is
+async fn diff_revisions(
+    hermes: HermesClient,
+    Path(name): Path<String>,
+    Query(query): Query<Vec<(String, String)>>,
+) -> Result<HermesResponse<serde_json::Value>, HermesError> {
+    hermes.diff_revisions(&name, &query).await
+}
+
+/// Handler for DELETE /nexus/:name - Move a workspace to the trash
+async fn delete_workspace(
+    hermes: HermesClient,
+    Path(name): Path<String>,
+) -> Result<HermesResponse<serde_json::Value>, HermesError> {
+    hermes.delete_workspace(&name).await
+}
+
+/// Handler for POST /nexus/:name/rename
+async fn rename_workspace(
+    hermes: HermesClient,
+    Path(name): Path<String>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<StatusCode, HermesError> {
+    hermes.rename_workspace(&name, &request).await
+}
+
+/// Handler for POST /nexus/migrate
+///
+/// Forwards bulk workspace migrations (and dry runs) to Hermes
+async fn migrate_workspaces(
+    hermes: HermesClient,
+    Json(request): Json<serde_json::Value>,
+) -> Result<HermesResponse<serde_json::Value>, HermesError> {
+    hermes.migrate_workspaces(&request).await
+}
+
+/// Handler for POST /nexus/:name/duplicate
+async fn duplicate_workspace(
+    hermes: HermesClient,
+    Path(name): Path<String>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<HermesResponse<serde_json::Value>, HermesError> {
+    hermes.duplicate_workspace(&name, &request).await
+}
+
+/// Handler for POST /nexus/:name/instantiate - Create a workspace from a template
+///
+/// Invalid parameters are answered with Hermes' 400 and its error body.
+async fn instantiate_template(
+    hermes: HermesClient,
+    Path(name): Path<String>,
+    Json(request): Json<serde_json::Value>,
+) -> Result<HermesResponse<serde_json::Value>, HermesError> {
+    hermes.instantiate_template(&name, &request).await
+}
+
+/// Handler for GET /trash - List deleted workspaces
+async fn list_trash(
+    hermes: HermesClient,
+) -> Result<HermesResponse<serde_json::Value>, HermesError> {
+    hermes.list_trash().await
+}
+
+/// Handler for POST /trash/:trash_id/restore
+async fn restore_from_trash(
+    hermes: HermesClient,
+    Path(trash_id): Path<String>,
+) -> Result<HermesResponse<serde_json::Value>, HermesError> {
+    hermes.restore_from_trash(&trash_id).await
+}
+
+/// Handler for DELETE /trash and DELETE /trash/:trash_id - Purge the trash
+async fn purge_trash(
+    hermes: HermesClient,
+    trash_id: Option<Path<String>>,
+) -> Result<HermesResponse<serde_json::Value>, HermesError> {
+    hermes
+        .purge_trash(trash_id.as_ref().map(|Path(id)| id.as_str()))
+        .await
+}
+
+/// Handler for GET /nexus/:name/export
+///
+/// Streams the workspace bundle produced by Hermes back to the client
+async fn export_workspace(
+    hermes: HermesClient,
+    Path(name): Path<String>,
+) -> Result<Bundle, HermesError> {
+    hermes.export_workspace(&name).await
+}
+
+/// Handler for POST /nexus/import
+///
+/// Forwards the uploaded bundle and query options (`name`, `dry_run`,
+/// `overwrite`) to Hermes
+async fn import_workspace(
+    hermes: HermesClient,
+    Query(query): Query<Vec<(String, String)>>,
+    body: Bytes,
+) -> Result<HermesResponse<serde_json::Value>, HermesError> {
+    hermes.import_workspace(&query, body).await
+}
 
-    match state.hermes_client.get(&url).send().await {
+/// Handler for GET /artifacts/:sha256 - Stream an artifact from Hermes,
+/// with byte range support
+async fn fetch_artifact(
+    hermes: HermesClient,
+    Path(sha256): Path<String>,
+    headers: HeaderMap,
+) -> Result<Artifact, HermesError> {
+    hermes.fetch_artifact(&sha256, headers.get(header::RANGE)).await
+}
+
+/// Handler for DELETE /logs - Clear all logs in Exdoida
+async fn clear_logs(State(state): State<AppState>) -> StatusCode {
+    let url = format!("{}/logs", state.exdoida_url);
+
+    match state.http_client.delete(&url).send().await {
+        Ok(response) if response.status().is_success() => {
+            info!("Cleared Exdoida logs");
+            StatusCode::OK
+        }
         Ok(response) => {
-            if response.status().is_success() {
-                match response.json::<serde_json::Value>().await {
-                    Ok(data) => Ok(Json(data)),
-                    Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
-                }
-            } else {
-                Err(StatusCode::BAD_GATEWAY)
-            }
+            warn!("Exdoida returned status: {}", response.status());
+            StatusCode::BAD_GATEWAY
+        }
+        Err(e) => {
+            warn!("Failed to connect to Exdoida: {}", e);
+            StatusCode::BAD_GATEWAY
         }
-        Err(_) => Err(StatusCode::BAD_GATEWAY),
     }
 }
 
@@ -229,22 +800,80 @@ fn create_router(state: AppState) -> Router {
     Router::new()
         // Health check
         .route("/health", get(health_check))
+        .route("/health/all", get(health_check_all))
+        // Authentication
+        .route("/auth/login", post(auth::login))
+        .route("/auth/me", get(auth::me))
         // WebSocket endpoint
         .route("/ws", get(websocket::websocket_handler))
         // Node registry
         .route("/nodes/registry", get(get_node_registry))
+        .route("/nodes/:node_id", get(get_node_info))
         // Graph execution
         .route("/graphs/run", post(execute_graph))
+        .route("/graphs/translate", post(translate_graph))
+        .route("/executions/:execution_id/cancel", post(cancel_execution))
+        // Blobs (size limited by the store rather than the body limit)
+        .route(
+            "/blobs",
+            put(blobs::upload)
+                .post(blobs::upload_multipart)
+                .layer(DefaultBodyLimit::disable()),
+        )
+        .route("/blobs/:sha256", get(blobs::download))
+        .route("/artifacts/:sha256", get(fetch_artifact))
         // Workspace management
         .route("/nexus/save", post(save_workspace))
         .route("/nexus/list", get(list_workspaces))
         .route("/nexus/load/:name", get(load_workspace))
+        .route("/nexus/migrate", post(migrate_workspaces))
+        .route(
+            "/nexus/import",
+            post(import_workspace).layer(DefaultBodyLimit::max(MAX_BUNDLE_SIZE)),
+        )
+        .route("/nexus/:name/export", get(export_workspace))
+        .route("/nexus/:name/revisions", get(list_revisions))
+        .route("/nexus/:name/revisions/:revision", get(load_revision))
+        .route(
+            "/nexus/:name/revisions/:revision/restore",
+            post(restore_revision),
+        )
+        .route("/nexus/:name/diff", get(diff_revisions))
+        .route("/nexus/:name", delete(delete_workspace))
+        .route("/nexus/:name/rename", post(rename_workspace))
+        .route("/nexus/:name/duplicate", post(duplicate_workspace))
+        .route("/nexus/:name/run", post(run_workspace))
+        .route("/nexus/:name/instantiate", post(instantiate_template))
+        // Trash (deleted workspaces)
+        .route("/trash", get(list_trash).delete(purge_trash))
+        .route("/trash/:trash_id", delete(purge_trash))
+        .route("/trash/:trash_id/restore", post(restore_from_trash))
+        // Logs
+        .route("/logs", delete(clear_logs))
         // Middleware
-        .layer(CorsLayer::permissive())
+        .route_layer(middleware::from_fn_with_state(state.clone(), rate_limit::limit))
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth::authorize))
+        .layer(cors_layer(&state.cors_origins))
         .layer(TraceLayer::new_for_http())
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
         .with_state(state)
 }
 
+/// Read a password from stdin and print its hash for `config.yaml`
+fn hash_password() -> Result<()> {
+    let mut password = String::new();
+    std::io::stdin().read_line(&mut password)?;
+
+    let password = password.trim_end_matches(['\r', '\n']);
+    if password.is_empty() {
+        anyhow::bail!("Usage: echo <password> | ndnm-brazil hash-password");
+    }
+
+    println!("{}", auth::hash_password(password)?);
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize tracing
@@ -255,6 +884,14 @@ async fn main() -> Result<()> {
         )
         .init();
 
+    // One-shot commands
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("hash-password") => return hash_password(),
+        Some(command) => anyhow::bail!("Unknown command '{}'", command),
+        None => {}
+    }
+
     info!("Starting NDNM Brazil - Backend-for-Frontend 🇧🇷");
 
     // Get Hermes URL from environment or use default
@@ -263,8 +900,36 @@ async fn main() -> Result<()> {
 
     info!("Configured to connect to Hermes at: {}", hermes_url);
 
+    let exdoida_url =
+        std::env::var("EXDOIDA_URL").unwrap_or_else(|_| DEFAULT_EXDOIDA_URL.to_string());
+
     // Create application state
-    let state = AppState::new(hermes_url);
+    let hermes = HermesClient::new(&hermes_url)
+        .with_signer(Signer::from_env()?)
+        .with_timeouts(hermes::Timeouts::from_env());
+
+    let state = AppState::new(hermes_url)
+        .with_hermes(hermes)
+        .with_registry(registry::RegistryCache::default().with_ttl(registry::ttl_from_env()))
+        .with_auth(auth::Auth::from_env()?)
+        .with_rate_limiter(rate_limit::RateLimiter::from_env()?)
+        .with_blobs(blobs::BlobStore::from_env())
+        .with_exdoida_url(exdoida_url)
+        .with_cors_origins(cors_origins_from_env()?);
+    let shutdown = state.shutdown.clone();
+    let ws_tasks = state.ws_tasks.clone();
+
+    // Save collaborative rooms periodically and once more on shutdown
+    let persist_task = tokio::spawn(collab::persist_loop(
+        state.clone(),
+        collab::persist_interval_from_env(),
+    ));
+
+    // Keep the node registry cache fresh and announce its changes
+    tokio::spawn(registry::refresh_loop(state.clone()));
+
+    // Report rate limiter counters to Exdoida
+    tokio::spawn(rate_limit::report_loop(state.clone()));
 
     // Build router
     let app = create_router(state);
@@ -280,8 +945,27 @@ async fn main() -> Result<()> {
     info!("WebSocket endpoint: ws://{}/ws", addr);
 
     // Start server
+    let drain_timeout = ndnm_libs::drain_timeout_from_env();
     let listener = TcpListener::bind(&addr).await?;
-    axum::serve(listener, app).await?;
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(async move {
+            ndnm_libs::shutdown_signal().await;
+            info!("Shutdown signal received, closing WebSocket clients");
+            shutdown.cancel();
+        })
+        .await?;
+
+    // Upgraded WebSocket connections are not tracked by axum, so wait for
+    // them to send their close frames before exiting
+    ws_tasks.close();
+    if tokio::time::timeout(drain_timeout, ws_tasks.wait()).await.is_err() {
+        warn!("Timed out waiting for WebSocket clients to close");
+    }
+
+    if persist_task.await.is_err() {
+        warn!("Failed to save collaborative rooms");
+    }
 
+    info!("Brazil stopped");
     Ok(())
 }
@@ -0,0 +1,607 @@
+//! Per-client rate limiting
+//!
+//! Every client (the authenticated user, or the client IP when
+//! authentication is disabled or the route is public) has a token bucket
+//! per [`Quota`]:
+//!
+//! - `run` - `POST /graphs/run`, `POST /nexus/:name/run` and `run_graph`
+//! - `save` - every other write (saves, renames, deletes, imports, logins)
+//!   and `save_workspace`
+//! - `read` - reads, `POST /graphs/translate` and the read commands
+//! - `ws_messages` - every WebSocket command except heartbeat `pong`s
+//!
+//! A bucket holds up to `burst` requests and refills at `per_minute`.
+//! Requests over the limit are answered with 429 and `Retry-After`;
+//! WebSocket commands with a `rate_limited` error whose details carry
+//! `retry_after_secs`.
+//!
+//! Limits are set in the `rate_limits` section of Brazil's config file
+//! (the defaults are shown):
+//!
+//! ```yaml
+//! rate_limits:
+//!   enabled: true
+//!   run: { burst: 10, per_minute: 30 }
+//!   save: { burst: 30, per_minute: 120 }
+//!   read: { burst: 200, per_minute: 1200 }
+//!   ws_messages: { burst: 100, per_minute: 1200 }
+//! ```
+//!
+//! Allowed and limited requests per quota are reported to Exdoida
+//! (`POST /counters`) every [`REPORT_INTERVAL`].
+
+use crate::auth::Principal;
+use crate::commands::Command;
+use crate::config::BrazilConfig;
+use crate::AppState;
+use axum::{
+    extract::{ConnectInfo, MatchedPath, Request, State},
+    http::Method,
+    middleware::Next,
+    response::Response,
+};
+use ndnm_libs::AppError;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+/// Interval between counter reports to Exdoida
+pub const REPORT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Name under which counters are reported to Exdoida
+const COUNTER_SOURCE: &str = "ndnm-brazil";
+
+/// Group of requests sharing a bucket
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Quota {
+    /// Graph and workspace runs
+    Run,
+    /// Writes
+    Save,
+    /// Reads
+    Read,
+    /// WebSocket commands
+    WsMessages,
+}
+
+impl Quota {
+    /// Every quota, in counter order
+    const ALL: [Quota; 4] = [Quota::Run, Quota::Save, Quota::Read, Quota::WsMessages];
+
+    /// Position in [`Quota::ALL`]
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl fmt::Display for Quota {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Quota::Run => "run",
+            Quota::Save => "save",
+            Quota::Read => "read",
+            Quota::WsMessages => "ws_messages",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Size and refill rate of a bucket
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct Limit {
+    /// Requests allowed in a burst
+    pub burst: u32,
+
+    /// Requests allowed per minute once the burst is spent
+    pub per_minute: u32,
+}
+
+impl Limit {
+    /// Tokens added per second
+    fn rate(&self) -> f64 {
+        f64::from(self.per_minute) / 60.0
+    }
+}
+
+/// The `rate_limits` section of the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    /// Whether requests are limited
+    pub enabled: bool,
+
+    /// Limit of [`Quota::Run`]
+    pub run: Limit,
+
+    /// Limit of [`Quota::Save`]
+    pub save: Limit,
+
+    /// Limit of [`Quota::Read`]
+    pub read: Limit,
+
+    /// Limit of [`Quota::WsMessages`]
+    pub ws_messages: Limit,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            run: Limit {
+                burst: 10,
+                per_minute: 30,
+            },
+            save: Limit {
+                burst: 30,
+                per_minute: 120,
+            },
+            read: Limit {
+                burst: 200,
+                per_minute: 1200,
+            },
+            ws_messages: Limit {
+                burst: 100,
+                per_minute: 1200,
+            },
+        }
+    }
+}
+
+impl RateLimitConfig {
+    /// Limit of a quota
+    fn limit(&self, quota: Quota) -> Limit {
+        match quota {
+            Quota::Run => self.run,
+            Quota::Save => self.save,
+            Quota::Read => self.read,
+            Quota::WsMessages => self.ws_messages,
+        }
+    }
+}
+
+/// A request refused by the rate limiter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimited {
+    /// Quota that was exhausted
+    pub quota: Quota,
+
+    /// Seconds until the next request would be accepted
+    pub retry_after_secs: u64,
+}
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The {} rate limit was exceeded, retry in {}s",
+            self.quota, self.retry_after_secs
+        )
+    }
+}
+
+impl From<RateLimited> for AppError {
+    fn from(limited: RateLimited) -> Self {
+        AppError::TooManyRequests {
+            message: limited.to_string(),
+            retry_after_secs: limited.retry_after_secs,
+        }
+    }
+}
+
+/// Token bucket of one client and quota
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    /// A full bucket
+    fn new(limit: Limit, now: Instant) -> Self {
+        Self {
+            tokens: f64::from(limit.burst),
+            updated: now,
+        }
+    }
+
+    /// Refill the bucket up to `now`
+    fn refill(&mut self, limit: Limit, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.rate()).min(f64::from(limit.burst));
+        self.updated = now;
+    }
+
+    /// Take a token, or return the time until one is available
+    fn take(&mut self, limit: Limit, now: Instant) -> Result<(), Duration> {
+        self.refill(limit, now);
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return Ok(());
+        }
+
+        // A bucket that never refills is retried after a minute
+        let wait = if limit.per_minute == 0 {
+            Duration::from_secs(60)
+        } else {
+            Duration::from_secs_f64((1.0 - self.tokens) / limit.rate())
+        };
+        Err(wait)
+    }
+}
+
+/// Key identifying a client, stored as a request extension
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ClientKey(String);
+
+impl ClientKey {
+    /// Key of an authenticated user
+    pub fn user(subject: &str) -> Self {
+        Self(format!("user:{}", subject))
+    }
+
+    /// Key of an anonymous client, by IP address
+    pub fn ip(addr: Option<SocketAddr>) -> Self {
+        match addr {
+            Some(addr) => Self(format!("ip:{}", addr.ip())),
+            None => Self("ip:unknown".to_string()),
+        }
+    }
+}
+
+/// Buckets and counters of an enabled limiter
+struct Limiter {
+    config: RateLimitConfig,
+    buckets: Mutex<HashMap<(ClientKey, Quota), Bucket>>,
+    allowed: [AtomicU64; 4],
+    limited: [AtomicU64; 4],
+}
+
+/// Rate limiter shared by all handlers
+///
+/// The default limits nothing.
+#[derive(Clone, Default)]
+pub struct RateLimiter {
+    inner: Option<Arc<Limiter>>,
+}
+
+impl RateLimiter {
+    /// Load the `rate_limits` section of Brazil's config file (see
+    /// [`crate::config`])
+    pub fn from_env() -> Result<Self, AppError> {
+        Self::from_config(BrazilConfig::from_env()?.rate_limits)
+    }
+
+    /// Build a limiter from a parsed `rate_limits` section
+    ///
+    /// # Returns
+    ///
+    /// * `Err(AppError::ConfigError)` - A limit has a burst of zero
+    pub fn from_config(config: RateLimitConfig) -> Result<Self, AppError> {
+        if !config.enabled {
+            info!("Rate limiting is disabled");
+            return Ok(Self::default());
+        }
+
+        if let Some(quota) = Quota::ALL.iter().find(|q| config.limit(**q).burst == 0) {
+            return Err(AppError::ConfigError(format!(
+                "rate_limits.{}.burst must be at least 1",
+                quota
+            )));
+        }
+
+        Ok(Self {
+            inner: Some(Arc::new(Limiter {
+                config,
+                buckets: Mutex::new(HashMap::new()),
+                allowed: Default::default(),
+                limited: Default::default(),
+            })),
+        })
+    }
+
+    /// Whether requests are limited
+    pub fn is_enabled(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    /// Take a request from a client's bucket
+    pub fn check(&self, key: &ClientKey, quota: Quota) -> Result<(), RateLimited> {
+        let Some(limiter) = &self.inner else {
+            return Ok(());
+        };
+
+        let limit = limiter.config.limit(quota);
+        let now = Instant::now();
+
+        let result = limiter
+            .buckets
+            .lock()
+            .unwrap()
+            .entry((key.clone(), quota))
+            .or_insert_with(|| Bucket::new(limit, now))
+            .take(limit, now);
+
+        match result {
+            Ok(()) => {
+                limiter.allowed[quota.index()].fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            }
+            Err(wait) => {
+                limiter.limited[quota.index()].fetch_add(1, Ordering::Relaxed);
+                Err(RateLimited {
+                    quota,
+                    retry_after_secs: wait.as_secs_f64().ceil().max(1.0) as u64,
+                })
+            }
+        }
+    }
+
+    /// Take a WebSocket command from a client's buckets
+    ///
+    /// Every command but `pong` counts against [`Quota::WsMessages`];
+    /// runs, saves and reads also count against their own quota, like
+    /// their HTTP routes.
+    pub fn check_command(&self, key: &ClientKey, command: &Command) -> Result<(), RateLimited> {
+        if matches!(command, Command::Pong) {
+            return Ok(());
+        }
+
+        self.check(key, Quota::WsMessages)?;
+
+        match command {
+            Command::RunGraph(_) => self.check(key, Quota::Run),
+            Command::SaveWorkspace(_) => self.check(key, Quota::Save),
+            Command::LoadWorkspace { .. } | Command::ListWorkspaces(_) | Command::GetRegistry => {
+                self.check(key, Quota::Read)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Forget buckets that refilled completely
+    fn prune(&self) {
+        let Some(limiter) = &self.inner else {
+            return;
+        };
+
+        let now = Instant::now();
+        limiter
+            .buckets
+            .lock()
+            .unwrap()
+            .retain(|(_, quota), bucket| {
+                let limit = limiter.config.limit(*quota);
+                bucket.refill(limit, now);
+                bucket.tokens < f64::from(limit.burst)
+            });
+    }
+
+    /// Allowed and limited requests per quota, and the number of clients
+    /// with a bucket
+    pub fn counters(&self) -> BTreeMap<String, u64> {
+        let Some(limiter) = &self.inner else {
+            return BTreeMap::new();
+        };
+
+        let mut counters = BTreeMap::new();
+        for quota in Quota::ALL {
+            let allowed = limiter.allowed[quota.index()].load(Ordering::Relaxed);
+            let limited = limiter.limited[quota.index()].load(Ordering::Relaxed);
+            counters.insert(format!("rate_limit.{}.allowed", quota), allowed);
+            counters.insert(format!("rate_limit.{}.limited", quota), limited);
+        }
+
+        let buckets = limiter.buckets.lock().unwrap();
+        let mut clients: Vec<&ClientKey> = buckets.keys().map(|(key, _)| key).collect();
+        clients.sort_by(|a, b| a.0.cmp(&b.0));
+        clients.dedup();
+        counters.insert("rate_limit.clients".to_string(), clients.len() as u64);
+
+        counters
+    }
+}
+
+/// Quota of a route, or `None` for routes that are never limited
+pub fn route_quota(method: &Method, path: &str) -> Option<Quota> {
+    if path == "/health" {
+        return None;
+    }
+
+    let quota = match (method, path) {
+        (&Method::POST, "/graphs/run" | "/nexus/:name/run") => Quota::Run,
+        (&Method::POST, "/graphs/translate") => Quota::Read,
+        (&Method::GET | &Method::HEAD, _) => Quota::Read,
+        _ => Quota::Save,
+    };
+
+    Some(quota)
+}
+
+/// Middleware limiting requests per client and route quota
+///
+/// Runs after [`crate::auth::authorize`]; the client's [`ClientKey`] is
+/// stored as a request extension for the WebSocket handler.
+pub async fn limit(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let principal = request
+        .extensions()
+        .get::<Principal>()
+        .filter(|_| state.auth.is_enabled());
+    let key = match principal {
+        Some(principal) => ClientKey::user(&principal.subject),
+        None => ClientKey::ip(
+            request
+                .extensions()
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| *addr),
+        ),
+    };
+
+    let path = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| request.uri().path().to_string());
+
+    if let Some(quota) = route_quota(request.method(), &path) {
+        state.rate_limiter.check(&key, quota)?;
+    }
+
+    request.extensions_mut().insert(key);
+    Ok(next.run(request).await)
+}
+
+/// Report the counters to Exdoida periodically until Brazil shuts down
+pub async fn report_loop(state: AppState) {
+    if !state.rate_limiter.is_enabled() {
+        return;
+    }
+
+    let mut ticker = tokio::time::interval(REPORT_INTERVAL);
+    ticker.tick().await;
+
+    loop {
+        tokio::select! {
+            _ = state.shutdown.cancelled() => break,
+            _ = ticker.tick() => {
+                state.rate_limiter.prune();
+                report(&state).await;
+            }
+        }
+    }
+}
+
+/// Send the counters to Exdoida; Brazil keeps going if it is down
+async fn report(state: &AppState) {
+    let url = format!("{}/counters", state.exdoida_url);
+    let body = json!({
+        "source": COUNTER_SOURCE,
+        "counters": state.rate_limiter.counters()
+    });
+
+    let result = state
+        .http_client
+        .post(&url)
+        .timeout(REPORT_INTERVAL)
+        .json(&body)
+        .send()
+        .await;
+
+    match result {
+        Ok(response) if response.status().is_success() => {}
+        Ok(response) => debug!("Exdoida returned status: {}", response.status()),
+        Err(e) => debug!("Failed to report counters to Exdoida: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{offline_state, serve_brazil};
+    use reqwest::StatusCode;
+
+    /// Limiter allowing one request of each quota per second
+    fn tight_limiter() -> RateLimiter {
+        let limit = Limit {
+            burst: 1,
+            per_minute: 60,
+        };
+        RateLimiter::from_config(RateLimitConfig {
+            enabled: true,
+            run: limit,
+            save: limit,
+            read: limit,
+            ws_messages: limit,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_bucket_refills() {
+        let limit = Limit {
+            burst: 2,
+            per_minute: 30,
+        };
+        let start = Instant::now();
+        let mut bucket = Bucket::new(limit, start);
+
+        assert!(bucket.take(limit, start).is_ok());
+        assert!(bucket.take(limit, start).is_ok());
+        assert_eq!(bucket.take(limit, start), Err(Duration::from_secs(2)));
+
+        // One token every two seconds
+        assert!(bucket.take(limit, start + Duration::from_secs(2)).is_ok());
+        assert!(bucket.take(limit, start + Duration::from_secs(3)).is_err());
+    }
+
+    #[test]
+    fn test_buckets_are_per_client_and_quota() {
+        let limiter = tight_limiter();
+        let alice = ClientKey::user("alice");
+        let bob = ClientKey::user("bob");
+
+        assert!(limiter.check(&alice, Quota::Run).is_ok());
+        let limited = limiter.check(&alice, Quota::Run).unwrap_err();
+        assert_eq!(limited.quota, Quota::Run);
+        assert_eq!(limited.retry_after_secs, 1);
+
+        assert!(limiter.check(&alice, Quota::Read).is_ok());
+        assert!(limiter.check(&bob, Quota::Run).is_ok());
+
+        // Heartbeats are never limited
+        assert!(limiter.check_command(&alice, &Command::Pong).is_ok());
+        assert!(limiter
+            .check_command(&alice, &Command::GetRegistry)
+            .is_err());
+
+        let counters = limiter.counters();
+        assert_eq!(counters["rate_limit.run.allowed"], 2);
+        assert_eq!(counters["rate_limit.run.limited"], 1);
+        assert_eq!(counters["rate_limit.clients"], 2);
+
+        assert!(RateLimiter::default().check(&alice, Quota::Run).is_ok());
+        assert_eq!(route_quota(&Method::GET, "/health"), None);
+        assert_eq!(
+            route_quota(&Method::POST, "/nexus/:name/run"),
+            Some(Quota::Run)
+        );
+        assert_eq!(route_quota(&Method::DELETE, "/trash"), Some(Quota::Save));
+    }
+
+    #[tokio::test]
+    async fn test_router_answers_429_with_retry_after() {
+        let url = serve_brazil(offline_state().with_rate_limiter(tight_limiter())).await;
+
+        let client = reqwest::Client::new();
+        let response = client
+            .get(format!("{}/nexus/list", url))
+            .send()
+            .await
+            .unwrap();
+        assert_ne!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        let response = client
+            .get(format!("{}/nexus/list", url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()["retry-after"], "1");
+
+        // Health checks are not limited
+        for _ in 0..3 {
+            let response = client.get(format!("{}/health", url)).send().await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+    }
+}
@@ -0,0 +1,364 @@
+//! Cached node registry
+//!
+//! `GET /nodes/registry`, the `get_registry` command and graph translation
+//! are served from the last registry fetched from Hermes instead of asking
+//! Hermes every time. Clients get the registry's `ETag` and can revalidate
+//! with `If-None-Match`.
+//!
+//! The cache is revalidated with a conditional request to Hermes when:
+//!
+//! - it is older than its TTL (`REGISTRY_TTL_SECS`, default 30 seconds)
+//! - Hermes announces a different `ETag` in `x-registry-etag` on any
+//!   response
+//! - the background refresh runs, once per TTL
+//!
+//! When the registry changed, clients subscribed to `registry` receive:
+//!
+//! ```json
+//! { "type": "registry_updated", "etag": "\"...\"",
+//!   "added": [ { "node_id": ... } ], "removed": ["node-id"], "changed": [ { "node_id": ... } ] }
+//! ```
+
+use crate::hermes::{HermesClient, HermesError};
+use crate::translate::{RegistryNode, RegistryResponse};
+use crate::websocket::Broadcaster;
+use crate::AppState;
+use axum::http::{header, HeaderMap, HeaderValue};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+/// Default time a cached registry is used without revalidation
+const DEFAULT_TTL: Duration = Duration::from_secs(30);
+
+/// Time a cached registry is used without revalidation, from
+/// `REGISTRY_TTL_SECS`
+pub fn ttl_from_env() -> Duration {
+    std::env::var("REGISTRY_TTL_SECS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .filter(|&secs| secs > 0)
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_TTL)
+}
+
+/// A registry fetched from Hermes
+#[derive(Debug)]
+pub struct CachedRegistry {
+    /// `ETag` of the registry
+    pub etag: HeaderValue,
+    /// Registry as returned by Hermes, relayed to clients
+    pub body: Value,
+    /// Registry nodes, for graph translation
+    pub nodes: Vec<RegistryNode>,
+}
+
+impl CachedRegistry {
+    /// Build a cached registry from a Hermes response body
+    fn new(etag: HeaderValue, body: Value) -> Result<Self, HermesError> {
+        let registry = RegistryResponse::deserialize(&body)
+            .map_err(|e| HermesError::InvalidResponse(format!("Invalid registry: {}", e)))?;
+
+        Ok(Self {
+            etag,
+            body,
+            nodes: registry.nodes,
+        })
+    }
+
+    /// Whether a request's `If-None-Match` matches this registry
+    pub fn matches(&self, headers: &HeaderMap) -> bool {
+        headers
+            .get(header::IF_NONE_MATCH)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| {
+                v.split(',')
+                    .map(str::trim)
+                    .any(|tag| tag == "*" || tag.as_bytes() == self.etag.as_bytes())
+            })
+    }
+
+    /// Registry entries by node ID
+    fn entries(&self) -> BTreeMap<&str, &Value> {
+        self.body
+            .get("nodes")
+            .and_then(|nodes| nodes.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|node| Some((node.get("node_id")?.as_str()?, node)))
+            .collect()
+    }
+}
+
+/// Node types added, removed and changed between two registries
+#[derive(Debug, Default, Serialize, PartialEq)]
+pub struct RegistryDiff {
+    /// Entries of new nodes
+    pub added: Vec<Value>,
+    /// IDs of nodes that are gone
+    pub removed: Vec<String>,
+    /// New entries of nodes whose configuration or port changed
+    pub changed: Vec<Value>,
+}
+
+impl RegistryDiff {
+    /// Compare two registries
+    pub fn between(old: &CachedRegistry, new: &CachedRegistry) -> Self {
+        let old = old.entries();
+        let new = new.entries();
+
+        let mut diff = Self::default();
+        for (node_id, entry) in &new {
+            match old.get(node_id) {
+                None => diff.added.push((*entry).clone()),
+                Some(previous) if previous != entry => diff.changed.push((*entry).clone()),
+                Some(_) => {}
+            }
+        }
+        diff.removed = old
+            .keys()
+            .filter(|node_id| !new.contains_key(*node_id))
+            .map(|node_id| node_id.to_string())
+            .collect();
+
+        diff
+    }
+
+    /// Whether the registries are the same
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// The cached registry and when it was last revalidated
+struct Entry {
+    registry: Arc<CachedRegistry>,
+    checked_at: Instant,
+}
+
+/// Cache of the Hermes node registry, shared by all handlers
+#[derive(Clone)]
+pub struct RegistryCache {
+    /// Current registry, if fetched yet
+    entry: Arc<RwLock<Option<Entry>>>,
+    /// Held while refreshing, so concurrent callers share one request
+    refreshing: Arc<Mutex<()>>,
+    /// Time the registry is used without revalidation
+    ttl: Duration,
+}
+
+impl Default for RegistryCache {
+    fn default() -> Self {
+        Self {
+            entry: Arc::default(),
+            refreshing: Arc::default(),
+            ttl: DEFAULT_TTL,
+        }
+    }
+}
+
+impl RegistryCache {
+    /// Revalidate the registry after `ttl`
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Time the registry is used without revalidation
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// The cached registry, if it is still fresh
+    fn fresh(&self, hermes: &HermesClient) -> Option<Arc<CachedRegistry>> {
+        let entry = self.entry.read().unwrap();
+        let entry = entry.as_ref()?;
+
+        let announced = hermes.registry_etag();
+        let unchanged = announced.is_none_or(|etag| etag == entry.registry.etag);
+
+        (unchanged && entry.checked_at.elapsed() < self.ttl).then(|| entry.registry.clone())
+    }
+
+    /// Get the registry, revalidating it with Hermes when needed
+    ///
+    /// # Arguments
+    ///
+    /// * `hermes` - Client used to revalidate
+    /// * `broadcaster` - Receives `registry_updated` when the registry changed
+    pub async fn get(
+        &self,
+        hermes: &HermesClient,
+        broadcaster: &Broadcaster,
+    ) -> Result<Arc<CachedRegistry>, HermesError> {
+        if let Some(registry) = self.fresh(hermes) {
+            return Ok(registry);
+        }
+
+        let _refreshing = self.refreshing.lock().await;
+
+        // Another caller may have refreshed while we waited
+        if let Some(registry) = self.fresh(hermes) {
+            return Ok(registry);
+        }
+
+        self.revalidate(hermes, broadcaster).await
+    }
+
+    /// Revalidate the registry with Hermes now
+    pub async fn refresh(
+        &self,
+        hermes: &HermesClient,
+        broadcaster: &Broadcaster,
+    ) -> Result<Arc<CachedRegistry>, HermesError> {
+        let _refreshing = self.refreshing.lock().await;
+        self.revalidate(hermes, broadcaster).await
+    }
+
+    /// Fetch the registry if it changed and announce the changes
+    ///
+    /// Must be called with `refreshing` held.
+    async fn revalidate(
+        &self,
+        hermes: &HermesClient,
+        broadcaster: &Broadcaster,
+    ) -> Result<Arc<CachedRegistry>, HermesError> {
+        let current = self
+            .entry
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|entry| entry.registry.clone());
+
+        let response = hermes
+            .registry::<Value>(current.as_ref().map(|registry| &registry.etag))
+            .await?;
+
+        let registry = match (response, current) {
+            (None, Some(current)) => current,
+            (None, None) => {
+                return Err(HermesError::InvalidResponse(
+                    "Hermes answered an unconditional registry request with 304".to_string(),
+                ))
+            }
+            (Some(response), current) => {
+                let etag = response.etag.ok_or_else(|| {
+                    HermesError::InvalidResponse("Hermes sent the registry without an ETag".into())
+                })?;
+                let registry = Arc::new(CachedRegistry::new(etag, response.body)?);
+
+                if let Some(current) = current {
+                    let diff = RegistryDiff::between(&current, &registry);
+                    if !diff.is_empty() {
+                        info!(
+                            "Node registry changed: {} added, {} removed, {} changed",
+                            diff.added.len(),
+                            diff.removed.len(),
+                            diff.changed.len()
+                        );
+                        broadcaster
+                            .publish_registry_updated(&registry.etag, &diff)
+                            .await;
+                    }
+                }
+
+                registry
+            }
+        };
+
+        *self.entry.write().unwrap() = Some(Entry {
+            registry: registry.clone(),
+            checked_at: Instant::now(),
+        });
+
+        Ok(registry)
+    }
+}
+
+/// Revalidate the registry once per TTL until Brazil shuts down, so
+/// clients hear about changes without asking
+pub async fn refresh_loop(state: AppState) {
+    let mut ticker = tokio::time::interval(state.registry.ttl());
+
+    loop {
+        tokio::select! {
+            _ = state.shutdown.cancelled() => break,
+            _ = ticker.tick() => {
+                if let Err(e) = state.registry.refresh(&state.hermes, &state.ws_broadcaster).await {
+                    warn!("Failed to refresh the node registry: {}", e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::websocket::Topic;
+    use crate::test_support::{fake_hermes, registry_node};
+    use serde_json::json;
+    use std::sync::atomic::Ordering;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn test_cache_revalidates_on_announced_etag() {
+        let fake = fake_hermes().await;
+        let hermes = HermesClient::new(fake.url.clone());
+        let broadcaster = Broadcaster::new();
+        let cache = RegistryCache::default().with_ttl(Duration::from_secs(3600));
+
+        let client_id = Uuid::new_v4();
+        let mut rx = broadcaster.register(client_id);
+        broadcaster.subscribe(client_id, &[Topic::Registry]);
+
+        let registry = cache.get(&hermes, &broadcaster).await.unwrap();
+        assert_eq!(registry.nodes.len(), 2);
+        cache.get(&hermes, &broadcaster).await.unwrap();
+        assert_eq!(fake.fetches.load(Ordering::SeqCst), 1);
+
+        // Hermes restarts with a changed registry and announces it
+        *fake.registry.write().unwrap() = (
+            "\"v2\"".to_string(),
+            json!({ "nodes": [registry_node("a", 3020), registry_node("c", 3012)] }),
+        );
+        assert!(hermes.is_healthy().await);
+
+        let registry = cache.get(&hermes, &broadcaster).await.unwrap();
+        assert_eq!(registry.etag, "\"v2\"");
+        assert_eq!(fake.fetches.load(Ordering::SeqCst), 2);
+
+        let update: Value = serde_json::from_str(&rx.recv().await.unwrap()).unwrap();
+        assert_eq!(update["type"], "registry_updated");
+        assert_eq!(update["etag"], "\"v2\"");
+        assert_eq!(update["added"][0]["node_id"], "c");
+        assert_eq!(update["removed"], json!(["b"]));
+        assert_eq!(update["changed"][0]["port"], 3020);
+
+        // An unchanged registry is revalidated without being fetched again
+        cache.refresh(&hermes, &broadcaster).await.unwrap();
+        assert_eq!(fake.fetches.load(Ordering::SeqCst), 2);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_if_none_match() {
+        let registry =
+            CachedRegistry::new(HeaderValue::from_static("\"v1\""), json!({ "nodes": [] }))
+                .unwrap();
+
+        let mut headers = HeaderMap::new();
+        assert!(!registry.matches(&headers));
+        headers.insert(
+            header::IF_NONE_MATCH,
+            HeaderValue::from_static("\"v0\", \"v1\""),
+        );
+        assert!(registry.matches(&headers));
+        headers.insert(header::IF_NONE_MATCH, HeaderValue::from_static("\"v0\""));
+        assert!(!registry.matches(&headers));
+    }
+}
@@ -0,0 +1,227 @@
+//! Fixtures shared by the unit tests
+//!
+//! - [`serve`] and [`serve_brazil`] run a router on a free local port
+//! - [`fake_hermes`] answers the Hermes routes the tests call
+//! - [`offline_state`] is an [`AppState`] whose Hermes calls fail fast
+
+use crate::hermes::REQUEST_ID_HEADER;
+use crate::AppState;
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use ndnm_libs::range::{content_range, parse_range};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// Content of every artifact served by [`fake_hermes`]
+pub const ARTIFACT: &[u8] = b"0123456789";
+
+/// Serve an app on a free local port and return its base URL
+pub async fn serve(app: Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    url
+}
+
+/// Serve Brazil's router, with client addresses as in `main`, and return
+/// its base URL
+pub async fn serve_brazil(state: AppState) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let app = crate::create_router(state).into_make_service_with_connect_info::<SocketAddr>();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    url
+}
+
+/// Application state pointing at a port nothing listens on, so Hermes
+/// calls fail fast
+pub fn offline_state() -> AppState {
+    AppState::new("http://127.0.0.1:9".to_string())
+}
+
+/// Registry entry of a node listening on `port`
+pub fn registry_node(node_id: &str, port: u16) -> Value {
+    json!({
+        "node_id": node_id,
+        "config": {
+            "node_id_hash": node_id,
+            "label": node_id,
+            "node_type": "test",
+            "sections": [],
+            "input_fields": []
+        },
+        "port": port
+    })
+}
+
+/// A running fake Hermes
+#[derive(Clone)]
+pub struct FakeHermes {
+    /// Base URL
+    pub url: String,
+
+    /// Registry `ETag` and body; starts as `"v1"` with nodes `a` and `b`
+    pub registry: Arc<RwLock<(String, Value)>>,
+
+    /// Registry bodies served (revalidations answered 304 do not count)
+    pub fetches: Arc<AtomicUsize>,
+}
+
+/// Start a fake Hermes
+///
+/// Besides the registry, it answers:
+///
+/// - `GET /nexus/load/demo` with an empty graph; other workspaces are 400
+/// - `GET /nodes/:node_id` with the node ID and the request ID it was sent;
+///   `missing` is 400
+/// - `POST /graphs/run` after `delay_ms` (from the body) with a successful
+///   result
+/// - `POST /executions/:id/cancel`, and `GET /executions/:id/events` with
+///   one progress event of instance `scan`
+/// - `POST /nexus/:name/duplicate` with the name it was sent
+/// - `GET /nexus/:name/export` with a bundle named after the workspace
+/// - `GET /trash` with a body that is not JSON
+/// - `GET /artifacts/:sha256` with [`ARTIFACT`], honouring `Range`
+pub async fn fake_hermes() -> FakeHermes {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let fake = FakeHermes {
+        url: format!("http://{}", listener.local_addr().unwrap()),
+        registry: Arc::new(RwLock::new((
+            "\"v1\"".to_string(),
+            json!({ "nodes": [registry_node("a", 3010), registry_node("b", 3011)] }),
+        ))),
+        fetches: Arc::default(),
+    };
+
+    let app = Router::new()
+        .route("/nodes/registry", get(fake_registry))
+        .route(
+            "/health",
+            get(|State(fake): State<FakeHermes>| async move {
+                let etag = fake.registry.read().unwrap().0.clone();
+                [("x-registry-etag", etag)]
+            }),
+        )
+        .route("/nodes/:node_id", get(fake_node))
+        .route(
+            "/nexus/load/:name",
+            get(|Path(name): Path<String>| async move {
+                if name == "demo" {
+                    Ok(Json(json!({ "graph": { "nodes": [], "connections": [] } })))
+                } else {
+                    Err((
+                        StatusCode::BAD_REQUEST,
+                        Json(json!({ "error": format!("Workspace '{}' not found", name) })),
+                    ))
+                }
+            }),
+        )
+        .route(
+            "/graphs/run",
+            post(|Json(body): Json<Value>| async move {
+                let delay = body["delay_ms"].as_u64().unwrap_or(0);
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+                Json(json!({
+                    "execution_id": body["execution_id"],
+                    "status": "success",
+                    "node_results": {}
+                }))
+            }),
+        )
+        .route(
+            "/executions/:id/cancel",
+            post(|Path(id): Path<String>| async move {
+                Json(json!({ "execution_id": id, "cancelled": true }))
+            }),
+        )
+        .route(
+            "/executions/:id/events",
+            get(|Path(id): Path<String>| async move {
+                let event = json!({
+                    "execution_id": id,
+                    "instance_id": "scan",
+                    "type": "progress",
+                    "percent": 50.0
+                });
+                (
+                    [(header::CONTENT_TYPE, "text/event-stream")],
+                    format!("event: progress\ndata: {}\n\n", event),
+                )
+            }),
+        )
+        .route(
+            "/nexus/:name/duplicate",
+            post(|Path(name): Path<String>| async move { Json(json!({ "name": name })) }),
+        )
+        .route(
+            "/nexus/:name/export",
+            get(|Path(name): Path<String>| async move {
+                let disposition = format!("attachment; filename=\"{}.tar.gz\"", name);
+                ([(header::CONTENT_DISPOSITION, disposition)], "bundle")
+            }),
+        )
+        .route("/trash", get(|| async { "not json" }))
+        .route("/artifacts/:sha256", get(fake_artifact))
+        .with_state(fake.clone());
+
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    fake
+}
+
+/// `GET /nodes/registry`, revalidated with `If-None-Match`
+async fn fake_registry(State(fake): State<FakeHermes>, headers: HeaderMap) -> Response {
+    let (etag, body) = fake.registry.read().unwrap().clone();
+    let registry_etag = [("x-registry-etag", etag.clone())];
+
+    if headers
+        .get(header::IF_NONE_MATCH)
+        .is_some_and(|v| v == etag.as_str())
+    {
+        return (StatusCode::NOT_MODIFIED, registry_etag).into_response();
+    }
+
+    fake.fetches.fetch_add(1, Ordering::SeqCst);
+    (registry_etag, [(header::ETAG, etag)], Json(body)).into_response()
+}
+
+/// `GET /nodes/:node_id`, echoing the request ID
+async fn fake_node(Path(node_id): Path<String>, headers: HeaderMap) -> Response {
+    let request_id = headers
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+
+    if node_id == "missing" {
+        let error = json!({ "error": "Node 'missing' not found" });
+        return (StatusCode::BAD_REQUEST, Json(error)).into_response();
+    }
+    Json(json!({ "node_id": node_id, "request_id": request_id })).into_response()
+}
+
+/// `GET /artifacts/:sha256`, with single byte ranges
+async fn fake_artifact(headers: HeaderMap) -> Response {
+    let size = ARTIFACT.len() as u64;
+
+    match parse_range(headers.get(header::RANGE), size) {
+        Ok(None) => ([(header::ACCEPT_RANGES, "bytes")], ARTIFACT).into_response(),
+        Ok(Some(range)) => (
+            StatusCode::PARTIAL_CONTENT,
+            [(header::CONTENT_RANGE, content_range(&range, size))],
+            &ARTIFACT[range.start as usize..range.end as usize],
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::RANGE_NOT_SATISFIABLE,
+            [(header::CONTENT_RANGE, e.content_range())],
+        )
+            .into_response(),
+    }
+}
@@ -0,0 +1,578 @@
+//! Translation from the frontend graph format to Hermes' graph format
+//!
+//! ndnm-argos describes graphs as React Flow exports them:
+//!
+//! ```json
+//! {
+//!   "nodes": [{ "id": "n1", "node_type": "...", "port": 3011, "data": { ... } }],
+//!   "connections": [{ "from_node_id": "n1", "from_output_index": 0,
+//!                     "to_node_id": "n2", "to_input_index": 1 }]
+//! }
+//! ```
+//!
+//! Hermes expects a `GraphDefinition` with `instance_id`, `node_type_id` and
+//! real handle names. Node types are resolved against the Hermes registry,
+//! handle indexes are mapped to handle names through the node's sections and
+//! ports always come from Hermes (the frontend's `port` is ignored).
+//!
+//! A node's `inputs` give values to input handles directly, by handle name
+//! (typically blob references to uploaded files, see `crate::blobs`).
+//!
+//! Problems are reported per field (e.g. `connections[2].to_input_index`)
+//! so the UI can point at the offending node or wire.
+
+use ndnm_libs::{NodeConfig, SectionBehavior};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Graph in the frontend format
+#[derive(Debug, Clone, Deserialize)]
+pub struct FrontendGraph {
+    /// Node instances
+    pub nodes: Vec<FrontendNode>,
+
+    /// Wires between nodes
+    #[serde(default)]
+    pub connections: Vec<FrontendConnection>,
+}
+
+/// Node instance in the frontend format
+///
+/// The frontend's `port` is not read; ports come from the Hermes registry.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FrontendNode {
+    /// Instance ID
+    pub id: String,
+
+    /// Node type (registry node ID or config `node_type`)
+    pub node_type: String,
+
+    /// Node data; keys matching the node's input fields become input values
+    #[serde(default)]
+    pub data: Map<String, Value>,
+
+    /// Values of input handles, by handle name
+    #[serde(default)]
+    pub inputs: Map<String, Value>,
+
+    /// Position in the editor
+    #[serde(default)]
+    pub position: Option<Value>,
+}
+
+/// Wire in the frontend format
+#[derive(Debug, Clone, Deserialize)]
+pub struct FrontendConnection {
+    /// Source node instance ID
+    pub from_node_id: String,
+
+    /// Index of the source output handle
+    #[serde(default)]
+    pub from_output_index: usize,
+
+    /// Explicit source handle name, overriding the index
+    #[serde(default)]
+    pub from_handle: Option<String>,
+
+    /// Target node instance ID
+    pub to_node_id: String,
+
+    /// Index of the target input handle
+    #[serde(default)]
+    pub to_input_index: usize,
+
+    /// Explicit target handle name, overriding the index
+    #[serde(default)]
+    pub to_handle: Option<String>,
+}
+
+/// Graph in the format Hermes expects (`GraphDefinition`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HermesGraph {
+    /// Node instances
+    pub nodes: Vec<HermesNode>,
+
+    /// Connections between node handles
+    pub connections: Vec<HermesConnection>,
+}
+
+/// Node instance in Hermes' format
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HermesNode {
+    /// Instance ID
+    pub instance_id: String,
+
+    /// Registry node ID
+    pub node_type_id: String,
+
+    /// Input field values
+    pub input_values: Map<String, Value>,
+
+    /// Values of unconnected input handles
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub inputs: Map<String, Value>,
+
+    /// Position in the editor
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<Value>,
+}
+
+/// Connection in Hermes' format
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HermesConnection {
+    /// Source node instance ID
+    pub from_node: String,
+
+    /// Source handle name
+    pub from_handle: String,
+
+    /// Target node instance ID
+    pub to_node: String,
+
+    /// Target handle name
+    pub to_handle: String,
+}
+
+/// Node as listed by Hermes' `/nodes/registry`
+#[derive(Debug, Clone, Deserialize)]
+pub struct RegistryNode {
+    /// Registry node ID
+    pub node_id: String,
+
+    /// Node configuration
+    pub config: NodeConfig,
+
+    /// Port assigned by Hermes
+    pub port: u16,
+}
+
+/// Response of Hermes' `/nodes/registry`
+#[derive(Debug, Deserialize)]
+pub struct RegistryResponse {
+    /// Registered nodes
+    pub nodes: Vec<RegistryNode>,
+}
+
+/// Result of a successful translation
+#[derive(Debug, Serialize)]
+pub struct TranslatedGraph {
+    /// Graph ready to be sent to Hermes
+    pub graph: HermesGraph,
+
+    /// Port of each node instance, as assigned by Hermes
+    pub ports: BTreeMap<String, u16>,
+}
+
+/// A problem with one field of the frontend graph
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct TranslationError {
+    /// Path of the offending field (e.g. `nodes[0].node_type`)
+    pub field: String,
+
+    /// What is wrong with it
+    pub message: String,
+}
+
+impl TranslationError {
+    fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+/// Response body for a graph that could not be translated
+#[derive(Debug, Serialize)]
+pub struct TranslationErrorResponse {
+    /// Summary message
+    pub error: String,
+
+    /// Field-level errors
+    pub errors: Vec<TranslationError>,
+}
+
+impl TranslationErrorResponse {
+    /// Wrap field-level errors in a response body
+    pub fn new(errors: Vec<TranslationError>) -> Self {
+        Self {
+            error: format!("Graph translation failed with {} error(s)", errors.len()),
+            errors,
+        }
+    }
+}
+
+/// Direction of a handle on a node
+#[derive(Debug, Clone, Copy)]
+enum Direction {
+    Input,
+    Output,
+}
+
+/// Parse a frontend graph, reporting the path of any malformed field
+pub fn parse_frontend_graph(value: Value) -> Result<FrontendGraph, Vec<TranslationError>> {
+    serde_path_to_error::deserialize(value).map_err(|e| {
+        let field = e.path().to_string();
+        vec![TranslationError::new(field, e.inner().to_string())]
+    })
+}
+
+/// Translate a frontend graph into Hermes' format
+///
+/// # Arguments
+///
+/// * `graph` - Graph as sent by the frontend
+/// * `registry` - Nodes registered in Hermes
+///
+/// # Returns
+///
+/// * `Ok(TranslatedGraph)` - Hermes graph and resolved ports
+/// * `Err(Vec<TranslationError>)` - Every problem found, by field
+pub fn translate_graph(
+    graph: &FrontendGraph,
+    registry: &[RegistryNode],
+) -> Result<TranslatedGraph, Vec<TranslationError>> {
+    let mut errors = Vec::new();
+    let mut nodes = Vec::new();
+    let mut ports = BTreeMap::new();
+    let mut instances: HashMap<&str, &RegistryNode> = HashMap::new();
+    let mut seen = HashSet::new();
+
+    for (i, node) in graph.nodes.iter().enumerate() {
+        if !seen.insert(node.id.as_str()) {
+            errors.push(TranslationError::new(
+                format!("nodes[{}].id", i),
+                format!("Duplicate node id '{}'", node.id),
+            ));
+            continue;
+        }
+
+        let registry_node = match resolve_node_type(&node.node_type, registry) {
+            Ok(registry_node) => registry_node,
+            Err(message) => {
+                errors.push(TranslationError::new(
+                    format!("nodes[{}].node_type", i),
+                    message,
+                ));
+                continue;
+            }
+        };
+
+        // Only declared input fields are node settings; the rest is UI state
+        let input_values = node
+            .data
+            .iter()
+            .filter(|(key, value)| {
+                !value.is_null()
+                    && registry_node
+                        .config
+                        .input_fields
+                        .iter()
+                        .any(|f| &f.name == *key)
+            })
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+
+        for handle in node.inputs.keys() {
+            if !has_handle(&registry_node.config, handle, Direction::Input) {
+                errors.push(TranslationError::new(
+                    format!("nodes[{}].inputs.{}", i, handle),
+                    format!("Node type has no input handle '{}'", handle),
+                ));
+            }
+        }
+
+        instances.insert(&node.id, registry_node);
+        ports.insert(node.id.clone(), registry_node.port);
+        nodes.push(HermesNode {
+            instance_id: node.id.clone(),
+            node_type_id: registry_node.node_id.clone(),
+            input_values,
+            inputs: node.inputs.clone(),
+            position: node.position.clone(),
+        });
+    }
+
+    let mut connections = Vec::new();
+
+    for (i, conn) in graph.connections.iter().enumerate() {
+        // Wires to nodes whose type failed to resolve were reported already
+        let unresolved = |id: &str| seen.contains(id) && !instances.contains_key(id);
+        if unresolved(&conn.from_node_id) || unresolved(&conn.to_node_id) {
+            continue;
+        }
+
+        let from = resolve_handle(
+            &instances,
+            &conn.from_node_id,
+            conn.from_handle.as_deref(),
+            conn.from_output_index,
+            Direction::Output,
+        )
+        .map_err(|(field, message)| {
+            TranslationError::new(format!("connections[{}].{}", i, field), message)
+        });
+
+        let to = resolve_handle(
+            &instances,
+            &conn.to_node_id,
+            conn.to_handle.as_deref(),
+            conn.to_input_index,
+            Direction::Input,
+        )
+        .map_err(|(field, message)| {
+            TranslationError::new(format!("connections[{}].{}", i, field), message)
+        });
+
+        match (from, to) {
+            (Ok(from_handle), Ok(to_handle)) => connections.push(HermesConnection {
+                from_node: conn.from_node_id.clone(),
+                from_handle,
+                to_node: conn.to_node_id.clone(),
+                to_handle,
+            }),
+            (from, to) => errors.extend(from.err().into_iter().chain(to.err())),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    Ok(TranslatedGraph {
+        graph: HermesGraph { nodes, connections },
+        ports,
+    })
+}
+
+/// Find the registry node for a frontend node type
+///
+/// Matches the registry node ID first, then a unique config `node_type`.
+fn resolve_node_type<'a>(
+    node_type: &str,
+    registry: &'a [RegistryNode],
+) -> Result<&'a RegistryNode, String> {
+    if let Some(node) = registry.iter().find(|n| n.node_id == node_type) {
+        return Ok(node);
+    }
+
+    let matches: Vec<&RegistryNode> = registry
+        .iter()
+        .filter(|n| n.config.node_type == node_type)
+        .collect();
+
+    match matches.as_slice() {
+        [node] => Ok(node),
+        [] => Err(format!("Unknown node type '{}'", node_type)),
+        _ => Err(format!(
+            "Node type '{}' is ambiguous; use one of: {}",
+            node_type,
+            matches
+                .iter()
+                .map(|n| n.node_id.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+/// Resolve the handle name of one end of a connection
+///
+/// Errors carry the name of the offending connection field.
+fn resolve_handle(
+    instances: &HashMap<&str, &RegistryNode>,
+    node_id: &str,
+    handle: Option<&str>,
+    index: usize,
+    direction: Direction,
+) -> Result<String, (&'static str, String)> {
+    let (node_field, handle_field, index_field) = match direction {
+        Direction::Input => ("to_node_id", "to_handle", "to_input_index"),
+        Direction::Output => ("from_node_id", "from_handle", "from_output_index"),
+    };
+
+    let Some(node) = instances.get(node_id) else {
+        return Err((node_field, format!("Unknown node '{}'", node_id)));
+    };
+
+    match handle {
+        Some(handle) if has_handle(&node.config, handle, direction) => Ok(handle.to_string()),
+        Some(handle) => Err((
+            handle_field,
+            format!(
+                "Node '{}' has no {} handle '{}'",
+                node_id,
+                direction.name(),
+                handle
+            ),
+        )),
+        None => handle_for_index(&node.config, index, direction)
+            .map_err(|message| (index_field, format!("Node '{}': {}", node_id, message))),
+    }
+}
+
+/// Map a handle index to a handle name
+///
+/// Indexes run through the sections in order: a `static` section has one
+/// handle named after its base name, an `auto_increment` section takes all
+/// remaining indexes (`<base>_0`, `<base>_1`, ...). Handles of
+/// `dynamic_per_file` sections are named after files and must be sent by
+/// name.
+fn handle_for_index(
+    config: &NodeConfig,
+    index: usize,
+    direction: Direction,
+) -> Result<String, String> {
+    let mut remaining = index;
+
+    for section in &config.sections {
+        let base = direction.base_name(section);
+
+        match section.behavior {
+            SectionBehavior::Static if remaining == 0 => return Ok(base.to_string()),
+            SectionBehavior::Static => remaining -= 1,
+            SectionBehavior::AutoIncrement => return Ok(format!("{}_{}", base, remaining)),
+            SectionBehavior::DynamicPerFile => {
+                return Err(format!(
+                    "{} {} falls in section '{}', whose handles are named per file; send the handle name instead",
+                    direction.name(),
+                    index,
+                    section.section_name
+                ));
+            }
+        }
+    }
+
+    Err(format!("no {} handle at index {}", direction.name(), index))
+}
+
+/// Whether a node declares a handle, either by base name or `<base>_<suffix>`
+fn has_handle(config: &NodeConfig, handle: &str, direction: Direction) -> bool {
+    config.sections.iter().any(|section| {
+        let base = direction.base_name(section);
+        handle == base
+            || handle
+                .strip_prefix(base)
+                .is_some_and(|rest| rest.len() > 1 && rest.starts_with('_'))
+    })
+}
+
+impl Direction {
+    /// Lower-case name used in messages
+    fn name(self) -> &'static str {
+        match self {
+            Direction::Input => "input",
+            Direction::Output => "output",
+        }
+    }
+
+    /// Base handle name of a section in this direction
+    fn base_name(self, section: &ndnm_libs::Section) -> &str {
+        match self {
+            Direction::Input => &section.slot_template.input.name,
+            Direction::Output => &section.slot_template.output.name,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn registry() -> Vec<RegistryNode> {
+        let config: NodeConfig = ndnm_libs::load_config("../nodes/node-file-browser/config.yaml")
+            .expect("file browser config");
+
+        vec![RegistryNode {
+            node_id: config.node_id_hash.clone(),
+            config,
+            port: 3100,
+        }]
+    }
+
+    #[test]
+    fn test_translate_graph() {
+        let graph = parse_frontend_graph(json!({
+            "nodes": [
+                {
+                    "id": "a",
+                    "node_type": "filesystem",
+                    "port": 3011,
+                    "data": { "target_directory": "./in", "label": "Source", "onChange": null }
+                },
+                {
+                    "id": "b",
+                    "node_type": "filesystem",
+                    "data": {},
+                    "inputs": { "copy_input_2": { "$blob": "sha256:abc" } }
+                }
+            ],
+            "connections": [
+                { "from_node_id": "a", "from_output_index": 1, "to_node_id": "b", "to_input_index": 0 },
+                {
+                    "from_node_id": "a", "from_handle": "internal_output_notes.txt",
+                    "to_node_id": "b", "to_input_index": 1
+                }
+            ]
+        }))
+        .unwrap();
+
+        let translated = translate_graph(&graph, &registry()).unwrap();
+
+        assert_eq!(translated.ports["a"], 3100);
+        let a = &translated.graph.nodes[0];
+        assert_eq!(
+            a.node_type_id,
+            "hash_sha256_de_viniciusxpb_node-file-browser"
+        );
+        assert_eq!(a.input_values.len(), 1);
+        assert_eq!(a.input_values["target_directory"], json!("./in"));
+        assert!(a.inputs.is_empty());
+        assert_eq!(
+            translated.graph.nodes[1].inputs["copy_input_2"],
+            json!({ "$blob": "sha256:abc" })
+        );
+
+        let conn = &translated.graph.connections[0];
+        assert_eq!(conn.from_handle, "copied_output_1");
+        assert_eq!(conn.to_handle, "copy_input_0");
+        assert_eq!(
+            translated.graph.connections[1].from_handle,
+            "internal_output_notes.txt"
+        );
+    }
+
+    #[test]
+    fn test_field_level_errors() {
+        let graph = parse_frontend_graph(json!({
+            "nodes": [
+                { "id": "a", "node_type": "filesystem", "inputs": { "nope": "x" } },
+                { "id": "b", "node_type": "add" }
+            ],
+            "connections": [
+                { "from_node_id": "a", "from_output_index": 0, "to_node_id": "missing", "to_input_index": 0 },
+                { "from_node_id": "a", "from_handle": "bogus", "to_node_id": "a", "to_input_index": 0 }
+            ]
+        }))
+        .unwrap();
+
+        let errors = translate_graph(&graph, &registry()).unwrap_err();
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+
+        assert_eq!(
+            fields,
+            vec![
+                "nodes[0].inputs.nope",
+                "nodes[1].node_type",
+                "connections[0].to_node_id",
+                "connections[1].from_handle"
+            ]
+        );
+
+        let errors =
+            parse_frontend_graph(json!({ "nodes": [{ "id": "a", "node_type": 3 }] })).unwrap_err();
+        assert_eq!(errors[0].field, "nodes[0].node_type");
+    }
+}
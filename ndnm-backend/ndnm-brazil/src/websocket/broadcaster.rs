@@ -0,0 +1,438 @@
+//! Connection registry and topic subscriptions
+//!
+//! Every WebSocket client gets its own bounded queue, registered under its
+//! client ID. Messages are sent either to one client (command replies) or
+//! to the clients subscribed to a topic:
+//!
+//! - `execution:<id>` - progress and completion of one execution
+//! - `workspace:<name>` - saves and runs of one workspace
+//! - `registry` - changes to the node registry
+//! - `logs` - execution lifecycle log lines
+//!
+//! Delivery never waits on a client: a client whose queue is full is a slow
+//! consumer and is evicted, which closes its connection. Other clients are
+//! unaffected.
+
+use crate::registry::RegistryDiff;
+use axum::http::HeaderValue;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use tokio::sync::mpsc;
+use tracing::warn;
+use uuid::Uuid;
+
+/// Number of messages queued per client before it counts as a slow consumer
+pub const CLIENT_QUEUE_CAPACITY: usize = 100;
+
+/// A topic clients can subscribe to
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Topic {
+    /// Events of one execution
+    Execution(String),
+
+    /// Events of one workspace
+    Workspace(String),
+
+    /// Node registry changes
+    Registry,
+
+    /// Log lines
+    Logs,
+}
+
+impl fmt::Display for Topic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Topic::Execution(id) => write!(f, "execution:{}", id),
+            Topic::Workspace(name) => write!(f, "workspace:{}", name),
+            Topic::Registry => write!(f, "registry"),
+            Topic::Logs => write!(f, "logs"),
+        }
+    }
+}
+
+impl FromStr for Topic {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("execution", id)) if !id.is_empty() => Ok(Topic::Execution(id.to_string())),
+            Some(("workspace", name)) if !name.is_empty() => Ok(Topic::Workspace(name.to_string())),
+            None if s == "registry" => Ok(Topic::Registry),
+            None if s == "logs" => Ok(Topic::Logs),
+            _ => Err(format!(
+                "Unknown topic '{}' (expected execution:<id>, workspace:<name>, registry or logs)",
+                s
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for Topic {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Topic> for String {
+    fn from(topic: Topic) -> Self {
+        topic.to_string()
+    }
+}
+
+/// A registered client
+struct Client {
+    /// Queue drained by the client's send task
+    tx: mpsc::Sender<String>,
+
+    /// Topics the client is subscribed to
+    topics: HashSet<Topic>,
+}
+
+/// Registry of connected WebSocket clients
+#[derive(Clone)]
+pub struct Broadcaster {
+    /// Connected clients by client ID
+    clients: Arc<RwLock<HashMap<Uuid, Client>>>,
+}
+
+impl Broadcaster {
+    /// Create a new broadcaster
+    pub fn new() -> Self {
+        Self {
+            clients: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// Register a client and return the receiving end of its queue
+    ///
+    /// The receiver yields `None` once the client is unregistered or
+    /// evicted as a slow consumer.
+    pub fn register(&self, client_id: Uuid) -> mpsc::Receiver<String> {
+        let (tx, rx) = mpsc::channel(CLIENT_QUEUE_CAPACITY);
+
+        self.clients.write().unwrap().insert(
+            client_id,
+            Client {
+                tx,
+                topics: HashSet::new(),
+            },
+        );
+
+        rx
+    }
+
+    /// Remove a client
+    pub fn unregister(&self, client_id: Uuid) {
+        self.clients.write().unwrap().remove(&client_id);
+    }
+
+    /// Whether a client is registered (not unregistered or evicted)
+    pub fn is_registered(&self, client_id: Uuid) -> bool {
+        self.clients.read().unwrap().contains_key(&client_id)
+    }
+
+    /// Number of connected clients
+    pub fn client_count(&self) -> usize {
+        self.clients.read().unwrap().len()
+    }
+
+    /// Subscribe a client to topics
+    ///
+    /// # Returns
+    ///
+    /// All topics the client is now subscribed to, or `None` if the client
+    /// is not connected
+    pub fn subscribe(&self, client_id: Uuid, topics: &[Topic]) -> Option<Vec<Topic>> {
+        let mut clients = self.clients.write().unwrap();
+        let client = clients.get_mut(&client_id)?;
+
+        client.topics.extend(topics.iter().cloned());
+        Some(sorted(&client.topics))
+    }
+
+    /// Unsubscribe a client from topics
+    ///
+    /// # Returns
+    ///
+    /// All topics the client is still subscribed to, or `None` if the
+    /// client is not connected
+    pub fn unsubscribe(&self, client_id: Uuid, topics: &[Topic]) -> Option<Vec<Topic>> {
+        let mut clients = self.clients.write().unwrap();
+        let client = clients.get_mut(&client_id)?;
+
+        for topic in topics {
+            client.topics.remove(topic);
+        }
+        Some(sorted(&client.topics))
+    }
+
+    /// Send a JSON message to the clients subscribed to any of the topics
+    ///
+    /// A client subscribed to several of the topics receives it once.
+    ///
+    /// # Arguments
+    ///
+    /// * `topics` - Topics the message belongs to
+    /// * `message` - JSON value to send
+    pub async fn publish(&self, topics: &[Topic], message: &Value) {
+        self.deliver(message, |client| {
+            topics.iter().any(|topic| client.topics.contains(topic))
+        });
+    }
+
+    /// Announce a finished execution
+    ///
+    /// Sent to the execution's subscribers and, when the graph came from a
+    /// workspace, to the workspace's subscribers; a log line goes to `logs`.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - Execution response from Hermes
+    /// * `workspace` - Workspace the graph came from, if any
+    pub async fn publish_execution_complete(&self, data: &Value, workspace: Option<&str>) {
+        let execution_id = data
+            .get("execution_id")
+            .and_then(|id| id.as_str())
+            .unwrap_or_default();
+
+        let mut topics = vec![Topic::Execution(execution_id.to_string())];
+        topics.extend(workspace.map(|name| Topic::Workspace(name.to_string())));
+
+        self.publish(
+            &topics,
+            &json!({
+                "type": "graph_execution_complete",
+                "execution_id": execution_id,
+                "workspace": workspace,
+                "data": data
+            }),
+        )
+        .await;
+
+        let status = data
+            .get("status")
+            .and_then(|s| s.as_str())
+            .unwrap_or("unknown");
+        self.publish(
+            &[Topic::Logs],
+            &json!({
+                "type": "log",
+                "level": if status == "success" { "info" } else { "warn" },
+                "message": format!("Execution {} finished with status {}", execution_id, status)
+            }),
+        )
+        .await;
+    }
+
+    /// Forward a node's progress event to the execution's subscribers
+    ///
+    /// Sent as `node_progress` or `node_partial`, carrying the node instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - Execution event from Hermes
+    pub async fn publish_execution_event(&self, event: &Value) {
+        let field = |key: &str| event.get(key).and_then(|v| v.as_str()).unwrap_or_default();
+        let execution_id = field("execution_id");
+
+        self.publish(
+            &[Topic::Execution(execution_id.to_string())],
+            &json!({
+                "type": format!("node_{}", field("type")),
+                "execution_id": execution_id,
+                "instance_id": field("instance_id"),
+                "data": event
+            }),
+        )
+        .await;
+    }
+
+    /// Announce a saved workspace to its subscribers
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Workspace name
+    /// * `revision` - Revision info returned by Hermes
+    pub async fn publish_workspace_saved(&self, name: &str, revision: &Value) {
+        self.publish(
+            &[Topic::Workspace(name.to_string())],
+            &json!({
+                "type": "workspace_saved",
+                "workspace": name,
+                "data": revision
+            }),
+        )
+        .await;
+    }
+
+    /// Announce a changed node registry to the `registry` subscribers
+    ///
+    /// # Arguments
+    ///
+    /// * `etag` - `ETag` of the new registry
+    /// * `diff` - Node types added, removed and changed
+    pub async fn publish_registry_updated(&self, etag: &HeaderValue, diff: &RegistryDiff) {
+        self.publish(
+            &[Topic::Registry],
+            &json!({
+                "type": "registry_updated",
+                "etag": etag.to_str().unwrap_or_default(),
+                "added": diff.added,
+                "removed": diff.removed,
+                "changed": diff.changed
+            }),
+        )
+        .await;
+    }
+
+    /// Send a message to one client, waiting for room in its queue
+    ///
+    /// Used for replies, which must not be dropped.
+    ///
+    /// # Returns
+    ///
+    /// `false` if the client is no longer connected
+    pub async fn send_to(&self, client_id: Uuid, message: String) -> bool {
+        let tx = match self.clients.read().unwrap().get(&client_id) {
+            Some(client) => client.tx.clone(),
+            None => return false,
+        };
+
+        tx.send(message).await.is_ok()
+    }
+
+    /// Queue a message for every client matching the filter
+    ///
+    /// Clients whose queue is full or closed are evicted.
+    fn deliver(&self, message: &Value, filter: impl Fn(&Client) -> bool) {
+        let Ok(text) = serde_json::to_string(message) else {
+            return;
+        };
+
+        let mut evicted = Vec::new();
+
+        for (client_id, client) in self.clients.read().unwrap().iter() {
+            if !filter(client) {
+                continue;
+            }
+
+            match client.tx.try_send(text.clone()) {
+                Ok(()) => {}
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    warn!("Evicting slow WebSocket client {}", client_id);
+                    evicted.push(*client_id);
+                }
+                Err(mpsc::error::TrySendError::Closed(_)) => evicted.push(*client_id),
+            }
+        }
+
+        if !evicted.is_empty() {
+            let mut clients = self.clients.write().unwrap();
+            for client_id in evicted {
+                clients.remove(&client_id);
+            }
+        }
+    }
+}
+
+/// Topics in a stable order, for replies
+fn sorted(topics: &HashSet<Topic>) -> Vec<Topic> {
+    let mut topics: Vec<Topic> = topics.iter().cloned().collect();
+    topics.sort_by_key(|t| t.to_string());
+    topics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_topic_parsing() {
+        assert_eq!(
+            "execution:abc".parse::<Topic>().unwrap(),
+            Topic::Execution("abc".to_string())
+        );
+        assert_eq!(
+            "workspace:demo".parse::<Topic>().unwrap(),
+            Topic::Workspace("demo".to_string())
+        );
+        assert_eq!("registry".parse::<Topic>().unwrap(), Topic::Registry);
+        assert_eq!(Topic::Logs.to_string(), "logs");
+
+        assert!("execution:".parse::<Topic>().is_err());
+        assert!("weather".parse::<Topic>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_publish_reaches_subscribers_only() {
+        let broadcaster = Broadcaster::new();
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut rx_a = broadcaster.register(a);
+        let mut rx_b = broadcaster.register(b);
+
+        let exec = Topic::Execution("exec-1".to_string());
+        let workspace = Topic::Workspace("demo".to_string());
+        broadcaster.subscribe(a, &[exec.clone(), workspace.clone()]);
+
+        broadcaster
+            .publish(&[exec, workspace], &json!({ "type": "done" }))
+            .await;
+
+        // Subscribed to both topics, but the message arrives once
+        assert!(rx_a.recv().await.unwrap().contains("done"));
+        assert!(rx_a.try_recv().is_err());
+        assert!(rx_b.try_recv().is_err());
+
+        assert!(broadcaster.send_to(b, "direct".to_string()).await);
+        assert_eq!(rx_b.recv().await.unwrap(), "direct");
+        assert!(rx_a.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_slow_consumer_is_evicted() {
+        let broadcaster = Broadcaster::new();
+        let (slow, fast) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut rx_slow = broadcaster.register(slow);
+        let mut rx_fast = broadcaster.register(fast);
+        broadcaster.subscribe(slow, &[Topic::Logs]);
+        broadcaster.subscribe(fast, &[Topic::Logs]);
+
+        // The fast client keeps up; the slow one never reads
+        for i in 0..CLIENT_QUEUE_CAPACITY + 10 {
+            broadcaster
+                .publish(&[Topic::Logs], &json!({ "seq": i }))
+                .await;
+            let msg: Value = serde_json::from_str(&rx_fast.recv().await.unwrap()).unwrap();
+            assert_eq!(msg["seq"], json!(i));
+        }
+
+        assert_eq!(broadcaster.client_count(), 1);
+        assert!(!broadcaster.send_to(slow, "reply".to_string()).await);
+
+        // The slow client can still drain what was queued, then sees the end
+        let mut received = 0;
+        while rx_slow.recv().await.is_some() {
+            received += 1;
+        }
+        assert_eq!(received, CLIENT_QUEUE_CAPACITY);
+    }
+
+    #[tokio::test]
+    async fn test_unregister_closes_queue() {
+        let broadcaster = Broadcaster::new();
+        let client = Uuid::new_v4();
+        let mut rx = broadcaster.register(client);
+
+        broadcaster.unregister(client);
+        assert!(rx.recv().await.is_none());
+        assert!(broadcaster.subscribe(client, &[Topic::Logs]).is_none());
+    }
+}
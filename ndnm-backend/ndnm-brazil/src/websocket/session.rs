@@ -0,0 +1,340 @@
+//! Resumable WebSocket sessions
+//!
+//! Every connection belongs to a session identified by a random token,
+//! sent to the client in the `connected` message. Messages delivered to the
+//! client carry a `session_seq` and the last [`REPLAY_BUFFER_SIZE`] of them
+//! are kept in the session's replay buffer.
+//!
+//! When the connection drops, the session is detached: the client stays
+//! registered in the broadcaster and its queue keeps filling up. A client
+//! reconnecting with `?session=<token>&last_seq=<n>` within
+//! [`SESSION_TTL`] gets the same client ID back, the buffered messages after
+//! `n` are replayed in order and the queued ones follow. Sessions that are
+//! not resumed in time expire; sessions whose queue overflowed meanwhile
+//! cannot be resumed.
+
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use super::Broadcaster;
+
+/// Number of delivered messages kept for replay per session
+pub const REPLAY_BUFFER_SIZE: usize = 256;
+
+/// How long a detached session can be resumed
+pub const SESSION_TTL: Duration = Duration::from_secs(60);
+
+/// A client session
+struct Session {
+    /// Client ID, kept across reconnects
+    client_id: Uuid,
+
+    /// Subject of the caller who started the session
+    owner: String,
+
+    /// Sequence number of the last delivered message
+    last_seq: u64,
+
+    /// Recently delivered messages, oldest first
+    replay: VecDeque<(u64, String)>,
+
+    /// Client queue while no connection is attached
+    parked: Option<mpsc::Receiver<String>>,
+
+    /// Incremented on every attach, so stale expiry timers do nothing
+    generation: u64,
+}
+
+/// A connection attached to a session
+pub struct Attached {
+    /// Session token
+    pub token: String,
+
+    /// Client ID of the session
+    pub client_id: Uuid,
+
+    /// Client queue to deliver
+    pub rx: mpsc::Receiver<String>,
+
+    /// Whether an existing session was resumed
+    pub resumed: bool,
+
+    /// Messages to redeliver before the queue, in order
+    pub replay: Vec<String>,
+
+    /// Whether every missed message could be replayed
+    pub replay_complete: bool,
+
+    /// Generation of the session for this connection
+    pub generation: u64,
+}
+
+/// Registry of client sessions
+#[derive(Clone, Default)]
+pub struct Sessions {
+    /// Sessions by token
+    sessions: Arc<Mutex<HashMap<String, Session>>>,
+}
+
+impl Sessions {
+    /// Attach a connection, resuming a session when possible
+    ///
+    /// # Arguments
+    ///
+    /// * `broadcaster` - Broadcaster the client is registered with
+    /// * `token` - Session token sent by the client, if any
+    /// * `last_seq` - Last `session_seq` the client received
+    /// * `owner` - Subject of the caller; other callers cannot resume the session
+    pub fn attach(
+        &self,
+        broadcaster: &Broadcaster,
+        token: Option<&str>,
+        last_seq: u64,
+        owner: &str,
+    ) -> Attached {
+        let mut sessions = self.sessions.lock().unwrap();
+
+        // A session whose client was evicted while detached cannot resume
+        if let Some(token) = token
+            && let Some(session) = sessions.get(token)
+            && session.parked.is_some()
+            && !broadcaster.is_registered(session.client_id)
+        {
+            sessions.remove(token);
+        }
+
+        if let Some(token) = token
+            && let Some(session) = sessions.get_mut(token)
+            && session.owner == owner
+            && let Some(rx) = session.parked.take()
+        {
+            session.generation += 1;
+
+            let oldest = session.replay.front().map(|(seq, _)| *seq);
+            let replay_complete =
+                last_seq >= session.last_seq || oldest.is_some_and(|oldest| oldest <= last_seq + 1);
+
+            return Attached {
+                token: token.to_string(),
+                client_id: session.client_id,
+                rx,
+                resumed: true,
+                replay: session
+                    .replay
+                    .iter()
+                    .filter(|(seq, _)| *seq > last_seq)
+                    .map(|(_, msg)| msg.clone())
+                    .collect(),
+                replay_complete,
+                generation: session.generation,
+            };
+        }
+
+        let token = Uuid::new_v4().to_string();
+        let client_id = Uuid::new_v4();
+        let rx = broadcaster.register(client_id);
+
+        sessions.insert(
+            token.clone(),
+            Session {
+                client_id,
+                owner: owner.to_string(),
+                last_seq: 0,
+                replay: VecDeque::new(),
+                parked: None,
+                generation: 0,
+            },
+        );
+
+        Attached {
+            token,
+            client_id,
+            rx,
+            resumed: false,
+            replay: Vec::new(),
+            replay_complete: true,
+            generation: 0,
+        }
+    }
+
+    /// Stamp a message with the next sequence number and record it for replay
+    ///
+    /// # Returns
+    ///
+    /// The message to send
+    pub fn stamp(&self, token: &str, message: String) -> String {
+        let mut sessions = self.sessions.lock().unwrap();
+        let Some(session) = sessions.get_mut(token) else {
+            return message;
+        };
+
+        let Ok(Value::Object(mut object)) = serde_json::from_str::<Value>(&message) else {
+            return message;
+        };
+
+        session.last_seq += 1;
+        object.insert("session_seq".to_string(), session.last_seq.into());
+        let stamped = Value::Object(object).to_string();
+
+        if session.replay.len() == REPLAY_BUFFER_SIZE {
+            session.replay.pop_front();
+        }
+        session
+            .replay
+            .push_back((session.last_seq, stamped.clone()));
+
+        stamped
+    }
+
+    /// Detach a connection, parking its queue until the session is resumed
+    pub fn detach(&self, token: &str, rx: mpsc::Receiver<String>) {
+        if let Some(session) = self.sessions.lock().unwrap().get_mut(token) {
+            session.parked = Some(rx);
+        }
+    }
+
+    /// Remove a session that is still detached at the given generation
+    ///
+    /// # Returns
+    ///
+    /// The session's client ID if it was removed
+    pub fn expire(&self, token: &str, generation: u64) -> Option<Uuid> {
+        let mut sessions = self.sessions.lock().unwrap();
+
+        let session = sessions.get(token)?;
+        if session.generation != generation || session.parked.is_none() {
+            return None;
+        }
+
+        sessions.remove(token).map(|session| session.client_id)
+    }
+
+    /// Remove a session right away
+    pub fn remove(&self, token: &str) {
+        self.sessions.lock().unwrap().remove(token);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::websocket::Topic;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_resume_replays_missed_messages() {
+        let broadcaster = Broadcaster::new();
+        let sessions = Sessions::default();
+
+        let mut first = sessions.attach(&broadcaster, None, 0, "alice");
+        assert!(!first.resumed);
+        broadcaster.subscribe(first.client_id, &[Topic::Logs]);
+
+        // Three messages delivered, but the client only saw the first
+        for i in 1..=3 {
+            broadcaster
+                .publish(&[Topic::Logs], &json!({ "n": i }))
+                .await;
+            let msg = first.rx.recv().await.unwrap();
+            let stamped: Value = serde_json::from_str(&sessions.stamp(&first.token, msg)).unwrap();
+            assert_eq!(stamped["session_seq"], json!(i));
+        }
+
+        sessions.detach(&first.token, first.rx);
+
+        // Published while disconnected
+        broadcaster
+            .publish(&[Topic::Logs], &json!({ "n": 4 }))
+            .await;
+
+        let mut resumed = sessions.attach(&broadcaster, Some(&first.token), 1, "alice");
+        assert!(resumed.resumed);
+        assert!(resumed.replay_complete);
+        assert_eq!(resumed.client_id, first.client_id);
+
+        let replayed: Vec<Value> = resumed
+            .replay
+            .iter()
+            .map(|m| serde_json::from_str(m).unwrap())
+            .collect();
+        assert_eq!(replayed.len(), 2);
+        assert_eq!(replayed[0]["n"], json!(2));
+        assert_eq!(replayed[1]["n"], json!(3));
+
+        let queued: Value = serde_json::from_str(&resumed.rx.recv().await.unwrap()).unwrap();
+        assert_eq!(queued["n"], json!(4));
+
+        // A session can only be attached once at a time
+        let other = sessions.attach(&broadcaster, Some(&first.token), 0, "alice");
+        assert!(!other.resumed);
+        assert_ne!(other.client_id, first.client_id);
+
+        // and only by its owner
+        sessions.detach(&first.token, resumed.rx);
+        let stolen = sessions.attach(&broadcaster, Some(&first.token), 0, "mallory");
+        assert!(!stolen.resumed);
+        assert!(
+            sessions
+                .attach(&broadcaster, Some(&first.token), 0, "alice")
+                .resumed
+        );
+    }
+
+    #[test]
+    fn test_evicted_session_cannot_resume() {
+        let broadcaster = Broadcaster::new();
+        let sessions = Sessions::default();
+        let attached = sessions.attach(&broadcaster, None, 0, "alice");
+
+        sessions.detach(&attached.token, attached.rx);
+        broadcaster.unregister(attached.client_id);
+
+        let fresh = sessions.attach(&broadcaster, Some(&attached.token), 0, "alice");
+        assert!(!fresh.resumed);
+        assert_ne!(fresh.token, attached.token);
+    }
+
+    #[test]
+    fn test_replay_buffer_is_bounded() {
+        let broadcaster = Broadcaster::new();
+        let sessions = Sessions::default();
+        let attached = sessions.attach(&broadcaster, None, 0, "alice");
+
+        for i in 0..REPLAY_BUFFER_SIZE + 10 {
+            sessions.stamp(&attached.token, json!({ "n": i }).to_string());
+        }
+        sessions.detach(&attached.token, attached.rx);
+
+        let resumed = sessions.attach(&broadcaster, Some(&attached.token), 0, "alice");
+        assert_eq!(resumed.replay.len(), REPLAY_BUFFER_SIZE);
+        assert!(!resumed.replay_complete);
+    }
+
+    #[test]
+    fn test_expire() {
+        let broadcaster = Broadcaster::new();
+        let sessions = Sessions::default();
+        let attached = sessions.attach(&broadcaster, None, 0, "alice");
+        let token = attached.token.clone();
+
+        // Attached sessions do not expire
+        assert!(sessions.expire(&token, 0).is_none());
+
+        sessions.detach(&token, attached.rx);
+        let resumed = sessions.attach(&broadcaster, Some(&token), 0, "alice");
+        sessions.detach(&token, resumed.rx);
+
+        // The timer of the first detach is stale
+        assert!(sessions.expire(&token, 0).is_none());
+        assert_eq!(sessions.expire(&token, 1), Some(attached.client_id));
+        assert!(
+            !sessions
+                .attach(&broadcaster, Some(&token), 0, "alice")
+                .resumed
+        );
+    }
+}
@@ -0,0 +1,82 @@
+//! # Counter Storage
+//!
+//! Latest counter values reported by each service (e.g. Brazil's rate
+//! limiter). Services report cumulative totals, so each report replaces the
+//! previous one for its source and a lost report costs nothing.
+
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Counters reported by one service
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CounterReport {
+    /// When the report was received
+    pub updated_at: DateTime<Utc>,
+    /// Counter values by name
+    pub counters: BTreeMap<String, u64>,
+}
+
+/// Thread-safe storage of the last counter report of each service
+#[derive(Clone, Default)]
+pub struct CounterStore {
+    /// Last report by source service
+    reports: Arc<DashMap<String, CounterReport>>,
+}
+
+impl CounterStore {
+    /// Create an empty counter store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the counters of a service
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - Source service name
+    /// * `counters` - Current counter values
+    pub fn report(&self, source: impl Into<String>, counters: BTreeMap<String, u64>) {
+        self.reports.insert(
+            source.into(),
+            CounterReport {
+                updated_at: Utc::now(),
+                counters,
+            },
+        );
+    }
+
+    /// Last report of every service
+    pub fn all(&self) -> BTreeMap<String, CounterReport> {
+        self.reports
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_replaces_previous_values() {
+        let store = CounterStore::new();
+
+        store.report(
+            "ndnm-brazil",
+            BTreeMap::from([("rate_limit.run.limited".to_string(), 1)]),
+        );
+        store.report(
+            "ndnm-brazil",
+            BTreeMap::from([("rate_limit.run.limited".to_string(), 4)]),
+        );
+        store.report("other", BTreeMap::new());
+
+        let all = store.all();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all["ndnm-brazil"].counters["rate_limit.run.limited"], 4);
+    }
+}
@@ -9,7 +9,14 @@
 //! - **Non-blocking**: System continues if Exdoida is down
 //! - **Independent**: Does not depend on other services
 //! - **Lightweight**: Minimal overhead on the main system
+//!
+//! ## Counters
+//!
+//! Besides logs, services report cumulative counters (such as Brazil's rate
+//! limiter hits) with `POST /counters`; the last report of each service is
+//! served by `GET /counters` and `GET /metrics`.
 
+mod counters;
 mod storage;
 mod udp_server;
 
@@ -21,6 +28,7 @@ use axum::{
     Json, Router,
 };
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use tokio::net::TcpListener;
 use tower_http::{cors::CorsLayer, trace::TraceLayer};
@@ -28,6 +36,7 @@ use tower_http::cors::Any;
 use tracing::info;
 use chrono::Utc;
 
+use counters::{CounterReport, CounterStore};
 use storage::{LogEntry, LogStore};
 
 /// Application state
@@ -35,6 +44,8 @@ use storage::{LogEntry, LogStore};
 struct AppState {
     /// Log storage
     log_store: Arc<LogStore>,
+    /// Counters reported by services
+    counters: CounterStore,
 }
 
 // === API Handlers ===
@@ -108,6 +119,8 @@ struct MetricsResponse {
     total_logs: usize,
     logs_by_level: std::collections::HashMap<String, usize>,
     logs_by_source: std::collections::HashMap<String, usize>,
+    /// Last counters reported by each service
+    counters: BTreeMap<String, CounterReport>,
 }
 
 /// Handler for GET /metrics - Get metrics
@@ -126,9 +139,33 @@ async fn get_metrics(State(state): State<AppState>) -> Json<MetricsResponse> {
         total_logs: state.log_store.count(),
         logs_by_level,
         logs_by_source,
+        counters: state.counters.all(),
     })
 }
 
+/// Request body for POST /counters
+#[derive(Debug, Deserialize)]
+struct ReportCountersRequest {
+    /// Source service name
+    source: String,
+    /// Current counter values, replacing the previous report
+    counters: BTreeMap<String, u64>,
+}
+
+/// Handler for POST /counters - Record a service's counters
+async fn report_counters(
+    State(state): State<AppState>,
+    Json(request): Json<ReportCountersRequest>,
+) -> StatusCode {
+    state.counters.report(request.source, request.counters);
+    StatusCode::OK
+}
+
+/// Handler for GET /counters - Last counters reported by each service
+async fn get_counters(State(state): State<AppState>) -> Json<BTreeMap<String, CounterReport>> {
+    Json(state.counters.all())
+}
+
 /// Handler for DELETE /logs - Clear all logs
 async fn clear_logs(State(state): State<AppState>) -> StatusCode {
     state.log_store.clear();
@@ -149,6 +186,7 @@ fn create_router(state: AppState) -> Router {
         .route("/logs", axum::routing::delete(clear_logs))
         .route("/logs", post(create_log))
         .route("/metrics", get(get_metrics))
+        .route("/counters", get(get_counters).post(report_counters))
         .layer(cors)
         .layer(TraceLayer::new_for_http())
         .with_state(state)
@@ -177,6 +215,7 @@ async fn main() -> Result<()> {
     // Create application state
     let state = AppState {
         log_store: log_store.clone(),
+        counters: CounterStore::new(),
     };
 
     // Start UDP server in background
@@ -200,8 +239,11 @@ async fn main() -> Result<()> {
 
     let app = create_router(state);
     let listener = TcpListener::bind(&addr).await?;
-    axum::serve(listener, app).await?;
+    axum::serve(listener, app)
+        .with_graceful_shutdown(ndnm_libs::shutdown_signal())
+        .await?;
 
+    info!("Exdoida stopped");
     Ok(())
 }
 
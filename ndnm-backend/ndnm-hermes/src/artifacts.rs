@@ -0,0 +1,505 @@
+//! Content-addressed artifact store
+//!
+//! Nodes exchange large values (`BLOB` and `FILE_CONTENT` outputs) through
+//! this store instead of the orchestrator's JSON: a node uploads the content
+//! with `PUT /artifacts`, outputs the returned `{"$ref": "sha256:<hex>"}`
+//! handle, and downstream nodes fetch it from `GET /artifacts/:sha256`
+//! (see `ndnm_libs::artifact`).
+//!
+//! ## Garbage Collection
+//!
+//! Every execution holds a reference to each artifact in its node inputs
+//! and outputs until it finishes. Artifacts nobody holds are deleted by the
+//! collection that runs after each execution, once they have been
+//! unreferenced for `ARTIFACT_GRACE_SECS` (default one hour) - long enough
+//! for clients to download the final outputs. Uploads that never appear in
+//! an execution are collected the same way.
+//!
+//! Artifacts live in `ARTIFACT_DIR` (default `./artifacts`) and are limited
+//! to `ARTIFACT_MAX_BYTES` each (default 256 MiB).
+
+use crate::AppState;
+use axum::{
+    body::{Body, Bytes},
+    extract::{Path, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures_util::{Stream, StreamExt};
+use ndnm_libs::range::{content_range, parse_range};
+use ndnm_libs::{AppError, ArtifactRef};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::SeekFrom;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio_util::io::ReaderStream;
+use tracing::{info, warn};
+
+/// Default directory of the store
+const DEFAULT_DIR: &str = "./artifacts";
+
+/// Default size limit of one artifact
+pub const DEFAULT_MAX_SIZE: usize = 256 * 1024 * 1024;
+
+/// Default time an unreferenced artifact is kept
+const DEFAULT_GRACE: Duration = Duration::from_secs(60 * 60);
+
+/// Reference count and last use of an artifact
+#[derive(Debug)]
+struct Usage {
+    /// Executions holding the artifact
+    holders: usize,
+    /// When the artifact was last stored, fetched or released
+    last_used: Instant,
+}
+
+/// Artifact store on the local filesystem
+pub struct ArtifactStore {
+    /// Root directory
+    dir: PathBuf,
+    /// Size limit of one artifact
+    max_size: usize,
+    /// Time an unreferenced artifact is kept
+    grace: Duration,
+    /// Usage of the artifacts seen since Hermes started
+    usage: Mutex<HashMap<String, Usage>>,
+}
+
+impl ArtifactStore {
+    /// Create a store in the given directory with the default limits
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            max_size: DEFAULT_MAX_SIZE,
+            grace: DEFAULT_GRACE,
+            usage: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Create a store from `ARTIFACT_DIR`, `ARTIFACT_MAX_BYTES` and
+    /// `ARTIFACT_GRACE_SECS`
+    pub fn from_env() -> Self {
+        let dir = std::env::var("ARTIFACT_DIR").unwrap_or_else(|_| DEFAULT_DIR.to_string());
+        let number = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .filter(|n| *n > 0)
+        };
+
+        let mut store = Self::new(dir);
+        if let Some(max_size) = number("ARTIFACT_MAX_BYTES") {
+            store.max_size = max_size as usize;
+        }
+        if let Some(secs) = number("ARTIFACT_GRACE_SECS") {
+            store = store.with_grace(Duration::from_secs(secs));
+        }
+        store
+    }
+
+    /// Keep unreferenced artifacts for the given time
+    pub fn with_grace(mut self, grace: Duration) -> Self {
+        self.grace = grace;
+        self
+    }
+
+    /// Size limit of one artifact
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// Path of an artifact's content; artifacts are spread over 256
+    /// directories
+    fn path(&self, handle: &ArtifactRef) -> PathBuf {
+        let hex = handle.sha256();
+        self.dir.join(&hex[..2]).join(hex)
+    }
+
+    /// Record a use of an artifact without changing its holders
+    fn touch(&self, handle: &ArtifactRef) {
+        self.usage
+            .lock()
+            .unwrap()
+            .entry(handle.sha256().to_string())
+            .or_insert(Usage {
+                holders: 0,
+                last_used: Instant::now(),
+            })
+            .last_used = Instant::now();
+    }
+
+    /// Store content and return its handle and size
+    ///
+    /// The content is written to a temporary file as it arrives, so uploads
+    /// are never held in memory whole.
+    ///
+    /// # Returns
+    ///
+    /// * `Err(AppError::BadRequest)` - The content exceeds the size limit or
+    ///   the stream failed
+    pub async fn put_stream<S, E>(&self, mut stream: S) -> Result<(ArtifactRef, u64), AppError>
+    where
+        S: Stream<Item = Result<Bytes, E>> + Unpin,
+        E: std::fmt::Display,
+    {
+        fs::create_dir_all(&self.dir).await?;
+        let tmp = self.dir.join(format!(".upload.{}", uuid::Uuid::new_v4()));
+
+        let (hex, size) = match self.write_upload(&tmp, &mut stream).await {
+            Ok(written) => written,
+            Err(e) => {
+                let _ = fs::remove_file(&tmp).await;
+                return Err(e);
+            }
+        };
+        let handle = ArtifactRef::parse(&hex)?;
+        let path = self.path(&handle);
+
+        if fs::try_exists(&path).await? {
+            fs::remove_file(&tmp).await?;
+        } else {
+            fs::create_dir_all(path.parent().unwrap_or(&self.dir)).await?;
+            fs::rename(&tmp, &path).await?;
+            info!("Stored artifact {} ({} bytes)", handle, size);
+        }
+
+        self.touch(&handle);
+        Ok((handle, size))
+    }
+
+    /// Write a stream to a file, returning the hex SHA-256 and size of its
+    /// content
+    async fn write_upload<S, E>(
+        &self,
+        path: &std::path::Path,
+        stream: &mut S,
+    ) -> Result<(String, u64), AppError>
+    where
+        S: Stream<Item = Result<Bytes, E>> + Unpin,
+        E: std::fmt::Display,
+    {
+        let mut file = fs::File::create(path).await?;
+        let mut hasher = Sha256::new();
+        let mut size = 0;
+
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk
+                .map_err(|e| AppError::BadRequest(format!("Failed to read artifact: {}", e)))?;
+            size += chunk.len() as u64;
+            if size > self.max_size as u64 {
+                return Err(AppError::BadRequest(format!(
+                    "Artifact exceeds the limit of {} bytes",
+                    self.max_size
+                )));
+            }
+
+            hasher.update(&chunk);
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await?;
+
+        let hex = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+        Ok((hex, size))
+    }
+
+    /// Whether an artifact is stored
+    pub async fn contains(&self, handle: &ArtifactRef) -> bool {
+        fs::try_exists(self.path(handle)).await.unwrap_or(false)
+    }
+
+    /// Open an artifact, returning the file and its size
+    async fn open(&self, handle: &ArtifactRef) -> Result<(fs::File, u64), AppError> {
+        let file = fs::File::open(self.path(handle)).await?;
+        let size = file.metadata().await?.len();
+
+        self.touch(handle);
+        Ok((file, size))
+    }
+
+    /// Add a holder to each artifact
+    pub fn retain(&self, handles: &[ArtifactRef]) {
+        let mut usage = self.usage.lock().unwrap();
+        let now = Instant::now();
+
+        for handle in handles {
+            let entry = usage.entry(handle.sha256().to_string()).or_insert(Usage {
+                holders: 0,
+                last_used: now,
+            });
+            entry.holders += 1;
+            entry.last_used = now;
+        }
+    }
+
+    /// Remove a holder from each artifact
+    pub fn release(&self, handles: &[ArtifactRef]) {
+        let mut usage = self.usage.lock().unwrap();
+        let now = Instant::now();
+
+        for handle in handles {
+            if let Some(entry) = usage.get_mut(handle.sha256()) {
+                entry.holders = entry.holders.saturating_sub(1);
+                entry.last_used = now;
+            }
+        }
+    }
+
+    /// Delete the artifacts nobody holds that have been unused for longer
+    /// than the grace period
+    ///
+    /// Artifacts left over from a previous run of Hermes are judged by
+    /// their modification time.
+    ///
+    /// # Returns
+    ///
+    /// Number of artifacts deleted
+    pub async fn collect(&self) -> Result<usize, AppError> {
+        let mut removed = 0;
+        let Ok(mut shards) = fs::read_dir(&self.dir).await else {
+            return Ok(0);
+        };
+
+        while let Some(shard) = shards.next_entry().await? {
+            if !shard.file_type().await?.is_dir() {
+                continue;
+            }
+
+            let mut entries = fs::read_dir(shard.path()).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let name = entry.file_name().to_string_lossy().to_string();
+                let Ok(handle) = ArtifactRef::parse(&name) else {
+                    continue;
+                };
+
+                let known = self
+                    .usage
+                    .lock()
+                    .unwrap()
+                    .get(handle.sha256())
+                    .map(|usage| usage.holders == 0 && usage.last_used.elapsed() > self.grace);
+                let expired = match known {
+                    Some(expired) => expired,
+                    None => {
+                        let modified = entry.metadata().await?.modified()?;
+                        let age = SystemTime::now()
+                            .duration_since(modified)
+                            .unwrap_or_default();
+                        age > self.grace
+                    }
+                };
+
+                if !expired {
+                    continue;
+                }
+
+                // A holder may have appeared while the lock was released
+                let claimed = {
+                    let mut usage = self.usage.lock().unwrap();
+                    let held = usage.get(handle.sha256()).is_some_and(|u| u.holders > 0);
+                    if !held {
+                        usage.remove(handle.sha256());
+                    }
+                    !held
+                };
+                if !claimed {
+                    continue;
+                }
+
+                // Another collection may have deleted it already
+                match fs::remove_file(entry.path()).await {
+                    Ok(()) => removed += 1,
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        }
+
+        if removed > 0 {
+            info!("Collected {} unreferenced artifact(s)", removed);
+        }
+        Ok(removed)
+    }
+}
+
+/// Artifacts held by one execution, released when it is dropped
+///
+/// Dropping the lease also starts a collection, so every execution is
+/// followed by one.
+pub struct ArtifactLease {
+    store: Arc<ArtifactStore>,
+    held: Vec<ArtifactRef>,
+}
+
+impl ArtifactLease {
+    /// Start an empty lease
+    pub fn new(store: Arc<ArtifactStore>) -> Self {
+        Self {
+            store,
+            held: Vec::new(),
+        }
+    }
+
+    /// Store the lease holds artifacts of
+    pub fn store(&self) -> &ArtifactStore {
+        &self.store
+    }
+
+    /// Hold the given artifacts until the lease is dropped
+    pub fn hold(&mut self, handles: Vec<ArtifactRef>) {
+        self.store.retain(&handles);
+        self.held.extend(handles);
+    }
+}
+
+impl Drop for ArtifactLease {
+    fn drop(&mut self) {
+        self.store.release(&self.held);
+
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            let store = self.store.clone();
+            runtime.spawn(async move {
+                if let Err(e) = store.collect().await {
+                    warn!("Artifact collection failed: {}", e);
+                }
+            });
+        }
+    }
+}
+
+/// Handler for PUT /artifacts - Store an artifact, streamed to disk
+///
+/// Answers 201 with `{"$ref": "sha256:<hex>", "size": n}`.
+pub async fn put_artifact(
+    State(state): State<AppState>,
+    body: Body,
+) -> Result<(StatusCode, Json<serde_json::Value>), AppError> {
+    let (handle, size) = state.artifacts.put_stream(body.into_data_stream()).await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({ "$ref": handle.to_string(), "size": size })),
+    ))
+}
+
+/// Handler for GET /artifacts/:sha256 - Stream an artifact
+///
+/// Supports single byte ranges (`206 Partial Content`).
+pub async fn get_artifact(
+    State(state): State<AppState>,
+    Path(hash): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let handle = ArtifactRef::parse(&hash)?;
+
+    if !state.artifacts.contains(&handle).await {
+        let body = Json(json!({ "error": format!("Artifact {} not found", handle) }));
+        return Ok((StatusCode::NOT_FOUND, body).into_response());
+    }
+
+    let (mut file, size) = state.artifacts.open(&handle).await?;
+
+    let requested = match parse_range(headers.get(header::RANGE), size) {
+        Ok(requested) => requested,
+        Err(e) => {
+            let body = Json(json!({ "error": e.to_string() }));
+            let content_range = [(header::CONTENT_RANGE, e.content_range())];
+            return Ok((StatusCode::RANGE_NOT_SATISFIABLE, content_range, body).into_response());
+        }
+    };
+    let range = requested.clone().unwrap_or(0..size);
+    file.seek(SeekFrom::Start(range.start)).await?;
+    let body = Body::from_stream(ReaderStream::new(file.take(range.end - range.start)));
+
+    let mut response = (
+        [
+            (header::CONTENT_TYPE, "application/octet-stream".to_string()),
+            (header::CONTENT_LENGTH, (range.end - range.start).to_string()),
+            (header::ACCEPT_RANGES, "bytes".to_string()),
+            (header::ETAG, format!("\"{}\"", handle.sha256())),
+        ],
+        body,
+    )
+        .into_response();
+
+    if let Some(range) = requested
+        && let Ok(value) = HeaderValue::from_str(&content_range(&range, size))
+    {
+        *response.status_mut() = StatusCode::PARTIAL_CONTENT;
+        response.headers_mut().insert(header::CONTENT_RANGE, value);
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Store content given in one chunk
+    async fn put(store: &ArtifactStore, content: &'static [u8]) -> ArtifactRef {
+        let chunks = futures_util::stream::iter([Ok::<_, AppError>(Bytes::from_static(content))]);
+        store.put_stream(chunks).await.unwrap().0
+    }
+
+    #[tokio::test]
+    async fn test_put_deduplicates() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ArtifactStore::new(dir.path());
+
+        let handle = put(&store, b"test").await;
+        assert_eq!(
+            handle.sha256(),
+            "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+        );
+        assert_eq!(put(&store, b"test").await, handle);
+        assert!(store.contains(&handle).await);
+
+        let (_, size) = store.open(&handle).await.unwrap();
+        assert_eq!(size, 4);
+    }
+
+    #[tokio::test]
+    async fn test_put_stream_enforces_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = ArtifactStore::new(dir.path());
+        store.max_size = 4;
+
+        let chunks = [b"abc".as_slice(), b"de"].map(|c| Ok::<_, AppError>(Bytes::from_static(c)));
+        let result = store.put_stream(futures_util::stream::iter(chunks)).await;
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+
+        // The partial upload is removed
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_collect_spares_held_artifacts() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(ArtifactStore::new(dir.path()).with_grace(Duration::ZERO));
+
+        let held = put(&store, b"held").await;
+        let loose = put(&store, b"loose").await;
+
+        let mut lease = ArtifactLease::new(store.clone());
+        lease.hold(vec![held.clone()]);
+
+        assert_eq!(store.collect().await.unwrap(), 1);
+        assert!(store.contains(&held).await);
+        assert!(!store.contains(&loose).await);
+
+        // Two holders: the artifact survives the first release
+        store.retain(std::slice::from_ref(&held));
+        drop(lease);
+        store.collect().await.unwrap();
+        assert!(store.contains(&held).await);
+
+        store.release(std::slice::from_ref(&held));
+        store.collect().await.unwrap();
+        assert!(!store.contains(&held).await);
+    }
+}
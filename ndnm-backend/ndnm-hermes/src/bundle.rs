@@ -0,0 +1,992 @@
+//! Workspace bundle module
+//!
+//! Packs a workspace into a single `.tar.gz` archive to share pipelines
+//! between machines. A bundle contains:
+//!
+//! - `manifest.json`: bundle description (node types, versions, files)
+//! - `workspace.json`: the workspace data
+//! - `nodes/<node_type>/config.yaml`: config of every referenced node type
+//! - `files/<instance_id>/<filename>`: files found in the target directory
+//!   of every node exposing a `target_directory` field (e.g. node-file-browser)
+//!
+//! Imports check the versions of the node types the workspace uses against
+//! the local registry before anything is written into `nexus/`. Target
+//! directories must stay inside their node's directory.
+
+use crate::migration::migrate_workspace;
+use crate::registry::NodeRegistry;
+use crate::workspace::{
+    sanitize_filename, RevisionInfo, SaveWorkspaceRequest, WorkspaceData, WorkspaceManager,
+};
+use axum::body::Bytes;
+use chrono::{DateTime, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use ndnm_libs::AppError;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use tracing::info;
+
+/// Version of the bundle format written by this Hermes
+pub const BUNDLE_FORMAT_VERSION: u32 = 1;
+
+/// Maximum size of a bundle, compressed or unpacked (256 MiB)
+pub const MAX_BUNDLE_SIZE: usize = 256 * 1024 * 1024;
+
+/// Input field holding the directory managed by file-based nodes
+const TARGET_DIRECTORY_FIELD: &str = "target_directory";
+
+/// Archive path of the bundle manifest
+const MANIFEST_PATH: &str = "manifest.json";
+
+/// Archive path of the workspace data
+const WORKSPACE_PATH: &str = "workspace.json";
+
+/// Description of the contents of a bundle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleManifest {
+    /// Bundle format version
+    pub format_version: u32,
+
+    /// Name of the exported workspace
+    pub name: String,
+
+    /// When the bundle was created
+    pub exported_at: DateTime<Utc>,
+
+    /// Node types referenced by the workspace
+    pub node_types: Vec<BundledNodeType>,
+
+    /// Files pinned from node target directories
+    pub files: Vec<BundledFile>,
+}
+
+/// A node type referenced by a bundled workspace
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundledNodeType {
+    /// Node type ID
+    pub node_type_id: String,
+
+    /// Node config version the workspace matches
+    pub version: u32,
+
+    /// Archive path of the node's `config.yaml`
+    ///
+    /// `None` when the node type was not registered on the exporting machine.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub config: Option<String>,
+}
+
+/// A file pinned from a node's target directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundledFile {
+    /// Node instance owning the target directory
+    pub instance_id: String,
+
+    /// File name inside the target directory
+    pub filename: String,
+
+    /// Archive path of the file contents
+    pub path: String,
+}
+
+/// Query parameters for the import endpoint
+#[derive(Debug, Default, Deserialize)]
+pub struct ImportOptions {
+    /// Name to import the workspace as (defaults to the bundled name)
+    #[serde(default)]
+    pub name: Option<String>,
+
+    /// Only check compatibility, without writing anything
+    #[serde(default)]
+    pub dry_run: bool,
+
+    /// Replace an existing workspace with the same name
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+/// How a bundled node type relates to the local registry
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CompatibilityStatus {
+    /// Same version registered locally
+    Compatible,
+
+    /// Older than the local version; migrations are applied on load
+    Upgradable,
+
+    /// Node type is not registered locally
+    Missing,
+
+    /// Bundle was made with a newer version than the local one
+    RequiresNewerVersion,
+}
+
+/// Compatibility of a single bundled node type
+#[derive(Debug, Serialize)]
+pub struct NodeCompatibility {
+    /// Node type ID
+    pub node_type_id: String,
+
+    /// Version recorded in the bundle
+    pub bundled_version: u32,
+
+    /// Version registered locally, if any
+    pub local_version: Option<u32>,
+
+    /// Compatibility status
+    pub status: CompatibilityStatus,
+}
+
+/// Response for the import endpoint
+#[derive(Debug, Serialize)]
+pub struct ImportResponse {
+    /// Name the workspace was (or would be) imported as
+    pub name: String,
+
+    /// Whether the workspace was written to `nexus/`
+    pub imported: bool,
+
+    /// Whether every bundled node type can be used locally
+    pub compatible: bool,
+
+    /// Per node type compatibility report
+    pub node_types: Vec<NodeCompatibility>,
+
+    /// Files written into node target directories
+    pub files_written: usize,
+
+    /// Files skipped because a file with the same name already existed
+    pub files_skipped: usize,
+
+    /// Revision created by the import
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revision: Option<RevisionInfo>,
+}
+
+/// Export a workspace as a `.tar.gz` bundle
+///
+/// The workspace is migrated to the current node versions first, so the
+/// bundled graph matches the bundled node configs.
+///
+/// # Arguments
+///
+/// * `manager` - Workspace manager owning the workspace
+/// * `registry` - Registry used to locate node configs and target directories
+/// * `name` - Workspace name
+///
+/// # Returns
+///
+/// The compressed archive bytes
+pub async fn export_workspace(
+    manager: &WorkspaceManager,
+    registry: &NodeRegistry,
+    name: &str,
+) -> Result<Vec<u8>, AppError> {
+    let mut data = manager.load_workspace(name).await?;
+    migrate_workspace(&mut data, registry);
+
+    let registry = registry.clone();
+    let name = name.to_string();
+    blocking(move || write_bundle(&registry, &name, &data)).await
+}
+
+/// Pack a loaded workspace with its node configs and pinned files
+fn write_bundle(
+    registry: &NodeRegistry,
+    name: &str,
+    data: &WorkspaceData,
+) -> Result<Vec<u8>, AppError> {
+    let mut entries: Vec<(String, Vec<u8>)> = Vec::new();
+    let mut node_types = Vec::new();
+    let mut files = Vec::new();
+
+    let node_type_ids: BTreeSet<&String> =
+        data.graph.nodes.iter().map(|n| &n.node_type_id).collect();
+
+    for node_type_id in node_type_ids {
+        let Some(node_info) = registry.get_node(node_type_id) else {
+            node_types.push(BundledNodeType {
+                node_type_id: node_type_id.clone(),
+                version: data.node_versions.get(node_type_id).copied().unwrap_or(1),
+                config: None,
+            });
+            continue;
+        };
+
+        let config_path = format!("nodes/{}/config.yaml", sanitize_filename(node_type_id));
+        let contents = fs::read(node_info.path.join("config.yaml"))
+            .map_err(|e| AppError::Internal(format!("Failed to read node config: {}", e)))?;
+        entries.push((config_path.clone(), contents));
+
+        node_types.push(BundledNodeType {
+            node_type_id: node_type_id.clone(),
+            version: node_info.config.version,
+            config: Some(config_path),
+        });
+    }
+
+    let mut total_size = 0;
+
+    for node in &data.graph.nodes {
+        let Some(dir) = target_directory(registry, data, &node.instance_id)? else {
+            continue;
+        };
+
+        for filename in list_files(&dir)? {
+            let contents = fs::read(dir.join(&filename))
+                .map_err(|e| AppError::Internal(format!("Failed to read pinned file: {}", e)))?;
+
+            total_size += contents.len();
+            if total_size > MAX_BUNDLE_SIZE {
+                return Err(AppError::BadRequest(format!(
+                    "Workspace '{}' has more than {} bytes of pinned files",
+                    name, MAX_BUNDLE_SIZE
+                )));
+            }
+
+            let path = format!(
+                "files/{}/{}",
+                sanitize_filename(&node.instance_id),
+                filename
+            );
+            entries.push((path.clone(), contents));
+            files.push(BundledFile {
+                instance_id: node.instance_id.clone(),
+                filename,
+                path,
+            });
+        }
+    }
+
+    let manifest = BundleManifest {
+        format_version: BUNDLE_FORMAT_VERSION,
+        name: name.to_string(),
+        exported_at: Utc::now(),
+        node_types,
+        files,
+    };
+
+    let manifest_json = serde_json::to_vec_pretty(&manifest)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize manifest: {}", e)))?;
+    let workspace_json = serde_json::to_vec_pretty(data)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize workspace: {}", e)))?;
+
+    entries.insert(0, (MANIFEST_PATH.to_string(), manifest_json));
+    entries.insert(1, (WORKSPACE_PATH.to_string(), workspace_json));
+
+    let archive = write_archive(&entries)?;
+    info!(
+        "Workspace '{}' exported ({} node types, {} files, {} bytes)",
+        name,
+        manifest.node_types.len(),
+        manifest.files.len(),
+        archive.len()
+    );
+
+    Ok(archive)
+}
+
+/// Import a workspace from a `.tar.gz` bundle
+///
+/// Nothing is written unless every node type the workspace uses is compatible
+/// with the local registry. Pinned files are restored into the target
+/// directories of the imported nodes, never overwriting existing files;
+/// bundles with target directories outside their node's directory are
+/// rejected.
+///
+/// # Arguments
+///
+/// * `manager` - Workspace manager to import into
+/// * `registry` - Registry to check compatibility against
+/// * `archive` - Compressed bundle bytes
+/// * `options` - Import name, dry run and overwrite flags
+pub async fn import_workspace(
+    manager: &WorkspaceManager,
+    registry: &NodeRegistry,
+    archive: Bytes,
+    options: ImportOptions,
+) -> Result<ImportResponse, AppError> {
+    let entries = blocking(move || read_archive(&archive)).await?;
+
+    let manifest: BundleManifest = parse_entry(&entries, MANIFEST_PATH)?;
+    if manifest.format_version > BUNDLE_FORMAT_VERSION {
+        return Err(AppError::BadRequest(format!(
+            "Bundle format version {} is newer than the supported version {}",
+            manifest.format_version, BUNDLE_FORMAT_VERSION
+        )));
+    }
+
+    let data: WorkspaceData = parse_entry(&entries, WORKSPACE_PATH)?;
+    let name = options.name.unwrap_or_else(|| manifest.name.clone());
+
+    let node_types = check_compatibility(&manifest, &data, registry);
+    let compatible = node_types.iter().all(|n| {
+        matches!(
+            n.status,
+            CompatibilityStatus::Compatible | CompatibilityStatus::Upgradable
+        )
+    });
+
+    let mut response = ImportResponse {
+        name: name.clone(),
+        imported: false,
+        compatible,
+        node_types,
+        files_written: 0,
+        files_skipped: 0,
+        revision: None,
+    };
+
+    if !compatible || options.dry_run {
+        return Ok(response);
+    }
+
+    // The save checks again under the workspace lock; checking here keeps
+    // pinned files from being restored for a name that is already taken
+    if !options.overwrite && manager.workspace_exists(&name).await? {
+        return Err(AppError::BadRequest(format!(
+            "Workspace '{}' already exists",
+            name
+        )));
+    }
+
+    let (registry, workspace, files) = (registry.clone(), data.clone(), manifest.files.clone());
+    (response.files_written, response.files_skipped) =
+        blocking(move || restore_files(&registry, &workspace, &files, entries)).await?;
+
+    let revision = manager
+        .save_workspace(SaveWorkspaceRequest {
+            name: name.clone(),
+            data,
+            message: Some(format!("Imported from bundle '{}'", manifest.name)),
+            // Revision 0 requires that the workspace does not exist yet
+            expected_revision: (!options.overwrite).then_some(0),
+            must_exist: false,
+        })
+        .await?;
+
+    info!(
+        "Workspace '{}' imported ({} files written, {} skipped)",
+        name, response.files_written, response.files_skipped
+    );
+
+    response.imported = true;
+    response.revision = Some(revision);
+    Ok(response)
+}
+
+/// Restore pinned files into the target directories of a workspace's nodes
+///
+/// Every target directory is resolved before anything is written. Existing
+/// files, including symlinks, are never overwritten.
+///
+/// # Returns
+///
+/// The number of files written and skipped
+fn restore_files(
+    registry: &NodeRegistry,
+    data: &WorkspaceData,
+    files: &[BundledFile],
+    mut entries: HashMap<String, Vec<u8>>,
+) -> Result<(usize, usize), AppError> {
+    let mut directories = HashMap::new();
+    for node in &data.graph.nodes {
+        if let Some(dir) = target_directory(registry, data, &node.instance_id)? {
+            directories.insert(node.instance_id.clone(), dir);
+        }
+    }
+
+    let (mut written, mut skipped) = (0, 0);
+
+    for file in files {
+        let Some(dir) = directories.get(&file.instance_id) else {
+            continue;
+        };
+
+        // Only plain file names are accepted, never paths
+        if Path::new(&file.filename).file_name() != Some(file.filename.as_ref()) {
+            return Err(AppError::BadRequest(format!(
+                "Invalid file name in bundle: {}",
+                file.filename
+            )));
+        }
+
+        let contents = entries.remove(&file.path).ok_or_else(|| {
+            AppError::BadRequest(format!("Bundle is missing file: {}", file.path))
+        })?;
+
+        fs::create_dir_all(dir)
+            .map_err(|e| AppError::Internal(format!("Failed to create directory: {}", e)))?;
+
+        // `create_new` also fails on a symlink, dangling or not, instead of
+        // following it
+        let destination = dir.join(&file.filename);
+        let mut out = match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&destination)
+        {
+            Ok(out) => out,
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                skipped += 1;
+                continue;
+            }
+            Err(e) => {
+                return Err(AppError::Internal(format!(
+                    "Failed to create pinned file: {}",
+                    e
+                )));
+            }
+        };
+        out.write_all(&contents)
+            .map_err(|e| AppError::Internal(format!("Failed to write pinned file: {}", e)))?;
+        written += 1;
+    }
+
+    Ok((written, skipped))
+}
+
+/// Compare the versions of the node types a bundle uses with the local
+/// registry
+///
+/// Covers the node types listed in the manifest and those of the bundled
+/// workspace, which a hand-made bundle may leave out of the manifest.
+fn check_compatibility(
+    manifest: &BundleManifest,
+    data: &WorkspaceData,
+    registry: &NodeRegistry,
+) -> Vec<NodeCompatibility> {
+    let mut versions: BTreeMap<&str, u32> = manifest
+        .node_types
+        .iter()
+        .map(|bundled| (bundled.node_type_id.as_str(), bundled.version))
+        .collect();
+    for node in &data.graph.nodes {
+        versions.entry(&node.node_type_id).or_insert_with(|| {
+            data.node_versions.get(&node.node_type_id).copied().unwrap_or(1)
+        });
+    }
+
+    versions
+        .into_iter()
+        .map(|(node_type_id, bundled_version)| {
+            let local_version = registry.get_node(node_type_id).map(|n| n.config.version);
+
+            let status = match local_version {
+                None => CompatibilityStatus::Missing,
+                Some(local) if local < bundled_version => {
+                    CompatibilityStatus::RequiresNewerVersion
+                }
+                Some(local) if local > bundled_version => CompatibilityStatus::Upgradable,
+                Some(_) => CompatibilityStatus::Compatible,
+            };
+
+            NodeCompatibility {
+                node_type_id: node_type_id.to_string(),
+                bundled_version,
+                local_version,
+                status,
+            }
+        })
+        .collect()
+}
+
+/// Resolve the target directory of a node instance
+///
+/// Only node types declaring a `target_directory` input field are considered.
+/// Paths are resolved against the node's directory, which is the working
+/// directory of the node process, and must stay inside it.
+///
+/// # Returns
+///
+/// * `Err(AppError::BadRequest)` - The target directory is absolute, goes up
+///   with `..`, leads outside the node's directory through a symlink, or
+///   cannot be resolved
+fn target_directory(
+    registry: &NodeRegistry,
+    data: &WorkspaceData,
+    instance_id: &str,
+) -> Result<Option<PathBuf>, AppError> {
+    let Some(node) = data.graph.nodes.iter().find(|n| n.instance_id == instance_id) else {
+        return Ok(None);
+    };
+    let Some(node_info) = registry.get_node(&node.node_type_id) else {
+        return Ok(None);
+    };
+
+    if !node_info
+        .config
+        .input_fields
+        .iter()
+        .any(|f| f.name == TARGET_DIRECTORY_FIELD)
+    {
+        return Ok(None);
+    }
+
+    let Some(target) = node
+        .input_values
+        .get(TARGET_DIRECTORY_FIELD)
+        .and_then(|v| v.as_str())
+    else {
+        return Ok(None);
+    };
+
+    let invalid = || {
+        AppError::BadRequest(format!(
+            "Target directory of node '{}' must stay inside the node directory: {}",
+            instance_id, target
+        ))
+    };
+
+    if !Path::new(target)
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(invalid());
+    }
+
+    // Symlinks inside the node directory may still lead out of it. The part
+    // of the path that does not exist yet is created as plain directories,
+    // so resolving the nearest existing ancestor is enough.
+    let dir = node_info.path.join(target);
+    let base = node_info.path.canonicalize().map_err(|_| invalid())?;
+    let existing = dir
+        .ancestors()
+        .find(|a| a.symlink_metadata().is_ok())
+        .ok_or_else(invalid)?;
+    let resolved = existing.canonicalize().map_err(|_| invalid())?;
+    if !resolved.starts_with(&base) {
+        return Err(invalid());
+    }
+
+    Ok(Some(dir))
+}
+
+/// List the regular files at the top level of a directory
+fn list_files(dir: &Path) -> Result<Vec<String>, AppError> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let entries = fs::read_dir(dir)
+        .map_err(|e| AppError::Internal(format!("Failed to read target directory: {}", e)))?;
+
+    let mut files: Vec<String> = entries
+        .flatten()
+        .filter(|entry| entry.path().is_file())
+        .filter_map(|entry| entry.file_name().to_str().map(|s| s.to_string()))
+        .collect();
+    files.sort();
+
+    Ok(files)
+}
+
+/// Build a gzip-compressed tar archive from in-memory entries
+fn write_archive(entries: &[(String, Vec<u8>)]) -> Result<Vec<u8>, AppError> {
+    let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    let mtime = Utc::now().timestamp().max(0) as u64;
+
+    for (path, contents) in entries {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(mtime);
+        header.set_cksum();
+
+        builder
+            .append_data(&mut header, path, contents.as_slice())
+            .map_err(|e| AppError::Internal(format!("Failed to write bundle entry: {}", e)))?;
+    }
+
+    builder
+        .into_inner()
+        .and_then(|encoder| encoder.finish())
+        .map_err(|e| AppError::Internal(format!("Failed to finish bundle: {}", e)))
+}
+
+/// Read every file of a gzip-compressed tar archive into memory
+fn read_archive(archive: &[u8]) -> Result<HashMap<String, Vec<u8>>, AppError> {
+    let invalid = |e: std::io::Error| AppError::BadRequest(format!("Invalid bundle: {}", e));
+
+    let mut tar = tar::Archive::new(GzDecoder::new(archive));
+    let mut entries = HashMap::new();
+    let mut total_size = 0;
+
+    for entry in tar.entries().map_err(invalid)? {
+        let mut entry = entry.map_err(invalid)?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let path = entry.path().map_err(invalid)?.to_string_lossy().into_owned();
+
+        let too_large = || {
+            AppError::BadRequest(format!(
+                "Bundle exceeds {} bytes once unpacked",
+                MAX_BUNDLE_SIZE
+            ))
+        };
+
+        // The header is checked first so that a bomb is rejected without
+        // being inflated, and reads are capped in case the header lies
+        let remaining = (MAX_BUNDLE_SIZE - total_size) as u64;
+        if entry.header().size().map_err(invalid)? > remaining {
+            return Err(too_large());
+        }
+
+        let mut contents = Vec::new();
+        entry
+            .by_ref()
+            .take(remaining + 1)
+            .read_to_end(&mut contents)
+            .map_err(invalid)?;
+
+        total_size += contents.len();
+        if total_size > MAX_BUNDLE_SIZE {
+            return Err(too_large());
+        }
+
+        entries.insert(path, contents);
+    }
+
+    Ok(entries)
+}
+
+/// Parse a JSON entry of a bundle
+fn parse_entry<T: serde::de::DeserializeOwned>(
+    entries: &HashMap<String, Vec<u8>>,
+    path: &str,
+) -> Result<T, AppError> {
+    let contents = entries
+        .get(path)
+        .ok_or_else(|| AppError::BadRequest(format!("Bundle is missing {}", path)))?;
+
+    serde_json::from_slice(contents)
+        .map_err(|e| AppError::BadRequest(format!("Invalid {} in bundle: {}", path, e)))
+}
+
+/// Run file and archive work on the blocking thread pool
+async fn blocking<T, F>(work: F) -> Result<T, AppError>
+where
+    F: FnOnce() -> Result<T, AppError> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(|e| AppError::Internal(format!("Bundle task failed: {}", e)))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::NodeInfo;
+    use ndnm_libs::load_config;
+    use tempfile::TempDir;
+
+    const BROWSER_TYPE: &str = "hash_sha256_de_viniciusxpb_node-file-browser";
+
+    /// Registry with the file browser registered from a copy of its node directory
+    fn registry(node_dir: &Path) -> NodeRegistry {
+        fs::copy(
+            "../nodes/node-file-browser/config.yaml",
+            node_dir.join("config.yaml"),
+        )
+        .unwrap();
+        let config = load_config(node_dir.join("config.yaml")).unwrap();
+
+        let mut registry = NodeRegistry::new();
+        registry
+            .register(NodeInfo {
+                node_id: config.node_id_hash.clone(),
+                config,
+                path: node_dir.to_path_buf(),
+                port: 3001,
+                is_running: false,
+            })
+            .unwrap();
+        registry
+    }
+
+    fn workspace(target: &str) -> WorkspaceData {
+        serde_json::from_value(serde_json::json!({
+            "graph": {
+                "nodes": [{
+                    "instance_id": "browser",
+                    "node_type_id": BROWSER_TYPE,
+                    "input_values": {"target_directory": target}
+                }],
+                "connections": []
+            }
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_export_and_import() {
+        let source = TempDir::new().unwrap();
+        let source_nodes = TempDir::new().unwrap();
+        let source_registry = registry(source_nodes.path());
+        let source_manager = WorkspaceManager::new(source.path());
+
+        fs::create_dir_all(source_nodes.path().join("files")).unwrap();
+        fs::write(source_nodes.path().join("files/notes.txt"), "hello").unwrap();
+
+        source_manager
+            .save_workspace(SaveWorkspaceRequest {
+                name: "shared".to_string(),
+                data: workspace("./files"),
+                message: None,
+                expected_revision: None,
+                must_exist: false,
+            })
+            .await
+            .unwrap();
+
+        let archive = export_workspace(&source_manager, &source_registry, "shared")
+            .await
+            .map(Bytes::from)
+            .unwrap();
+
+        let target = TempDir::new().unwrap();
+        let target_nodes = TempDir::new().unwrap();
+        let target_registry = registry(target_nodes.path());
+        let target_manager = WorkspaceManager::new(target.path());
+
+        let dry_run = ImportOptions {
+            dry_run: true,
+            ..Default::default()
+        };
+        let response = import_workspace(&target_manager, &target_registry, archive.clone(), dry_run)
+            .await
+            .unwrap();
+        assert!(response.compatible);
+        assert!(!response.imported);
+        assert!(!target_manager.workspace_exists("shared").await.unwrap());
+
+        let response = import_workspace(
+            &target_manager,
+            &target_registry,
+            archive.clone(),
+            ImportOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert!(response.imported);
+        assert_eq!(response.files_written, 1);
+        assert_eq!(response.node_types[0].status, CompatibilityStatus::Compatible);
+        assert_eq!(
+            fs::read_to_string(target_nodes.path().join("files/notes.txt")).unwrap(),
+            "hello"
+        );
+
+        // Importing again needs an explicit overwrite
+        let result = import_workspace(
+            &target_manager,
+            &target_registry,
+            archive.clone(),
+            ImportOptions::default(),
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_import_checks_compatibility() {
+        let source = TempDir::new().unwrap();
+        let source_nodes = TempDir::new().unwrap();
+        let source_registry = registry(source_nodes.path());
+        let source_manager = WorkspaceManager::new(source.path());
+
+        source_manager
+            .save_workspace(SaveWorkspaceRequest {
+                name: "shared".to_string(),
+                data: workspace("./files"),
+                message: None,
+                expected_revision: None,
+                must_exist: false,
+            })
+            .await
+            .unwrap();
+
+        let archive = export_workspace(&source_manager, &source_registry, "shared")
+            .await
+            .map(Bytes::from)
+            .unwrap();
+
+        // A machine without the file browser cannot import the bundle
+        let target = TempDir::new().unwrap();
+        let target_manager = WorkspaceManager::new(target.path());
+        let response = import_workspace(
+            &target_manager,
+            &NodeRegistry::new(),
+            archive.clone(),
+            ImportOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(!response.compatible);
+        assert!(!response.imported);
+        assert_eq!(response.node_types[0].status, CompatibilityStatus::Missing);
+        assert!(!target_manager.workspace_exists("shared").await.unwrap());
+    }
+
+    /// Bundle of `data` whose manifest lists no node types
+    fn bundle(data: &WorkspaceData, files: Vec<BundledFile>) -> Bytes {
+        let manifest = BundleManifest {
+            format_version: BUNDLE_FORMAT_VERSION,
+            name: "crafted".to_string(),
+            exported_at: Utc::now(),
+            node_types: vec![],
+            files,
+        };
+        let mut entries = vec![
+            (MANIFEST_PATH.to_string(), serde_json::to_vec(&manifest).unwrap()),
+            (WORKSPACE_PATH.to_string(), serde_json::to_vec(data).unwrap()),
+        ];
+        entries.push(("files/browser/evil.txt".to_string(), b"evil".to_vec()));
+        write_archive(&entries).unwrap().into()
+    }
+
+    #[tokio::test]
+    async fn test_import_rejects_target_outside_node_directory() {
+        let target = TempDir::new().unwrap();
+        let target_nodes = TempDir::new().unwrap();
+        let target_registry = registry(target_nodes.path());
+        let target_manager = WorkspaceManager::new(target.path());
+        let outside = TempDir::new().unwrap();
+
+        let escape = outside.path().join("x");
+        for dir in [escape.to_str().unwrap(), "../../x", "./files/../../x"] {
+            let file = BundledFile {
+                instance_id: "browser".to_string(),
+                filename: "evil.txt".to_string(),
+                path: "files/browser/evil.txt".to_string(),
+            };
+            let archive = bundle(&workspace(dir), vec![file]);
+
+            let result = import_workspace(
+                &target_manager,
+                &target_registry,
+                archive.clone(),
+                ImportOptions::default(),
+            )
+            .await;
+            assert!(matches!(result, Err(AppError::BadRequest(_))), "{}", dir);
+        }
+
+        assert!(!escape.exists());
+        assert!(!target_manager.workspace_exists("crafted").await.unwrap());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_import_does_not_follow_symlinks() {
+        let target = TempDir::new().unwrap();
+        let target_nodes = TempDir::new().unwrap();
+        let target_registry = registry(target_nodes.path());
+        let target_manager = WorkspaceManager::new(target.path());
+        let outside = TempDir::new().unwrap();
+        let file = || BundledFile {
+            instance_id: "browser".to_string(),
+            filename: "evil.txt".to_string(),
+            path: "files/browser/evil.txt".to_string(),
+        };
+
+        // A directory that does not exist yet, below a symlink leading out
+        std::os::unix::fs::symlink(outside.path(), target_nodes.path().join("link")).unwrap();
+        let archive = bundle(&workspace("./link/new"), vec![file()]);
+        let result = import_workspace(
+            &target_manager,
+            &target_registry,
+            archive,
+            ImportOptions::default(),
+        )
+        .await;
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+        assert!(!outside.path().join("new").exists());
+
+        // A dangling symlink in place of the pinned file
+        let files = target_nodes.path().join("files");
+        fs::create_dir_all(&files).unwrap();
+        let escape = outside.path().join("evil.txt");
+        std::os::unix::fs::symlink(&escape, files.join("evil.txt")).unwrap();
+        let archive = bundle(&workspace("./files"), vec![file()]);
+        let response = import_workspace(
+            &target_manager,
+            &target_registry,
+            archive,
+            ImportOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.files_skipped, 1);
+        assert!(!escape.exists());
+    }
+
+    #[test]
+    fn test_read_archive_checks_entry_sizes_first() {
+        // The header claims more than a bundle may hold; the data is never read
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(MAX_BUNDLE_SIZE as u64 + 1);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "bomb", &b"small"[..])
+            .unwrap();
+        let archive = builder.into_inner().unwrap().finish().unwrap();
+
+        let error = read_archive(&archive).unwrap_err();
+        assert!(error.to_string().contains("once unpacked"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_imports_do_not_overwrite() {
+        let target = TempDir::new().unwrap();
+        let target_nodes = TempDir::new().unwrap();
+        let target_registry = registry(target_nodes.path());
+        let target_manager = WorkspaceManager::new(target.path());
+
+        let archive = bundle(&workspace("./files"), vec![]);
+        let import = || {
+            import_workspace(
+                &target_manager,
+                &target_registry,
+                archive.clone(),
+                ImportOptions::default(),
+            )
+        };
+        let (first, second) = tokio::join!(import(), import());
+        assert!(first.is_ok() != second.is_ok());
+        assert_eq!(target_manager.current_revision("crafted").await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_import_checks_node_types_of_workspace() {
+        let target = TempDir::new().unwrap();
+        let target_manager = WorkspaceManager::new(target.path());
+
+        // The manifest leaves out the file browser the workspace uses
+        let archive = bundle(&workspace("./files"), vec![]);
+        let response = import_workspace(
+            &target_manager,
+            &NodeRegistry::new(),
+            archive.clone(),
+            ImportOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(!response.compatible);
+        assert_eq!(response.node_types[0].node_type_id, BROWSER_TYPE);
+        assert_eq!(response.node_types[0].status, CompatibilityStatus::Missing);
+        assert!(!target_manager.workspace_exists("crafted").await.unwrap());
+    }
+}
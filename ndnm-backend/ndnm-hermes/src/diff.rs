@@ -0,0 +1,209 @@
+//! Graph diff module
+//!
+//! Computes a structural diff between two graph definitions, used to compare
+//! workspace revisions
+
+use crate::orchestrator::{Connection, GraphDefinition, GraphNode};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+
+/// Structural difference between two graphs
+#[derive(Debug, Default, Serialize)]
+pub struct GraphDiff {
+    /// Instance IDs of nodes present only in the newer graph
+    pub nodes_added: Vec<String>,
+
+    /// Instance IDs of nodes present only in the older graph
+    pub nodes_removed: Vec<String>,
+
+    /// Nodes present in both graphs whose definition changed
+    pub nodes_changed: Vec<NodeChange>,
+
+    /// Connections present only in the newer graph
+    pub connections_added: Vec<Connection>,
+
+    /// Connections present only in the older graph
+    pub connections_removed: Vec<Connection>,
+}
+
+/// Description of how a single node changed between two graphs
+#[derive(Debug, Serialize)]
+pub struct NodeChange {
+    /// Node instance ID
+    pub instance_id: String,
+
+    /// Changed fields, e.g. `node_type_id`, `position`, `input_values.<name>`
+    /// or `inputs.<handle>`
+    pub changed_fields: Vec<String>,
+}
+
+/// Compute the structural diff going from `old` to `new`
+///
+/// Nodes are matched by `instance_id`; connections are matched by their four
+/// endpoints, so a rewired connection shows up as one removal plus one addition.
+pub fn diff_graphs(old: &GraphDefinition, new: &GraphDefinition) -> GraphDiff {
+    let old_nodes: HashMap<&str, &GraphNode> = old
+        .nodes
+        .iter()
+        .map(|n| (n.instance_id.as_str(), n))
+        .collect();
+    let new_nodes: HashMap<&str, &GraphNode> = new
+        .nodes
+        .iter()
+        .map(|n| (n.instance_id.as_str(), n))
+        .collect();
+
+    let mut diff = GraphDiff::default();
+
+    // Iterate in graph order so the output is stable
+    for node in &new.nodes {
+        match old_nodes.get(node.instance_id.as_str()) {
+            None => diff.nodes_added.push(node.instance_id.clone()),
+            Some(old_node) => {
+                let changed_fields = changed_node_fields(old_node, node);
+                if !changed_fields.is_empty() {
+                    diff.nodes_changed.push(NodeChange {
+                        instance_id: node.instance_id.clone(),
+                        changed_fields,
+                    });
+                }
+            }
+        }
+    }
+
+    for node in &old.nodes {
+        if !new_nodes.contains_key(node.instance_id.as_str()) {
+            diff.nodes_removed.push(node.instance_id.clone());
+        }
+    }
+
+    for conn in &new.connections {
+        if !old.connections.iter().any(|c| same_connection(c, conn)) {
+            diff.connections_added.push(conn.clone());
+        }
+    }
+
+    for conn in &old.connections {
+        if !new.connections.iter().any(|c| same_connection(c, conn)) {
+            diff.connections_removed.push(conn.clone());
+        }
+    }
+
+    diff
+}
+
+/// List the fields that differ between two versions of the same node
+fn changed_node_fields(old: &GraphNode, new: &GraphNode) -> Vec<String> {
+    let mut changed = Vec::new();
+
+    if old.node_type_id != new.node_type_id {
+        changed.push("node_type_id".to_string());
+    }
+
+    let old_position = old.position.as_ref().map(|p| (p.x, p.y));
+    let new_position = new.position.as_ref().map(|p| (p.x, p.y));
+    if old_position != new_position {
+        changed.push("position".to_string());
+    }
+
+    // BTreeSet keeps field names sorted and deduplicated
+    let field_names: BTreeSet<&String> = old
+        .input_values
+        .keys()
+        .chain(new.input_values.keys())
+        .collect();
+
+    for name in field_names {
+        if old.input_values.get(name) != new.input_values.get(name) {
+            changed.push(format!("input_values.{}", name));
+        }
+    }
+
+    let handle_names: BTreeSet<&String> = old.inputs.keys().chain(new.inputs.keys()).collect();
+
+    for name in handle_names {
+        if old.inputs.get(name) != new.inputs.get(name) {
+            changed.push(format!("inputs.{}", name));
+        }
+    }
+
+    changed
+}
+
+/// Whether two connections link the same handles
+fn same_connection(a: &Connection, b: &Connection) -> bool {
+    a.from_node == b.from_node
+        && a.from_handle == b.from_handle
+        && a.to_node == b.to_node
+        && a.to_handle == b.to_handle
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn node(instance_id: &str, target: &str) -> GraphNode {
+        let mut input_values = HashMap::new();
+        input_values.insert("target_directory".to_string(), json!(target));
+
+        GraphNode {
+            instance_id: instance_id.to_string(),
+            node_type_id: "type1".to_string(),
+            input_values,
+            inputs: HashMap::new(),
+            position: None,
+        }
+    }
+
+    fn connection(from: &str, to: &str) -> Connection {
+        Connection {
+            from_node: from.to_string(),
+            from_handle: "copied_output_0".to_string(),
+            to_node: to.to_string(),
+            to_handle: "copy_input_0".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_diff_identical_graphs() {
+        let graph = GraphDefinition {
+            nodes: vec![node("a", "./in")],
+            connections: vec![],
+        };
+
+        let diff = diff_graphs(&graph, &graph);
+        assert!(diff.nodes_added.is_empty());
+        assert!(diff.nodes_removed.is_empty());
+        assert!(diff.nodes_changed.is_empty());
+        assert!(diff.connections_added.is_empty());
+        assert!(diff.connections_removed.is_empty());
+    }
+
+    #[test]
+    fn test_diff_nodes_and_connections() {
+        let old = GraphDefinition {
+            nodes: vec![node("a", "./in"), node("b", "./out")],
+            connections: vec![connection("a", "b")],
+        };
+        let new = GraphDefinition {
+            nodes: vec![node("a", "./other"), node("c", "./out")],
+            connections: vec![connection("a", "c")],
+        };
+
+        let diff = diff_graphs(&old, &new);
+
+        assert_eq!(diff.nodes_added, vec!["c".to_string()]);
+        assert_eq!(diff.nodes_removed, vec!["b".to_string()]);
+        assert_eq!(diff.nodes_changed.len(), 1);
+        assert_eq!(diff.nodes_changed[0].instance_id, "a");
+        assert_eq!(
+            diff.nodes_changed[0].changed_fields,
+            vec!["input_values.target_directory".to_string()]
+        );
+        assert_eq!(diff.connections_added.len(), 1);
+        assert_eq!(diff.connections_added[0].to_node, "c");
+        assert_eq!(diff.connections_removed.len(), 1);
+        assert_eq!(diff.connections_removed[0].to_node, "b");
+    }
+}
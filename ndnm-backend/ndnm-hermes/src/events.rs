@@ -0,0 +1,195 @@
+//! Progress events of running executions
+//!
+//! Nodes that stream their `/run` response report progress and partial
+//! outputs (see `ndnm_libs::progress`). Hermes forwards them to the
+//! listeners of the execution, on `GET /executions/:execution_id/events`:
+//!
+//! ```text
+//! event: progress
+//! data: {"execution_id":"...","instance_id":"scan","type":"progress","percent":40.0}
+//! ```
+//!
+//! Listeners may subscribe before the execution starts, so they miss none
+//! of its events; the stream ends when the execution does.
+
+use crate::AppState;
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use ndnm_libs::{RunEvent, StreamFormat};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::warn;
+
+/// Events buffered per execution; listeners further behind skip events
+const CHANNEL_CAPACITY: usize = 256;
+
+/// Event reported by a node of an execution
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExecutionEvent {
+    /// Execution the node runs in
+    pub execution_id: String,
+
+    /// Node instance that reported the event
+    pub instance_id: String,
+
+    /// The event (`progress` or `partial`)
+    #[serde(flatten)]
+    pub event: RunEvent,
+}
+
+/// Channels of the executions that have listeners
+#[derive(Default)]
+pub struct ExecutionEvents {
+    channels: Mutex<HashMap<String, broadcast::Sender<ExecutionEvent>>>,
+}
+
+impl ExecutionEvents {
+    /// Create an empty hub
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Listen to the events of an execution, running or not yet started
+    pub fn subscribe(self: &Arc<Self>, execution_id: &str) -> Subscription {
+        let rx = self
+            .channels
+            .lock()
+            .unwrap()
+            .entry(execution_id.to_string())
+            .or_insert_with(|| broadcast::channel(CHANNEL_CAPACITY).0)
+            .subscribe();
+
+        Subscription {
+            events: self.clone(),
+            execution_id: execution_id.to_string(),
+            rx,
+        }
+    }
+
+    /// Send an event to the listeners of its execution, if any
+    pub fn publish(&self, event: ExecutionEvent) {
+        if let Some(tx) = self.channels.lock().unwrap().get(&event.execution_id) {
+            let _ = tx.send(event);
+        }
+    }
+
+    /// End the event streams of a finished execution
+    pub fn close(&self, execution_id: &str) {
+        self.channels.lock().unwrap().remove(execution_id);
+    }
+}
+
+/// A listener of an execution's events
+pub struct Subscription {
+    events: Arc<ExecutionEvents>,
+    execution_id: String,
+    rx: broadcast::Receiver<ExecutionEvent>,
+}
+
+impl Subscription {
+    /// The next event, or `None` once the execution has ended
+    pub async fn next(&mut self) -> Option<ExecutionEvent> {
+        loop {
+            match self.rx.recv().await {
+                Ok(event) => return Some(event),
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(
+                        "Listener of execution {} skipped {} events",
+                        self.execution_id, skipped
+                    );
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+impl Drop for Subscription {
+    /// Drop the channel with its last listener
+    fn drop(&mut self) {
+        let mut channels = self.events.channels.lock().unwrap();
+        if channels
+            .get(&self.execution_id)
+            .is_some_and(|tx| tx.receiver_count() <= 1)
+        {
+            channels.remove(&self.execution_id);
+        }
+    }
+}
+
+/// Handler for GET /executions/:execution_id/events
+///
+/// Streams the execution's events as Server-Sent Events, named by type.
+pub async fn stream_events(
+    State(state): State<AppState>,
+    Path(execution_id): Path<String>,
+) -> Response {
+    let subscription = state.orchestrator.events().subscribe(&execution_id);
+
+    let events = futures_util::stream::unfold(subscription, |mut subscription| async move {
+        let event = subscription.next().await?;
+        let frame = StreamFormat::Sse.encode(event.event.kind(), &event);
+        Some((Ok::<_, Infallible>(frame), subscription))
+    });
+
+    (
+        [
+            (header::CONTENT_TYPE, StreamFormat::Sse.content_type()),
+            (header::CACHE_CONTROL, "no-cache"),
+        ],
+        Body::from_stream(events),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn progress(execution_id: &str, percent: f32) -> ExecutionEvent {
+        ExecutionEvent {
+            execution_id: execution_id.to_string(),
+            instance_id: "scan".to_string(),
+            event: RunEvent::Progress {
+                percent,
+                message: None,
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_subscription_ends_with_execution() {
+        let events = Arc::new(ExecutionEvents::new());
+
+        // Events without listeners go nowhere
+        events.publish(progress("exec-1", 10.0));
+
+        let mut subscription = events.subscribe("exec-1");
+        events.publish(progress("exec-1", 50.0));
+        events.publish(progress("exec-2", 60.0));
+        events.close("exec-1");
+
+        assert_eq!(subscription.next().await, Some(progress("exec-1", 50.0)));
+        assert_eq!(subscription.next().await, None);
+    }
+
+    #[test]
+    fn test_last_listener_drops_channel() {
+        let events = Arc::new(ExecutionEvents::new());
+
+        let first = events.subscribe("exec-1");
+        let second = events.subscribe("exec-1");
+        drop(first);
+        assert!(events.channels.lock().unwrap().contains_key("exec-1"));
+
+        drop(second);
+        assert!(events.channels.lock().unwrap().is_empty());
+    }
+}
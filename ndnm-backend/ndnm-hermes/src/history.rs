@@ -0,0 +1,101 @@
+//! Execution history module
+//!
+//! Persists execution records through the nexus [`Storage`] backend, so that
+//! runs cut short by a shutdown are not silently lost
+
+use crate::orchestrator::{ExecutionStatus, GraphDefinition, NodeExecutionResult};
+use crate::storage::Storage;
+use chrono::{DateTime, Utc};
+use ndnm_libs::AppError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::info;
+
+/// A persisted record of a graph execution
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutionRecord {
+    /// Execution ID
+    pub execution_id: String,
+
+    /// Final status of the execution
+    pub status: ExecutionStatus,
+
+    /// When the execution started
+    pub started_at: DateTime<Utc>,
+
+    /// When the execution stopped (finished, failed or was interrupted)
+    pub finished_at: DateTime<Utc>,
+
+    /// Graph that was being executed
+    pub graph: GraphDefinition,
+
+    /// Results of the nodes that ran before the execution stopped
+    pub node_results: HashMap<String, NodeExecutionResult>,
+
+    /// Error or interruption reason
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Execution history kept in the nexus storage
+pub struct ExecutionHistory {
+    /// Backend where execution records are stored
+    storage: Arc<dyn Storage>,
+}
+
+impl ExecutionHistory {
+    /// Create a new execution history
+    ///
+    /// # Arguments
+    ///
+    /// * `storage` - Backend where execution records are stored
+    pub fn new(storage: Arc<dyn Storage>) -> Self {
+        Self { storage }
+    }
+
+    /// Persist an execution record, replacing any previous record with the same ID
+    pub async fn record(&self, record: &ExecutionRecord) -> Result<(), AppError> {
+        self.storage.record_execution(record).await?;
+
+        info!(
+            "Execution '{}' recorded as {:?}",
+            record.execution_id, record.status
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::FsStorage;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_record_execution() {
+        let temp_dir = TempDir::new().unwrap();
+        let history = ExecutionHistory::new(Arc::new(FsStorage::new(temp_dir.path())));
+
+        let record = ExecutionRecord {
+            execution_id: "exec-1".to_string(),
+            status: ExecutionStatus::Interrupted,
+            started_at: Utc::now(),
+            finished_at: Utc::now(),
+            graph: GraphDefinition {
+                nodes: vec![],
+                connections: vec![],
+            },
+            node_results: HashMap::new(),
+            error: Some("shutdown".to_string()),
+        };
+
+        history.record(&record).await.unwrap();
+
+        let contents = fs::read_to_string(temp_dir.path().join("executions/exec-1.json")).unwrap();
+        let loaded: ExecutionRecord = serde_json::from_str(&contents).unwrap();
+        assert!(matches!(loaded.status, ExecutionStatus::Interrupted));
+        assert_eq!(loaded.error.as_deref(), Some("shutdown"));
+    }
+}
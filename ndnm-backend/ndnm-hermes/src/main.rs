@@ -15,29 +15,106 @@
 //! 4. Executes graphs by coordinating node execution
 //! 5. Handles data flow between nodes
 //! 6. Provides API for ndnm-brazil (BFF)
+//!
+//! ## Node Registry
+//!
+//! `GET /nodes/registry` carries an `ETag` derived from the registry's
+//! content and answers `If-None-Match` with 304. Every response also carries
+//! it in `x-registry-etag`, so Brazil notices a changed registry (e.g. after
+//! a restart with new nodes) without polling.
+//!
+//! ## Artifacts
+//!
+//! Nodes pass large outputs to each other as `{"$ref": "sha256:<hex>"}`
+//! handles to Hermes' artifact store (`PUT /artifacts`,
+//! `GET /artifacts/:sha256`) rather than as JSON. Running executions hold
+//! the artifacts in their outputs; the rest are collected after each
+//! execution (see `artifacts`).
+//!
+//! ## Progress Events
+//!
+//! Nodes that stream their `/run` response report progress and partial
+//! outputs while they run. Hermes forwards them to the listeners of
+//! `GET /executions/:execution_id/events` (Server-Sent Events), which may
+//! subscribe before the execution starts (see `events`).
+//!
+//! ## Wire Format
+//!
+//! `/run` bodies are sent to nodes as JSON, or as MessagePack or CBOR with
+//! `NODE_WIRE_FORMAT=msgpack|cbor`, which carries bytes values
+//! (`{"$bytes": "<base64>"}`) as raw bytes. Nodes that reject the format
+//! with 415 are called with JSON instead (see `ndnm_libs::wire`).
+//!
+//! ## Cancellation
+//!
+//! Nodes get the execution and instance they run for with every `/run`
+//! call, and `NODE_TIMEOUT_SECS` as their deadline when set. When an
+//! execution is cancelled or interrupted, Hermes calls
+//! `POST /cancel/:execution_id` on the node it was running so the node
+//! stops too (see `ndnm_libs::context`).
+//!
+//! ## Shutdown
+//!
+//! On SIGINT/SIGTERM Hermes stops accepting new executions, gives in-flight
+//! ones `SHUTDOWN_TIMEOUT_SECS` to finish, then cancels the rest and persists
+//! them as `interrupted` in the execution history.
+//!
+//! ## Service Authentication
+//!
+//! With `NDNM_SERVICE_SECRET` set, every route except the health checks
+//! only accepts requests signed with the shared secret (i.e. from Brazil,
+//! or from nodes using the artifact store),
+//! and Hermes signs its `/run` calls to nodes (see `ndnm_libs::signing`).
+//!
+//! ## Storage
+//!
+//! Workspaces and execution history live in `./nexus`, either as loose JSON
+//! files (`NEXUS_STORAGE=fs`, the default) or in an embedded SQLite database
+//! (`NEXUS_STORAGE=sqlite`, path from `NEXUS_DB`, default `nexus/nexus.db`).
+//! An existing `nexus/` directory is imported into a fresh database with:
+//!
+//! ```text
+//! ndnm-hermes import-nexus [nexus_dir] [db_path]
+//! ```
 
+mod artifacts;
+mod bundle;
+mod diff;
 mod discovery;
+mod events;
+mod history;
+mod migration;
 mod orchestrator;
 mod registry;
+mod storage;
+mod template;
+mod validation;
 mod workspace;
 
 use anyhow::Result;
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    routing::{get, post},
+    body::Bytes,
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{delete, get, post, put, MethodRouter},
     Json, Router,
 };
-use ndnm_libs::AppError;
+use ndnm_libs::{signing, AppError, Signer, Verifier, WireFormat};
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::net::TcpListener;
 use tower_http::trace::TraceLayer;
 use tracing::{info, warn};
 
+use artifacts::ArtifactStore;
 use discovery::DiscoveryService;
+use history::ExecutionHistory;
 use orchestrator::{GraphExecutionRequest, Orchestrator};
 use registry::NodeRegistry;
+use storage::{FsStorage, SqliteStorage};
 use workspace::WorkspaceManager;
 
 /// Main application state shared across all handlers
@@ -45,10 +122,14 @@ use workspace::WorkspaceManager;
 struct AppState {
     /// Registry of discovered nodes
     registry: Arc<NodeRegistry>,
+    /// `ETag` of the registry, sent on every response
+    registry_etag: HeaderValue,
     /// Orchestrator for graph execution
     orchestrator: Arc<Orchestrator>,
     /// Workspace manager for persistence
     workspace_manager: Arc<WorkspaceManager>,
+    /// Artifacts exchanged between nodes
+    artifacts: Arc<ArtifactStore>,
 }
 
 // === API Handlers ===
@@ -172,12 +253,33 @@ async fn health_check_all(State(state): State<AppState>) -> Json<SystemHealthRes
 /// Handler for GET /nodes/registry - Get all registered nodes
 ///
 /// Returns the complete structure of all discovered nodes, including
-/// their configurations, sections, slots, and behaviors
-async fn get_node_registry(
-    State(state): State<AppState>,
-) -> Result<Json<registry::NodeRegistryResponse>, AppError> {
+/// their configurations, sections, slots, and behaviors. Callers sending
+/// the registry's `ETag` in `If-None-Match` get 304 Not Modified.
+async fn get_node_registry(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    let etag = state.registry_etag.clone();
+
+    let unchanged = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.split(',').any(|tag| tag.trim() == etag || tag.trim() == "*"));
+    if unchanged {
+        return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response();
+    }
+
     let nodes = state.registry.get_all_nodes();
-    Ok(Json(registry::NodeRegistryResponse { nodes }))
+    ([(header::ETAG, etag)], Json(registry::NodeRegistryResponse { nodes })).into_response()
+}
+
+/// Header carrying the registry's `ETag` on every response, so clients
+/// caching the registry notice when it changes
+const REGISTRY_ETAG_HEADER: &str = "x-registry-etag";
+
+/// Middleware adding the registry's `ETag` to a response
+async fn add_registry_etag(State(state): State<AppState>, mut response: Response) -> Response {
+    response
+        .headers_mut()
+        .insert(REGISTRY_ETAG_HEADER, state.registry_etag.clone());
+    response
 }
 
 /// Handler for GET /nodes/{node_id} - Get specific node info
@@ -194,59 +296,539 @@ async fn get_node_info(
 
 /// Handler for POST /graphs/run - Execute a graph
 ///
-/// Receives a graph definition and orchestrates its execution
+/// Receives a graph definition and orchestrates its execution. When the
+/// request names the workspace it came from, the outcome is recorded as the
+/// workspace's last run.
 async fn execute_graph(
     State(state): State<AppState>,
     Json(request): Json<GraphExecutionRequest>,
 ) -> Result<Json<orchestrator::GraphExecutionResponse>, AppError> {
     info!("Received graph execution request");
-    let result = state.orchestrator.execute_graph(request).await?;
+    let result = run_graph(&state, request).await?;
     Ok(Json(result))
 }
 
+/// Handler for POST /executions/{execution_id}/cancel - Cancel an execution
+///
+/// The execution stops at the node currently being called; its run
+/// request answers with status `interrupted`.
+async fn cancel_execution(
+    State(state): State<AppState>,
+    Path(execution_id): Path<String>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    state.orchestrator.cancel_execution(&execution_id)?;
+    Ok(Json(serde_json::json!({
+        "execution_id": execution_id,
+        "cancelled": true
+    })))
+}
+
+/// Handler for POST /nexus/{name}/run - Run a saved workspace
+///
+/// The workspace is migrated to the current node versions and its
+/// parameters are filled from the request for this run only; the saved
+/// workspace is not changed (apart from its last run).
+async fn run_workspace(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(request): Json<template::RunWorkspaceRequest>,
+) -> Result<Json<orchestrator::GraphExecutionResponse>, AppError> {
+    info!("Running workspace: {}", name);
+    let mut data = state.workspace_manager.load_workspace(&name).await?;
+
+    migration::migrate_workspace(&mut data, &state.registry);
+    template::apply_parameters(&mut data, &request.parameters)?;
+
+    let result = run_graph(
+        &state,
+        GraphExecutionRequest {
+            execution_id: request.execution_id,
+            graph: data.graph,
+            workspace: Some(name),
+        },
+    )
+    .await?;
+    Ok(Json(result))
+}
+
+/// Execute a graph and record the outcome as its workspace's last run
+async fn run_graph(
+    state: &AppState,
+    request: GraphExecutionRequest,
+) -> Result<orchestrator::GraphExecutionResponse, AppError> {
+    let workspace = request.workspace.clone();
+    let result = state.orchestrator.execute_graph(request).await?;
+
+    if let Some(name) = workspace {
+        let last_run = workspace::LastRun {
+            execution_id: result.execution_id.clone(),
+            status: result.status.clone(),
+            finished_at: chrono::Utc::now(),
+        };
+
+        if let Err(e) = state.workspace_manager.record_last_run(&name, last_run).await {
+            warn!("Failed to record last run of workspace '{}': {}", name, e);
+        }
+    }
+
+    Ok(result)
+}
+
 /// Handler for POST /nexus/save - Save workspace
+///
+/// Returns the revision created by this save, with its number as `ETag`.
+/// The revision the client last loaded can be sent as `expected_revision` or
+/// in an `If-Match` header; stale saves get a 409 Conflict carrying the
+/// current version of the workspace.
 async fn save_workspace(
     State(state): State<AppState>,
-    Json(request): Json<workspace::SaveWorkspaceRequest>,
-) -> Result<StatusCode, AppError> {
+    headers: HeaderMap,
+    Json(mut request): Json<workspace::SaveWorkspaceRequest>,
+) -> Result<Response, AppError> {
     info!("Saving workspace: {}", request.name);
-    state.workspace_manager.save_workspace(request).await?;
-    Ok(StatusCode::OK)
+
+    // Checked by the workspace manager under its write lock
+    match parse_if_match(&headers)? {
+        Some(IfMatch::Any) => request.must_exist = true,
+        Some(IfMatch::Revision(revision)) => {
+            if request.expected_revision.is_some_and(|expected| expected != revision) {
+                return Err(AppError::BadRequest(
+                    "If-Match header does not match expected_revision".to_string(),
+                ));
+            }
+            request.expected_revision = Some(revision);
+        }
+        None => {}
+    }
+
+    migration::stamp_node_versions(&mut request.data, &state.registry);
+    let name = request.name.clone();
+
+    match state.workspace_manager.save_workspace(request).await {
+        Ok(revision) => {
+            let etag = revision_etag(revision.revision);
+            Ok(([(header::ETAG, etag)], Json(revision)).into_response())
+        }
+        Err(AppError::Conflict(error)) => save_conflict(&state, &name, error).await,
+        Err(e) => Err(e),
+    }
+}
+
+/// Build the 409 response for a stale save, including the current version
+async fn save_conflict(state: &AppState, name: &str, error: String) -> Result<Response, AppError> {
+    warn!("Rejected stale save of workspace '{}': {}", name, error);
+
+    let current_revision = state.workspace_manager.current_revision(name).await?;
+    let current = if current_revision > 0 {
+        Some(
+            state
+                .workspace_manager
+                .load_revision(name, current_revision)
+                .await?,
+        )
+    } else {
+        None
+    };
+
+    let body = workspace::SaveConflictResponse {
+        error,
+        current_revision,
+        current,
+    };
+    Ok((StatusCode::CONFLICT, Json(body)).into_response())
+}
+
+/// Precondition sent in an `If-Match` header
+enum IfMatch {
+    /// `*`: the workspace must already exist
+    Any,
+
+    /// The workspace must be at this revision
+    Revision(u64),
+}
+
+/// Parse the `If-Match` header of a save request
+fn parse_if_match(headers: &HeaderMap) -> Result<Option<IfMatch>, AppError> {
+    let Some(value) = headers.get(header::IF_MATCH) else {
+        return Ok(None);
+    };
+
+    let value = value
+        .to_str()
+        .map_err(|_| AppError::BadRequest("Invalid If-Match header".to_string()))?
+        .trim();
+
+    if value == "*" {
+        return Ok(Some(IfMatch::Any));
+    }
+
+    // If-Match uses the strong comparison (RFC 9110 13.1.1)
+    if value.starts_with("W/") {
+        return Err(AppError::BadRequest(format!(
+            "Weak ETags cannot be used in If-Match: {}",
+            value
+        )));
+    }
+
+    value
+        .trim_matches('"')
+        .parse()
+        .map(|revision| Some(IfMatch::Revision(revision)))
+        .map_err(|_| AppError::BadRequest(format!("Invalid If-Match revision: {}", value)))
+}
+
+/// Format a workspace revision number as an `ETag` value
+fn revision_etag(revision: u64) -> String {
+    format!("\"{}\"", revision)
 }
 
 /// Handler for GET /nexus/load/{name} - Load workspace
+///
+/// Node migrations are applied to the returned data (the saved file is left
+/// untouched until the next save), and the workspace is validated against
+/// the current node registry. The current revision is returned in the body
+/// and as `ETag`.
 async fn load_workspace(
     State(state): State<AppState>,
     Path(name): Path<String>,
-) -> Result<Json<workspace::WorkspaceData>, AppError> {
+) -> Result<impl IntoResponse, AppError> {
     info!("Loading workspace: {}", name);
-    let data = state.workspace_manager.load_workspace(&name).await?;
-    Ok(Json(data))
+    let (revision, mut data) = state.workspace_manager.load_current(&name).await?;
+
+    let migration = migration::migrate_workspace(&mut data, &state.registry);
+    if migration.migrated {
+        info!("Workspace '{}' migrated to current node versions", name);
+    }
+
+    let validation = validation::validate_graph(&data.graph, &state.registry);
+
+    if !validation.valid {
+        warn!("Workspace '{}' does not match the current node registry", name);
+    }
+
+    let response = workspace::LoadWorkspaceResponse {
+        revision,
+        data,
+        migration,
+        validation,
+    };
+    Ok(([(header::ETAG, revision_etag(revision))], Json(response)))
+}
+
+/// Handler for POST /nexus/migrate - Migrate saved workspaces in bulk
+async fn migrate_workspaces(
+    State(state): State<AppState>,
+    Json(request): Json<migration::BulkMigrationRequest>,
+) -> Result<Json<migration::BulkMigrationResponse>, AppError> {
+    info!("Migrating workspaces (dry run: {})", request.dry_run);
+    let response =
+        migration::migrate_workspaces(&state.workspace_manager, &state.registry, request).await?;
+    Ok(Json(response))
 }
 
-/// Handler for GET /nexus/list - List all workspaces
+/// Handler for GET /nexus/{name}/export - Export a workspace as a tar.gz bundle
+async fn export_workspace(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    info!("Exporting workspace: {}", name);
+    let archive = bundle::export_workspace(&state.workspace_manager, &state.registry, &name).await?;
+
+    let disposition = format!(
+        "attachment; filename=\"{}.tar.gz\"",
+        workspace::sanitize_filename(&name)
+    );
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/gzip".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        archive,
+    ))
+}
+
+/// Handler for POST /nexus/import - Import a workspace from a tar.gz bundle
+///
+/// Nothing is written when a bundled node type is incompatible with the
+/// local registry, or when `dry_run` is set
+async fn import_workspace(
+    State(state): State<AppState>,
+    Query(options): Query<bundle::ImportOptions>,
+    body: Bytes,
+) -> Result<Json<bundle::ImportResponse>, AppError> {
+    info!("Importing workspace bundle ({} bytes)", body.len());
+    let response =
+        bundle::import_workspace(&state.workspace_manager, &state.registry, body, options)
+            .await?;
+
+    if !response.compatible {
+        warn!(
+            "Bundle for workspace '{}' is not compatible with the local node registry",
+            response.name
+        );
+    }
+
+    Ok(Json(response))
+}
+
+/// Handler for GET /nexus/list - List workspaces
+///
+/// Supports `tag`, `node_type` and `q` (description text) filters and
+/// `sort=name|modified`, returning metadata summaries
 async fn list_workspaces(
     State(state): State<AppState>,
+    Query(query): Query<workspace::WorkspaceQuery>,
 ) -> Result<Json<workspace::WorkspaceListResponse>, AppError> {
-    let workspaces = state.workspace_manager.list_workspaces().await?;
+    let workspaces = state.workspace_manager.search_workspaces(&query).await?;
     Ok(Json(workspace::WorkspaceListResponse { workspaces }))
 }
 
+/// Handler for DELETE /nexus/{name} - Move a workspace to the trash
+async fn delete_workspace(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<workspace::TrashEntry>, AppError> {
+    info!("Deleting workspace: {}", name);
+    let entry = state.workspace_manager.delete_workspace(&name).await?;
+    Ok(Json(entry))
+}
+
+/// Handler for POST /nexus/{name}/rename - Rename a workspace
+async fn rename_workspace(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(request): Json<workspace::WorkspaceNameRequest>,
+) -> Result<StatusCode, AppError> {
+    state
+        .workspace_manager
+        .rename_workspace(&name, &request.new_name)
+        .await?;
+    Ok(StatusCode::OK)
+}
+
+/// Handler for POST /nexus/{name}/duplicate - Copy a workspace under a new name
+async fn duplicate_workspace(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(request): Json<workspace::WorkspaceNameRequest>,
+) -> Result<Json<workspace::RevisionInfo>, AppError> {
+    let revision = state
+        .workspace_manager
+        .duplicate_workspace(&name, &request.new_name)
+        .await?;
+    Ok(Json(revision))
+}
+
+/// Handler for POST /nexus/{name}/instantiate - Create a workspace from a template
+async fn instantiate_template(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(request): Json<template::InstantiateTemplateRequest>,
+) -> Result<Json<workspace::RevisionInfo>, AppError> {
+    let revision =
+        template::instantiate_template(&state.workspace_manager, &name, request).await?;
+    Ok(Json(revision))
+}
+
+/// Handler for GET /trash - List deleted workspaces
+async fn list_trash(
+    State(state): State<AppState>,
+) -> Result<Json<workspace::TrashListResponse>, AppError> {
+    let entries = state.workspace_manager.list_trash().await?;
+    Ok(Json(workspace::TrashListResponse { entries }))
+}
+
+/// Handler for POST /trash/{trash_id}/restore - Restore a deleted workspace
+async fn restore_from_trash(
+    State(state): State<AppState>,
+    Path(trash_id): Path<String>,
+) -> Result<Json<workspace::TrashEntry>, AppError> {
+    let entry = state
+        .workspace_manager
+        .restore_from_trash(&trash_id)
+        .await?;
+    Ok(Json(entry))
+}
+
+/// Handler for DELETE /trash - Permanently delete everything in the trash
+async fn purge_trash(
+    State(state): State<AppState>,
+) -> Result<Json<workspace::PurgeResponse>, AppError> {
+    let purged = state.workspace_manager.purge_trash(None).await?;
+    Ok(Json(workspace::PurgeResponse { purged }))
+}
+
+/// Handler for DELETE /trash/{trash_id} - Permanently delete one trash entry
+async fn purge_trash_entry(
+    State(state): State<AppState>,
+    Path(trash_id): Path<String>,
+) -> Result<Json<workspace::PurgeResponse>, AppError> {
+    let purged = state
+        .workspace_manager
+        .purge_trash(Some(&trash_id))
+        .await?;
+    Ok(Json(workspace::PurgeResponse { purged }))
+}
+
+/// Handler for GET /nexus/{name}/revisions - List workspace revisions
+async fn list_revisions(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<workspace::RevisionListResponse>, AppError> {
+    let revisions = state.workspace_manager.list_revisions(&name).await?;
+    Ok(Json(workspace::RevisionListResponse { name, revisions }))
+}
+
+/// Handler for GET /nexus/{name}/revisions/{revision} - Load a specific revision
+async fn load_revision(
+    State(state): State<AppState>,
+    Path((name, revision)): Path<(String, u64)>,
+) -> Result<Json<workspace::WorkspaceRevision>, AppError> {
+    info!("Loading workspace '{}' at revision {}", name, revision);
+    let data = state.workspace_manager.load_revision(&name, revision).await?;
+    Ok(Json(data))
+}
+
+/// Handler for POST /nexus/{name}/revisions/{revision}/restore - Restore a revision
+///
+/// Returns the new revision holding the restored data
+async fn restore_revision(
+    State(state): State<AppState>,
+    Path((name, revision)): Path<(String, u64)>,
+) -> Result<Json<workspace::RevisionInfo>, AppError> {
+    let restored = state
+        .workspace_manager
+        .restore_revision(&name, revision)
+        .await?;
+    Ok(Json(restored))
+}
+
+/// Query parameters for the revision diff endpoint
+#[derive(Debug, Deserialize)]
+struct DiffQuery {
+    /// Older revision number
+    from: u64,
+    /// Newer revision number
+    to: u64,
+}
+
+/// Handler for GET /nexus/{name}/diff?from=&to= - Diff two revisions
+async fn diff_revisions(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(query): Query<DiffQuery>,
+) -> Result<Json<workspace::RevisionDiff>, AppError> {
+    let diff = state
+        .workspace_manager
+        .diff_revisions(&name, query.from, query.to)
+        .await?;
+    Ok(Json(diff))
+}
+
 /// Create the main HTTP router with all endpoints
-fn create_router(state: AppState) -> Router {
-    Router::new()
-        .route("/health", get(health_check))
-        .route("/health/all", get(health_check_all))
+///
+/// With a verifier, every route except the health checks requires a
+/// signed request.
+fn create_router(state: AppState, verifier: Option<Verifier>) -> Router {
+    let mut router = Router::new()
         .route("/nodes/registry", get(get_node_registry))
         .route("/nodes/:node_id", get(get_node_info))
         .route("/graphs/run", post(execute_graph))
+        .route("/executions/:execution_id/cancel", post(cancel_execution))
+        .route("/executions/:execution_id/events", get(events::stream_events))
         .route("/nexus/save", post(save_workspace))
         .route("/nexus/load/:name", get(load_workspace))
         .route("/nexus/list", get(list_workspaces))
+        .route("/nexus/migrate", post(migrate_workspaces))
+        .route("/nexus/:name/export", get(export_workspace))
+        .route("/nexus/:name/revisions", get(list_revisions))
+        .route("/nexus/:name/revisions/:revision", get(load_revision))
+        .route(
+            "/nexus/:name/revisions/:revision/restore",
+            post(restore_revision),
+        )
+        .route("/nexus/:name/diff", get(diff_revisions))
+        .route("/nexus/:name", delete(delete_workspace))
+        .route("/nexus/:name/rename", post(rename_workspace))
+        .route("/nexus/:name/duplicate", post(duplicate_workspace))
+        .route("/nexus/:name/run", post(run_workspace))
+        .route("/nexus/:name/instantiate", post(instantiate_template))
+        .route("/trash", get(list_trash).delete(purge_trash))
+        .route("/trash/:trash_id", delete(purge_trash_entry))
+        .route("/trash/:trash_id/restore", post(restore_from_trash))
+        .route("/artifacts/:sha256", get(artifacts::get_artifact));
+
+    if let Some(verifier) = &verifier {
+        router = router.route_layer(middleware::from_fn_with_state(
+            verifier.clone(),
+            signing::verify_request,
+        ));
+    }
+
+    let max_artifact_size = state.artifacts.max_size();
+    router
+        .route(
+            "/nexus/import",
+            large_body(post(import_workspace), verifier.as_ref(), bundle::MAX_BUNDLE_SIZE),
+        )
+        .route(
+            "/artifacts",
+            large_body(put(artifacts::put_artifact), verifier.as_ref(), max_artifact_size),
+        )
+        .route("/health", get(health_check))
+        .route("/health/all", get(health_check_all))
+        .layer(middleware::map_response_with_state(
+            state.clone(),
+            add_registry_etag,
+        ))
         .layer(TraceLayer::new_for_http())
         .with_state(state)
 }
 
+/// Raise the body limit of a route, verifying signatures up to that limit
+fn large_body(
+    route: MethodRouter<AppState>,
+    verifier: Option<&Verifier>,
+    limit: usize,
+) -> MethodRouter<AppState> {
+    let route = route.layer(DefaultBodyLimit::max(limit));
+    match verifier {
+        Some(verifier) => route.route_layer(middleware::from_fn_with_state(
+            verifier.with_body_limit(limit),
+            signing::verify_request,
+        )),
+        None => route,
+    }
+}
+
+/// Import an existing nexus directory into a new SQLite database
+///
+/// Usage: `ndnm-hermes import-nexus [nexus_dir] [db_path]`
+async fn import_nexus(args: &[String]) -> Result<()> {
+    let nexus_dir = PathBuf::from(args.first().map_or("./nexus", String::as_str));
+    let db_path = args
+        .get(1)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| storage::db_path_from_env(&nexus_dir));
+
+    if !nexus_dir.is_dir() {
+        anyhow::bail!("Nexus directory {:?} does not exist", nexus_dir);
+    }
+
+    info!("Importing {:?} into {:?}", nexus_dir, db_path);
+
+    let source = FsStorage::new(&nexus_dir);
+    let summary = SqliteStorage::open(&db_path)?.import_nexus(&source).await?;
+
+    info!(
+        "Imported {} workspaces ({} revisions), {} trash entries and {} executions",
+        summary.workspaces, summary.revisions, summary.trash_entries, summary.executions
+    );
+    info!("Start Hermes with NEXUS_STORAGE=sqlite to use the database");
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize tracing
@@ -257,6 +839,14 @@ async fn main() -> Result<()> {
         )
         .init();
 
+    // One-shot commands
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("import-nexus") => return import_nexus(&args[1..]).await,
+        Some(command) => anyhow::bail!("Unknown command '{}'", command),
+        None => {}
+    }
+
     info!("Starting NDNM Hermes - The Orchestrator");
 
     // Initialize discovery service
@@ -278,34 +868,79 @@ async fn main() -> Result<()> {
         );
     }
 
+    // Open the nexus storage backend
+    let storage = storage::open_from_env(std::path::Path::new("./nexus"))?;
+
+    // Shared secret for signing calls to nodes and verifying calls from Brazil
+    let signer = Signer::from_env()?;
+    if signer.is_some() {
+        info!("Service request signing enabled");
+    } else {
+        warn!("NDNM_SERVICE_SECRET is not set; requests are not signed or verified");
+    }
+
+    // Artifacts nodes exchange by handle
+    let artifacts = Arc::new(ArtifactStore::from_env());
+
+    // Encoding of /run bodies sent to nodes
+    let wire_format = match std::env::var("NODE_WIRE_FORMAT") {
+        Ok(name) => name.parse::<WireFormat>()?,
+        Err(_) => WireFormat::Json,
+    };
+    info!("Calling nodes with {} bodies", wire_format);
+
+    // Deadline of each node run
+    let node_timeout = std::env::var("NODE_TIMEOUT_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .map(std::time::Duration::from_secs);
+
     // Initialize orchestrator
-    let orchestrator = Orchestrator::new(Arc::new(registry.clone()));
+    let orchestrator = Orchestrator::new(Arc::new(registry.clone()))
+        .with_history(ExecutionHistory::new(storage.clone()))
+        .with_signer(signer.clone())
+        .with_artifacts(artifacts.clone())
+        .with_wire_format(wire_format)
+        .with_node_timeout(node_timeout);
+    let orchestrator = Arc::new(orchestrator);
 
     // Initialize workspace manager
-    let workspace_manager = WorkspaceManager::new("./nexus");
+    let workspace_manager = WorkspaceManager::with_storage(storage);
 
     // Create app state
+    let registry_etag = HeaderValue::from_str(&registry.etag())?;
     let state = AppState {
         registry: Arc::new(registry),
-        orchestrator: Arc::new(orchestrator),
+        registry_etag,
+        orchestrator: orchestrator.clone(),
         workspace_manager: Arc::new(workspace_manager),
+        artifacts,
     };
 
-    // Build router
-    let app = create_router(state);
-
     // Get port from environment or use default
     let port = std::env::var("PORT")
         .ok()
         .and_then(|p| p.parse().ok())
         .unwrap_or(3000);
 
+    // Build router
+    let app = create_router(state, signer.map(|signer| Verifier::new(signer, port)));
+
     let addr = format!("0.0.0.0:{}", port);
     info!("Starting Hermes API server on {}", addr);
 
     // Start server
+    let drain_timeout = ndnm_libs::drain_timeout_from_env();
     let listener = TcpListener::bind(&addr).await?;
-    axum::serve(listener, app).await?;
-
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async move {
+            ndnm_libs::shutdown_signal().await;
+            info!("Shutdown signal received, draining executions");
+            orchestrator.drain(drain_timeout).await;
+        })
+        .await?;
+
+    info!("Hermes stopped");
     Ok(())
 }
@@ -0,0 +1,587 @@
+//! Workspace migration module
+//!
+//! Applies the declarative migrations shipped in node `config.yaml` files to
+//! saved workspaces, so renamed handles and input fields keep working after a
+//! node is upgraded
+
+use crate::orchestrator::{Connection, GraphNode};
+use crate::registry::NodeRegistry;
+use crate::workspace::{SaveWorkspaceRequest, WorkspaceData, WorkspaceManager};
+use ndnm_libs::{AppError, MigrationStep};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use tracing::{info, warn};
+
+/// Report of the migrations applied to a workspace
+#[derive(Debug, Default, Serialize)]
+pub struct MigrationReport {
+    /// Whether any migration changed the workspace
+    pub migrated: bool,
+
+    /// Migrations applied, per node type
+    pub applied: Vec<AppliedMigration>,
+}
+
+/// Migrations applied to the instances of a single node type
+#[derive(Debug, Serialize)]
+pub struct AppliedMigration {
+    /// Node type ID
+    pub node_type_id: String,
+
+    /// Version the workspace was saved with
+    pub from_version: u32,
+
+    /// Version the workspace was upgraded to
+    pub to_version: u32,
+
+    /// Human-readable description of every change made
+    pub changes: Vec<String>,
+}
+
+/// Request body for the bulk migration endpoint
+#[derive(Debug, Default, Deserialize)]
+pub struct BulkMigrationRequest {
+    /// Only report what would change, without saving anything
+    #[serde(default)]
+    pub dry_run: bool,
+
+    /// Workspaces to migrate (all workspaces when omitted)
+    #[serde(default)]
+    pub names: Option<Vec<String>>,
+}
+
+/// Response for the bulk migration endpoint
+#[derive(Debug, Serialize)]
+pub struct BulkMigrationResponse {
+    /// Whether this was a dry run
+    pub dry_run: bool,
+
+    /// Result for each workspace considered
+    pub workspaces: Vec<WorkspaceMigration>,
+}
+
+/// Migration result for a single workspace
+#[derive(Debug, Serialize)]
+pub struct WorkspaceMigration {
+    /// Workspace name
+    pub name: String,
+
+    /// Migrations applied (or that would be applied on a dry run)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub report: Option<MigrationReport>,
+
+    /// Error that prevented the workspace from being migrated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Upgrade a workspace to the node versions currently in the registry
+///
+/// For each referenced node type, every migration with a version above the
+/// one recorded in `node_versions` (and up to the registered version) is
+/// applied in ascending order. Node types missing from the registry, or
+/// saved with a newer version than the registered one, are left untouched.
+///
+/// # Returns
+///
+/// A report describing every change made to the workspace
+pub fn migrate_workspace(data: &mut WorkspaceData, registry: &NodeRegistry) -> MigrationReport {
+    let mut report = MigrationReport::default();
+
+    // BTreeSet keeps node types sorted so the report is stable
+    let node_types: BTreeSet<String> = data
+        .graph
+        .nodes
+        .iter()
+        .map(|n| n.node_type_id.clone())
+        .collect();
+
+    for node_type_id in node_types {
+        let Some(node_info) = registry.get_node(&node_type_id) else {
+            continue;
+        };
+
+        let current_version = node_info.config.version;
+        let saved_version = data.node_versions.get(&node_type_id).copied().unwrap_or(1);
+
+        if saved_version < current_version {
+            let mut migrations: Vec<_> = node_info
+                .config
+                .migrations
+                .iter()
+                .filter(|m| m.version > saved_version && m.version <= current_version)
+                .collect();
+            migrations.sort_by_key(|m| m.version);
+
+            let mut changes = Vec::new();
+            for migration in migrations {
+                for step in &migration.steps {
+                    changes.extend(apply_step(data, &node_type_id, step));
+                }
+            }
+
+            report.applied.push(AppliedMigration {
+                node_type_id: node_type_id.clone(),
+                from_version: saved_version,
+                to_version: current_version,
+                changes,
+            });
+        }
+
+        if saved_version <= current_version {
+            data.node_versions.insert(node_type_id, current_version);
+        }
+    }
+
+    report.migrated = !report.applied.is_empty();
+    report
+}
+
+/// Record the registered version of node types the workspace does not track yet
+///
+/// Called when saving, so workspaces created against the current registry
+/// are not migrated again by later upgrades they already reflect.
+pub fn stamp_node_versions(data: &mut WorkspaceData, registry: &NodeRegistry) {
+    for node in &data.graph.nodes {
+        if !data.node_versions.contains_key(&node.node_type_id)
+            && let Some(node_info) = registry.get_node(&node.node_type_id)
+        {
+            data.node_versions
+                .insert(node.node_type_id.clone(), node_info.config.version);
+        }
+    }
+}
+
+/// Migrate saved workspaces in bulk
+///
+/// Each migrated workspace is saved as a new revision, so the migration can
+/// be undone by restoring the previous one. On a dry run nothing is written.
+///
+/// # Arguments
+///
+/// * `manager` - Workspace manager owning the workspaces
+/// * `registry` - Registry providing current node versions and migrations
+/// * `request` - Workspaces to migrate and whether this is a dry run
+pub async fn migrate_workspaces(
+    manager: &WorkspaceManager,
+    registry: &NodeRegistry,
+    request: BulkMigrationRequest,
+) -> Result<BulkMigrationResponse, AppError> {
+    let names = match request.names {
+        Some(names) => names,
+        None => manager.list_workspaces().await?,
+    };
+
+    let mut workspaces = Vec::new();
+
+    for name in names {
+        let result = migrate_saved_workspace(manager, registry, &name, request.dry_run).await;
+
+        let migration = match result {
+            Ok(report) => WorkspaceMigration {
+                name,
+                report: Some(report),
+                error: None,
+            },
+            Err(e) => {
+                warn!("Failed to migrate workspace '{}': {}", name, e);
+                WorkspaceMigration {
+                    name,
+                    report: None,
+                    error: Some(e.to_string()),
+                }
+            }
+        };
+
+        workspaces.push(migration);
+    }
+
+    Ok(BulkMigrationResponse {
+        dry_run: request.dry_run,
+        workspaces,
+    })
+}
+
+/// Migrate a single saved workspace, saving it unless this is a dry run
+async fn migrate_saved_workspace(
+    manager: &WorkspaceManager,
+    registry: &NodeRegistry,
+    name: &str,
+    dry_run: bool,
+) -> Result<MigrationReport, AppError> {
+    let (revision, mut data) = manager.load_current(name).await?;
+    let report = migrate_workspace(&mut data, registry);
+
+    if report.migrated && !dry_run {
+        let upgrades: Vec<String> = report
+            .applied
+            .iter()
+            .map(|a| {
+                format!(
+                    "{} v{} -> v{}",
+                    a.node_type_id, a.from_version, a.to_version
+                )
+            })
+            .collect();
+
+        manager
+            .save_workspace(SaveWorkspaceRequest {
+                name: name.to_string(),
+                data,
+                message: Some(format!("Migrated {}", upgrades.join(", "))),
+                // Never overwrite edits saved while the migration was running
+                expected_revision: Some(revision),
+                must_exist: false,
+            })
+            .await?;
+
+        info!("Workspace '{}' migrated", name);
+    }
+
+    Ok(report)
+}
+
+/// Apply one migration step to every instance of a node type
+fn apply_step(data: &mut WorkspaceData, node_type_id: &str, step: &MigrationStep) -> Vec<String> {
+    let mut changes = Vec::new();
+
+    let instances: BTreeSet<String> = data
+        .graph
+        .nodes
+        .iter()
+        .filter(|n| n.node_type_id == node_type_id)
+        .map(|n| n.instance_id.clone())
+        .collect();
+
+    match step {
+        MigrationStep::RenameHandle { from, to } => {
+            for conn in &mut data.graph.connections {
+                changes.extend(rename_connection_handles(conn, &instances, from, to));
+            }
+        }
+        MigrationStep::RenameField { from, to } => {
+            for node in instances_mut(&mut data.graph.nodes, node_type_id) {
+                if let Some(value) = node.input_values.remove(from) {
+                    node.input_values.insert(to.clone(), value);
+                    changes.push(format!(
+                        "{}: renamed field '{}' to '{}'",
+                        node.instance_id, from, to
+                    ));
+                }
+            }
+
+            // Keep workspace parameters bound to the renamed field
+            for parameter in &mut data.parameters {
+                for binding in &mut parameter.bindings {
+                    if instances.contains(&binding.instance_id) && binding.field == *from {
+                        binding.field = to.clone();
+                        changes.push(format!(
+                            "parameter '{}': rebound to '{}.{}'",
+                            parameter.name, binding.instance_id, to
+                        ));
+                    }
+                }
+            }
+        }
+        MigrationStep::DefaultField { field, value } => {
+            for node in instances_mut(&mut data.graph.nodes, node_type_id) {
+                if !node.input_values.contains_key(field) {
+                    node.input_values.insert(field.clone(), value.clone());
+                    changes.push(format!(
+                        "{}: set field '{}' to {}",
+                        node.instance_id, field, value
+                    ));
+                }
+            }
+        }
+        MigrationStep::MapValue { field, from, to } => {
+            for node in instances_mut(&mut data.graph.nodes, node_type_id) {
+                if let Some(value) = node.input_values.get_mut(field)
+                    && value == from
+                {
+                    *value = to.clone();
+                    changes.push(format!(
+                        "{}: mapped field '{}' from {} to {}",
+                        node.instance_id, field, from, to
+                    ));
+                }
+            }
+        }
+    }
+
+    changes
+}
+
+/// Iterate over the graph nodes of a given type
+fn instances_mut<'a>(
+    nodes: &'a mut [GraphNode],
+    node_type_id: &'a str,
+) -> impl Iterator<Item = &'a mut GraphNode> {
+    nodes
+        .iter_mut()
+        .filter(move |n| n.node_type_id == node_type_id)
+}
+
+/// Rename the handles of a connection attached to one of the given instances
+fn rename_connection_handles(
+    conn: &mut Connection,
+    instances: &BTreeSet<String>,
+    from: &str,
+    to: &str,
+) -> Vec<String> {
+    let mut changes = Vec::new();
+
+    if instances.contains(&conn.from_node)
+        && let Some(renamed) = rename_handle(&conn.from_handle, from, to)
+    {
+        changes.push(format!(
+            "{}: renamed handle '{}' to '{}'",
+            conn.from_node, conn.from_handle, renamed
+        ));
+        conn.from_handle = renamed;
+    }
+
+    if instances.contains(&conn.to_node)
+        && let Some(renamed) = rename_handle(&conn.to_handle, from, to)
+    {
+        changes.push(format!(
+            "{}: renamed handle '{}' to '{}'",
+            conn.to_node, conn.to_handle, renamed
+        ));
+        conn.to_handle = renamed;
+    }
+
+    changes
+}
+
+/// Rename a handle generated from the `from` base name, keeping its suffix
+fn rename_handle(handle: &str, from: &str, to: &str) -> Option<String> {
+    if handle == from {
+        return Some(to.to_string());
+    }
+
+    handle
+        .strip_prefix(from)
+        .filter(|rest| rest.starts_with('_'))
+        .map(|rest| format!("{}{}", to, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orchestrator::GraphDefinition;
+    use crate::registry::NodeInfo;
+    use crate::template::{ParameterBinding, ParameterType, WorkspaceParameter};
+    use ndnm_libs::{Migration, NodeConfig};
+    use serde_json::json;
+    use std::collections::{BTreeMap, HashMap};
+    use std::path::PathBuf;
+
+    fn registry(version: u32, migrations: Vec<Migration>) -> NodeRegistry {
+        let mut registry = NodeRegistry::new();
+        registry
+            .register(NodeInfo {
+                node_id: "browser_type".to_string(),
+                config: NodeConfig {
+                    node_id_hash: "browser_type".to_string(),
+                    label: "Browser".to_string(),
+                    node_type: "filesystem".to_string(),
+                    version,
+                    sections: vec![],
+                    input_fields: vec![],
+                    migrations,
+                },
+                path: PathBuf::from("/test"),
+                port: 3001,
+                is_running: false,
+            })
+            .unwrap();
+        registry
+    }
+
+    fn workspace() -> WorkspaceData {
+        let node = |instance_id: &str| {
+            let mut input_values = HashMap::new();
+            input_values.insert("folder".to_string(), json!("./in"));
+            input_values.insert("mode".to_string(), json!("old"));
+
+            GraphNode {
+                instance_id: instance_id.to_string(),
+                node_type_id: "browser_type".to_string(),
+                input_values,
+                inputs: HashMap::new(),
+                position: None,
+            }
+        };
+
+        WorkspaceData {
+            schema_version: 1,
+            graph: GraphDefinition {
+                nodes: vec![node("a"), node("b")],
+                connections: vec![Connection {
+                    from_node: "a".to_string(),
+                    from_handle: "copied_output_0".to_string(),
+                    to_node: "b".to_string(),
+                    to_handle: "copy_input_0".to_string(),
+                }],
+            },
+            node_versions: BTreeMap::new(),
+            parameters: vec![],
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_migrate_workspace() {
+        let registry = registry(
+            3,
+            vec![
+                // Declared out of order on purpose
+                Migration {
+                    version: 3,
+                    steps: vec![MigrationStep::MapValue {
+                        field: "mode".to_string(),
+                        from: json!("old"),
+                        to: json!("new"),
+                    }],
+                },
+                Migration {
+                    version: 2,
+                    steps: vec![
+                        MigrationStep::RenameHandle {
+                            from: "copy_input".to_string(),
+                            to: "import_input".to_string(),
+                        },
+                        MigrationStep::RenameField {
+                            from: "folder".to_string(),
+                            to: "target_directory".to_string(),
+                        },
+                        MigrationStep::DefaultField {
+                            field: "recursive".to_string(),
+                            value: json!(false),
+                        },
+                    ],
+                },
+            ],
+        );
+
+        let mut data = workspace();
+        data.parameters.push(WorkspaceParameter {
+            name: "folder".to_string(),
+            param_type: ParameterType::Path,
+            default: None,
+            description: None,
+            bindings: vec![ParameterBinding {
+                instance_id: "a".to_string(),
+                field: "folder".to_string(),
+            }],
+        });
+        let report = migrate_workspace(&mut data, &registry);
+
+        assert!(report.migrated);
+        assert_eq!(data.parameters[0].bindings[0].field, "target_directory");
+        assert_eq!(report.applied[0].from_version, 1);
+        assert_eq!(report.applied[0].to_version, 3);
+        assert_eq!(data.node_versions["browser_type"], 3);
+
+        let node = &data.graph.nodes[1];
+        assert_eq!(node.input_values["target_directory"], "./in");
+        assert!(!node.input_values.contains_key("folder"));
+        assert_eq!(node.input_values["recursive"], false);
+        assert_eq!(node.input_values["mode"], "new");
+        assert_eq!(data.graph.connections[0].to_handle, "import_input_0");
+        assert_eq!(data.graph.connections[0].from_handle, "copied_output_0");
+
+        // Migrating again is a no-op
+        let report = migrate_workspace(&mut data, &registry);
+        assert!(!report.migrated);
+    }
+
+    #[test]
+    fn test_skips_applied_migrations() {
+        let registry = registry(
+            2,
+            vec![Migration {
+                version: 2,
+                steps: vec![MigrationStep::RenameField {
+                    from: "folder".to_string(),
+                    to: "target_directory".to_string(),
+                }],
+            }],
+        );
+
+        let mut data = workspace();
+        data.node_versions.insert("browser_type".to_string(), 2);
+
+        let report = migrate_workspace(&mut data, &registry);
+        assert!(!report.migrated);
+        assert!(data.graph.nodes[0].input_values.contains_key("folder"));
+    }
+
+    #[tokio::test]
+    async fn test_bulk_migration_dry_run() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let manager = WorkspaceManager::new(temp_dir.path());
+        let registry = registry(
+            2,
+            vec![Migration {
+                version: 2,
+                steps: vec![MigrationStep::RenameField {
+                    from: "folder".to_string(),
+                    to: "target_directory".to_string(),
+                }],
+            }],
+        );
+
+        manager
+            .save_workspace(SaveWorkspaceRequest {
+                name: "legacy".to_string(),
+                data: workspace(),
+                message: None,
+                expected_revision: None,
+                must_exist: false,
+            })
+            .await
+            .unwrap();
+
+        let dry_run = BulkMigrationRequest {
+            dry_run: true,
+            names: None,
+        };
+        let response = migrate_workspaces(&manager, &registry, dry_run)
+            .await
+            .unwrap();
+        assert!(response.workspaces[0].report.as_ref().unwrap().migrated);
+        assert_eq!(manager.list_revisions("legacy").await.unwrap().len(), 1);
+
+        let response = migrate_workspaces(&manager, &registry, BulkMigrationRequest::default())
+            .await
+            .unwrap();
+        assert!(response.workspaces[0].report.as_ref().unwrap().migrated);
+        assert_eq!(manager.list_revisions("legacy").await.unwrap().len(), 2);
+
+        let saved = manager.load_workspace("legacy").await.unwrap();
+        assert!(
+            saved.graph.nodes[0]
+                .input_values
+                .contains_key("target_directory")
+        );
+        assert_eq!(saved.node_versions["browser_type"], 2);
+    }
+
+    #[test]
+    fn test_rename_handle() {
+        assert_eq!(
+            rename_handle("copy_input_3", "copy_input", "import_input"),
+            Some("import_input_3".to_string())
+        );
+        assert_eq!(
+            rename_handle("copy_input", "copy_input", "import_input"),
+            Some("import_input".to_string())
+        );
+        assert_eq!(
+            rename_handle("copy_inputs", "copy_input", "import_input"),
+            None
+        );
+    }
+}
@@ -3,15 +3,28 @@
 //! Coordinates the execution of graphs by managing node execution order,
 //! data flow, and error handling
 
+use crate::artifacts::{ArtifactLease, ArtifactStore};
+use crate::events::{ExecutionEvent, ExecutionEvents};
+use crate::history::{ExecutionHistory, ExecutionRecord};
 use crate::registry::NodeRegistry;
-use ndnm_libs::AppError;
+use chrono::{DateTime, Utc};
+use ndnm_libs::progress::EventDecoder;
+use ndnm_libs::{signing, AppError, ArtifactRef, RunEvent, Signer, StreamFormat, WireFormat};
+use reqwest::{header, StatusCode};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
 use tracing::{error, info, warn};
 use uuid::Uuid;
 
+/// Time given to cancelled executions to persist their state and unregister
+const CANCEL_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
 /// Request to execute a graph
 #[derive(Debug, Clone, Deserialize)]
 pub struct GraphExecutionRequest {
@@ -20,10 +33,14 @@ pub struct GraphExecutionRequest {
 
     /// Graph definition containing nodes and connections
     pub graph: GraphDefinition,
+
+    /// Workspace the graph was loaded from, used to record its last run
+    #[serde(default)]
+    pub workspace: Option<String>,
 }
 
 /// Graph definition structure
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct GraphDefinition {
     /// List of node instances in the graph
     pub nodes: Vec<GraphNode>,
@@ -45,6 +62,11 @@ pub struct GraphNode {
     #[serde(default)]
     pub input_values: HashMap<String, Value>,
 
+    /// Values of input handles that have no connection (e.g. uploaded
+    /// files); a connection to the same handle takes precedence
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub inputs: HashMap<String, Value>,
+
     /// Position in UI (optional, for frontend)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub position: Option<Position>,
@@ -91,7 +113,7 @@ pub struct GraphExecutionResponse {
 }
 
 /// Status of graph execution
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum ExecutionStatus {
     /// Execution completed successfully
@@ -102,10 +124,13 @@ pub enum ExecutionStatus {
 
     /// Execution is still in progress
     InProgress,
+
+    /// Execution was stopped before completion (e.g. Hermes shut down)
+    Interrupted,
 }
 
 /// Result from a single node execution
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NodeExecutionResult {
     /// Node instance ID
     pub instance_id: String,
@@ -129,6 +154,62 @@ pub struct Orchestrator {
 
     /// HTTP client for communicating with nodes
     client: reqwest::Client,
+
+    /// Cancellation tokens of the executions currently in flight
+    active_executions: Mutex<HashMap<String, CancellationToken>>,
+
+    /// In-flight executions cancelled on request rather than by shutdown
+    cancel_requests: Mutex<HashSet<String>>,
+
+    /// Whether new executions are accepted (cleared while draining)
+    accepting: AtomicBool,
+
+    /// Notified every time an in-flight execution finishes
+    execution_finished: Notify,
+
+    /// Where interrupted executions are persisted
+    history: Option<ExecutionHistory>,
+
+    /// Signs `/run` calls to nodes, when a service secret is configured
+    signer: Option<Signer>,
+
+    /// Store of the artifacts nodes exchange by handle
+    artifacts: Option<Arc<ArtifactStore>>,
+
+    /// Encoding of `/run` bodies sent to nodes
+    wire_format: WireFormat,
+
+    /// Node types that rejected `wire_format` and are called with JSON
+    json_only_nodes: Mutex<HashSet<String>>,
+
+    /// Listeners of the progress events nodes report
+    events: Arc<ExecutionEvents>,
+
+    /// Time each node run may take, sent to nodes as their deadline
+    node_timeout: Option<Duration>,
+}
+
+/// Removes an execution from the in-flight set when it goes out of scope
+struct ActiveExecutionGuard<'a> {
+    orchestrator: &'a Orchestrator,
+    execution_id: String,
+}
+
+impl Drop for ActiveExecutionGuard<'_> {
+    fn drop(&mut self) {
+        self.orchestrator
+            .active_executions
+            .lock()
+            .unwrap()
+            .remove(&self.execution_id);
+        self.orchestrator
+            .cancel_requests
+            .lock()
+            .unwrap()
+            .remove(&self.execution_id);
+        self.orchestrator.events.close(&self.execution_id);
+        self.orchestrator.execution_finished.notify_waiters();
+    }
 }
 
 impl Orchestrator {
@@ -141,6 +222,198 @@ impl Orchestrator {
         Self {
             registry,
             client: reqwest::Client::new(),
+            active_executions: Mutex::new(HashMap::new()),
+            cancel_requests: Mutex::new(HashSet::new()),
+            accepting: AtomicBool::new(true),
+            execution_finished: Notify::new(),
+            history: None,
+            signer: None,
+            artifacts: None,
+            wire_format: WireFormat::Json,
+            json_only_nodes: Mutex::new(HashSet::new()),
+            events: Arc::new(ExecutionEvents::new()),
+            node_timeout: None,
+        }
+    }
+
+    /// Persist interrupted executions to the given history
+    pub fn with_history(mut self, history: ExecutionHistory) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    /// Sign `/run` calls to nodes with the given signer
+    pub fn with_signer(mut self, signer: Option<Signer>) -> Self {
+        self.signer = signer;
+        self
+    }
+
+    /// Hold the artifacts in node outputs while executions run
+    pub fn with_artifacts(mut self, artifacts: Arc<ArtifactStore>) -> Self {
+        self.artifacts = Some(artifacts);
+        self
+    }
+
+    /// Encode `/run` bodies in the given format
+    ///
+    /// Nodes answering `415 Unsupported Media Type` are called with JSON
+    /// from then on.
+    pub fn with_wire_format(mut self, wire_format: WireFormat) -> Self {
+        self.wire_format = wire_format;
+        self
+    }
+
+    /// Give each node run `timeout` to finish
+    ///
+    /// Nodes get it as the deadline of their execution context and stop
+    /// once it has passed.
+    pub fn with_node_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.node_timeout = timeout;
+        self
+    }
+
+    /// Listeners of the progress events of executions
+    pub fn events(&self) -> &Arc<ExecutionEvents> {
+        &self.events
+    }
+
+    /// Number of executions currently in flight
+    pub fn active_count(&self) -> usize {
+        self.active_executions.lock().unwrap().len()
+    }
+
+    /// Cancel an in-flight execution
+    ///
+    /// The execution stops at the node currently being called, which is
+    /// asked to cancel its run, and is persisted as `interrupted`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - The execution was asked to stop
+    /// * `Err(AppError)` - No execution with this ID is running
+    pub fn cancel_execution(&self, execution_id: &str) -> Result<(), AppError> {
+        let active = self.active_executions.lock().unwrap();
+
+        let token = active.get(execution_id).ok_or_else(|| {
+            AppError::BadRequest(format!("Execution '{}' is not running", execution_id))
+        })?;
+
+        self.cancel_requests
+            .lock()
+            .unwrap()
+            .insert(execution_id.to_string());
+        token.cancel();
+
+        info!("Execution {} cancelled on request", execution_id);
+        Ok(())
+    }
+
+    /// Stop accepting new executions and wait for in-flight ones to finish
+    ///
+    /// Executions still running when `timeout` elapses are cancelled; they
+    /// stop at the node currently being called and are persisted as
+    /// `interrupted`.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - How long in-flight executions may keep running
+    pub async fn drain(&self, timeout: Duration) {
+        self.accepting.store(false, Ordering::SeqCst);
+
+        let active = self.active_count();
+        if active == 0 {
+            return;
+        }
+
+        info!(
+            "Waiting up to {:?} for {} in-flight execution(s) to finish",
+            timeout, active
+        );
+
+        if tokio::time::timeout(timeout, self.wait_idle()).await.is_ok() {
+            info!("All in-flight executions finished");
+            return;
+        }
+
+        let tokens: Vec<CancellationToken> = self
+            .active_executions
+            .lock()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect();
+
+        warn!(
+            "Drain deadline reached, cancelling {} execution(s)",
+            tokens.len()
+        );
+
+        for token in tokens {
+            token.cancel();
+        }
+
+        if tokio::time::timeout(CANCEL_GRACE_PERIOD, self.wait_idle())
+            .await
+            .is_err()
+        {
+            warn!("Some cancelled executions did not stop in time");
+        }
+    }
+
+    /// Wait until no execution is in flight
+    async fn wait_idle(&self) {
+        loop {
+            // Register interest before checking, so a notification between
+            // the check and the await is not missed
+            let notified = self.execution_finished.notified();
+            if self.active_executions.lock().unwrap().is_empty() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// Register an execution as in flight and return its cancellation token
+    fn register_execution(&self, execution_id: &str) -> Result<CancellationToken, AppError> {
+        let mut active = self.active_executions.lock().unwrap();
+
+        if active.contains_key(execution_id) {
+            return Err(AppError::BadRequest(format!(
+                "Execution '{}' is already running",
+                execution_id
+            )));
+        }
+
+        let token = CancellationToken::new();
+        active.insert(execution_id.to_string(), token.clone());
+        Ok(token)
+    }
+
+    /// Persist an interrupted execution so it can be inspected after restart
+    async fn persist_interrupted(
+        &self,
+        execution_id: &str,
+        started_at: DateTime<Utc>,
+        graph: &GraphDefinition,
+        node_results: &HashMap<String, NodeExecutionResult>,
+        reason: &str,
+    ) {
+        let Some(history) = &self.history else {
+            return;
+        };
+
+        let record = ExecutionRecord {
+            execution_id: execution_id.to_string(),
+            status: ExecutionStatus::Interrupted,
+            started_at,
+            finished_at: Utc::now(),
+            graph: graph.clone(),
+            node_results: node_results.clone(),
+            error: Some(reason.to_string()),
+        };
+
+        if let Err(e) = history.record(&record).await {
+            error!("Failed to persist interrupted execution {}: {}", execution_id, e);
         }
     }
 
@@ -162,6 +435,12 @@ impl Orchestrator {
             .execution_id
             .unwrap_or_else(|| Uuid::new_v4().to_string());
 
+        if !self.accepting.load(Ordering::SeqCst) {
+            return Err(AppError::ServiceUnavailable(
+                "Hermes is shutting down and not accepting new executions".to_string(),
+            ));
+        }
+
         info!("Starting graph execution: {}", execution_id);
 
         // Validate graph structure
@@ -172,15 +451,88 @@ impl Orchestrator {
 
         info!("Execution order: {:?}", execution_order);
 
+        // Track the execution so a shutdown can wait for it or cancel it
+        let started_at = Utc::now();
+        let cancel_token = self.register_execution(&execution_id)?;
+        let _guard = ActiveExecutionGuard {
+            orchestrator: self,
+            execution_id: execution_id.clone(),
+        };
+
+        // Artifacts in node inputs and outputs are held until the execution
+        // ends
+        let mut lease = self.artifacts.clone().map(ArtifactLease::new);
+        if let Some(lease) = &mut lease {
+            let handles = request
+                .graph
+                .nodes
+                .iter()
+                .flat_map(|node| node.inputs.values())
+                .flat_map(ArtifactRef::find_all)
+                .collect();
+            lease.hold(handles);
+        }
+
         // Execute nodes in order
         let mut node_results = HashMap::new();
         let mut outputs_cache: HashMap<String, HashMap<String, Value>> = HashMap::new();
 
         for instance_id in execution_order {
-            let result = self
-                .execute_node(&instance_id, &request.graph, &outputs_cache)
+            let result = tokio::select! {
+                biased;
+                _ = cancel_token.cancelled() => None,
+                result = self.execute_node(
+                    &execution_id,
+                    &instance_id,
+                    &request.graph,
+                    &outputs_cache,
+                ) => Some(result),
+            };
+
+            let Some(result) = result else {
+                let reason = if self.cancel_requests.lock().unwrap().contains(&execution_id) {
+                    "Execution cancelled on request".to_string()
+                } else {
+                    "Execution interrupted by Hermes shutdown".to_string()
+                };
+                warn!("Execution {} interrupted at node {}", execution_id, instance_id);
+                self.cancel_node(&execution_id, &instance_id, &request.graph);
+
+                node_results.insert(
+                    instance_id.clone(),
+                    NodeExecutionResult {
+                        instance_id,
+                        status: "interrupted".to_string(),
+                        outputs: None,
+                        error: Some(reason.clone()),
+                    },
+                );
+
+                self.persist_interrupted(
+                    &execution_id,
+                    started_at,
+                    &request.graph,
+                    &node_results,
+                    &reason,
+                )
                 .await;
 
+                return Ok(GraphExecutionResponse {
+                    execution_id,
+                    status: ExecutionStatus::Interrupted,
+                    node_results,
+                    error: Some(reason),
+                });
+            };
+
+            let result = match (result, &mut lease) {
+                (Ok((node_result, outputs)), Some(lease)) => self
+                    .hold_artifacts(lease, &instance_id, &outputs)
+                    .await
+                    .map(|()| (node_result, outputs)),
+                (result, _) => result,
+            };
+
             match result {
                 Ok((node_result, outputs)) => {
                     outputs_cache.insert(instance_id.clone(), outputs);
@@ -218,6 +570,67 @@ impl Orchestrator {
         })
     }
 
+    /// Ask the node running `instance_id` to cancel its run
+    ///
+    /// Sent in the background; nodes that are gone or no longer run the
+    /// execution are left alone.
+    fn cancel_node(&self, execution_id: &str, instance_id: &str, graph: &GraphDefinition) {
+        let Some(node_info) = graph
+            .nodes
+            .iter()
+            .find(|n| n.instance_id == instance_id)
+            .and_then(|n| self.registry.get_node(&n.node_type_id))
+        else {
+            return;
+        };
+
+        let url = format!("http://localhost:{}/cancel/{}", node_info.port, execution_id);
+        let request = self.client.post(url);
+        let signer = self.signer.clone();
+        let instance_id = instance_id.to_string();
+
+        tokio::spawn(async move {
+            match signing::send(request, signer.as_ref()).await {
+                Ok(response) if response.status().is_success() => {
+                    info!("Node {} cancelled its run", instance_id);
+                }
+                Ok(response) => {
+                    warn!("Node {} refused to cancel: {}", instance_id, response.status());
+                }
+                Err(e) => warn!("Failed to cancel node {}: {}", instance_id, e),
+            }
+        });
+    }
+
+    /// Hold the artifacts a node output until the execution ends
+    ///
+    /// # Returns
+    ///
+    /// * `Err(AppError)` - The node output a handle to a missing artifact
+    async fn hold_artifacts(
+        &self,
+        lease: &mut ArtifactLease,
+        instance_id: &str,
+        outputs: &HashMap<String, Value>,
+    ) -> Result<(), AppError> {
+        let mut handles = Vec::new();
+        for value in outputs.values() {
+            handles.extend(ArtifactRef::find_all(value));
+        }
+
+        for handle in &handles {
+            if !lease.store().contains(handle).await {
+                return Err(AppError::Internal(format!(
+                    "Node '{}' output unknown artifact {}",
+                    instance_id, handle
+                )));
+            }
+        }
+
+        lease.hold(handles);
+        Ok(())
+    }
+
     /// Validate graph structure
     ///
     /// Checks that all referenced nodes exist in the registry
@@ -325,9 +738,12 @@ impl Orchestrator {
 
     /// Execute a single node
     ///
-    /// Gathers inputs from previous node outputs and executes the node
+    /// Gathers inputs from previous node outputs and executes the node.
+    /// JSON calls accept a streamed response, whose progress events are
+    /// forwarded to the execution's listeners.
     async fn execute_node(
         &self,
+        execution_id: &str,
         instance_id: &str,
         graph: &GraphDefinition,
         outputs_cache: &HashMap<String, HashMap<String, Value>>,
@@ -349,8 +765,8 @@ impl Orchestrator {
                 AppError::Internal(format!("Node type {} not in registry", graph_node.node_type_id))
             })?;
 
-        // Gather inputs from connections
-        let mut inputs = HashMap::new();
+        // Gather inputs from the node's own values, then from connections
+        let mut inputs = graph_node.inputs.clone();
 
         for conn in &graph.connections {
             if conn.to_node == instance_id {
@@ -377,10 +793,14 @@ impl Orchestrator {
         let url = format!("http://localhost:{}/run", node_info.port);
 
         #[derive(Serialize)]
-        struct RunRequest {
+        struct RunRequest<'a> {
             inputs: HashMap<String, Value>,
             #[serde(skip_serializing_if = "Option::is_none")]
             target_directory: Option<String>,
+            execution_id: &'a str,
+            instance_id: &'a str,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            timeout_ms: Option<u64>,
         }
 
         let request_body = RunRequest {
@@ -388,17 +808,53 @@ impl Orchestrator {
             target_directory: graph_node.input_values.get("target_directory")
                 .and_then(|v| v.as_str())
                 .map(|s| s.to_string()),
+            execution_id,
+            instance_id,
+            timeout_ms: self.node_timeout.map(|t| t.as_millis() as u64),
         };
 
-        let response = self
-            .client
-            .post(&url)
-            .json(&request_body)
-            .send()
-            .await
-            .map_err(|e| {
-                AppError::Internal(format!("Failed to call node '{}': {}", instance_id, e))
-            })?;
+        let node_type_id = &graph_node.node_type_id;
+        let mut format = if self.json_only_nodes.lock().unwrap().contains(node_type_id) {
+            WireFormat::Json
+        } else {
+            self.wire_format
+        };
+
+        let response = loop {
+            // Progress streams are JSON, so binary calls ask for a single body
+            let accept = match format {
+                WireFormat::Json => {
+                    format!("{}, {}", StreamFormat::Ndjson.content_type(), format.content_type())
+                }
+                _ => format.content_type().to_string(),
+            };
+            let request = self
+                .client
+                .post(&url)
+                .header(header::CONTENT_TYPE, format.content_type())
+                .header(header::ACCEPT, accept)
+                .body(format.encode(&request_body)?);
+            let response = signing::send(request, self.signer.as_ref())
+                .await
+                .map_err(|e| {
+                    AppError::Internal(format!("Failed to call node '{}': {}", instance_id, e))
+                })?;
+
+            // Nodes that only speak JSON reject binary bodies
+            if response.status() == StatusCode::UNSUPPORTED_MEDIA_TYPE
+                && format != WireFormat::Json
+            {
+                info!(
+                    "Node type '{}' does not accept {} bodies, falling back to JSON",
+                    node_type_id, format
+                );
+                self.json_only_nodes.lock().unwrap().insert(node_type_id.clone());
+                format = WireFormat::Json;
+                continue;
+            }
+
+            break response;
+        };
 
         if !response.status().is_success() {
             let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
@@ -408,17 +864,34 @@ impl Orchestrator {
             )));
         }
 
+        if let Some(stream) = StreamFormat::from_headers(response.headers()) {
+            let outputs = self
+                .read_event_stream(execution_id, instance_id, stream, response)
+                .await?;
+
+            let node_result = NodeExecutionResult {
+                instance_id: instance_id.to_string(),
+                status: "success".to_string(),
+                outputs: Some(outputs.clone()),
+                error: None,
+            };
+            return Ok((node_result, outputs));
+        }
+
         #[derive(Deserialize)]
         struct RunResponse {
             outputs: HashMap<String, Value>,
         }
 
-        let run_response: RunResponse = response.json().await.map_err(|e| {
+        let parse_error = |e: &dyn std::fmt::Display| {
             AppError::Internal(format!(
                 "Failed to parse response from node '{}': {}",
                 instance_id, e
             ))
-        })?;
+        };
+        let format = WireFormat::from_headers(response.headers()).map_err(|e| parse_error(&e))?;
+        let body = response.bytes().await.map_err(|e| parse_error(&e))?;
+        let run_response: RunResponse = format.decode(&body).map_err(|e| parse_error(&e))?;
 
         let node_result = NodeExecutionResult {
             instance_id: instance_id.to_string(),
@@ -429,11 +902,127 @@ impl Orchestrator {
 
         Ok((node_result, run_response.outputs))
     }
+
+    /// Read a node's streamed `/run` response, forwarding its progress
+    /// events, and return its outputs
+    ///
+    /// # Returns
+    ///
+    /// * `Err(AppError)` - The node reported an error, or the stream broke
+    ///   off before the node was done
+    async fn read_event_stream(
+        &self,
+        execution_id: &str,
+        instance_id: &str,
+        stream: StreamFormat,
+        mut response: reqwest::Response,
+    ) -> Result<HashMap<String, Value>, AppError> {
+        let stream_error = |e: &dyn std::fmt::Display| {
+            AppError::Internal(format!("Failed to read stream from node '{}': {}", instance_id, e))
+        };
+        let mut decoder = EventDecoder::new(stream);
+
+        while let Some(chunk) = response.chunk().await.map_err(|e| stream_error(&e))? {
+            let events = decoder
+                .push::<RunEvent>(&chunk)
+                .map_err(|e| stream_error(&e))?;
+
+            for event in events {
+                match event {
+                    RunEvent::Done { outputs } => return Ok(outputs),
+                    RunEvent::Error { error } => {
+                        return Err(AppError::Internal(format!(
+                            "Node '{}' returned error: {}",
+                            instance_id, error
+                        )));
+                    }
+                    event => self.events.publish(ExecutionEvent {
+                        execution_id: execution_id.to_string(),
+                        instance_id: instance_id.to_string(),
+                        event,
+                    }),
+                }
+            }
+        }
+
+        Err(stream_error(&"stream ended before the node was done"))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::registry::NodeInfo;
+    use crate::storage::FsStorage;
+    use axum::{routing::post, Json, Router};
+    use ndnm_libs::NodeConfig;
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    /// Serve an app on a free local port and return the port
+    async fn serve(app: Router) -> u16 {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        port
+    }
+
+    /// A "node" that accepts connections but never answers
+    async fn silent_node() -> u16 {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut sockets = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                sockets.push(socket);
+            }
+        });
+        port
+    }
+
+    /// Node of type `node_id` listening on `port`
+    fn node_info(node_id: &str, port: u16) -> NodeInfo {
+        NodeInfo {
+            node_id: node_id.to_string(),
+            config: NodeConfig {
+                node_id_hash: node_id.to_string(),
+                label: node_id.to_string(),
+                node_type: "test".to_string(),
+                version: 1,
+                sections: vec![],
+                input_fields: vec![],
+                migrations: vec![],
+            },
+            path: PathBuf::from("/test"),
+            port,
+            is_running: true,
+        }
+    }
+
+    /// Registry holding a single node of type `test` listening on `port`
+    fn test_node(port: u16) -> NodeRegistry {
+        let mut registry = NodeRegistry::new();
+        registry.register(node_info("test", port)).unwrap();
+        registry
+    }
+
+    /// Request running one `test` node as `node1`
+    fn run_request(execution_id: &str) -> GraphExecutionRequest {
+        GraphExecutionRequest {
+            execution_id: Some(execution_id.to_string()),
+            graph: GraphDefinition {
+                nodes: vec![GraphNode {
+                    instance_id: "node1".to_string(),
+                    node_type_id: "test".to_string(),
+                    input_values: HashMap::new(),
+                    inputs: HashMap::new(),
+                    position: None,
+                }],
+                connections: vec![],
+            },
+            workspace: None,
+        }
+    }
 
     #[test]
     fn test_validate_graph_empty() {
@@ -459,12 +1048,14 @@ mod tests {
                     instance_id: "node1".to_string(),
                     node_type_id: "type1".to_string(),
                     input_values: HashMap::new(),
+                    inputs: HashMap::new(),
                     position: None,
                 },
                 GraphNode {
                     instance_id: "node2".to_string(),
                     node_type_id: "type2".to_string(),
                     input_values: HashMap::new(),
+                    inputs: HashMap::new(),
                     position: None,
                 },
             ],
@@ -484,4 +1075,227 @@ mod tests {
         assert_eq!(order[0], "node1");
         assert_eq!(order[1], "node2");
     }
+
+    #[tokio::test]
+    async fn test_drain_rejects_new_executions() {
+        let orchestrator = Orchestrator::new(Arc::new(NodeRegistry::new()));
+        orchestrator.drain(Duration::from_millis(10)).await;
+
+        let request = GraphExecutionRequest {
+            execution_id: None,
+            graph: GraphDefinition {
+                nodes: vec![],
+                connections: vec![],
+            },
+            workspace: None,
+        };
+
+        let result = orchestrator.execute_graph(request).await;
+        assert!(matches!(result, Err(AppError::ServiceUnavailable(_))));
+    }
+
+    #[tokio::test]
+    async fn test_drain_interrupts_and_persists_execution() {
+        let registry = test_node(silent_node().await);
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let storage = Arc::new(FsStorage::new(temp_dir.path()));
+        let orchestrator = Arc::new(
+            Orchestrator::new(Arc::new(registry)).with_history(ExecutionHistory::new(storage)),
+        );
+
+        let running = orchestrator.clone();
+        let execution =
+            tokio::spawn(async move { running.execute_graph(run_request("exec-slow")).await });
+
+        // Wait for the execution to be in flight before draining
+        while orchestrator.active_count() == 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        orchestrator.drain(Duration::from_millis(50)).await;
+
+        let response = execution.await.unwrap().unwrap();
+        assert!(matches!(response.status, ExecutionStatus::Interrupted));
+        assert_eq!(orchestrator.active_count(), 0);
+        assert!(temp_dir.path().join("executions/exec-slow.json").exists());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_execution() {
+        let registry = test_node(silent_node().await);
+
+        let orchestrator = Arc::new(Orchestrator::new(Arc::new(registry)));
+        assert!(orchestrator.cancel_execution("exec-cancel").is_err());
+
+        let running = orchestrator.clone();
+        let execution =
+            tokio::spawn(async move { running.execute_graph(run_request("exec-cancel")).await });
+
+        while orchestrator.active_count() == 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        orchestrator.cancel_execution("exec-cancel").unwrap();
+
+        let response = execution.await.unwrap().unwrap();
+        assert!(matches!(response.status, ExecutionStatus::Interrupted));
+        assert_eq!(
+            response.error.as_deref(),
+            Some("Execution cancelled on request")
+        );
+        assert_eq!(orchestrator.active_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_reaches_running_node() {
+        use axum::extract::Path;
+        use tokio::sync::mpsc;
+
+        // A node that never finishes its run and reports what it is sent
+        let (tx, mut rx) = mpsc::unbounded_channel::<Value>();
+        let cancels = tx.clone();
+        let app = Router::new()
+            .route(
+                "/run",
+                post(move |Json(body): Json<Value>| async move {
+                    tx.send(body).unwrap();
+                    std::future::pending::<()>().await
+                }),
+            )
+            .route(
+                "/cancel/:execution_id",
+                post(move |Path(execution_id): Path<String>| async move {
+                    cancels.send(Value::String(execution_id)).unwrap();
+                }),
+            );
+        let registry = test_node(serve(app).await);
+
+        let orchestrator = Arc::new(
+            Orchestrator::new(Arc::new(registry))
+                .with_node_timeout(Some(Duration::from_secs(30))),
+        );
+
+        let running = orchestrator.clone();
+        let execution =
+            tokio::spawn(async move { running.execute_graph(run_request("exec-cancel")).await });
+
+        let run = rx.recv().await.unwrap();
+        assert_eq!(run["execution_id"], "exec-cancel");
+        assert_eq!(run["instance_id"], "node1");
+        assert_eq!(run["timeout_ms"], 30_000);
+
+        orchestrator.cancel_execution("exec-cancel").unwrap();
+        let response = execution.await.unwrap().unwrap();
+        assert!(matches!(response.status, ExecutionStatus::Interrupted));
+        assert_eq!(rx.recv().await.unwrap(), "exec-cancel");
+    }
+
+    #[tokio::test]
+    async fn test_wire_format_falls_back_to_json() {
+        use axum::{body::Bytes, http::HeaderMap};
+        use ndnm_libs::wire::{bytes_value, Wire};
+
+        // A node that only speaks JSON, and one that answers in any format
+        let legacy = serve(Router::new().route(
+            "/run",
+            post(|headers: HeaderMap, body: Bytes| async move {
+                if headers[header::CONTENT_TYPE] != "application/json" {
+                    return Err(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+                }
+                let body: Value = serde_json::from_slice(&body).unwrap();
+                Ok(Json(json!({ "outputs": { "echo": body["inputs"]["data"] } })))
+            }),
+        ))
+        .await;
+        let binary = serve(Router::new().route(
+            "/run",
+            post(|Wire(format, body): Wire<Value>| async move {
+                let outputs =
+                    json!({ "echo": body["inputs"]["data"], "format": format.to_string() });
+                Wire(format, json!({ "outputs": outputs }))
+            }),
+        ))
+        .await;
+
+        let mut registry = NodeRegistry::new();
+        for (id, port) in [("legacy", legacy), ("binary", binary)] {
+            registry.register(node_info(id, port)).unwrap();
+        }
+
+        let orchestrator = Orchestrator::new(Arc::new(registry))
+            .with_wire_format(WireFormat::MessagePack);
+
+        let data = bytes_value(&[0, 1, 2, 255]);
+        let node = |id: &str| GraphNode {
+            instance_id: id.to_string(),
+            node_type_id: id.to_string(),
+            input_values: HashMap::new(),
+            inputs: HashMap::from([("data".to_string(), data.clone())]),
+            position: None,
+        };
+        let request = GraphExecutionRequest {
+            execution_id: None,
+            graph: GraphDefinition {
+                nodes: vec![node("legacy"), node("binary")],
+                connections: vec![],
+            },
+            workspace: None,
+        };
+
+        let response = orchestrator.execute_graph(request).await.unwrap();
+        assert!(matches!(response.status, ExecutionStatus::Success));
+
+        let outputs = |id: &str| response.node_results[id].outputs.clone().unwrap();
+        assert_eq!(outputs("legacy")["echo"], data);
+        assert_eq!(outputs("binary")["echo"], data);
+        assert_eq!(outputs("binary")["format"], "msgpack");
+        assert!(orchestrator.json_only_nodes.lock().unwrap().contains("legacy"));
+        assert!(!orchestrator.json_only_nodes.lock().unwrap().contains("binary"));
+    }
+
+    #[tokio::test]
+    async fn test_streamed_progress_reaches_listeners() {
+        use axum::http::HeaderMap;
+        use ndnm_libs::progress::stream_run;
+
+        // A node that streams when asked to
+        let app = Router::new().route(
+            "/run",
+            post(|headers: HeaderMap| async move {
+                let stream = StreamFormat::from_accept(&headers).unwrap();
+                stream_run(stream, |progress| async move {
+                    progress.report(50.0, "halfway");
+                    Ok(HashMap::from([("result".to_string(), json!("done"))]))
+                })
+            }),
+        );
+        let orchestrator = Orchestrator::new(Arc::new(test_node(serve(app).await)));
+
+        // Listen before the execution starts
+        let mut subscription = orchestrator.events().subscribe("exec-stream");
+
+        let response = orchestrator
+            .execute_graph(run_request("exec-stream"))
+            .await
+            .unwrap();
+
+        assert!(matches!(response.status, ExecutionStatus::Success));
+        assert_eq!(
+            response.node_results["node1"].outputs.as_ref().unwrap()["result"],
+            "done"
+        );
+        assert_eq!(
+            subscription.next().await,
+            Some(ExecutionEvent {
+                execution_id: "exec-stream".to_string(),
+                instance_id: "node1".to_string(),
+                event: RunEvent::Progress {
+                    percent: 50.0,
+                    message: Some("halfway".to_string()),
+                },
+            })
+        );
+        assert_eq!(subscription.next().await, None);
+    }
 }
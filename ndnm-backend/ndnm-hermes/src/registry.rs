@@ -4,6 +4,7 @@
 
 use ndnm_libs::NodeConfig;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::path::PathBuf;
 
@@ -86,6 +87,29 @@ impl NodeRegistry {
         self.nodes.len()
     }
 
+    /// `ETag` identifying the registry's content
+    ///
+    /// Registries with the same nodes and configurations share an `ETag`,
+    /// so clients can tell when the registry changed across restarts.
+    pub fn etag(&self) -> String {
+        let mut nodes: Vec<&NodeInfo> = self.nodes.values().collect();
+        nodes.sort_by(|a, b| a.node_id.cmp(&b.node_id));
+
+        let mut hasher = Sha256::new();
+        for node in nodes {
+            hasher.update(serde_json::to_vec(node).unwrap_or_default());
+            hasher.update(b"\n");
+        }
+
+        let digest: String = hasher
+            .finalize()
+            .iter()
+            .take(16)
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        format!("\"{}\"", digest)
+    }
+
     /// Check if a node exists
     pub fn contains(&self, node_id: &str) -> bool {
         self.nodes.contains_key(node_id)
@@ -117,8 +141,10 @@ mod tests {
                 node_id_hash: node_id.to_string(),
                 label: "Test Node".to_string(),
                 node_type: "test".to_string(),
+                version: 1,
                 sections: vec![],
                 input_fields: vec![],
+                migrations: vec![],
             },
             path: PathBuf::from("/test"),
             port: 3001,
@@ -169,4 +195,23 @@ mod tests {
         let retrieved = registry.get_node("test_node").unwrap();
         assert!(retrieved.is_running);
     }
+
+    #[test]
+    fn test_etag() {
+        let mut registry = NodeRegistry::new();
+        registry.register(create_test_node_info("a")).unwrap();
+        registry.register(create_test_node_info("b")).unwrap();
+
+        let mut reordered = NodeRegistry::new();
+        reordered.register(create_test_node_info("b")).unwrap();
+        reordered.register(create_test_node_info("a")).unwrap();
+        assert_eq!(registry.etag(), reordered.etag());
+
+        let mut changed = create_test_node_info("b");
+        changed.config.version = 2;
+        let mut updated = NodeRegistry::new();
+        updated.register(create_test_node_info("a")).unwrap();
+        updated.register(changed).unwrap();
+        assert_ne!(registry.etag(), updated.etag());
+    }
 }
@@ -0,0 +1,163 @@
+//! Storage backends for the nexus
+//!
+//! Workspaces, their revisions, the trash and execution history are kept
+//! behind the [`Storage`] trait so the persistence layer can be swapped
+//! without touching the workspace logic:
+//!
+//! - [`FsStorage`] - loose JSON files in the `nexus/` directory (default)
+//! - [`SqliteStorage`] - a single embedded SQLite database, where every
+//!   operation touching several records runs in one transaction
+//!
+//! The backend is chosen at startup from `NEXUS_STORAGE` (`fs` or `sqlite`);
+//! the SQLite database path comes from `NEXUS_DB`. An existing `nexus/`
+//! directory can be imported into a fresh database with
+//! [`SqliteStorage::import_nexus`].
+//!
+//! Backends only store and retrieve data. Business rules (schema versions,
+//! optimistic locking, name collisions) live in the
+//! [`WorkspaceManager`](crate::workspace::WorkspaceManager).
+
+mod fs;
+mod sqlite;
+
+pub use fs::FsStorage;
+pub use sqlite::SqliteStorage;
+
+use crate::history::ExecutionRecord;
+use crate::workspace::{RevisionInfo, TrashEntry, WorkspaceData, WorkspaceRevision};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use ndnm_libs::AppError;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::info;
+
+/// Default location of the SQLite database inside the nexus directory
+pub const DEFAULT_DB_FILE: &str = "nexus.db";
+
+/// Persistence backend for workspaces, revisions, trash and executions
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Names of all stored workspaces, sorted
+    async fn list_workspaces(&self) -> Result<Vec<String>, AppError>;
+
+    /// Whether the current version of a workspace exists
+    async fn workspace_exists(&self, name: &str) -> Result<bool, AppError>;
+
+    /// Current version of a workspace, or `None` if it does not exist
+    async fn read_workspace(&self, name: &str) -> Result<Option<WorkspaceData>, AppError>;
+
+    /// Overwrite the current version of a workspace without recording a revision
+    async fn write_workspace(&self, name: &str, data: &WorkspaceData) -> Result<(), AppError>;
+
+    /// Overwrite the current version and record it as the next revision
+    async fn save_revision(
+        &self,
+        name: &str,
+        data: &WorkspaceData,
+        message: Option<String>,
+    ) -> Result<RevisionInfo, AppError>;
+
+    /// Sorted revision numbers recorded for a workspace
+    async fn revision_numbers(&self, name: &str) -> Result<Vec<u64>, AppError>;
+
+    /// A single revision, or `None` if it does not exist
+    async fn read_revision(
+        &self,
+        name: &str,
+        revision: u64,
+    ) -> Result<Option<WorkspaceRevision>, AppError>;
+
+    /// When a workspace was last saved, if known
+    async fn modified_at(&self, name: &str) -> Result<Option<DateTime<Utc>>, AppError>;
+
+    /// Move a workspace and its revisions to a new name
+    async fn rename_workspace(&self, name: &str, new_name: &str) -> Result<(), AppError>;
+
+    /// Move a workspace and its revisions to the trash under `entry`
+    async fn trash_workspace(&self, entry: &TrashEntry) -> Result<(), AppError>;
+
+    /// All trash entries, in no particular order
+    async fn list_trash(&self) -> Result<Vec<TrashEntry>, AppError>;
+
+    /// A single trash entry, or `None` if it does not exist
+    async fn read_trash_entry(&self, trash_id: &str) -> Result<Option<TrashEntry>, AppError>;
+
+    /// Move a trashed workspace back under its original name
+    async fn restore_trash_entry(&self, entry: &TrashEntry) -> Result<(), AppError>;
+
+    /// Permanently delete one trash entry, or all of them when `trash_id` is `None`
+    ///
+    /// Returns the number of entries deleted.
+    async fn purge_trash(&self, trash_id: Option<&str>) -> Result<usize, AppError>;
+
+    /// Persist an execution record, replacing any previous record with the same ID
+    async fn record_execution(&self, record: &ExecutionRecord) -> Result<(), AppError>;
+
+    /// All persisted execution records
+    async fn list_executions(&self) -> Result<Vec<ExecutionRecord>, AppError>;
+}
+
+/// Storage backend selected by `NEXUS_STORAGE`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageBackend {
+    /// Loose JSON files
+    Fs,
+
+    /// Embedded SQLite database
+    Sqlite,
+}
+
+impl StorageBackend {
+    /// Read the backend from `NEXUS_STORAGE`, defaulting to the filesystem
+    pub fn from_env() -> Result<Self, AppError> {
+        match std::env::var("NEXUS_STORAGE") {
+            Err(_) => Ok(Self::Fs),
+            Ok(value) => match value.to_lowercase().as_str() {
+                "" | "fs" => Ok(Self::Fs),
+                "sqlite" => Ok(Self::Sqlite),
+                other => Err(AppError::ConfigError(format!(
+                    "Unknown NEXUS_STORAGE '{}', expected 'fs' or 'sqlite'",
+                    other
+                ))),
+            },
+        }
+    }
+}
+
+/// Path of the SQLite database from `NEXUS_DB`, or `<nexus_dir>/nexus.db`
+pub fn db_path_from_env(nexus_dir: &Path) -> PathBuf {
+    std::env::var("NEXUS_DB")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| nexus_dir.join(DEFAULT_DB_FILE))
+}
+
+/// Open the storage backend configured in the environment
+///
+/// # Arguments
+///
+/// * `nexus_dir` - Nexus directory (files for `fs`, default database location for `sqlite`)
+pub fn open_from_env(nexus_dir: &Path) -> Result<Arc<dyn Storage>, AppError> {
+    match StorageBackend::from_env()? {
+        StorageBackend::Fs => {
+            info!("Using filesystem storage in {:?}", nexus_dir);
+            Ok(Arc::new(FsStorage::new(nexus_dir)))
+        }
+        StorageBackend::Sqlite => {
+            let db_path = db_path_from_env(nexus_dir);
+            info!("Using SQLite storage at {:?}", db_path);
+            Ok(Arc::new(SqliteStorage::open(&db_path)?))
+        }
+    }
+}
+
+/// Run blocking storage work on the blocking thread pool
+async fn blocking<T, F>(work: F) -> Result<T, AppError>
+where
+    F: FnOnce() -> Result<T, AppError> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(|e| AppError::Internal(format!("Storage task failed: {}", e)))?
+}
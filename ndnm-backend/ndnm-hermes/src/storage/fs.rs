@@ -0,0 +1,518 @@
+//! Filesystem storage backend
+//!
+//! The current version of each workspace lives in `nexus/<name>.json` and
+//! every save is kept as a numbered revision under `nexus/.history/<name>/`.
+//! Deleted workspaces (with their history) are moved to `nexus/.trash/` and
+//! execution records are written to `nexus/executions/<id>.json`.
+//!
+//! All file access runs on the blocking thread pool.
+
+use super::{blocking, Storage};
+use crate::history::ExecutionRecord;
+use crate::workspace::{
+    sanitize_filename, RevisionInfo, TrashEntry, WorkspaceData, WorkspaceRevision,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use ndnm_libs::AppError;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Folder inside the nexus directory holding workspace revisions
+const HISTORY_DIR: &str = ".history";
+
+/// Folder inside the nexus directory holding deleted workspaces
+const TRASH_DIR: &str = ".trash";
+
+/// Folder inside the nexus directory holding execution records
+const EXECUTIONS_DIR: &str = "executions";
+
+/// Metadata file describing a trash entry
+const TRASH_ENTRY_FILE: &str = "entry.json";
+
+/// Workspace file inside a trash entry
+const TRASHED_WORKSPACE_FILE: &str = "workspace.json";
+
+/// History folder inside a trash entry
+const TRASHED_HISTORY_DIR: &str = "history";
+
+/// Storage backed by loose JSON files in the nexus directory
+#[derive(Debug, Clone)]
+pub struct FsStorage {
+    /// Directory where workspaces are stored
+    nexus_dir: PathBuf,
+}
+
+/// A workspace in the trash, with everything needed to restore it
+#[derive(Debug)]
+pub struct TrashedWorkspace {
+    /// Trash entry metadata
+    pub entry: TrashEntry,
+
+    /// Workspace data at the time it was deleted
+    pub data: WorkspaceData,
+
+    /// Revisions of the workspace, oldest first
+    pub revisions: Vec<WorkspaceRevision>,
+}
+
+impl FsStorage {
+    /// Create a new filesystem storage
+    ///
+    /// # Arguments
+    ///
+    /// * `nexus_dir` - Path to the nexus directory
+    pub fn new<P: AsRef<Path>>(nexus_dir: P) -> Self {
+        let nexus_dir = nexus_dir.as_ref().to_path_buf();
+
+        // Create nexus directory if it doesn't exist
+        if !nexus_dir.exists()
+            && let Err(e) = fs::create_dir_all(&nexus_dir)
+        {
+            warn!("Failed to create nexus directory: {}", e);
+        }
+
+        Self { nexus_dir }
+    }
+
+    /// Read a trashed workspace together with its revisions
+    ///
+    /// Used when importing a nexus directory into another backend.
+    pub async fn read_trashed(&self, trash_id: &str) -> Result<TrashedWorkspace, AppError> {
+        let this = self.clone();
+        let trash_id = trash_id.to_string();
+        blocking(move || {
+            let entry_dir = this.trash_entry_dir(&trash_id)?;
+            let entry = read_json(&entry_dir.join(TRASH_ENTRY_FILE), "trash entry")?;
+            let data = read_json(&entry_dir.join(TRASHED_WORKSPACE_FILE), "workspace file")?;
+
+            let history_dir = entry_dir.join(TRASHED_HISTORY_DIR);
+            let revisions = revision_numbers_in(&history_dir)?
+                .into_iter()
+                .map(|revision| {
+                    read_json(
+                        &history_dir.join(revision_file(revision)),
+                        "workspace revision",
+                    )
+                })
+                .collect::<Result<_, _>>()?;
+
+            Ok(TrashedWorkspace {
+                entry,
+                data,
+                revisions,
+            })
+        })
+        .await
+    }
+
+    /// Path of the current version of a workspace
+    fn workspace_path(&self, name: &str) -> PathBuf {
+        self.nexus_dir
+            .join(format!("{}.json", sanitize_filename(name)))
+    }
+
+    /// Directory holding the revisions of a workspace
+    fn history_dir(&self, name: &str) -> PathBuf {
+        self.nexus_dir
+            .join(HISTORY_DIR)
+            .join(sanitize_filename(name))
+    }
+
+    /// Directory holding deleted workspaces
+    fn trash_dir(&self) -> PathBuf {
+        self.nexus_dir.join(TRASH_DIR)
+    }
+
+    /// Directory holding execution records
+    fn executions_dir(&self) -> PathBuf {
+        self.nexus_dir.join(EXECUTIONS_DIR)
+    }
+
+    /// Directory of an existing trash entry
+    fn trash_entry_dir(&self, trash_id: &str) -> Result<PathBuf, AppError> {
+        let entry_dir = self.trash_dir().join(sanitize_filename(trash_id));
+
+        if !entry_dir.is_dir() {
+            return Err(AppError::BadRequest(format!(
+                "Trash entry '{}' not found",
+                trash_id
+            )));
+        }
+
+        Ok(entry_dir)
+    }
+
+    /// Record workspace data as the next revision
+    fn append_revision(
+        &self,
+        name: &str,
+        data: WorkspaceData,
+        message: Option<String>,
+    ) -> Result<RevisionInfo, AppError> {
+        let history_dir = self.history_dir(name);
+        fs::create_dir_all(&history_dir).map_err(|e| {
+            AppError::Internal(format!("Failed to create workspace history: {}", e))
+        })?;
+
+        let revision = revision_numbers_in(&history_dir)?
+            .last()
+            .map_or(1, |latest| latest + 1);
+
+        let entry = WorkspaceRevision {
+            revision,
+            saved_at: Utc::now(),
+            message,
+            data,
+        };
+
+        write_json(
+            &history_dir.join(revision_file(revision)),
+            &entry,
+            "workspace revision",
+        )?;
+
+        Ok(entry.info())
+    }
+}
+
+#[async_trait]
+impl Storage for FsStorage {
+    async fn list_workspaces(&self) -> Result<Vec<String>, AppError> {
+        let nexus_dir = self.nexus_dir.clone();
+        blocking(move || {
+            if !nexus_dir.exists() {
+                return Ok(Vec::new());
+            }
+
+            let entries = fs::read_dir(&nexus_dir).map_err(|e| {
+                AppError::Internal(format!("Failed to read nexus directory: {}", e))
+            })?;
+
+            let mut workspaces = Vec::new();
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.is_file()
+                    && path.extension().is_some_and(|ext| ext == "json")
+                    && let Some(name) = path.file_stem().and_then(|stem| stem.to_str())
+                {
+                    workspaces.push(name.to_string());
+                }
+            }
+
+            workspaces.sort();
+            Ok(workspaces)
+        })
+        .await
+    }
+
+    async fn workspace_exists(&self, name: &str) -> Result<bool, AppError> {
+        let path = self.workspace_path(name);
+        blocking(move || Ok(path.exists())).await
+    }
+
+    async fn read_workspace(&self, name: &str) -> Result<Option<WorkspaceData>, AppError> {
+        let path = self.workspace_path(name);
+        blocking(move || {
+            if !path.exists() {
+                return Ok(None);
+            }
+            read_json(&path, "workspace file").map(Some)
+        })
+        .await
+    }
+
+    async fn write_workspace(&self, name: &str, data: &WorkspaceData) -> Result<(), AppError> {
+        let path = self.workspace_path(name);
+        let data = data.clone();
+        blocking(move || write_json(&path, &data, "workspace file")).await
+    }
+
+    async fn save_revision(
+        &self,
+        name: &str,
+        data: &WorkspaceData,
+        message: Option<String>,
+    ) -> Result<RevisionInfo, AppError> {
+        let this = self.clone();
+        let name = name.to_string();
+        let data = data.clone();
+        blocking(move || {
+            write_json(&this.workspace_path(&name), &data, "workspace file")?;
+            this.append_revision(&name, data, message)
+        })
+        .await
+    }
+
+    async fn revision_numbers(&self, name: &str) -> Result<Vec<u64>, AppError> {
+        let history_dir = self.history_dir(name);
+        blocking(move || revision_numbers_in(&history_dir)).await
+    }
+
+    async fn read_revision(
+        &self,
+        name: &str,
+        revision: u64,
+    ) -> Result<Option<WorkspaceRevision>, AppError> {
+        let path = self.history_dir(name).join(revision_file(revision));
+        blocking(move || {
+            if !path.exists() {
+                return Ok(None);
+            }
+            read_json(&path, "workspace revision").map(Some)
+        })
+        .await
+    }
+
+    async fn modified_at(&self, name: &str) -> Result<Option<DateTime<Utc>>, AppError> {
+        let history_dir = self.history_dir(name);
+        let workspace_path = self.workspace_path(name);
+        blocking(move || {
+            // Prefer the latest revision file; workspaces saved before
+            // revisions were kept only have the workspace file
+            let path = revision_numbers_in(&history_dir)?
+                .last()
+                .map(|revision| history_dir.join(revision_file(*revision)))
+                .unwrap_or(workspace_path);
+
+            Ok(fs::metadata(path)
+                .and_then(|m| m.modified())
+                .ok()
+                .map(DateTime::<Utc>::from))
+        })
+        .await
+    }
+
+    async fn rename_workspace(&self, name: &str, new_name: &str) -> Result<(), AppError> {
+        let this = self.clone();
+        let name = name.to_string();
+        let new_name = new_name.to_string();
+        blocking(move || {
+            move_path(&this.workspace_path(&name), &this.workspace_path(&new_name))?;
+
+            let history_dir = this.history_dir(&name);
+            if history_dir.exists() {
+                move_path(&history_dir, &this.history_dir(&new_name))?;
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    async fn trash_workspace(&self, entry: &TrashEntry) -> Result<(), AppError> {
+        let this = self.clone();
+        let entry = entry.clone();
+        blocking(move || {
+            let entry_dir = this.trash_dir().join(sanitize_filename(&entry.trash_id));
+            fs::create_dir_all(&entry_dir)
+                .map_err(|e| AppError::Internal(format!("Failed to create trash entry: {}", e)))?;
+
+            write_json(&entry_dir.join(TRASH_ENTRY_FILE), &entry, "trash entry")?;
+
+            move_path(
+                &this.workspace_path(&entry.name),
+                &entry_dir.join(TRASHED_WORKSPACE_FILE),
+            )?;
+
+            let history_dir = this.history_dir(&entry.name);
+            if history_dir.exists() {
+                move_path(&history_dir, &entry_dir.join(TRASHED_HISTORY_DIR))?;
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    async fn list_trash(&self) -> Result<Vec<TrashEntry>, AppError> {
+        let trash_dir = self.trash_dir();
+        blocking(move || {
+            if !trash_dir.exists() {
+                return Ok(Vec::new());
+            }
+
+            let entries = fs::read_dir(&trash_dir).map_err(|e| {
+                AppError::Internal(format!("Failed to read trash directory: {}", e))
+            })?;
+
+            let mut trash = Vec::new();
+            for entry in entries.flatten() {
+                match read_json(&entry.path().join(TRASH_ENTRY_FILE), "trash entry") {
+                    Ok(trash_entry) => trash.push(trash_entry),
+                    Err(e) => warn!("Skipping invalid trash entry {:?}: {}", entry.path(), e),
+                }
+            }
+            Ok(trash)
+        })
+        .await
+    }
+
+    async fn read_trash_entry(&self, trash_id: &str) -> Result<Option<TrashEntry>, AppError> {
+        let path = self
+            .trash_dir()
+            .join(sanitize_filename(trash_id))
+            .join(TRASH_ENTRY_FILE);
+        blocking(move || {
+            if !path.exists() {
+                return Ok(None);
+            }
+            read_json(&path, "trash entry").map(Some)
+        })
+        .await
+    }
+
+    async fn restore_trash_entry(&self, entry: &TrashEntry) -> Result<(), AppError> {
+        let this = self.clone();
+        let entry = entry.clone();
+        blocking(move || {
+            let entry_dir = this.trash_entry_dir(&entry.trash_id)?;
+
+            move_path(
+                &entry_dir.join(TRASHED_WORKSPACE_FILE),
+                &this.workspace_path(&entry.name),
+            )?;
+
+            let trashed_history = entry_dir.join(TRASHED_HISTORY_DIR);
+            if trashed_history.exists() {
+                move_path(&trashed_history, &this.history_dir(&entry.name))?;
+            }
+
+            fs::remove_dir_all(&entry_dir)
+                .map_err(|e| AppError::Internal(format!("Failed to remove trash entry: {}", e)))
+        })
+        .await
+    }
+
+    async fn purge_trash(&self, trash_id: Option<&str>) -> Result<usize, AppError> {
+        let this = self.clone();
+        let trash_id = trash_id.map(str::to_string);
+        blocking(move || {
+            let entry_dirs = match trash_id {
+                Some(trash_id) => vec![this.trash_entry_dir(&trash_id)?],
+                None => {
+                    let trash_dir = this.trash_dir();
+                    if !trash_dir.exists() {
+                        return Ok(0);
+                    }
+                    fs::read_dir(&trash_dir)
+                        .map_err(|e| {
+                            AppError::Internal(format!("Failed to read trash directory: {}", e))
+                        })?
+                        .flatten()
+                        .map(|entry| entry.path())
+                        .filter(|path| path.is_dir())
+                        .collect()
+                }
+            };
+
+            for entry_dir in &entry_dirs {
+                fs::remove_dir_all(entry_dir).map_err(|e| {
+                    AppError::Internal(format!("Failed to purge trash entry: {}", e))
+                })?;
+            }
+
+            Ok(entry_dirs.len())
+        })
+        .await
+    }
+
+    async fn record_execution(&self, record: &ExecutionRecord) -> Result<(), AppError> {
+        let executions_dir = self.executions_dir();
+        let record = record.clone();
+        blocking(move || {
+            fs::create_dir_all(&executions_dir).map_err(|e| {
+                AppError::Internal(format!("Failed to create executions directory: {}", e))
+            })?;
+
+            write_json(
+                &executions_dir.join(format!("{}.json", sanitize_filename(&record.execution_id))),
+                &record,
+                "execution record",
+            )
+        })
+        .await
+    }
+
+    async fn list_executions(&self) -> Result<Vec<ExecutionRecord>, AppError> {
+        let executions_dir = self.executions_dir();
+        blocking(move || {
+            if !executions_dir.exists() {
+                return Ok(Vec::new());
+            }
+
+            let entries = fs::read_dir(&executions_dir).map_err(|e| {
+                AppError::Internal(format!("Failed to read executions directory: {}", e))
+            })?;
+
+            let mut records = Vec::new();
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.extension().is_none_or(|ext| ext != "json") {
+                    continue;
+                }
+                match read_json(&path, "execution record") {
+                    Ok(record) => records.push(record),
+                    Err(e) => warn!("Skipping invalid execution record {:?}: {}", path, e),
+                }
+            }
+            Ok(records)
+        })
+        .await
+    }
+}
+
+/// File name of a revision inside a history directory
+fn revision_file(revision: u64) -> String {
+    format!("{:06}.json", revision)
+}
+
+/// Sorted revision numbers found in a history directory
+fn revision_numbers_in(history_dir: &Path) -> Result<Vec<u64>, AppError> {
+    if !history_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let entries = fs::read_dir(history_dir)
+        .map_err(|e| AppError::Internal(format!("Failed to read workspace history: {}", e)))?;
+
+    let mut numbers: Vec<u64> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                path.file_stem()?.to_str()?.parse().ok()
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    numbers.sort_unstable();
+    Ok(numbers)
+}
+
+/// Read and parse a JSON file
+fn read_json<T: DeserializeOwned>(path: &Path, what: &str) -> Result<T, AppError> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| AppError::Internal(format!("Failed to read {}: {}", what, e)))?;
+
+    serde_json::from_str(&contents)
+        .map_err(|e| AppError::Internal(format!("Failed to parse {}: {}", what, e)))
+}
+
+/// Serialize a value as pretty JSON and write it to a file
+fn write_json<T: Serialize>(path: &Path, value: &T, what: &str) -> Result<(), AppError> {
+    let json = serde_json::to_string_pretty(value)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize {}: {}", what, e)))?;
+
+    fs::write(path, json)
+        .map_err(|e| AppError::Internal(format!("Failed to write {}: {}", what, e)))
+}
+
+/// Move a file or directory, reporting failures as internal errors
+fn move_path(from: &Path, to: &Path) -> Result<(), AppError> {
+    fs::rename(from, to)
+        .map_err(|e| AppError::Internal(format!("Failed to move {:?} to {:?}: {}", from, to, e)))
+}
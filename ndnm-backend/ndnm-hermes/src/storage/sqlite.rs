@@ -0,0 +1,846 @@
+//! SQLite storage backend
+//!
+//! Keeps workspaces, revisions, the trash and execution history in a single
+//! embedded database. Saving a revision, renaming, trashing and restoring a
+//! workspace each run in one transaction, so a crash never leaves a
+//! workspace without its history or half moved to the trash.
+//!
+//! The connection is shared behind a mutex and used from the blocking
+//! thread pool.
+
+use super::fs::FsStorage;
+use super::{blocking, Storage};
+use crate::history::ExecutionRecord;
+use crate::workspace::{RevisionInfo, TrashEntry, WorkspaceData, WorkspaceRevision};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use ndnm_libs::AppError;
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Database schema, applied on every open
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS workspaces (
+        name        TEXT PRIMARY KEY,
+        data        TEXT NOT NULL,
+        modified_at TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS revisions (
+        workspace TEXT NOT NULL,
+        revision  INTEGER NOT NULL,
+        saved_at  TEXT NOT NULL,
+        message   TEXT,
+        data      TEXT NOT NULL,
+        PRIMARY KEY (workspace, revision)
+    );
+
+    CREATE TABLE IF NOT EXISTS trash (
+        trash_id   TEXT PRIMARY KEY,
+        name       TEXT NOT NULL,
+        deleted_at TEXT NOT NULL,
+        data       TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS trash_revisions (
+        trash_id TEXT NOT NULL REFERENCES trash (trash_id) ON DELETE CASCADE,
+        revision INTEGER NOT NULL,
+        saved_at TEXT NOT NULL,
+        message  TEXT,
+        data     TEXT NOT NULL,
+        PRIMARY KEY (trash_id, revision)
+    );
+
+    CREATE TABLE IF NOT EXISTS executions (
+        execution_id TEXT PRIMARY KEY,
+        status       TEXT NOT NULL,
+        started_at   TEXT NOT NULL,
+        finished_at  TEXT NOT NULL,
+        record       TEXT NOT NULL
+    );
+";
+
+/// Storage backed by an embedded SQLite database
+#[derive(Clone)]
+pub struct SqliteStorage {
+    /// Shared database connection
+    conn: Arc<Mutex<Connection>>,
+}
+
+/// Counts of records imported from a nexus directory
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ImportSummary {
+    /// Workspaces imported (current versions)
+    pub workspaces: usize,
+
+    /// Revisions imported across all workspaces
+    pub revisions: usize,
+
+    /// Trash entries imported
+    pub trash_entries: usize,
+
+    /// Execution records imported
+    pub executions: usize,
+}
+
+impl SqliteStorage {
+    /// Open (or create) a database and apply the schema
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the database file
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, AppError> {
+        let path = path.as_ref();
+
+        if let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                AppError::Internal(format!("Failed to create database directory: {}", e))
+            })?;
+        }
+
+        let conn = Connection::open(path).map_err(db_error)?;
+        Self::init(conn)
+    }
+
+    /// Open a private in-memory database (used by tests)
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self, AppError> {
+        Self::init(Connection::open_in_memory().map_err(db_error)?)
+    }
+
+    /// Apply connection settings and the schema
+    fn init(conn: Connection) -> Result<Self, AppError> {
+        conn.pragma_update(None, "foreign_keys", "ON")
+            .map_err(db_error)?;
+        conn.pragma_update(None, "journal_mode", "WAL")
+            .map_err(db_error)?;
+        conn.execute_batch(SCHEMA).map_err(db_error)?;
+
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Run work against the connection on the blocking thread pool
+    async fn with_conn<T, F>(&self, work: F) -> Result<T, AppError>
+    where
+        F: FnOnce(&mut Connection) -> Result<T, AppError> + Send + 'static,
+        T: Send + 'static,
+    {
+        let conn = self.conn.clone();
+        blocking(move || {
+            let mut conn = conn
+                .lock()
+                .map_err(|_| AppError::Internal("Database connection poisoned".to_string()))?;
+            work(&mut conn)
+        })
+        .await
+    }
+
+    /// Run work inside a transaction, committing only if it succeeds
+    async fn transaction<T, F>(&self, work: F) -> Result<T, AppError>
+    where
+        F: FnOnce(&Transaction) -> Result<T, AppError> + Send + 'static,
+        T: Send + 'static,
+    {
+        self.with_conn(move |conn| {
+            let tx = conn.transaction().map_err(db_error)?;
+            let result = work(&tx)?;
+            tx.commit().map_err(db_error)?;
+            Ok(result)
+        })
+        .await
+    }
+
+    /// Import an existing nexus directory into this database
+    ///
+    /// Workspaces keep their revision numbers and timestamps; trash entries
+    /// and execution records are carried over as well. Everything is
+    /// imported in a single transaction, and only into an empty database,
+    /// so the import either happens completely or not at all.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - Filesystem storage over the nexus directory to import
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(ImportSummary)` - Number of records imported
+    /// * `Err(AppError)` - The database is not empty or the source could not be read
+    pub async fn import_nexus(&self, source: &FsStorage) -> Result<ImportSummary, AppError> {
+        let mut workspaces = Vec::new();
+        for name in source.list_workspaces().await? {
+            let Some(data) = source.read_workspace(&name).await? else {
+                continue;
+            };
+
+            let mut revisions = Vec::new();
+            for revision in source.revision_numbers(&name).await? {
+                if let Some(revision) = source.read_revision(&name, revision).await? {
+                    revisions.push(revision);
+                }
+            }
+
+            let modified_at = source.modified_at(&name).await?.unwrap_or_else(Utc::now);
+            workspaces.push((name, data, revisions, modified_at));
+        }
+
+        let mut trash = Vec::new();
+        for entry in source.list_trash().await? {
+            trash.push(source.read_trashed(&entry.trash_id).await?);
+        }
+
+        let executions = source.list_executions().await?;
+
+        self.transaction(move |tx| {
+            let existing: i64 = tx
+                .query_row("SELECT COUNT(*) FROM workspaces", [], |row| row.get(0))
+                .map_err(db_error)?;
+            if existing > 0 {
+                return Err(AppError::BadRequest(
+                    "Database already contains workspaces; import into an empty database"
+                        .to_string(),
+                ));
+            }
+
+            let mut summary = ImportSummary::default();
+
+            for (name, data, revisions, modified_at) in &workspaces {
+                upsert_workspace(tx, name, data, modified_at)?;
+                for revision in revisions {
+                    insert_revision(tx, "revisions", name, revision)?;
+                }
+                summary.workspaces += 1;
+                summary.revisions += revisions.len();
+            }
+
+            for trashed in &trash {
+                insert_trash(tx, &trashed.entry, &trashed.data)?;
+                for revision in &trashed.revisions {
+                    insert_revision(tx, "trash_revisions", &trashed.entry.trash_id, revision)?;
+                }
+                summary.trash_entries += 1;
+            }
+
+            for record in &executions {
+                upsert_execution(tx, record)?;
+                summary.executions += 1;
+            }
+
+            Ok(summary)
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    async fn list_workspaces(&self) -> Result<Vec<String>, AppError> {
+        self.with_conn(|conn| {
+            let mut stmt = conn
+                .prepare("SELECT name FROM workspaces ORDER BY name")
+                .map_err(db_error)?;
+            let names = stmt
+                .query_map([], |row| row.get(0))
+                .map_err(db_error)?
+                .collect::<Result<_, _>>()
+                .map_err(db_error)?;
+            Ok(names)
+        })
+        .await
+    }
+
+    async fn workspace_exists(&self, name: &str) -> Result<bool, AppError> {
+        let name = name.to_string();
+        self.with_conn(move |conn| {
+            conn.query_row(
+                "SELECT EXISTS (SELECT 1 FROM workspaces WHERE name = ?1)",
+                [&name],
+                |row| row.get(0),
+            )
+            .map_err(db_error)
+        })
+        .await
+    }
+
+    async fn read_workspace(&self, name: &str) -> Result<Option<WorkspaceData>, AppError> {
+        let name = name.to_string();
+        self.with_conn(move |conn| {
+            let json: Option<String> = conn
+                .query_row(
+                    "SELECT data FROM workspaces WHERE name = ?1",
+                    [&name],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(db_error)?;
+
+            json.map(|json| from_json(&json, "workspace data"))
+                .transpose()
+        })
+        .await
+    }
+
+    async fn write_workspace(&self, name: &str, data: &WorkspaceData) -> Result<(), AppError> {
+        let name = name.to_string();
+        let json = to_json(data, "workspace data")?;
+        self.with_conn(move |conn| {
+            conn.execute(
+                "INSERT INTO workspaces (name, data, modified_at) VALUES (?1, ?2, ?3)
+                 ON CONFLICT (name) DO UPDATE SET data = excluded.data",
+                params![name, json, Utc::now().to_rfc3339()],
+            )
+            .map_err(db_error)?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn save_revision(
+        &self,
+        name: &str,
+        data: &WorkspaceData,
+        message: Option<String>,
+    ) -> Result<RevisionInfo, AppError> {
+        let name = name.to_string();
+        let data = data.clone();
+        self.transaction(move |tx| {
+            let latest: Option<i64> = tx
+                .query_row(
+                    "SELECT MAX(revision) FROM revisions WHERE workspace = ?1",
+                    [&name],
+                    |row| row.get(0),
+                )
+                .map_err(db_error)?;
+
+            let entry = WorkspaceRevision {
+                revision: latest.map_or(1, |latest| latest as u64 + 1),
+                saved_at: Utc::now(),
+                message,
+                data,
+            };
+
+            upsert_workspace(tx, &name, &entry.data, &entry.saved_at)?;
+            insert_revision(tx, "revisions", &name, &entry)?;
+
+            Ok(entry.info())
+        })
+        .await
+    }
+
+    async fn revision_numbers(&self, name: &str) -> Result<Vec<u64>, AppError> {
+        let name = name.to_string();
+        self.with_conn(move |conn| {
+            let mut stmt = conn
+                .prepare("SELECT revision FROM revisions WHERE workspace = ?1 ORDER BY revision")
+                .map_err(db_error)?;
+            let numbers = stmt
+                .query_map([&name], |row| row.get::<_, i64>(0))
+                .map_err(db_error)?
+                .map(|revision| revision.map(|revision| revision as u64))
+                .collect::<Result<_, _>>()
+                .map_err(db_error)?;
+            Ok(numbers)
+        })
+        .await
+    }
+
+    async fn read_revision(
+        &self,
+        name: &str,
+        revision: u64,
+    ) -> Result<Option<WorkspaceRevision>, AppError> {
+        let name = name.to_string();
+        self.with_conn(move |conn| {
+            let row: Option<(String, Option<String>, String)> = conn
+                .query_row(
+                    "SELECT saved_at, message, data FROM revisions
+                     WHERE workspace = ?1 AND revision = ?2",
+                    params![name, revision as i64],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+                )
+                .optional()
+                .map_err(db_error)?;
+
+            row.map(|(saved_at, message, data)| {
+                Ok(WorkspaceRevision {
+                    revision,
+                    saved_at: parse_timestamp(&saved_at)?,
+                    message,
+                    data: from_json(&data, "workspace revision")?,
+                })
+            })
+            .transpose()
+        })
+        .await
+    }
+
+    async fn modified_at(&self, name: &str) -> Result<Option<DateTime<Utc>>, AppError> {
+        let name = name.to_string();
+        self.with_conn(move |conn| {
+            let modified_at: Option<String> = conn
+                .query_row(
+                    "SELECT modified_at FROM workspaces WHERE name = ?1",
+                    [&name],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(db_error)?;
+
+            modified_at.map(|value| parse_timestamp(&value)).transpose()
+        })
+        .await
+    }
+
+    async fn rename_workspace(&self, name: &str, new_name: &str) -> Result<(), AppError> {
+        let name = name.to_string();
+        let new_name = new_name.to_string();
+        self.transaction(move |tx| {
+            tx.execute(
+                "UPDATE workspaces SET name = ?2 WHERE name = ?1",
+                [&name, &new_name],
+            )
+            .map_err(db_error)?;
+            tx.execute(
+                "UPDATE revisions SET workspace = ?2 WHERE workspace = ?1",
+                [&name, &new_name],
+            )
+            .map_err(db_error)?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn trash_workspace(&self, entry: &TrashEntry) -> Result<(), AppError> {
+        let entry = entry.clone();
+        self.transaction(move |tx| {
+            let data: String = tx
+                .query_row(
+                    "SELECT data FROM workspaces WHERE name = ?1",
+                    [&entry.name],
+                    |row| row.get(0),
+                )
+                .map_err(db_error)?;
+
+            tx.execute(
+                "INSERT INTO trash (trash_id, name, deleted_at, data) VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    entry.trash_id,
+                    entry.name,
+                    entry.deleted_at.to_rfc3339(),
+                    data
+                ],
+            )
+            .map_err(db_error)?;
+            tx.execute(
+                "INSERT INTO trash_revisions (trash_id, revision, saved_at, message, data)
+                 SELECT ?1, revision, saved_at, message, data FROM revisions WHERE workspace = ?2",
+                [&entry.trash_id, &entry.name],
+            )
+            .map_err(db_error)?;
+            tx.execute("DELETE FROM revisions WHERE workspace = ?1", [&entry.name])
+                .map_err(db_error)?;
+            tx.execute("DELETE FROM workspaces WHERE name = ?1", [&entry.name])
+                .map_err(db_error)?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn list_trash(&self) -> Result<Vec<TrashEntry>, AppError> {
+        self.with_conn(|conn| {
+            let mut stmt = conn
+                .prepare("SELECT trash_id, name, deleted_at FROM trash")
+                .map_err(db_error)?;
+            let rows: Vec<(String, String, String)> = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+                .map_err(db_error)?
+                .collect::<Result<_, _>>()
+                .map_err(db_error)?;
+
+            rows.into_iter()
+                .map(|(trash_id, name, deleted_at)| {
+                    Ok(TrashEntry {
+                        trash_id,
+                        name,
+                        deleted_at: parse_timestamp(&deleted_at)?,
+                    })
+                })
+                .collect()
+        })
+        .await
+    }
+
+    async fn read_trash_entry(&self, trash_id: &str) -> Result<Option<TrashEntry>, AppError> {
+        let trash_id = trash_id.to_string();
+        self.with_conn(move |conn| {
+            let row: Option<(String, String)> = conn
+                .query_row(
+                    "SELECT name, deleted_at FROM trash WHERE trash_id = ?1",
+                    [&trash_id],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .optional()
+                .map_err(db_error)?;
+
+            row.map(|(name, deleted_at)| {
+                Ok(TrashEntry {
+                    trash_id,
+                    name,
+                    deleted_at: parse_timestamp(&deleted_at)?,
+                })
+            })
+            .transpose()
+        })
+        .await
+    }
+
+    async fn restore_trash_entry(&self, entry: &TrashEntry) -> Result<(), AppError> {
+        let entry = entry.clone();
+        self.transaction(move |tx| {
+            let data: String = tx
+                .query_row(
+                    "SELECT data FROM trash WHERE trash_id = ?1",
+                    [&entry.trash_id],
+                    |row| row.get(0),
+                )
+                .map_err(db_error)?;
+
+            let modified_at: Option<String> = tx
+                .query_row(
+                    "SELECT MAX(saved_at) FROM trash_revisions WHERE trash_id = ?1",
+                    [&entry.trash_id],
+                    |row| row.get(0),
+                )
+                .map_err(db_error)?;
+
+            tx.execute(
+                "INSERT INTO workspaces (name, data, modified_at) VALUES (?1, ?2, ?3)",
+                params![
+                    entry.name,
+                    data,
+                    modified_at.unwrap_or_else(|| entry.deleted_at.to_rfc3339())
+                ],
+            )
+            .map_err(db_error)?;
+            tx.execute(
+                "INSERT INTO revisions (workspace, revision, saved_at, message, data)
+                 SELECT ?2, revision, saved_at, message, data FROM trash_revisions
+                 WHERE trash_id = ?1",
+                [&entry.trash_id, &entry.name],
+            )
+            .map_err(db_error)?;
+            tx.execute("DELETE FROM trash WHERE trash_id = ?1", [&entry.trash_id])
+                .map_err(db_error)?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn purge_trash(&self, trash_id: Option<&str>) -> Result<usize, AppError> {
+        let trash_id = trash_id.map(str::to_string);
+        self.transaction(move |tx| {
+            // Trashed revisions are removed through ON DELETE CASCADE
+            let purged = match trash_id {
+                Some(trash_id) => tx.execute("DELETE FROM trash WHERE trash_id = ?1", [&trash_id]),
+                None => tx.execute("DELETE FROM trash", []),
+            }
+            .map_err(db_error)?;
+            Ok(purged)
+        })
+        .await
+    }
+
+    async fn record_execution(&self, record: &ExecutionRecord) -> Result<(), AppError> {
+        let record = record.clone();
+        self.with_conn(move |conn| upsert_execution(conn, &record))
+            .await
+    }
+
+    async fn list_executions(&self) -> Result<Vec<ExecutionRecord>, AppError> {
+        self.with_conn(|conn| {
+            let mut stmt = conn
+                .prepare("SELECT record FROM executions ORDER BY started_at")
+                .map_err(db_error)?;
+            let rows: Vec<String> = stmt
+                .query_map([], |row| row.get(0))
+                .map_err(db_error)?
+                .collect::<Result<_, _>>()
+                .map_err(db_error)?;
+
+            rows.iter()
+                .map(|json| from_json(json, "execution record"))
+                .collect()
+        })
+        .await
+    }
+}
+
+/// Insert or replace the current version of a workspace
+fn upsert_workspace(
+    conn: &Connection,
+    name: &str,
+    data: &WorkspaceData,
+    modified_at: &DateTime<Utc>,
+) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT INTO workspaces (name, data, modified_at) VALUES (?1, ?2, ?3)
+         ON CONFLICT (name) DO UPDATE SET data = excluded.data, modified_at = excluded.modified_at",
+        params![
+            name,
+            to_json(data, "workspace data")?,
+            modified_at.to_rfc3339()
+        ],
+    )
+    .map_err(db_error)?;
+    Ok(())
+}
+
+/// Insert a revision into `revisions` or `trash_revisions`, keyed by `owner`
+fn insert_revision(
+    conn: &Connection,
+    table: &str,
+    owner: &str,
+    revision: &WorkspaceRevision,
+) -> Result<(), AppError> {
+    let owner_column = if table == "revisions" {
+        "workspace"
+    } else {
+        "trash_id"
+    };
+
+    conn.execute(
+        &format!(
+            "INSERT INTO {} ({}, revision, saved_at, message, data) VALUES (?1, ?2, ?3, ?4, ?5)",
+            table, owner_column
+        ),
+        params![
+            owner,
+            revision.revision as i64,
+            revision.saved_at.to_rfc3339(),
+            revision.message,
+            to_json(&revision.data, "workspace revision")?
+        ],
+    )
+    .map_err(db_error)?;
+    Ok(())
+}
+
+/// Insert a trash entry with the data of the deleted workspace
+fn insert_trash(
+    conn: &Connection,
+    entry: &TrashEntry,
+    data: &WorkspaceData,
+) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT INTO trash (trash_id, name, deleted_at, data) VALUES (?1, ?2, ?3, ?4)",
+        params![
+            entry.trash_id,
+            entry.name,
+            entry.deleted_at.to_rfc3339(),
+            to_json(data, "workspace data")?
+        ],
+    )
+    .map_err(db_error)?;
+    Ok(())
+}
+
+/// Insert or replace an execution record
+fn upsert_execution(conn: &Connection, record: &ExecutionRecord) -> Result<(), AppError> {
+    let status = serde_json::to_value(&record.status)
+        .ok()
+        .and_then(|value| value.as_str().map(str::to_string))
+        .unwrap_or_default();
+
+    conn.execute(
+        "INSERT OR REPLACE INTO executions (execution_id, status, started_at, finished_at, record)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            record.execution_id,
+            status,
+            record.started_at.to_rfc3339(),
+            record.finished_at.to_rfc3339(),
+            to_json(record, "execution record")?
+        ],
+    )
+    .map_err(db_error)?;
+    Ok(())
+}
+
+/// Serialize a value stored as a JSON column
+fn to_json<T: Serialize>(value: &T, what: &str) -> Result<String, AppError> {
+    serde_json::to_string(value)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize {}: {}", what, e)))
+}
+
+/// Parse a JSON column
+fn from_json<T: DeserializeOwned>(json: &str, what: &str) -> Result<T, AppError> {
+    serde_json::from_str(json)
+        .map_err(|e| AppError::Internal(format!("Failed to parse {}: {}", what, e)))
+}
+
+/// Parse an RFC 3339 timestamp column
+fn parse_timestamp(value: &str) -> Result<DateTime<Utc>, AppError> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|timestamp| timestamp.with_timezone(&Utc))
+        .map_err(|e| {
+            AppError::Internal(format!("Invalid timestamp '{}' in database: {}", value, e))
+        })
+}
+
+/// Report a database failure as an internal error
+fn db_error(e: rusqlite::Error) -> AppError {
+    AppError::Internal(format!("Database error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orchestrator::{ExecutionStatus, GraphDefinition};
+    use std::collections::HashMap;
+    use tempfile::TempDir;
+
+    fn workspace_data(description: &str) -> WorkspaceData {
+        serde_json::from_value(serde_json::json!({
+            "graph": { "nodes": [], "connections": [] },
+            "metadata": { "description": description }
+        }))
+        .unwrap()
+    }
+
+    fn description(data: &WorkspaceData) -> Option<&str> {
+        data.metadata.as_ref()?.description.as_deref()
+    }
+
+    fn execution(execution_id: &str) -> ExecutionRecord {
+        ExecutionRecord {
+            execution_id: execution_id.to_string(),
+            status: ExecutionStatus::Interrupted,
+            started_at: Utc::now(),
+            finished_at: Utc::now(),
+            graph: GraphDefinition::default(),
+            node_results: HashMap::new(),
+            error: Some("shutdown".to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_revisions_and_trash() {
+        let storage = SqliteStorage::open_in_memory().unwrap();
+
+        storage
+            .save_revision("demo", &workspace_data("v1"), None)
+            .await
+            .unwrap();
+        let second = storage
+            .save_revision("demo", &workspace_data("v2"), Some("second".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(second.revision, 2);
+        assert_eq!(storage.revision_numbers("demo").await.unwrap(), vec![1, 2]);
+
+        let current = storage.read_workspace("demo").await.unwrap().unwrap();
+        assert_eq!(description(&current), Some("v2"));
+
+        storage.rename_workspace("demo", "renamed").await.unwrap();
+        assert!(!storage.workspace_exists("demo").await.unwrap());
+        let first = storage.read_revision("renamed", 1).await.unwrap().unwrap();
+        assert_eq!(description(&first.data), Some("v1"));
+
+        let entry = TrashEntry {
+            trash_id: "renamed-1".to_string(),
+            name: "renamed".to_string(),
+            deleted_at: Utc::now(),
+        };
+        storage.trash_workspace(&entry).await.unwrap();
+        assert!(storage.list_workspaces().await.unwrap().is_empty());
+        assert!(storage
+            .revision_numbers("renamed")
+            .await
+            .unwrap()
+            .is_empty());
+
+        storage.restore_trash_entry(&entry).await.unwrap();
+        assert_eq!(
+            storage.revision_numbers("renamed").await.unwrap(),
+            vec![1, 2]
+        );
+        assert!(storage.list_trash().await.unwrap().is_empty());
+
+        storage.trash_workspace(&entry).await.unwrap();
+        assert_eq!(storage.purge_trash(None).await.unwrap(), 1);
+        assert!(storage
+            .read_trash_entry("renamed-1")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_import_nexus() {
+        let nexus = TempDir::new().unwrap();
+        let source = FsStorage::new(nexus.path());
+
+        source
+            .save_revision("kept", &workspace_data("v1"), None)
+            .await
+            .unwrap();
+        source
+            .save_revision("kept", &workspace_data("v2"), Some("second".to_string()))
+            .await
+            .unwrap();
+        source
+            .save_revision("deleted", &workspace_data("gone"), None)
+            .await
+            .unwrap();
+        source
+            .trash_workspace(&TrashEntry {
+                trash_id: "deleted-1".to_string(),
+                name: "deleted".to_string(),
+                deleted_at: Utc::now(),
+            })
+            .await
+            .unwrap();
+        source.record_execution(&execution("exec-1")).await.unwrap();
+
+        let storage = SqliteStorage::open(nexus.path().join("nexus.db")).unwrap();
+        let summary = storage.import_nexus(&source).await.unwrap();
+
+        assert_eq!(
+            summary,
+            ImportSummary {
+                workspaces: 1,
+                revisions: 2,
+                trash_entries: 1,
+                executions: 1,
+            }
+        );
+
+        let second = storage.read_revision("kept", 2).await.unwrap().unwrap();
+        assert_eq!(second.message.as_deref(), Some("second"));
+        assert_eq!(description(&second.data), Some("v2"));
+
+        storage
+            .restore_trash_entry(
+                &storage
+                    .read_trash_entry("deleted-1")
+                    .await
+                    .unwrap()
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(storage.revision_numbers("deleted").await.unwrap(), vec![1]);
+
+        let executions = storage.list_executions().await.unwrap();
+        assert_eq!(executions[0].execution_id, "exec-1");
+
+        // The import is one-shot: a populated database is left untouched
+        assert!(storage.import_nexus(&source).await.is_err());
+    }
+}
@@ -0,0 +1,423 @@
+//! Workspace parameters and templates
+//!
+//! A workspace can declare named parameters, each bound to one or more node
+//! input values (e.g. the `target_directory` of a file browser). Running
+//! the workspace with a parameter map fills those input values for that run
+//! only, so one pipeline can serve many input folders without copies.
+//!
+//! A parameterized workspace also acts as a template: instantiating it
+//! creates a new workspace with the parameters filled in.
+
+use crate::workspace::{RevisionInfo, SaveWorkspaceRequest, WorkspaceData, WorkspaceManager};
+use ndnm_libs::AppError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use tracing::info;
+
+/// A named workspace parameter
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WorkspaceParameter {
+    /// Parameter name, used as key in parameter maps
+    pub name: String,
+
+    /// Type of value the parameter accepts
+    #[serde(rename = "type")]
+    pub param_type: ParameterType,
+
+    /// Value used when none is given (the parameter is required without one)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<Value>,
+
+    /// Description shown to users filling in the parameter
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// Node input values set by this parameter
+    pub bindings: Vec<ParameterBinding>,
+}
+
+/// Type of a workspace parameter value
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ParameterType {
+    /// Any string
+    String,
+
+    /// Any JSON number
+    Number,
+
+    /// Whole number
+    Integer,
+
+    /// `true` or `false`
+    Boolean,
+
+    /// Non-empty file or directory path
+    Path,
+}
+
+impl ParameterType {
+    /// Whether a value is acceptable for this type
+    fn accepts(self, value: &Value) -> bool {
+        match self {
+            ParameterType::String => value.is_string(),
+            ParameterType::Number => value.is_number(),
+            ParameterType::Integer => value.is_i64() || value.is_u64(),
+            ParameterType::Boolean => value.is_boolean(),
+            ParameterType::Path => value.as_str().is_some_and(|s| !s.trim().is_empty()),
+        }
+    }
+}
+
+/// Node input value set by a parameter
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct ParameterBinding {
+    /// Instance ID of the node in the graph
+    pub instance_id: String,
+
+    /// Name of the input field on that node
+    pub field: String,
+}
+
+/// Request to run a saved workspace
+#[derive(Debug, Default, Deserialize)]
+pub struct RunWorkspaceRequest {
+    /// Optional execution ID (generated if not provided)
+    #[serde(default)]
+    pub execution_id: Option<String>,
+
+    /// Values for the workspace parameters, by name
+    #[serde(default)]
+    pub parameters: HashMap<String, Value>,
+}
+
+/// Request to create a workspace from a template
+#[derive(Debug, Deserialize)]
+pub struct InstantiateTemplateRequest {
+    /// Name of the new workspace (must not be in use)
+    pub new_name: String,
+
+    /// Values for the template parameters, by name
+    #[serde(default)]
+    pub parameters: HashMap<String, Value>,
+}
+
+/// Check the parameter declarations of a workspace
+///
+/// Parameter names must be unique, every binding must point to a node in the
+/// graph, no input value may be bound twice and defaults must match their
+/// parameter's type.
+pub fn validate_parameters(data: &WorkspaceData) -> Result<(), AppError> {
+    let instances: HashSet<&str> = data
+        .graph
+        .nodes
+        .iter()
+        .map(|n| n.instance_id.as_str())
+        .collect();
+
+    let mut names = HashSet::new();
+    let mut bound = HashSet::new();
+
+    for parameter in &data.parameters {
+        if !names.insert(parameter.name.as_str()) {
+            return Err(AppError::BadRequest(format!(
+                "Duplicate parameter '{}'",
+                parameter.name
+            )));
+        }
+
+        if parameter.bindings.is_empty() {
+            return Err(AppError::BadRequest(format!(
+                "Parameter '{}' is not bound to any node input",
+                parameter.name
+            )));
+        }
+
+        for binding in &parameter.bindings {
+            if !instances.contains(binding.instance_id.as_str()) {
+                return Err(AppError::BadRequest(format!(
+                    "Parameter '{}' is bound to unknown node '{}'",
+                    parameter.name, binding.instance_id
+                )));
+            }
+
+            if !bound.insert(binding) {
+                return Err(AppError::BadRequest(format!(
+                    "Input '{}' of node '{}' is bound to more than one parameter",
+                    binding.field, binding.instance_id
+                )));
+            }
+        }
+
+        if let Some(default) = &parameter.default {
+            check_type(parameter, default)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Resolve parameter values from a parameter map and the declared defaults
+///
+/// # Arguments
+///
+/// * `parameters` - Declared workspace parameters
+/// * `values` - Values given by the caller, by parameter name
+///
+/// # Returns
+///
+/// * `Ok(BTreeMap)` - Value of every declared parameter
+/// * `Err(AppError)` - Unknown parameter, wrong type or missing required value
+pub fn resolve_parameters(
+    parameters: &[WorkspaceParameter],
+    values: &HashMap<String, Value>,
+) -> Result<BTreeMap<String, Value>, AppError> {
+    if let Some(unknown) = values
+        .keys()
+        .find(|name| !parameters.iter().any(|p| &p.name == *name))
+    {
+        return Err(AppError::BadRequest(format!(
+            "Unknown parameter '{}'",
+            unknown
+        )));
+    }
+
+    let mut resolved = BTreeMap::new();
+
+    for parameter in parameters {
+        let value = values
+            .get(&parameter.name)
+            .or(parameter.default.as_ref())
+            .ok_or_else(|| {
+                AppError::BadRequest(format!(
+                    "Missing value for required parameter '{}'",
+                    parameter.name
+                ))
+            })?;
+
+        check_type(parameter, value)?;
+        resolved.insert(parameter.name.clone(), value.clone());
+    }
+
+    Ok(resolved)
+}
+
+/// Fill the bound node input values of a workspace with parameter values
+///
+/// # Arguments
+///
+/// * `data` - Workspace whose graph is updated
+/// * `values` - Values given by the caller, by parameter name
+///
+/// # Returns
+///
+/// * `Ok(BTreeMap)` - The value used for every parameter
+/// * `Err(AppError)` - The parameters or values are invalid
+pub fn apply_parameters(
+    data: &mut WorkspaceData,
+    values: &HashMap<String, Value>,
+) -> Result<BTreeMap<String, Value>, AppError> {
+    validate_parameters(data)?;
+    let resolved = resolve_parameters(&data.parameters, values)?;
+
+    for parameter in &data.parameters {
+        let value = &resolved[&parameter.name];
+
+        for binding in &parameter.bindings {
+            if let Some(node) = data
+                .graph
+                .nodes
+                .iter_mut()
+                .find(|n| n.instance_id == binding.instance_id)
+            {
+                node.input_values
+                    .insert(binding.field.clone(), value.clone());
+            }
+        }
+    }
+
+    Ok(resolved)
+}
+
+/// Create a new workspace from a template with its parameters filled in
+///
+/// The new workspace is a plain workspace: parameter declarations and the
+/// template's last run are not copied.
+///
+/// # Arguments
+///
+/// * `manager` - Workspace manager holding the template
+/// * `name` - Template workspace name
+/// * `request` - Name of the new workspace and parameter values
+pub async fn instantiate_template(
+    manager: &WorkspaceManager,
+    name: &str,
+    request: InstantiateTemplateRequest,
+) -> Result<RevisionInfo, AppError> {
+    if manager.workspace_exists(&request.new_name).await? {
+        return Err(AppError::BadRequest(format!(
+            "Workspace '{}' already exists",
+            request.new_name
+        )));
+    }
+
+    let mut data = manager.load_workspace(name).await?;
+
+    if data.parameters.is_empty() {
+        return Err(AppError::BadRequest(format!(
+            "Workspace '{}' has no parameters to fill in",
+            name
+        )));
+    }
+
+    apply_parameters(&mut data, &request.parameters)?;
+    data.parameters.clear();
+    if let Some(metadata) = &mut data.metadata {
+        metadata.last_run = None;
+    }
+
+    // Expecting revision 0 rejects names with leftover history
+    let revision = manager
+        .save_workspace(SaveWorkspaceRequest {
+            name: request.new_name.clone(),
+            data,
+            message: Some(format!("Instantiated from template '{}'", name)),
+            expected_revision: Some(0),
+            must_exist: false,
+        })
+        .await?;
+
+    info!(
+        "Workspace '{}' instantiated from template '{}'",
+        request.new_name, name
+    );
+    Ok(revision)
+}
+
+/// Reject a value that does not match its parameter's type
+fn check_type(parameter: &WorkspaceParameter, value: &Value) -> Result<(), AppError> {
+    if parameter.param_type.accepts(value) {
+        return Ok(());
+    }
+
+    Err(AppError::BadRequest(format!(
+        "Parameter '{}' expects a {:?} value, got {}",
+        parameter.name, parameter.param_type, value
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn template() -> WorkspaceData {
+        serde_json::from_value(json!({
+            "graph": {
+                "nodes": [{
+                    "instance_id": "browser",
+                    "node_type_id": "browser_type",
+                    "input_values": { "target_directory": "./default" }
+                }],
+                "connections": []
+            },
+            "parameters": [
+                {
+                    "name": "folder",
+                    "type": "path",
+                    "description": "Folder to browse",
+                    "bindings": [{ "instance_id": "browser", "field": "target_directory" }]
+                },
+                {
+                    "name": "recursive",
+                    "type": "boolean",
+                    "default": false,
+                    "bindings": [{ "instance_id": "browser", "field": "recursive" }]
+                }
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_apply_parameters() {
+        let mut data = template();
+        let values = HashMap::from([("folder".to_string(), json!("./photos"))]);
+
+        let resolved = apply_parameters(&mut data, &values).unwrap();
+        assert_eq!(resolved["recursive"], json!(false));
+
+        let inputs = &data.graph.nodes[0].input_values;
+        assert_eq!(inputs["target_directory"], json!("./photos"));
+        assert_eq!(inputs["recursive"], json!(false));
+    }
+
+    #[test]
+    fn test_rejects_invalid_values() {
+        // Missing required parameter
+        assert!(apply_parameters(&mut template(), &HashMap::new()).is_err());
+
+        // Wrong type
+        let values = HashMap::from([("folder".to_string(), json!(3))]);
+        assert!(apply_parameters(&mut template(), &values).is_err());
+
+        // Unknown parameter
+        let values = HashMap::from([
+            ("folder".to_string(), json!("./photos")),
+            ("colour".to_string(), json!("red")),
+        ]);
+        assert!(apply_parameters(&mut template(), &values).is_err());
+
+        // Binding to a node that is not in the graph
+        let mut data = template();
+        data.parameters[0].bindings[0].instance_id = "missing".to_string();
+        assert!(validate_parameters(&data).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_instantiate_template() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = WorkspaceManager::new(temp_dir.path());
+
+        manager
+            .save_workspace(SaveWorkspaceRequest {
+                name: "browse".to_string(),
+                data: template(),
+                message: None,
+                expected_revision: None,
+                must_exist: false,
+            })
+            .await
+            .unwrap();
+
+        let request = InstantiateTemplateRequest {
+            new_name: "browse-photos".to_string(),
+            parameters: HashMap::from([("folder".to_string(), json!("./photos"))]),
+        };
+        let revision = instantiate_template(&manager, "browse", request)
+            .await
+            .unwrap();
+        assert_eq!(revision.revision, 1);
+
+        let instance = manager.load_workspace("browse-photos").await.unwrap();
+        assert!(instance.parameters.is_empty());
+        assert_eq!(
+            instance.graph.nodes[0].input_values["target_directory"],
+            json!("./photos")
+        );
+
+        // The template itself is left untouched
+        let template = manager.load_workspace("browse").await.unwrap();
+        assert_eq!(template.parameters.len(), 2);
+
+        let request = InstantiateTemplateRequest {
+            new_name: "browse-photos".to_string(),
+            parameters: HashMap::from([("folder".to_string(), json!("./other"))]),
+        };
+        assert!(instantiate_template(&manager, "browse", request)
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,278 @@
+//! Workspace validation module
+//!
+//! Checks a saved graph against the current node registry, so workspaces that
+//! reference removed node types, handles or input fields are reported on load
+//! instead of failing later during execution
+
+use crate::orchestrator::GraphDefinition;
+use crate::registry::{NodeInfo, NodeRegistry};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Result of validating a graph against the node registry
+#[derive(Debug, Default, Serialize)]
+pub struct ValidationReport {
+    /// Whether the graph matches the current registry
+    pub valid: bool,
+
+    /// Nodes whose type is no longer registered
+    pub missing_node_types: Vec<MissingNodeType>,
+
+    /// Connections to handles the node type no longer exposes
+    pub unknown_handles: Vec<UnknownHandle>,
+
+    /// `input_values` entries for fields the node type no longer declares
+    pub unknown_fields: Vec<UnknownField>,
+
+    /// Connections referencing node instances that are not in the graph
+    pub dangling_connections: Vec<DanglingConnection>,
+}
+
+/// A node instance whose type is missing from the registry
+#[derive(Debug, Serialize)]
+pub struct MissingNodeType {
+    /// Node instance ID
+    pub instance_id: String,
+
+    /// Node type ID that could not be found
+    pub node_type_id: String,
+}
+
+/// Direction of a handle referenced by a connection
+#[derive(Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HandleDirection {
+    /// Handle receiving data (target of a connection)
+    Input,
+
+    /// Handle providing data (source of a connection)
+    Output,
+}
+
+/// A connection endpoint whose handle does not exist anymore
+#[derive(Debug, Serialize)]
+pub struct UnknownHandle {
+    /// Node instance ID
+    pub instance_id: String,
+
+    /// Handle name referenced by the connection
+    pub handle: String,
+
+    /// Whether the handle is used as an input or an output
+    pub direction: HandleDirection,
+}
+
+/// An input value for a field the node type no longer declares
+#[derive(Debug, Serialize)]
+pub struct UnknownField {
+    /// Node instance ID
+    pub instance_id: String,
+
+    /// Field name found in `input_values`
+    pub field: String,
+}
+
+/// A connection whose source or target instance is not in the graph
+#[derive(Debug, Serialize)]
+pub struct DanglingConnection {
+    /// Source node instance ID
+    pub from_node: String,
+
+    /// Target node instance ID
+    pub to_node: String,
+}
+
+/// Validate a graph against the node registry
+///
+/// Handles are matched against each section's slot template: a handle is
+/// valid when it equals the template name or extends it with a `_<suffix>`
+/// (e.g. `copy_input_0` or `internal_input_notes.txt` for `copy_input` and
+/// `internal_input`), since dynamic sections generate names at runtime.
+pub fn validate_graph(graph: &GraphDefinition, registry: &NodeRegistry) -> ValidationReport {
+    let mut report = ValidationReport::default();
+
+    // Resolve node types once; `None` marks instances with a missing type
+    let mut node_types: HashMap<&str, Option<NodeInfo>> = HashMap::new();
+
+    for node in &graph.nodes {
+        let node_info = registry.get_node(&node.node_type_id);
+
+        match &node_info {
+            None => report.missing_node_types.push(MissingNodeType {
+                instance_id: node.instance_id.clone(),
+                node_type_id: node.node_type_id.clone(),
+            }),
+            Some(info) => {
+                let mut fields: Vec<&String> = node
+                    .input_values
+                    .keys()
+                    .filter(|field| !info.config.input_fields.iter().any(|f| &f.name == *field))
+                    .collect();
+                fields.sort();
+
+                report
+                    .unknown_fields
+                    .extend(fields.into_iter().map(|field| UnknownField {
+                        instance_id: node.instance_id.clone(),
+                        field: field.clone(),
+                    }));
+            }
+        }
+
+        node_types.insert(node.instance_id.as_str(), node_info);
+    }
+
+    for conn in &graph.connections {
+        let (Some(from_type), Some(to_type)) = (
+            node_types.get(conn.from_node.as_str()),
+            node_types.get(conn.to_node.as_str()),
+        ) else {
+            report.dangling_connections.push(DanglingConnection {
+                from_node: conn.from_node.clone(),
+                to_node: conn.to_node.clone(),
+            });
+            continue;
+        };
+
+        if let Some(info) = from_type
+            && !has_handle(info, &conn.from_handle, HandleDirection::Output)
+        {
+            report.unknown_handles.push(UnknownHandle {
+                instance_id: conn.from_node.clone(),
+                handle: conn.from_handle.clone(),
+                direction: HandleDirection::Output,
+            });
+        }
+
+        if let Some(info) = to_type
+            && !has_handle(info, &conn.to_handle, HandleDirection::Input)
+        {
+            report.unknown_handles.push(UnknownHandle {
+                instance_id: conn.to_node.clone(),
+                handle: conn.to_handle.clone(),
+                direction: HandleDirection::Input,
+            });
+        }
+    }
+
+    report.valid = report.missing_node_types.is_empty()
+        && report.unknown_handles.is_empty()
+        && report.unknown_fields.is_empty()
+        && report.dangling_connections.is_empty();
+
+    report
+}
+
+/// Whether a node type exposes a handle in the given direction
+fn has_handle(info: &NodeInfo, handle: &str, direction: HandleDirection) -> bool {
+    info.config.sections.iter().any(|section| {
+        let base = match direction {
+            HandleDirection::Input => &section.slot_template.input.name,
+            HandleDirection::Output => &section.slot_template.output.name,
+        };
+
+        handle == base
+            || handle
+                .strip_prefix(base.as_str())
+                .is_some_and(|rest| rest.len() > 1 && rest.starts_with('_'))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orchestrator::{Connection, GraphNode};
+    use ndnm_libs::load_config;
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    fn registry() -> NodeRegistry {
+        let config =
+            load_config("../nodes/node-file-browser/config.yaml").expect("file browser config");
+
+        let mut registry = NodeRegistry::new();
+        registry
+            .register(NodeInfo {
+                node_id: config.node_id_hash.clone(),
+                config,
+                path: PathBuf::from("/test"),
+                port: 3001,
+                is_running: false,
+            })
+            .unwrap();
+        registry
+    }
+
+    fn browser(instance_id: &str) -> GraphNode {
+        let mut input_values = HashMap::new();
+        input_values.insert("target_directory".to_string(), json!("./files"));
+
+        GraphNode {
+            instance_id: instance_id.to_string(),
+            node_type_id: "hash_sha256_de_viniciusxpb_node-file-browser".to_string(),
+            input_values,
+            inputs: HashMap::new(),
+            position: None,
+        }
+    }
+
+    fn connection(from_handle: &str, to_handle: &str) -> Connection {
+        Connection {
+            from_node: "a".to_string(),
+            from_handle: from_handle.to_string(),
+            to_node: "b".to_string(),
+            to_handle: to_handle.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_valid_graph() {
+        let graph = GraphDefinition {
+            nodes: vec![browser("a"), browser("b")],
+            connections: vec![
+                connection("copied_output_0", "copy_input_0"),
+                connection("internal_output_notes.txt", "internal_input_notes.txt"),
+            ],
+        };
+
+        let report = validate_graph(&graph, &registry());
+        assert!(report.valid);
+    }
+
+    #[test]
+    fn test_reports_stale_references() {
+        let mut stale = browser("c");
+        stale.node_type_id = "removed_node".to_string();
+
+        let mut old_field = browser("b");
+        old_field
+            .input_values
+            .insert("old_setting".to_string(), json!(true));
+
+        let graph = GraphDefinition {
+            nodes: vec![browser("a"), old_field, stale],
+            connections: vec![
+                connection("renamed_output_0", "copy_input_0"),
+                connection("copied_output_0", "copy_inputs"),
+                Connection {
+                    from_node: "a".to_string(),
+                    from_handle: "copied_output_0".to_string(),
+                    to_node: "ghost".to_string(),
+                    to_handle: "copy_input_0".to_string(),
+                },
+            ],
+        };
+
+        let report = validate_graph(&graph, &registry());
+
+        assert!(!report.valid);
+        assert_eq!(report.missing_node_types.len(), 1);
+        assert_eq!(report.missing_node_types[0].instance_id, "c");
+        assert_eq!(report.unknown_fields.len(), 1);
+        assert_eq!(report.unknown_fields[0].field, "old_setting");
+        assert_eq!(report.unknown_handles.len(), 2);
+        assert_eq!(report.unknown_handles[0].direction, HandleDirection::Output);
+        assert_eq!(report.unknown_handles[1].handle, "copy_inputs");
+        assert_eq!(report.dangling_connections.len(), 1);
+    }
+}
@@ -1,66 +1,132 @@
 //! Workspace persistence module
 //!
-//! Manages saving and loading of workspace data to/from the nexus directory
+//! Manages saving and loading of workspace data through a [`Storage`]
+//! backend (loose JSON files in the nexus directory by default, or an
+//! embedded SQLite database; see [`crate::storage`]).
+//!
+//! Workspace names are normalized to letters, digits, `-` and `_` before
+//! they reach the storage backend, so every backend stores a workspace
+//! under the same name.
+//!
+//! Every save is kept as a numbered revision, so older versions can be
+//! listed, loaded, restored and compared.
+//!
+//! Deleted workspaces (with their history) are moved to the trash, from
+//! where they can be restored or purged for good.
+//!
+//! Workspace data carries a `schema_version`; graphs are stored as typed
+//! [`GraphDefinition`]s so malformed data is rejected when loaded.
+//!
+//! Saves can carry the revision the client last loaded (optimistic locking);
+//! if the workspace changed since, the save is rejected with a conflict.
+//!
+//! Workspaces can be searched by tag, node type or description through
+//! [`WorkspaceManager::search_workspaces`], which returns metadata summaries.
+//!
+//! Workspaces may declare parameters bound to node input values; see
+//! [`crate::template`].
 
+use crate::diff::{diff_graphs, GraphDiff};
+use crate::migration::MigrationReport;
+use crate::orchestrator::{ExecutionStatus, GraphDefinition};
+use crate::storage::Storage;
+use crate::template::{validate_parameters, WorkspaceParameter};
+use crate::validation::ValidationReport;
+use chrono::{DateTime, Utc};
 use ndnm_libs::AppError;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
-use std::fs;
-use std::path::{Path, PathBuf};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use tokio::sync::Mutex;
 use tracing::{info, warn};
 
+/// Current version of the workspace file schema
+pub const CURRENT_SCHEMA_VERSION: u32 = 1;
+
 /// Workspace manager for persistence
 pub struct WorkspaceManager {
-    /// Directory where workspaces are stored
-    nexus_dir: PathBuf,
+    /// Backend where workspaces are stored
+    storage: Arc<dyn Storage>,
+
+    /// Serializes writes so concurrent saves get distinct revision numbers
+    write_lock: Mutex<()>,
 }
 
 impl WorkspaceManager {
-    /// Create a new workspace manager
+    /// Create a workspace manager storing files in a nexus directory (used by tests)
     ///
     /// # Arguments
     ///
     /// * `nexus_dir` - Path to the nexus directory
-    pub fn new<P: AsRef<Path>>(nexus_dir: P) -> Self {
-        let nexus_dir = nexus_dir.as_ref().to_path_buf();
+    #[cfg(test)]
+    pub fn new<P: AsRef<std::path::Path>>(nexus_dir: P) -> Self {
+        Self::with_storage(Arc::new(crate::storage::FsStorage::new(nexus_dir)))
+    }
 
-        // Create nexus directory if it doesn't exist
-        if !nexus_dir.exists() {
-            if let Err(e) = fs::create_dir_all(&nexus_dir) {
-                warn!("Failed to create nexus directory: {}", e);
-            }
+    /// Create a workspace manager on top of any storage backend
+    ///
+    /// # Arguments
+    ///
+    /// * `storage` - Backend holding workspaces, revisions and the trash
+    pub fn with_storage(storage: Arc<dyn Storage>) -> Self {
+        Self {
+            storage,
+            write_lock: Mutex::new(()),
         }
-
-        Self { nexus_dir }
     }
 
     /// Save a workspace
     ///
+    /// Overwrites the current version and records the save as a new revision.
+    ///
     /// # Arguments
     ///
     /// * `request` - Save workspace request
     ///
     /// # Returns
     ///
-    /// * `Ok(())` if saved successfully
+    /// * `Ok(RevisionInfo)` - The revision created by this save
     /// * `Err(AppError)` if save failed
-    pub async fn save_workspace(&self, request: SaveWorkspaceRequest) -> Result<(), AppError> {
-        let filename = format!("{}.json", sanitize_filename(&request.name));
-        let file_path = self.nexus_dir.join(&filename);
+    pub async fn save_workspace(
+        &self,
+        mut request: SaveWorkspaceRequest,
+    ) -> Result<RevisionInfo, AppError> {
+        request.name = workspace_name(&request.name);
+        check_schema_version(request.data.schema_version)?;
+        validate_parameters(&request.data)?;
 
-        info!("Saving workspace to: {:?}", file_path);
+        let _guard = self.write_lock.lock().await;
 
-        // Serialize to JSON
-        let json = serde_json::to_string_pretty(&request.data).map_err(|e| {
-            AppError::Internal(format!("Failed to serialize workspace data: {}", e))
-        })?;
+        if request.must_exist && !self.storage.workspace_exists(&request.name).await? {
+            return Err(AppError::Conflict(format!(
+                "Workspace '{}' does not exist",
+                request.name
+            )));
+        }
 
-        // Write to file
-        fs::write(&file_path, json)
-            .map_err(|e| AppError::Internal(format!("Failed to write workspace file: {}", e)))?;
+        if let Some(expected) = request.expected_revision {
+            let current = self.latest_revision(&request.name).await?;
+            if current != expected {
+                return Err(AppError::Conflict(format!(
+                    "Workspace '{}' is at revision {}, expected revision {}",
+                    request.name, current, expected
+                )));
+            }
+        }
 
-        info!("Workspace '{}' saved successfully", request.name);
-        Ok(())
+        info!("Saving workspace '{}'", request.name);
+
+        let revision = self
+            .storage
+            .save_revision(&request.name, &request.data, request.message)
+            .await?;
+
+        info!(
+            "Workspace '{}' saved successfully (revision {})",
+            request.name, revision.revision
+        );
+        Ok(revision)
     }
 
     /// Load a workspace
@@ -74,28 +140,219 @@ impl WorkspaceManager {
     /// * `Ok(WorkspaceData)` - Loaded workspace data
     /// * `Err(AppError)` - Failed to load
     pub async fn load_workspace(&self, name: &str) -> Result<WorkspaceData, AppError> {
-        let filename = format!("{}.json", sanitize_filename(name));
-        let file_path = self.nexus_dir.join(&filename);
+        let name = &workspace_name(name);
+        info!("Loading workspace '{}'", name);
+
+        let data = self.read_workspace(name).await?;
+
+        info!("Workspace '{}' loaded successfully", name);
+        Ok(data)
+    }
+
+    /// Load a workspace together with its current revision number
+    ///
+    /// Both are read under the write lock, so the revision always matches
+    /// the returned data. Workspaces without revisions report revision 0.
+    pub async fn load_current(&self, name: &str) -> Result<(u64, WorkspaceData), AppError> {
+        let name = &workspace_name(name);
+        let _guard = self.write_lock.lock().await;
+
+        let data = self.read_workspace(name).await?;
+        let revision = self.latest_revision(name).await?;
+
+        Ok((revision, data))
+    }
+
+    /// Get the current revision number of a workspace (0 when it has none)
+    pub async fn current_revision(&self, name: &str) -> Result<u64, AppError> {
+        let name = &workspace_name(name);
+        self.latest_revision(name).await
+    }
+
+    /// Highest revision number of a workspace, or 0 when it has none
+    async fn latest_revision(&self, name: &str) -> Result<u64, AppError> {
+        Ok(self
+            .storage
+            .revision_numbers(name)
+            .await?
+            .last()
+            .copied()
+            .unwrap_or(0))
+    }
+
+    /// Read the current version of a workspace and check its schema version
+    async fn read_workspace(&self, name: &str) -> Result<WorkspaceData, AppError> {
+        let data = self
+            .storage
+            .read_workspace(name)
+            .await?
+            .ok_or_else(|| AppError::BadRequest(format!("Workspace '{}' not found", name)))?;
+
+        check_schema_version(data.schema_version)?;
+
+        Ok(data)
+    }
+
+    /// Search workspaces and summarize their metadata
+    ///
+    /// Workspaces that cannot be read are skipped with a warning.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - Filters and sort order
+    ///
+    /// # Returns
+    ///
+    /// Summaries of the matching workspaces
+    pub async fn search_workspaces(
+        &self,
+        query: &WorkspaceQuery,
+    ) -> Result<Vec<WorkspaceSummary>, AppError> {
+        let mut summaries = Vec::new();
+
+        for name in self.list_workspaces().await? {
+            let data = match self.read_workspace(&name).await {
+                Ok(data) => data,
+                Err(e) => {
+                    warn!("Skipping unreadable workspace '{}': {}", name, e);
+                    continue;
+                }
+            };
 
-        if !file_path.exists() {
+            let modified_at = self.storage.modified_at(&name).await.unwrap_or_default();
+            let summary = WorkspaceSummary::new(name.clone(), &data, modified_at);
+            if query.matches(&summary) {
+                summaries.push(summary);
+            }
+        }
+
+        // Names are already sorted by `list_workspaces`
+        if query.sort == WorkspaceSort::Modified {
+            summaries.sort_by_key(|s| Reverse(s.modified_at));
+        }
+
+        Ok(summaries)
+    }
+
+    /// Record the outcome of the latest execution of a workspace
+    ///
+    /// Only the current version is updated; no revision is created, since
+    /// running a workspace does not change its graph.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Workspace name
+    /// * `last_run` - Outcome of the execution
+    pub async fn record_last_run(&self, name: &str, last_run: LastRun) -> Result<(), AppError> {
+        let name = &workspace_name(name);
+        let _guard = self.write_lock.lock().await;
+
+        let mut data = self.read_workspace(name).await?;
+        data.metadata
+            .get_or_insert_with(WorkspaceMetadata::default)
+            .last_run = Some(last_run);
+
+        self.storage.write_workspace(name, &data).await
+    }
+
+    /// List the revisions of a workspace, oldest first
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Workspace name
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<RevisionInfo>)` - Revisions without their data
+    /// * `Err(AppError)` - Workspace has no history or it could not be read
+    pub async fn list_revisions(&self, name: &str) -> Result<Vec<RevisionInfo>, AppError> {
+        let name = &workspace_name(name);
+        let numbers = self.storage.revision_numbers(name).await?;
+
+        if numbers.is_empty() {
             return Err(AppError::BadRequest(format!(
-                "Workspace '{}' not found",
+                "Workspace '{}' has no revisions",
                 name
             )));
         }
 
-        info!("Loading workspace from: {:?}", file_path);
+        let mut revisions = Vec::with_capacity(numbers.len());
+        for revision in numbers {
+            revisions.push(self.load_revision(name, revision).await?.info());
+        }
+        Ok(revisions)
+    }
 
-        // Read file
-        let contents = fs::read_to_string(&file_path)
-            .map_err(|e| AppError::Internal(format!("Failed to read workspace file: {}", e)))?;
+    /// Load a specific revision of a workspace
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Workspace name
+    /// * `revision` - Revision number
+    pub async fn load_revision(
+        &self,
+        name: &str,
+        revision: u64,
+    ) -> Result<WorkspaceRevision, AppError> {
+        let name = &workspace_name(name);
+        self.storage
+            .read_revision(name, revision)
+            .await?
+            .ok_or_else(|| {
+                AppError::BadRequest(format!(
+                    "Revision {} of workspace '{}' not found",
+                    revision, name
+                ))
+            })
+    }
 
-        // Parse JSON
-        let data: WorkspaceData = serde_json::from_str(&contents)
-            .map_err(|e| AppError::Internal(format!("Failed to parse workspace data: {}", e)))?;
+    /// Restore a previous revision of a workspace
+    ///
+    /// The restored data becomes the current version and is recorded as a new
+    /// revision, so restoring never discards history.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Workspace name
+    /// * `revision` - Revision number to restore
+    pub async fn restore_revision(&self, name: &str, revision: u64) -> Result<RevisionInfo, AppError> {
+        let name = &workspace_name(name);
+        let restored = self.load_revision(name, revision).await?;
 
-        info!("Workspace '{}' loaded successfully", name);
-        Ok(data)
+        info!("Restoring workspace '{}' to revision {}", name, revision);
+
+        self.save_workspace(SaveWorkspaceRequest {
+            name: name.to_string(),
+            data: restored.data,
+            message: Some(format!("Restored from revision {}", revision)),
+            expected_revision: None,
+            must_exist: false,
+        })
+        .await
+    }
+
+    /// Compute the structural diff between two revisions of a workspace
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Workspace name
+    /// * `from` - Older revision number
+    /// * `to` - Newer revision number
+    pub async fn diff_revisions(
+        &self,
+        name: &str,
+        from: u64,
+        to: u64,
+    ) -> Result<RevisionDiff, AppError> {
+        let name = &workspace_name(name);
+        let from_graph = self.load_revision(name, from).await?.data.graph;
+        let to_graph = self.load_revision(name, to).await?.data.graph;
+
+        Ok(RevisionDiff {
+            from,
+            to,
+            diff: diff_graphs(&from_graph, &to_graph),
+        })
     }
 
     /// List all available workspaces
@@ -105,64 +362,204 @@ impl WorkspaceManager {
     /// * `Ok(Vec<String>)` - List of workspace names
     /// * `Err(AppError)` - Failed to list
     pub async fn list_workspaces(&self) -> Result<Vec<String>, AppError> {
-        if !self.nexus_dir.exists() {
-            return Ok(Vec::new());
-        }
-
-        let mut workspaces = Vec::new();
-
-        let entries = fs::read_dir(&self.nexus_dir).map_err(|e| {
-            AppError::Internal(format!("Failed to read nexus directory: {}", e))
-        })?;
-
-        for entry in entries {
-            if let Ok(entry) = entry {
-                let path = entry.path();
-                if path.is_file() {
-                    if let Some(ext) = path.extension() {
-                        if ext == "json" {
-                            if let Some(stem) = path.file_stem() {
-                                if let Some(name) = stem.to_str() {
-                                    workspaces.push(name.to_string());
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        }
-
-        workspaces.sort();
-        Ok(workspaces)
+        self.storage.list_workspaces().await
     }
 
     /// Delete a workspace
     ///
+    /// The workspace and its revisions are moved to the trash, from where
+    /// they can be restored with [`WorkspaceManager::restore_from_trash`].
+    ///
     /// # Arguments
     ///
     /// * `name` - Workspace name to delete
     ///
     /// # Returns
     ///
-    /// * `Ok(())` if deleted successfully
+    /// * `Ok(TrashEntry)` - The trash entry holding the deleted workspace
     /// * `Err(AppError)` if deletion failed
-    pub async fn delete_workspace(&self, name: &str) -> Result<(), AppError> {
-        let filename = format!("{}.json", sanitize_filename(name));
-        let file_path = self.nexus_dir.join(&filename);
+    pub async fn delete_workspace(&self, name: &str) -> Result<TrashEntry, AppError> {
+        let name = &workspace_name(name);
+        let _guard = self.write_lock.lock().await;
+
+        if !self.storage.workspace_exists(name).await? {
+            return Err(AppError::BadRequest(format!(
+                "Workspace '{}' not found",
+                name
+            )));
+        }
+
+        let deleted_at = Utc::now();
+        let entry = TrashEntry {
+            trash_id: format!(
+                "{}-{}",
+                sanitize_filename(name),
+                deleted_at.format("%Y%m%d%H%M%S%3f")
+            ),
+            name: name.to_string(),
+            deleted_at,
+        };
 
-        if !file_path.exists() {
+        self.storage.trash_workspace(&entry).await?;
+
+        info!(
+            "Workspace '{}' moved to trash as '{}'",
+            name, entry.trash_id
+        );
+        Ok(entry)
+    }
+
+    /// Rename a workspace, keeping its revisions
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Current workspace name
+    /// * `new_name` - New workspace name (must not be in use)
+    pub async fn rename_workspace(&self, name: &str, new_name: &str) -> Result<(), AppError> {
+        let (name, new_name) = (&workspace_name(name), &workspace_name(new_name));
+        let _guard = self.write_lock.lock().await;
+
+        if !self.storage.workspace_exists(name).await? {
             return Err(AppError::BadRequest(format!(
                 "Workspace '{}' not found",
                 name
             )));
         }
 
-        fs::remove_file(&file_path)
-            .map_err(|e| AppError::Internal(format!("Failed to delete workspace file: {}", e)))?;
+        if self.name_in_use(new_name).await? {
+            return Err(AppError::BadRequest(format!(
+                "Workspace '{}' already exists",
+                new_name
+            )));
+        }
 
-        info!("Workspace '{}' deleted successfully", name);
+        self.storage.rename_workspace(name, new_name).await?;
+
+        info!("Workspace '{}' renamed to '{}'", name, new_name);
         Ok(())
     }
+
+    /// Duplicate a workspace under a new name
+    ///
+    /// The copy starts its own history with a single revision.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Workspace to copy
+    /// * `new_name` - Name of the copy (must not be in use)
+    pub async fn duplicate_workspace(
+        &self,
+        name: &str,
+        new_name: &str,
+    ) -> Result<RevisionInfo, AppError> {
+        let (name, new_name) = (&workspace_name(name), &workspace_name(new_name));
+        let _guard = self.write_lock.lock().await;
+
+        if self.name_in_use(new_name).await? {
+            return Err(AppError::BadRequest(format!(
+                "Workspace '{}' already exists",
+                new_name
+            )));
+        }
+
+        let data = self.read_workspace(name).await?;
+
+        let revision = self
+            .storage
+            .save_revision(new_name, &data, Some(format!("Duplicated from '{}'", name)))
+            .await?;
+
+        info!("Workspace '{}' duplicated as '{}'", name, new_name);
+        Ok(revision)
+    }
+
+    /// List deleted workspaces, most recently deleted first
+    pub async fn list_trash(&self) -> Result<Vec<TrashEntry>, AppError> {
+        let mut trash = self.storage.list_trash().await?;
+        trash.sort_by_key(|entry| Reverse(entry.deleted_at));
+        Ok(trash)
+    }
+
+    /// Restore a deleted workspace from the trash
+    ///
+    /// # Arguments
+    ///
+    /// * `trash_id` - Identifier of the trash entry
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(TrashEntry)` - The restored entry
+    /// * `Err(AppError)` - Entry not found or a workspace with the same name exists
+    pub async fn restore_from_trash(&self, trash_id: &str) -> Result<TrashEntry, AppError> {
+        let _guard = self.write_lock.lock().await;
+
+        let entry = self.trash_entry(trash_id).await?;
+
+        if self.name_in_use(&entry.name).await? {
+            return Err(AppError::BadRequest(format!(
+                "Workspace '{}' already exists; rename or delete it before restoring",
+                entry.name
+            )));
+        }
+
+        self.storage.restore_trash_entry(&entry).await?;
+
+        info!("Workspace '{}' restored from trash", entry.name);
+        Ok(entry)
+    }
+
+    /// Permanently delete trash entries
+    ///
+    /// # Arguments
+    ///
+    /// * `trash_id` - Entry to purge, or `None` to empty the whole trash
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(usize)` - Number of entries purged
+    pub async fn purge_trash(&self, trash_id: Option<&str>) -> Result<usize, AppError> {
+        let _guard = self.write_lock.lock().await;
+
+        if let Some(trash_id) = trash_id {
+            self.trash_entry(trash_id).await?;
+        }
+
+        let purged = self.storage.purge_trash(trash_id).await?;
+
+        info!("Purged {} trash entries", purged);
+        Ok(purged)
+    }
+
+    /// Check whether a workspace with the given name exists
+    pub async fn workspace_exists(&self, name: &str) -> Result<bool, AppError> {
+        let name = &workspace_name(name);
+        self.storage.workspace_exists(name).await
+    }
+
+    /// Whether a name is taken by a workspace or by leftover revisions
+    async fn name_in_use(&self, name: &str) -> Result<bool, AppError> {
+        Ok(self.storage.workspace_exists(name).await?
+            || !self.storage.revision_numbers(name).await?.is_empty())
+    }
+
+    /// Look up an existing trash entry
+    async fn trash_entry(&self, trash_id: &str) -> Result<TrashEntry, AppError> {
+        self.storage
+            .read_trash_entry(trash_id)
+            .await?
+            .ok_or_else(|| AppError::BadRequest(format!("Trash entry '{}' not found", trash_id)))
+    }
+}
+
+/// Reject workspace data written with a newer schema than this Hermes supports
+fn check_schema_version(schema_version: u32) -> Result<(), AppError> {
+    if schema_version > CURRENT_SCHEMA_VERSION {
+        return Err(AppError::BadRequest(format!(
+            "Workspace schema version {} is newer than the supported version {}",
+            schema_version, CURRENT_SCHEMA_VERSION
+        )));
+    }
+    Ok(())
 }
 
 /// Request to save a workspace
@@ -173,21 +570,61 @@ pub struct SaveWorkspaceRequest {
 
     /// Workspace data to save
     pub data: WorkspaceData,
+
+    /// Optional message describing this revision
+    #[serde(default)]
+    pub message: Option<String>,
+
+    /// Revision the client last loaded
+    ///
+    /// When set, the save is rejected with a conflict if the workspace has
+    /// been saved since. Use 0 to require that the workspace has no revisions.
+    #[serde(default)]
+    pub expected_revision: Option<u64>,
+
+    /// Whether the save is rejected with a conflict if the workspace does
+    /// not exist yet (set from `If-Match: *`)
+    #[serde(skip)]
+    pub must_exist: bool,
 }
 
 /// Workspace data structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WorkspaceData {
+    /// Version of the workspace schema this data was written with
+    ///
+    /// Files saved before the field existed are read as version 1.
+    #[serde(default = "default_schema_version")]
+    pub schema_version: u32,
+
     /// Graph definition (nodes and connections)
-    pub graph: Value,
+    pub graph: GraphDefinition,
+
+    /// Node config version each referenced node type was saved with
+    ///
+    /// Node types missing from the map are treated as version 1.
+    #[serde(default)]
+    pub node_versions: BTreeMap<String, u32>,
+
+    /// Named parameters bound to node input values
+    ///
+    /// Workspaces with parameters can be run with a parameter map and used
+    /// as templates for new workspaces.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parameters: Vec<WorkspaceParameter>,
 
     /// Metadata about the workspace
     #[serde(skip_serializing_if = "Option::is_none")]
     pub metadata: Option<WorkspaceMetadata>,
 }
 
+/// Schema version assumed for workspace files without one
+fn default_schema_version() -> u32 {
+    1
+}
+
 /// Metadata about a workspace
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct WorkspaceMetadata {
     /// When the workspace was created
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -204,17 +641,294 @@ pub struct WorkspaceMetadata {
     /// Description of the workspace
     #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
+
+    /// Free-form tags used to group and filter workspaces
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
+    /// User currently responsible for the workspace
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+
+    /// Outcome of the latest execution, recorded by Hermes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_run: Option<LastRun>,
+}
+
+/// Outcome of the latest execution of a workspace
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LastRun {
+    /// Execution ID
+    pub execution_id: String,
+
+    /// Final status of the execution
+    pub status: ExecutionStatus,
+
+    /// When the execution finished
+    pub finished_at: DateTime<Utc>,
+}
+
+/// A saved revision of a workspace
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceRevision {
+    /// Revision number (starts at 1, increases with every save)
+    pub revision: u64,
+
+    /// When this revision was saved
+    pub saved_at: DateTime<Utc>,
+
+    /// Optional message describing the revision
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+
+    /// Workspace data at this revision
+    pub data: WorkspaceData,
+}
+
+impl WorkspaceRevision {
+    /// Summary of this revision without its data
+    pub fn info(&self) -> RevisionInfo {
+        RevisionInfo {
+            revision: self.revision,
+            saved_at: self.saved_at,
+            message: self.message.clone(),
+        }
+    }
+}
+
+/// Summary of a workspace revision
+#[derive(Debug, Clone, Serialize)]
+pub struct RevisionInfo {
+    /// Revision number
+    pub revision: u64,
+
+    /// When this revision was saved
+    pub saved_at: DateTime<Utc>,
+
+    /// Optional message describing the revision
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// Response for the revision list endpoint
+#[derive(Debug, Serialize)]
+pub struct RevisionListResponse {
+    /// Workspace name
+    pub name: String,
+
+    /// Revisions, oldest first
+    pub revisions: Vec<RevisionInfo>,
+}
+
+/// Structural diff between two revisions of a workspace
+#[derive(Debug, Serialize)]
+pub struct RevisionDiff {
+    /// Older revision number
+    pub from: u64,
+
+    /// Newer revision number
+    pub to: u64,
+
+    /// Changes going from `from` to `to`
+    #[serde(flatten)]
+    pub diff: GraphDiff,
+}
+
+/// A deleted workspace waiting in the trash
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashEntry {
+    /// Identifier used to restore or purge the entry
+    pub trash_id: String,
+
+    /// Original workspace name
+    pub name: String,
+
+    /// When the workspace was deleted
+    pub deleted_at: DateTime<Utc>,
+}
+
+/// Response for the trash list endpoint
+#[derive(Debug, Serialize)]
+pub struct TrashListResponse {
+    /// Deleted workspaces, most recent first
+    pub entries: Vec<TrashEntry>,
+}
+
+/// Response for the trash purge endpoints
+#[derive(Debug, Serialize)]
+pub struct PurgeResponse {
+    /// Number of entries permanently deleted
+    pub purged: usize,
+}
+
+/// Request to rename or duplicate a workspace
+#[derive(Debug, Deserialize)]
+pub struct WorkspaceNameRequest {
+    /// Name for the renamed or duplicated workspace
+    pub new_name: String,
+}
+
+/// Response body for a save rejected by optimistic locking
+#[derive(Debug, Serialize)]
+pub struct SaveConflictResponse {
+    /// Error message
+    pub error: String,
+
+    /// Current revision of the workspace
+    pub current_revision: u64,
+
+    /// Current version of the workspace, if it has any revision
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current: Option<WorkspaceRevision>,
+}
+
+/// Response for the workspace load endpoint
+#[derive(Debug, Serialize)]
+pub struct LoadWorkspaceResponse {
+    /// Current revision of the workspace, to send back as `expected_revision`
+    pub revision: u64,
+
+    /// Loaded workspace data, upgraded to the current node versions
+    #[serde(flatten)]
+    pub data: WorkspaceData,
+
+    /// Node migrations applied while loading
+    pub migration: MigrationReport,
+
+    /// Validation of the workspace against the current node registry
+    pub validation: ValidationReport,
+}
+
+/// Filters and sort order for the workspace list endpoint
+#[derive(Debug, Default, Deserialize)]
+pub struct WorkspaceQuery {
+    /// Only workspaces carrying this tag
+    #[serde(default)]
+    pub tag: Option<String>,
+
+    /// Only workspaces using this node type
+    #[serde(default)]
+    pub node_type: Option<String>,
+
+    /// Only workspaces whose description contains this text (case-insensitive)
+    #[serde(default)]
+    pub q: Option<String>,
+
+    /// Sort order of the results
+    #[serde(default)]
+    pub sort: WorkspaceSort,
+}
+
+impl WorkspaceQuery {
+    /// Whether a workspace summary passes every filter
+    fn matches(&self, summary: &WorkspaceSummary) -> bool {
+        if let Some(tag) = &self.tag
+            && !summary.tags.contains(tag)
+        {
+            return false;
+        }
+
+        if let Some(node_type) = &self.node_type
+            && !summary.node_types.contains(node_type)
+        {
+            return false;
+        }
+
+        if let Some(text) = &self.q {
+            let text = text.to_lowercase();
+            let description = summary.description.as_deref().unwrap_or_default();
+            if !description.to_lowercase().contains(&text) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// Sort order for workspace listings
+#[derive(Debug, Default, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkspaceSort {
+    /// Alphabetical by name
+    #[default]
+    Name,
+
+    /// Most recently modified first
+    Modified,
+}
+
+/// Summary of a workspace returned by the list endpoint
+#[derive(Debug, Serialize)]
+pub struct WorkspaceSummary {
+    /// Workspace name
+    pub name: String,
+
+    /// Description of the workspace
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// Workspace tags
+    pub tags: Vec<String>,
+
+    /// User currently responsible for the workspace
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+
+    /// When the workspace was last saved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modified_at: Option<DateTime<Utc>>,
+
+    /// Number of nodes in the graph
+    pub node_count: usize,
+
+    /// Node types used by the graph, sorted
+    pub node_types: Vec<String>,
+
+    /// Outcome of the latest execution
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_run: Option<LastRun>,
+}
+
+impl WorkspaceSummary {
+    /// Summarize a workspace
+    fn new(name: String, data: &WorkspaceData, modified_at: Option<DateTime<Utc>>) -> Self {
+        let metadata = data.metadata.clone().unwrap_or_default();
+
+        let node_types: BTreeSet<String> = data
+            .graph
+            .nodes
+            .iter()
+            .map(|n| n.node_type_id.clone())
+            .collect();
+
+        Self {
+            name,
+            description: metadata.description,
+            tags: metadata.tags,
+            owner: metadata.owner,
+            modified_at,
+            node_count: data.graph.nodes.len(),
+            node_types: node_types.into_iter().collect(),
+            last_run: metadata.last_run,
+        }
+    }
 }
 
 /// Response for workspace list endpoint
 #[derive(Debug, Serialize)]
 pub struct WorkspaceListResponse {
-    /// List of workspace names
-    pub workspaces: Vec<String>,
+    /// Summaries of the matching workspaces
+    pub workspaces: Vec<WorkspaceSummary>,
+}
+
+/// Normalize a workspace name before it reaches the storage backend
+fn workspace_name(name: &str) -> String {
+    sanitize_filename(name)
 }
 
 /// Sanitize a filename by removing/replacing invalid characters
-fn sanitize_filename(name: &str) -> String {
+pub fn sanitize_filename(name: &str) -> String {
     name.chars()
         .map(|c| {
             if c.is_alphanumeric() || c == '-' || c == '_' {
@@ -229,6 +943,7 @@ fn sanitize_filename(name: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::fs;
     use tempfile::TempDir;
 
     #[tokio::test]
@@ -237,18 +952,25 @@ mod tests {
         let manager = WorkspaceManager::new(temp_dir.path());
 
         let data = WorkspaceData {
-            graph: serde_json::json!({"nodes": [], "connections": []}),
+            schema_version: CURRENT_SCHEMA_VERSION,
+            graph: GraphDefinition::default(),
+            node_versions: BTreeMap::new(),
+            parameters: Vec::new(),
             metadata: Some(WorkspaceMetadata {
                 created_at: Some("2024-01-01".to_string()),
                 modified_at: None,
                 created_by: Some("test_user".to_string()),
                 description: Some("Test workspace".to_string()),
+                ..Default::default()
             }),
         };
 
         let request = SaveWorkspaceRequest {
             name: "test_workspace".to_string(),
             data: data.clone(),
+            message: None,
+            expected_revision: None,
+            must_exist: false,
         };
 
         // Save
@@ -274,13 +996,19 @@ mod tests {
 
         // Save a workspace
         let data = WorkspaceData {
-            graph: serde_json::json!({"nodes": []}),
+            schema_version: CURRENT_SCHEMA_VERSION,
+            graph: GraphDefinition::default(),
+            node_versions: BTreeMap::new(),
+            parameters: Vec::new(),
             metadata: None,
         };
 
         let request = SaveWorkspaceRequest {
             name: "workspace1".to_string(),
             data,
+            message: None,
+            expected_revision: None,
+            must_exist: false,
         };
 
         manager.save_workspace(request).await.unwrap();
@@ -291,6 +1019,334 @@ mod tests {
         assert!(list.contains(&"workspace1".to_string()));
     }
 
+    fn graph_data(target: &str) -> WorkspaceData {
+        let graph = serde_json::from_value(serde_json::json!({
+            "nodes": [{
+                "instance_id": "browser",
+                "node_type_id": "type1",
+                "input_values": {"target_directory": target}
+            }],
+            "connections": []
+        }))
+        .unwrap();
+
+        WorkspaceData {
+            schema_version: CURRENT_SCHEMA_VERSION,
+            graph,
+            node_versions: BTreeMap::new(),
+            parameters: Vec::new(),
+            metadata: None,
+        }
+    }
+
+    fn target_directory(data: &WorkspaceData) -> &serde_json::Value {
+        &data.graph.nodes[0].input_values["target_directory"]
+    }
+
+    async fn save(manager: &WorkspaceManager, target: &str, message: &str) -> RevisionInfo {
+        manager
+            .save_workspace(SaveWorkspaceRequest {
+                name: "versioned".to_string(),
+                data: graph_data(target),
+                message: Some(message.to_string()),
+                expected_revision: None,
+                must_exist: false,
+            })
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_save_keeps_revisions() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = WorkspaceManager::new(temp_dir.path());
+
+        assert_eq!(save(&manager, "./a", "first").await.revision, 1);
+        assert_eq!(save(&manager, "./b", "second").await.revision, 2);
+
+        let revisions = manager.list_revisions("versioned").await.unwrap();
+        assert_eq!(revisions.len(), 2);
+        assert_eq!(revisions[0].message.as_deref(), Some("first"));
+
+        let first = manager.load_revision("versioned", 1).await.unwrap();
+        assert_eq!(target_directory(&first.data), "./a");
+
+        // Revisions are not listed as workspaces
+        let list = manager.list_workspaces().await.unwrap();
+        assert_eq!(list, vec!["versioned".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_restore_revision() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = WorkspaceManager::new(temp_dir.path());
+
+        save(&manager, "./a", "first").await;
+        save(&manager, "./b", "second").await;
+
+        let restored = manager.restore_revision("versioned", 1).await.unwrap();
+        assert_eq!(restored.revision, 3);
+
+        let current = manager.load_workspace("versioned").await.unwrap();
+        assert_eq!(target_directory(&current), "./a");
+    }
+
+    #[tokio::test]
+    async fn test_diff_revisions() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = WorkspaceManager::new(temp_dir.path());
+
+        save(&manager, "./a", "first").await;
+        save(&manager, "./b", "second").await;
+
+        let diff = manager.diff_revisions("versioned", 1, 2).await.unwrap();
+        assert_eq!(diff.diff.nodes_changed.len(), 1);
+        assert!(diff.diff.nodes_added.is_empty());
+
+        assert!(manager.diff_revisions("versioned", 1, 9).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_delete_restore_and_purge() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = WorkspaceManager::new(temp_dir.path());
+
+        save(&manager, "./a", "first").await;
+        save(&manager, "./b", "second").await;
+
+        let entry = manager.delete_workspace("versioned").await.unwrap();
+        assert!(manager.list_workspaces().await.unwrap().is_empty());
+        assert_eq!(manager.list_trash().await.unwrap().len(), 1);
+
+        let restored = manager.restore_from_trash(&entry.trash_id).await.unwrap();
+        assert_eq!(restored.name, "versioned");
+        assert!(manager.load_workspace("versioned").await.is_ok());
+        assert_eq!(manager.list_revisions("versioned").await.unwrap().len(), 2);
+        assert!(manager.list_trash().await.unwrap().is_empty());
+
+        manager.delete_workspace("versioned").await.unwrap();
+        assert_eq!(manager.purge_trash(None).await.unwrap(), 1);
+        assert!(manager.list_trash().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rename_and_duplicate() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = WorkspaceManager::new(temp_dir.path());
+
+        save(&manager, "./a", "first").await;
+        save(&manager, "./b", "second").await;
+
+        manager.rename_workspace("versioned", "renamed").await.unwrap();
+        assert!(manager.load_workspace("versioned").await.is_err());
+        assert_eq!(manager.list_revisions("renamed").await.unwrap().len(), 2);
+
+        let copy = manager.duplicate_workspace("renamed", "copy").await.unwrap();
+        assert_eq!(copy.revision, 1);
+
+        let list = manager.list_workspaces().await.unwrap();
+        assert_eq!(list, vec!["copy".to_string(), "renamed".to_string()]);
+
+        // Names already in use are rejected
+        assert!(manager.rename_workspace("copy", "renamed").await.is_err());
+        assert!(manager.duplicate_workspace("copy", "renamed").await.is_err());
+
+        // Concurrent duplicates to the same name: only one gets it
+        let (first, second) = tokio::join!(
+            manager.duplicate_workspace("renamed", "twin"),
+            manager.duplicate_workspace("copy", "twin")
+        );
+        assert!(first.is_ok() != second.is_ok());
+        assert_eq!(manager.list_revisions("twin").await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_schema_version() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = WorkspaceManager::new(temp_dir.path());
+
+        // Files written before the schema field existed load as version 1
+        fs::write(
+            temp_dir.path().join("legacy.json"),
+            r#"{"graph": {"nodes": [], "connections": []}}"#,
+        )
+        .unwrap();
+        let legacy = manager.load_workspace("legacy").await.unwrap();
+        assert_eq!(legacy.schema_version, 1);
+
+        // Untyped graphs are rejected on load
+        fs::write(temp_dir.path().join("broken.json"), r#"{"graph": {"nodes": 3}}"#).unwrap();
+        assert!(manager.load_workspace("broken").await.is_err());
+
+        // Newer schemas are rejected on save
+        let mut data = graph_data("./a");
+        data.schema_version = CURRENT_SCHEMA_VERSION + 1;
+        let result = manager
+            .save_workspace(SaveWorkspaceRequest {
+                name: "future".to_string(),
+                data,
+                message: None,
+                expected_revision: None,
+                must_exist: false,
+            })
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_search_workspaces() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = WorkspaceManager::new(temp_dir.path());
+
+        for (name, tag, description) in [
+            ("alpha", "images", "Resize holiday photos"),
+            ("beta", "backup", "Nightly backup of documents"),
+        ] {
+            let mut data = graph_data("./a");
+            data.metadata = Some(WorkspaceMetadata {
+                description: Some(description.to_string()),
+                tags: vec![tag.to_string()],
+                ..Default::default()
+            });
+
+            manager
+                .save_workspace(SaveWorkspaceRequest {
+                    name: name.to_string(),
+                    data,
+                    message: None,
+                    expected_revision: None,
+                    must_exist: false,
+                })
+                .await
+                .unwrap();
+        }
+
+        let names = |summaries: Vec<WorkspaceSummary>| -> Vec<String> {
+            summaries.into_iter().map(|s| s.name).collect()
+        };
+
+        let all = manager
+            .search_workspaces(&WorkspaceQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(all[0].node_types, vec!["type1".to_string()]);
+        assert_eq!(names(all), vec!["alpha", "beta"]);
+
+        let by_tag = WorkspaceQuery {
+            tag: Some("backup".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(names(manager.search_workspaces(&by_tag).await.unwrap()), vec!["beta"]);
+
+        let by_text = WorkspaceQuery {
+            q: Some("PHOTOS".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(names(manager.search_workspaces(&by_text).await.unwrap()), vec!["alpha"]);
+
+        let by_node_type = WorkspaceQuery {
+            node_type: Some("other".to_string()),
+            ..Default::default()
+        };
+        assert!(manager.search_workspaces(&by_node_type).await.unwrap().is_empty());
+
+        // Recording a run updates the metadata without adding a revision
+        manager
+            .record_last_run(
+                "alpha",
+                LastRun {
+                    execution_id: "exec-1".to_string(),
+                    status: ExecutionStatus::Success,
+                    finished_at: Utc::now(),
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(manager.list_revisions("alpha").await.unwrap().len(), 1);
+
+        let all = manager
+            .search_workspaces(&WorkspaceQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(all[0].last_run.as_ref().unwrap().execution_id, "exec-1");
+        assert_eq!(all[0].tags, vec!["images".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_expected_revision() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = WorkspaceManager::new(temp_dir.path());
+
+        let save_at = |expected: u64, target: &str| SaveWorkspaceRequest {
+            name: "locked".to_string(),
+            data: graph_data(target),
+            message: None,
+            expected_revision: Some(expected),
+            must_exist: false,
+        };
+
+        // Revision 0 means the workspace must not exist yet
+        manager.save_workspace(save_at(0, "./a")).await.unwrap();
+        let (revision, _) = manager.load_current("locked").await.unwrap();
+        assert_eq!(revision, 1);
+
+        manager.save_workspace(save_at(1, "./b")).await.unwrap();
+
+        // A second client still holding revision 1 is rejected
+        let result = manager.save_workspace(save_at(1, "./c")).await;
+        assert!(matches!(result, Err(AppError::Conflict(_))));
+
+        let (revision, current) = manager.load_current("locked").await.unwrap();
+        assert_eq!(revision, 2);
+        assert_eq!(target_directory(&current), "./b");
+    }
+
+    #[tokio::test]
+    async fn test_must_exist() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = WorkspaceManager::new(temp_dir.path());
+
+        let save = |must_exist: bool| SaveWorkspaceRequest {
+            name: "existing".to_string(),
+            data: graph_data("./a"),
+            message: None,
+            expected_revision: None,
+            must_exist,
+        };
+
+        let result = manager.save_workspace(save(true)).await;
+        assert!(matches!(result, Err(AppError::Conflict(_))));
+        assert!(!manager.workspace_exists("existing").await.unwrap());
+
+        manager.save_workspace(save(false)).await.unwrap();
+        manager.save_workspace(save(true)).await.unwrap();
+        assert_eq!(manager.current_revision("existing").await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_names_are_normalized() {
+        let temp_dir = TempDir::new().unwrap();
+        let sqlite = crate::storage::SqliteStorage::open_in_memory().unwrap();
+
+        for manager in [
+            WorkspaceManager::new(temp_dir.path()),
+            WorkspaceManager::with_storage(Arc::new(sqlite)),
+        ] {
+            let request = SaveWorkspaceRequest {
+                name: "my demo".to_string(),
+                data: graph_data("./a"),
+                message: None,
+                expected_revision: None,
+                must_exist: false,
+            };
+            manager.save_workspace(request).await.unwrap();
+
+            assert_eq!(manager.list_workspaces().await.unwrap(), vec!["my_demo"]);
+            assert!(manager.workspace_exists("my/demo").await.unwrap());
+            assert_eq!(manager.current_revision("my_demo").await.unwrap(), 1);
+        }
+    }
+
     #[test]
     fn test_sanitize_filename() {
         assert_eq!(sanitize_filename("hello world"), "hello_world");
@@ -0,0 +1,90 @@
+//! Payload size and encode/decode time of `/run` bodies in each wire format.
+//!
+//! Run with `cargo bench -p ndnm-libs --bench wire`. The payload sizes are
+//! printed before the timings:
+//!
+//! ```text
+//! payload                 json     msgpack        cbor
+//! text                   20920       18915       18915
+//! image_256k            349580      262178      262178
+//! tensor_64k_f32        349582      262179      262179
+//! ```
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use ndnm_libs::wire::{bytes_value, WireFormat};
+use serde_json::{json, Value};
+
+const FORMATS: [WireFormat; 3] = [WireFormat::Json, WireFormat::MessagePack, WireFormat::Cbor];
+
+/// `/run` request bodies, from text only to mostly binary
+fn payloads() -> Vec<(&'static str, Value)> {
+    let text: String = (0..2_000).map(|i| format!("line {}\n", i)).collect();
+    let image: Vec<u8> = (0..256 * 1024)
+        .map(|i: u32| (i.wrapping_mul(2_654_435_761) >> 24) as u8)
+        .collect();
+    let tensor: Vec<u8> = (0..64 * 1024)
+        .flat_map(|i| (i as f32 * 0.001).to_le_bytes())
+        .collect();
+
+    vec![
+        ("text", json!({ "inputs": { "copy_input_0": text } })),
+        (
+            "image_256k",
+            json!({ "inputs": { "image": bytes_value(&image), "caption": "noise" } }),
+        ),
+        (
+            "tensor_64k_f32",
+            json!({ "inputs": { "weights": bytes_value(&tensor), "shape": [256, 256] } }),
+        ),
+    ]
+}
+
+fn print_sizes(payloads: &[(&str, Value)]) {
+    println!(
+        "{:<16} {:>11} {:>11} {:>11}",
+        "payload", "json", "msgpack", "cbor"
+    );
+    for (name, payload) in payloads {
+        let sizes: Vec<usize> = FORMATS
+            .iter()
+            .map(|format| format.encode(payload).unwrap().len())
+            .collect();
+        println!(
+            "{:<16} {:>11} {:>11} {:>11}",
+            name, sizes[0], sizes[1], sizes[2]
+        );
+    }
+}
+
+fn bench_wire(c: &mut Criterion) {
+    let payloads = payloads();
+    print_sizes(&payloads);
+
+    for (name, payload) in &payloads {
+        let mut encode = c.benchmark_group(format!("encode/{}", name));
+        for format in FORMATS {
+            encode.throughput(Throughput::Bytes(
+                format.encode(payload).unwrap().len() as u64
+            ));
+            encode.bench_with_input(
+                BenchmarkId::from_parameter(format),
+                payload,
+                |b, payload| b.iter(|| format.encode(black_box(payload)).unwrap()),
+            );
+        }
+        encode.finish();
+
+        let mut decode = c.benchmark_group(format!("decode/{}", name));
+        for format in FORMATS {
+            let body = format.encode(payload).unwrap();
+            decode.throughput(Throughput::Bytes(body.len() as u64));
+            decode.bench_with_input(BenchmarkId::from_parameter(format), &body, |b, body| {
+                b.iter(|| format.decode::<Value>(black_box(body)).unwrap())
+            });
+        }
+        decode.finish();
+    }
+}
+
+criterion_group!(benches, bench_wire);
+criterion_main!(benches);
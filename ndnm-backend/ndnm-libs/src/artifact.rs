@@ -0,0 +1,329 @@
+//! Artifact references and the client nodes use to exchange them.
+//!
+//! Large node outputs (images, tensors, file contents) do not travel through
+//! the orchestrator as JSON. A node stores them in Hermes' artifact store
+//! and outputs a handle instead:
+//!
+//! ```json
+//! { "$ref": "sha256:9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08" }
+//! ```
+//!
+//! Hermes passes handles to downstream nodes unchanged; they fetch the
+//! content when they need it. Artifacts are addressed by the SHA-256 of
+//! their content, so storing the same bytes twice yields the same handle.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! let artifacts = ArtifactClient::from_env()?;
+//!
+//! // Producer: store a large output and return its handle
+//! let handle = artifacts.put(png_bytes).await?;
+//! outputs.insert("image".to_string(), handle.to_value());
+//!
+//! // Consumer: fetch the content behind an input handle
+//! if let Some(handle) = ArtifactRef::from_value(&inputs["image"]) {
+//!     let bytes = artifacts.fetch(&handle).await?;
+//! }
+//! ```
+
+use crate::error::AppError;
+use crate::signing::{self, Signer};
+use axum::body::Bytes;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Key of an artifact handle object
+pub const REF_KEY: &str = "$ref";
+
+/// Prefix of the hash in an artifact handle
+const REF_PREFIX: &str = "sha256:";
+
+/// Default URL of Hermes, used when `HERMES_URL` is not set
+const DEFAULT_HERMES_URL: &str = "http://localhost:3000";
+
+/// Handle to an artifact in Hermes' store (`{"$ref": "sha256:<hex>"}`)
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "RawRef", into = "RawRef")]
+pub struct ArtifactRef {
+    sha256: String,
+}
+
+/// Wire format of [`ArtifactRef`]
+#[derive(Serialize, Deserialize)]
+struct RawRef {
+    #[serde(rename = "$ref")]
+    reference: String,
+}
+
+impl TryFrom<RawRef> for ArtifactRef {
+    type Error = AppError;
+
+    fn try_from(raw: RawRef) -> Result<Self, AppError> {
+        Self::parse(&raw.reference)
+    }
+}
+
+impl From<ArtifactRef> for RawRef {
+    fn from(handle: ArtifactRef) -> Self {
+        Self {
+            reference: handle.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ArtifactRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", REF_PREFIX, self.sha256)
+    }
+}
+
+impl ArtifactRef {
+    /// Parse a hash, with or without the `sha256:` prefix
+    ///
+    /// # Returns
+    ///
+    /// * `Err(AppError::BadRequest)` - Not 64 lower-case hex digits
+    pub fn parse(reference: &str) -> Result<Self, AppError> {
+        let hex = reference.strip_prefix(REF_PREFIX).unwrap_or(reference);
+        let valid = hex.len() == 64 && hex.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'));
+
+        if !valid {
+            return Err(AppError::BadRequest(format!(
+                "Invalid artifact reference '{}'",
+                reference
+            )));
+        }
+
+        Ok(Self {
+            sha256: hex.to_string(),
+        })
+    }
+
+    /// Hex SHA-256 of the content
+    pub fn sha256(&self) -> &str {
+        &self.sha256
+    }
+
+    /// The handle, if `value` is one
+    pub fn from_value(value: &Value) -> Option<Self> {
+        let reference = value.as_object()?.get(REF_KEY)?.as_str()?;
+        Self::parse(reference).ok()
+    }
+
+    /// The handle as a JSON value
+    pub fn to_value(&self) -> Value {
+        serde_json::json!({ REF_KEY: self.to_string() })
+    }
+
+    /// Every handle inside a JSON value, at any depth
+    pub fn find_all(value: &Value) -> Vec<Self> {
+        let mut found = Vec::new();
+        collect(value, &mut found);
+        found
+    }
+}
+
+/// Push the handles inside `value` to `found`
+fn collect(value: &Value, found: &mut Vec<ArtifactRef>) {
+    if let Some(handle) = ArtifactRef::from_value(value) {
+        found.push(handle);
+        return;
+    }
+
+    match value {
+        Value::Array(items) => items.iter().for_each(|item| collect(item, found)),
+        Value::Object(object) => object.values().for_each(|item| collect(item, found)),
+        _ => {}
+    }
+}
+
+/// Client for Hermes' artifact endpoints
+///
+/// Requests are signed when a service secret is configured, like Hermes'
+/// calls to the nodes.
+#[derive(Clone)]
+pub struct ArtifactClient {
+    http: reqwest::Client,
+    base_url: String,
+    signer: Option<Signer>,
+}
+
+impl ArtifactClient {
+    /// Create a client for the Hermes at `base_url`
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            signer: None,
+        }
+    }
+
+    /// Create a client from `HERMES_URL` (default `http://localhost:3000`)
+    /// and `NDNM_SERVICE_SECRET`
+    pub fn from_env() -> Result<Self, AppError> {
+        let base_url =
+            std::env::var("HERMES_URL").unwrap_or_else(|_| DEFAULT_HERMES_URL.to_string());
+        Ok(Self::new(base_url).with_signer(Signer::from_env()?))
+    }
+
+    /// Sign requests with the given signer
+    pub fn with_signer(mut self, signer: Option<Signer>) -> Self {
+        self.signer = signer;
+        self
+    }
+
+    /// Send a request, turning transport errors and non-success statuses
+    /// into errors
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response, AppError> {
+        let response = signing::send(request, self.signer.as_ref())
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to reach Hermes: {}", e)))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(AppError::Internal(format!(
+                "Hermes artifact store returned {}: {}",
+                status, body
+            )));
+        }
+
+        Ok(response)
+    }
+
+    /// Store content and return its handle
+    pub async fn put(&self, content: impl Into<Bytes>) -> Result<ArtifactRef, AppError> {
+        let url = format!("{}/artifacts", self.base_url);
+        let response = self.send(self.http.put(url).body(content.into())).await?;
+
+        response
+            .json()
+            .await
+            .map_err(|e| AppError::Internal(format!("Invalid artifact store response: {}", e)))
+    }
+
+    /// Fetch the content behind a handle
+    pub async fn fetch(&self, handle: &ArtifactRef) -> Result<Bytes, AppError> {
+        let url = format!("{}/artifacts/{}", self.base_url, handle.sha256());
+        let response = self.send(self.http.get(url)).await?;
+
+        response
+            .bytes()
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to read artifact {}: {}", handle, e)))
+    }
+
+    /// Replace the handles among a node's inputs with their content
+    ///
+    /// Handles are resolved as input values and as the values of object
+    /// inputs (e.g. the `content` of `{filename, content}`). UTF-8 content
+    /// becomes a string, anything else a bytes value (see `wire`).
+    pub async fn resolve_inputs(
+        &self,
+        inputs: &mut HashMap<String, Value>,
+    ) -> Result<(), AppError> {
+        for value in inputs.values_mut() {
+            if ArtifactRef::from_value(value).is_none()
+                && let Value::Object(fields) = value
+            {
+                for field in fields.values_mut() {
+                    self.resolve_value(field).await?;
+                }
+            } else {
+                self.resolve_value(value).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Replace a handle with its content
+    async fn resolve_value(&self, value: &mut Value) -> Result<(), AppError> {
+        let Some(handle) = ArtifactRef::from_value(value) else {
+            return Ok(());
+        };
+
+        let content = self.fetch(&handle).await?;
+        *value = match String::from_utf8(content.to_vec()) {
+            Ok(text) => Value::String(text),
+            Err(e) => crate::wire::bytes_value(e.as_bytes()),
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const HASH: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+    #[test]
+    fn test_ref_round_trip() {
+        let value = json!({ "$ref": format!("sha256:{}", HASH) });
+
+        let handle: ArtifactRef = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(handle.sha256(), HASH);
+        assert_eq!(serde_json::to_value(&handle).unwrap(), value);
+        assert_eq!(handle.to_value(), value);
+        assert_eq!(ArtifactRef::from_value(&value), Some(handle));
+
+        assert!(ArtifactRef::parse("sha256:../../etc").is_err());
+        assert!(serde_json::from_value::<ArtifactRef>(json!({ "$ref": "x" })).is_err());
+    }
+
+    #[test]
+    fn test_find_all() {
+        let handle = ArtifactRef::parse(HASH).unwrap();
+        let outputs = json!({
+            "image": handle.to_value(),
+            "files": [{ "filename": "a.bin", "content": handle.to_value() }],
+            "text": "plain"
+        });
+
+        assert_eq!(
+            ArtifactRef::find_all(&outputs),
+            vec![handle.clone(), handle]
+        );
+        assert!(ArtifactRef::find_all(&json!("plain")).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_inputs() {
+        use axum::{extract::Path, routing::get, Router};
+
+        let app = Router::new().route(
+            "/artifacts/:sha256",
+            get(|Path(sha256): Path<String>| async move {
+                if sha256 == HASH {
+                    b"text".to_vec()
+                } else {
+                    vec![0xff, 0x00]
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let text = ArtifactRef::parse(HASH).unwrap().to_value();
+        let binary = ArtifactRef::parse(&"0".repeat(64)).unwrap().to_value();
+        let mut inputs = HashMap::from([
+            ("a".to_string(), text),
+            ("b".to_string(), json!({ "filename": "b.bin", "content": binary })),
+            ("c".to_string(), json!("plain")),
+        ]);
+        ArtifactClient::new(url).resolve_inputs(&mut inputs).await.unwrap();
+
+        assert_eq!(inputs["a"], "text");
+        assert_eq!(
+            inputs["b"],
+            json!({ "filename": "b.bin", "content": crate::wire::bytes_value(&[0xff, 0x00]) })
+        );
+        assert_eq!(inputs["c"], "plain");
+    }
+}
@@ -20,6 +20,7 @@ use std::path::Path;
 /// node_id_hash: "hash_sha256_example"
 /// label: "Example Node"
 /// node_type: "processing"
+/// version: 2
 /// sections:
 ///   - section_name: "inputs"
 ///     section_label: "Input Files"
@@ -39,6 +40,12 @@ use std::path::Path;
 ///   - name: "setting"
 ///     label: "Configuration Setting"
 ///     type: "text"
+/// migrations:
+///   - version: 2
+///     steps:
+///       - op: "rename_field"
+///         from: "old_setting"
+///         to: "setting"
 /// ```
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NodeConfig {
@@ -53,6 +60,13 @@ pub struct NodeConfig {
     /// Functional category of the node (e.g., "filesystem", "processing", "ai")
     pub node_type: String,
 
+    /// Version of this node's configuration schema.
+    ///
+    /// Bumped whenever handles or input fields change in a way that needs
+    /// a migration. Defaults to 1.
+    #[serde(default = "default_node_version")]
+    pub version: u32,
+
     /// List of I/O sections, each with its own behavior and slot templates
     #[serde(default)]
     pub sections: Vec<Section>,
@@ -60,6 +74,55 @@ pub struct NodeConfig {
     /// Internal controls/settings for the node
     #[serde(default)]
     pub input_fields: Vec<InputFieldConfig>,
+
+    /// Declarative migrations upgrading saved workspaces to this version
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub migrations: Vec<Migration>,
+}
+
+/// Node configuration version assumed when `version` is not set
+fn default_node_version() -> u32 {
+    1
+}
+
+/// A migration upgrading workspaces from the previous node version.
+///
+/// Migrations are applied in ascending `version` order to every workspace
+/// saved with an older version of the node.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Migration {
+    /// Node version this migration upgrades to
+    pub version: u32,
+
+    /// Steps applied, in order, to every instance of the node
+    pub steps: Vec<MigrationStep>,
+}
+
+/// A single declarative migration step.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum MigrationStep {
+    /// Rename a handle base name (e.g., `copy_input` to `import_input`).
+    ///
+    /// Generated handles keep their suffix, so `copy_input_0` becomes
+    /// `import_input_0`.
+    RenameHandle { from: String, to: String },
+
+    /// Rename an input field, keeping its saved value
+    RenameField { from: String, to: String },
+
+    /// Set a value for a new input field when it is not already present
+    DefaultField {
+        field: String,
+        value: serde_json::Value,
+    },
+
+    /// Replace a specific saved value of an input field
+    MapValue {
+        field: String,
+        from: serde_json::Value,
+        to: serde_json::Value,
+    },
 }
 
 /// A section groups related I/O slots with a specific behavior.
@@ -334,6 +397,37 @@ mod tests {
         assert_eq!(parsed, SectionBehavior::AutoIncrement);
     }
 
+    #[test]
+    fn test_migration_step_serde() {
+        let yaml = r#"
+version: 2
+steps:
+  - op: "rename_handle"
+    from: "copy_input"
+    to: "import_input"
+  - op: "default_field"
+    field: "recursive"
+    value: false
+"#;
+
+        let migration: Migration = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(migration.version, 2);
+        assert_eq!(
+            migration.steps[0],
+            MigrationStep::RenameHandle {
+                from: "copy_input".to_string(),
+                to: "import_input".to_string(),
+            }
+        );
+        assert_eq!(
+            migration.steps[1],
+            MigrationStep::DefaultField {
+                field: "recursive".to_string(),
+                value: serde_json::Value::Bool(false),
+            }
+        );
+    }
+
     #[test]
     fn test_slot_type_serde() {
         let slot_type = SlotType::FileContent;
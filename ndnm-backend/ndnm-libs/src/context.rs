@@ -0,0 +1,314 @@
+//! Execution context of a node run, and cancellation.
+//!
+//! Every call to [`Node::process`](crate::Node::process) gets an
+//! [`ExecutionContext`] saying which execution and graph node it runs for,
+//! until when, and whether it has been cancelled. Long-running nodes check
+//! it between units of work:
+//!
+//! ```rust,ignore
+//! for file in files {
+//!     ctx.check()?;
+//!     copy(file)?;
+//! }
+//! ```
+//!
+//! The harness keeps the runs in flight in [`Executions`]; Hermes cancels
+//! them with `POST /cancel/:execution_id` when their execution is
+//! cancelled or interrupted. A cancel can overtake the `/run` it is meant
+//! for, so cancels of executions with no run in flight are remembered for
+//! [`CANCEL_TTL`] and runs of those executions start cancelled.
+
+use crate::error::AppError;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+/// How long a cancel is remembered for runs that have not started yet
+pub const CANCEL_TTL: Duration = Duration::from_secs(60);
+
+/// What a node run belongs to, and when it has to stop
+#[derive(Debug, Clone)]
+pub struct ExecutionContext {
+    execution_id: String,
+    instance_id: String,
+    deadline: Option<Instant>,
+    cancel_token: CancellationToken,
+}
+
+impl ExecutionContext {
+    /// A context without deadline, cancelled only through its token
+    ///
+    /// # Arguments
+    ///
+    /// * `execution_id` - Execution the run belongs to
+    /// * `instance_id` - Graph node instance being run
+    pub fn new(execution_id: impl Into<String>, instance_id: impl Into<String>) -> Self {
+        Self {
+            execution_id: execution_id.into(),
+            instance_id: instance_id.into(),
+            deadline: None,
+            cancel_token: CancellationToken::new(),
+        }
+    }
+
+    /// Stop the run at `deadline`
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Cancel the run with the given token
+    pub fn with_cancel_token(mut self, cancel_token: CancellationToken) -> Self {
+        self.cancel_token = cancel_token;
+        self
+    }
+
+    /// Execution the run belongs to
+    pub fn execution_id(&self) -> &str {
+        &self.execution_id
+    }
+
+    /// Graph node instance being run
+    pub fn instance_id(&self) -> &str {
+        &self.instance_id
+    }
+
+    /// When the run has to stop, if ever
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Token cancelling the run
+    pub fn cancel_token(&self) -> &CancellationToken {
+        &self.cancel_token
+    }
+
+    /// Whether the run was cancelled or is past its deadline
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel_token.is_cancelled() || self.deadline.is_some_and(|d| Instant::now() >= d)
+    }
+
+    /// Fail if the run has to stop
+    ///
+    /// # Returns
+    ///
+    /// * `Err(AppError::Cancelled)` - The run was cancelled or is past its
+    ///   deadline
+    pub fn check(&self) -> Result<(), AppError> {
+        if self.cancel_token.is_cancelled() {
+            return Err(AppError::Cancelled(format!(
+                "Execution '{}' was cancelled",
+                self.execution_id
+            )));
+        }
+
+        if self.deadline.is_some_and(|d| Instant::now() >= d) {
+            return Err(AppError::Cancelled(format!(
+                "Node '{}' passed its deadline",
+                self.instance_id
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Wait until the run is cancelled or reaches its deadline
+    pub async fn cancelled(&self) {
+        match self.deadline {
+            Some(deadline) => tokio::select! {
+                _ = self.cancel_token.cancelled() => {}
+                _ = tokio::time::sleep_until(deadline) => {}
+            },
+            None => self.cancel_token.cancelled().await,
+        }
+    }
+}
+
+/// Node runs in flight, by execution
+///
+/// Runs of the same execution share a token, so cancelling the execution
+/// stops all of them.
+#[derive(Debug, Clone, Default)]
+pub struct Executions {
+    running: Arc<Mutex<HashMap<String, (CancellationToken, usize)>>>,
+    /// Executions cancelled with no run in flight, and when
+    cancelled: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+impl Executions {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a run and return its context
+    ///
+    /// The run stays registered until the returned guard is dropped. It
+    /// starts cancelled if its execution was cancelled in the last
+    /// [`CANCEL_TTL`]. Runs without an execution ID are not registered and
+    /// only stop at their deadline.
+    ///
+    /// # Arguments
+    ///
+    /// * `execution_id` - Execution the run belongs to
+    /// * `instance_id` - Graph node instance being run
+    /// * `timeout` - Time the run may take, if limited
+    pub fn start(
+        &self,
+        execution_id: &str,
+        instance_id: &str,
+        timeout: Option<Duration>,
+    ) -> (ExecutionContext, RunGuard) {
+        let cancel_token = if execution_id.is_empty() {
+            CancellationToken::new()
+        } else {
+            let mut running = self.running.lock().unwrap();
+            let (token, runs) = running.entry(execution_id.to_string()).or_insert_with(|| {
+                let token = CancellationToken::new();
+                if self.was_cancelled(execution_id) {
+                    token.cancel();
+                }
+                (token, 0)
+            });
+            *runs += 1;
+            token.clone()
+        };
+
+        let mut ctx =
+            ExecutionContext::new(execution_id, instance_id).with_cancel_token(cancel_token);
+        if let Some(timeout) = timeout {
+            ctx = ctx.with_deadline(Instant::now() + timeout);
+        }
+
+        let guard = RunGuard {
+            executions: self.clone(),
+            execution_id: execution_id.to_string(),
+        };
+        (ctx, guard)
+    }
+
+    /// Cancel the runs of an execution
+    ///
+    /// If no run of the execution is in flight, the cancel is remembered
+    /// for [`CANCEL_TTL`] and applies to runs starting meanwhile.
+    ///
+    /// # Returns
+    ///
+    /// * `Err(AppError::BadRequest)` - The execution ID is empty
+    pub fn cancel(&self, execution_id: &str) -> Result<(), AppError> {
+        if execution_id.is_empty() {
+            return Err(AppError::BadRequest("Missing execution ID".to_string()));
+        }
+
+        let running = self.running.lock().unwrap();
+        match running.get(execution_id) {
+            Some((token, _)) => token.cancel(),
+            None => {
+                let mut cancelled = self.cancelled.lock().unwrap();
+                cancelled.retain(|_, at| at.elapsed() < CANCEL_TTL);
+                cancelled.insert(execution_id.to_string(), Instant::now());
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the execution was cancelled before any of its runs started
+    fn was_cancelled(&self, execution_id: &str) -> bool {
+        let mut cancelled = self.cancelled.lock().unwrap();
+        cancelled.retain(|_, at| at.elapsed() < CANCEL_TTL);
+        cancelled.contains_key(execution_id)
+    }
+
+    /// Number of runs in flight
+    pub fn count(&self) -> usize {
+        self.running
+            .lock()
+            .unwrap()
+            .values()
+            .map(|(_, runs)| runs)
+            .sum()
+    }
+}
+
+/// Unregisters a run from [`Executions`] when dropped
+#[derive(Debug)]
+pub struct RunGuard {
+    executions: Executions,
+    execution_id: String,
+}
+
+impl Drop for RunGuard {
+    fn drop(&mut self) {
+        let mut running = self.executions.running.lock().unwrap();
+        if let Some((_, runs)) = running.get_mut(&self.execution_id) {
+            *runs -= 1;
+            if *runs == 0 {
+                running.remove(&self.execution_id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cancel_stops_all_runs_of_execution() {
+        let executions = Executions::new();
+        let (first, first_guard) = executions.start("exec-1", "a", None);
+        let (second, _second_guard) = executions.start("exec-1", "b", None);
+        let (other, _other_guard) = executions.start("exec-2", "a", None);
+        assert_eq!(executions.count(), 3);
+
+        executions.cancel("exec-1").unwrap();
+        first.cancelled().await;
+        assert!(matches!(first.check(), Err(AppError::Cancelled(_))));
+        assert!(second.is_cancelled());
+        assert!(other.check().is_ok());
+
+        drop(first_guard);
+        assert_eq!(executions.count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_before_run_starts() {
+        let executions = Executions::new();
+        executions.cancel("exec-1").unwrap();
+
+        let (ctx, guard) = executions.start("exec-1", "a", None);
+        assert!(matches!(ctx.check(), Err(AppError::Cancelled(_))));
+        drop(guard);
+
+        // Later runs of the execution start cancelled too
+        let (ctx, _guard) = executions.start("exec-1", "b", None);
+        assert!(ctx.is_cancelled());
+
+        let (other, _other_guard) = executions.start("exec-2", "a", None);
+        assert!(other.check().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_runs_without_execution_are_not_registered() {
+        let executions = Executions::new();
+        let (ctx, _guard) = executions.start("", "a", None);
+        assert_eq!(executions.count(), 0);
+
+        assert!(matches!(executions.cancel(""), Err(AppError::BadRequest(_))));
+        assert!(ctx.check().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_deadline() {
+        let executions = Executions::new();
+        let (ctx, _guard) = executions.start("exec-1", "a", Some(Duration::from_millis(20)));
+        assert!(ctx.check().is_ok());
+
+        ctx.cancelled().await;
+        assert!(ctx.is_cancelled());
+        assert!(!ctx.cancel_token().is_cancelled());
+        assert!(matches!(ctx.check(), Err(AppError::Cancelled(_))));
+    }
+}
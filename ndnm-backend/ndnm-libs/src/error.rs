@@ -4,7 +4,7 @@
 //! all NDNM services and nodes.
 
 use axum::{
-    http::StatusCode,
+    http::{header, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
@@ -15,6 +15,11 @@ use thiserror::Error;
 ///
 /// This enum covers common error scenarios across all services:
 /// - Bad requests (invalid input, validation failures)
+/// - Conflicts (concurrent modifications)
+/// - Authentication and authorization failures
+/// - Rate limits
+/// - Unsupported body formats
+/// - Cancelled work
 /// - Internal errors (processing failures, system errors)
 /// - Configuration errors (invalid config files)
 /// - IO errors (file system, network)
@@ -50,6 +55,87 @@ pub enum AppError {
     #[error("Configuration error: {0}")]
     ConfigError(String),
 
+    /// Conflict - the resource was modified concurrently
+    ///
+    /// # Example
+    /// ```
+    /// use ndnm_libs::AppError;
+    /// let error = AppError::Conflict("Workspace changed since revision 3".to_string());
+    /// ```
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    /// Service unavailable - the service is shutting down or cannot accept work
+    ///
+    /// # Example
+    /// ```
+    /// use ndnm_libs::AppError;
+    /// let error = AppError::ServiceUnavailable("Hermes is shutting down".to_string());
+    /// ```
+    #[error("Service unavailable: {0}")]
+    ServiceUnavailable(String),
+
+    /// Unauthorized - missing, invalid or expired credentials
+    ///
+    /// # Example
+    /// ```
+    /// use ndnm_libs::AppError;
+    /// let error = AppError::Unauthorized("Missing bearer token".to_string());
+    /// ```
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    /// Forbidden - the caller is authenticated but lacks the required role
+    ///
+    /// # Example
+    /// ```
+    /// use ndnm_libs::AppError;
+    /// let error = AppError::Forbidden("Running graphs requires the operator role".to_string());
+    /// ```
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    /// Too many requests - the caller exceeded a rate limit
+    ///
+    /// Answered with a `Retry-After` header.
+    ///
+    /// # Example
+    /// ```
+    /// use ndnm_libs::AppError;
+    /// let error = AppError::TooManyRequests {
+    ///     message: "Run limit exceeded".to_string(),
+    ///     retry_after_secs: 6,
+    /// };
+    /// ```
+    #[error("Too many requests: {message}")]
+    TooManyRequests {
+        /// What was limited
+        message: String,
+        /// Seconds until the request would be accepted
+        retry_after_secs: u64,
+    },
+
+    /// Unsupported media type - the body is in a format the endpoint does
+    /// not accept
+    ///
+    /// # Example
+    /// ```
+    /// use ndnm_libs::AppError;
+    /// let error = AppError::UnsupportedMediaType("Expected application/json".to_string());
+    /// ```
+    #[error("Unsupported media type: {0}")]
+    UnsupportedMediaType(String),
+
+    /// Cancelled - the work was cancelled or ran past its deadline
+    ///
+    /// # Example
+    /// ```
+    /// use ndnm_libs::AppError;
+    /// let error = AppError::Cancelled("Execution 'exec-1' was cancelled".to_string());
+    /// ```
+    #[error("Cancelled: {0}")]
+    Cancelled(String),
+
     /// IO error wrapper
     ///
     /// Wraps standard IO errors for consistent error handling
@@ -74,13 +160,37 @@ pub enum AppError {
 /// Maps AppError variants to appropriate HTTP status codes:
 /// - BadRequest -> 400 Bad Request
 /// - ConfigError -> 400 Bad Request
+/// - Unauthorized -> 401 Unauthorized
+/// - Forbidden -> 403 Forbidden
+/// - Conflict -> 409 Conflict
+/// - UnsupportedMediaType -> 415 Unsupported Media Type
+/// - TooManyRequests -> 429 Too Many Requests, with `Retry-After`
+/// - Cancelled -> 499 Client Closed Request (nginx's non-standard status)
+/// - ServiceUnavailable -> 503 Service Unavailable
 /// - Internal/IoError/YamlError/JsonError -> 500 Internal Server Error
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
+        let retry_after = match &self {
+            AppError::TooManyRequests {
+                retry_after_secs, ..
+            } => Some(*retry_after_secs),
+            _ => None,
+        };
+
         let (status, error_message) = match self {
             AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
             AppError::ConfigError(msg) => (StatusCode::BAD_REQUEST, msg),
+            AppError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, msg),
+            AppError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg),
+            AppError::Conflict(msg) => (StatusCode::CONFLICT, msg),
+            AppError::UnsupportedMediaType(msg) => (StatusCode::UNSUPPORTED_MEDIA_TYPE, msg),
+            AppError::TooManyRequests { message, .. } => (StatusCode::TOO_MANY_REQUESTS, message),
+            AppError::Cancelled(msg) => (
+                StatusCode::from_u16(499).expect("499 is a valid status code"),
+                msg,
+            ),
             AppError::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
+            AppError::ServiceUnavailable(msg) => (StatusCode::SERVICE_UNAVAILABLE, msg),
             AppError::IoError(err) => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 format!("IO error: {}", err),
@@ -99,7 +209,13 @@ impl IntoResponse for AppError {
             "error": error_message,
         }));
 
-        (status, body).into_response()
+        let mut response = (status, body).into_response();
+        if let Some(secs) = retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(secs));
+        }
+        response
     }
 }
 
@@ -124,4 +240,42 @@ mod tests {
         let error = AppError::ConfigError("test error".to_string());
         assert_eq!(error.to_string(), "Configuration error: test error");
     }
+
+    #[test]
+    fn test_conflict_status() {
+        let response = AppError::Conflict("stale revision".to_string()).into_response();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+    }
+
+    #[test]
+    fn test_cancelled_status() {
+        let response = AppError::Cancelled("stopped".to_string()).into_response();
+        assert_eq!(response.status().as_u16(), 499);
+    }
+
+    #[test]
+    fn test_auth_error_statuses() {
+        let response = AppError::Unauthorized("expired".to_string()).into_response();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = AppError::Forbidden("viewer".to_string()).into_response();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[test]
+    fn test_too_many_requests_sets_retry_after() {
+        let response = AppError::TooManyRequests {
+            message: "run".to_string(),
+            retry_after_secs: 3,
+        }
+        .into_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "3");
+    }
+
+    #[test]
+    fn test_service_unavailable_status() {
+        let response = AppError::ServiceUnavailable("draining".to_string()).into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
 }
@@ -7,21 +7,42 @@
 //! ## Main Components
 //!
 //! - `Node` trait: Interface that all executable nodes must implement
+//! - `ExecutionContext`: Execution a node run belongs to, its deadline and
+//!   cancellation
 //! - `AppError`: Standardized error handling
 //! - Configuration structures for parsing node `config.yaml` files
 //! - Utility functions for config loading and validation
+//! - Graceful shutdown helpers shared by every service
+//! - HMAC request signing between Brazil, Hermes and the nodes
+//! - Handles to artifacts in Hermes' store, and the client nodes use for them
+//! - JSON, MessagePack and CBOR encodings of `/run` bodies
+//! - Progress reporting and streamed `/run` responses (NDJSON or SSE)
+//! - Byte ranges of downloads
 
+pub mod artifact;
 pub mod config;
+pub mod context;
 pub mod error;
 pub mod node;
+pub mod progress;
+pub mod range;
+pub mod shutdown;
+pub mod signing;
+pub mod wire;
 
 // Re-export main types for convenience
+pub use artifact::{ArtifactClient, ArtifactRef};
+pub use context::{ExecutionContext, Executions};
 pub use config::{
-    load_config, ConnectionCount, InputFieldConfig, InputSlotConfig, NodeConfig, OutputSlotConfig,
-    Section, SectionBehavior, SlotTemplate, SlotType,
+    load_config, ConnectionCount, InputFieldConfig, InputSlotConfig, Migration, MigrationStep,
+    NodeConfig, OutputSlotConfig, Section, SectionBehavior, SlotTemplate, SlotType,
 };
 pub use error::AppError;
 pub use node::Node;
+pub use progress::{Progress, RunEvent, StreamFormat};
+pub use shutdown::{drain_timeout_from_env, shutdown_signal};
+pub use signing::{Signer, Verifier};
+pub use wire::{Wire, WireFormat};
 
 /// Result type alias using AppError
 pub type Result<T> = std::result::Result<T, AppError>;
@@ -3,7 +3,9 @@
 //! This module defines the core `Node` trait that all executable nodes
 //! in the NDNM system must implement.
 
+use crate::context::ExecutionContext;
 use crate::error::AppError;
+use crate::progress::Progress;
 use async_trait::async_trait;
 use serde_json::Value;
 use std::collections::HashMap;
@@ -14,10 +16,14 @@ use std::collections::HashMap;
 /// 1. **Validation** (`validate`): Quick synchronous validation of inputs
 /// 2. **Processing** (`process`): Async execution of the node's main logic
 ///
+/// Long-running nodes can also implement `process_streaming` to report
+/// progress and partial outputs while they run, and should stop when their
+/// [`ExecutionContext`] is cancelled.
+///
 /// # Example Implementation
 ///
 /// ```rust,ignore
-/// use ndnm_libs::{Node, AppError};
+/// use ndnm_libs::{Node, AppError, ExecutionContext};
 /// use async_trait::async_trait;
 /// use serde_json::Value;
 /// use std::collections::HashMap;
@@ -34,7 +40,14 @@ use std::collections::HashMap;
 ///         Ok(())
 ///     }
 ///
-///     async fn process(&self, inputs: HashMap<String, Value>) -> Result<HashMap<String, Value>, AppError> {
+///     async fn process(
+///         &self,
+///         inputs: HashMap<String, Value>,
+///         ctx: &ExecutionContext,
+///     ) -> Result<HashMap<String, Value>, AppError> {
+///         // Stop if the execution was cancelled
+///         ctx.check()?;
+///
 ///         // Perform the actual processing
 ///         let mut outputs = HashMap::new();
 ///         outputs.insert("result".to_string(), Value::String("success".to_string()));
@@ -76,23 +89,53 @@ pub trait Node: Send + Sync {
     /// # Arguments
     ///
     /// * `inputs` - Map of input handle names to their values (JSON)
+    /// * `ctx` - Execution the run belongs to, its deadline and cancellation
     ///
     /// # Returns
     ///
     /// * `Ok(HashMap<String, Value>)` - Map of output handle names to their values
-    /// * `Err(AppError)` - Processing error
+    /// * `Err(AppError)` - Processing error (`AppError::Cancelled` when
+    ///   stopped by `ctx`)
     ///
     /// # Implementation Guidelines
     ///
     /// - This method can be async and take time
     /// - Access files, network, databases as needed
+    /// - Call `ctx.check()` between units of work
     /// - Return outputs matching the node's config.yaml output handles
     /// - Use descriptive error messages
     /// - Clean up resources on error
     async fn process(
         &self,
         inputs: HashMap<String, Value>,
+        ctx: &ExecutionContext,
     ) -> Result<HashMap<String, Value>, AppError>;
+
+    /// Streaming variant of `process`, reporting progress as it goes.
+    ///
+    /// Called by the harness when the caller of `/run` accepts a stream
+    /// (see `progress`). The default runs `process` without reports.
+    ///
+    /// # Arguments
+    ///
+    /// * `inputs` - Map of input handle names to their values (JSON)
+    /// * `ctx` - Execution the run belongs to, its deadline and cancellation
+    /// * `progress` - Handle to report progress and partial outputs through
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(HashMap<String, Value>)` - All outputs, including those already
+    ///   reported as partial
+    /// * `Err(AppError)` - Processing error
+    async fn process_streaming(
+        &self,
+        inputs: HashMap<String, Value>,
+        ctx: &ExecutionContext,
+        progress: &Progress,
+    ) -> Result<HashMap<String, Value>, AppError> {
+        let _ = progress;
+        self.process(inputs, ctx).await
+    }
 }
 
 #[cfg(test)]
@@ -115,7 +158,9 @@ mod tests {
         async fn process(
             &self,
             inputs: HashMap<String, Value>,
+            ctx: &ExecutionContext,
         ) -> Result<HashMap<String, Value>, AppError> {
+            ctx.check()?;
             let mut outputs = HashMap::new();
             if let Some(value) = inputs.get("test_input") {
                 outputs.insert("test_output".to_string(), value.clone());
@@ -147,7 +192,7 @@ mod tests {
         let mut inputs = HashMap::new();
         inputs.insert("test_input".to_string(), Value::String("test".to_string()));
 
-        let result = node.process(inputs).await;
+        let result = node.process(inputs, &ExecutionContext::new("exec-1", "node1")).await;
         assert!(result.is_ok());
 
         let outputs = result.unwrap();
@@ -156,4 +201,14 @@ mod tests {
             Some(&Value::String("test".to_string()))
         );
     }
+
+    #[tokio::test]
+    async fn test_process_cancelled() {
+        let node = TestNode;
+        let ctx = ExecutionContext::new("exec-1", "node1");
+        ctx.cancel_token().cancel();
+
+        let result = node.process(HashMap::new(), &ctx).await;
+        assert!(matches!(result, Err(AppError::Cancelled(_))));
+    }
 }
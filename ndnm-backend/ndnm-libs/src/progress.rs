@@ -0,0 +1,369 @@
+//! Streaming node runs: progress and partial outputs.
+//!
+//! A node reports progress while it runs through a [`Progress`] handle
+//! (see [`Node::process_streaming`](crate::Node::process_streaming)). When
+//! the caller of `/run` accepts a stream, the harness answers with one
+//! [`RunEvent`] per line (NDJSON) or per Server-Sent Event instead of a
+//! single body:
+//!
+//! ```text
+//! {"type":"progress","percent":40.0,"message":"Reading notes.txt"}
+//! {"type":"partial","outputs":{"summary":"..."}}
+//! {"type":"done","outputs":{"summary":"...","files":[...]}}
+//! ```
+//!
+//! The last event is always `done` or `error`. Streams are JSON, so bytes
+//! values travel base64-encoded (see `wire`).
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! async fn run_node(
+//!     State(node): State<MyNode>,
+//!     headers: HeaderMap,
+//!     Wire(format, req): Wire<RunRequest>,
+//! ) -> Result<Response, AppError> {
+//!     node.validate(&req.inputs)?;
+//!     let ctx = ExecutionContext::new(req.execution_id, req.instance_id);
+//!
+//!     if let Some(stream) = StreamFormat::from_accept(&headers) {
+//!         return Ok(progress::stream_run(stream, move |progress| async move {
+//!             node.process_streaming(req.inputs, &ctx, &progress).await
+//!         }));
+//!     }
+//!
+//!     let outputs = node.process(req.inputs, &ctx).await?;
+//!     Ok(Wire(format, RunResponse { outputs }).into_response())
+//! }
+//! ```
+
+use crate::error::AppError;
+use axum::{
+    body::{Body, Bytes},
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
+};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::future::Future;
+use tokio::sync::mpsc;
+
+/// Event of a streamed node run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RunEvent {
+    /// How far the run is
+    Progress {
+        /// Completion, from 0 to 100
+        percent: f32,
+        /// What the node is doing
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        message: Option<String>,
+    },
+
+    /// Outputs available before the run ends
+    Partial {
+        /// Output handle names to their values
+        outputs: HashMap<String, Value>,
+    },
+
+    /// The run succeeded (last event)
+    Done {
+        /// Output handle names to their values
+        outputs: HashMap<String, Value>,
+    },
+
+    /// The run failed (last event)
+    Error {
+        /// What went wrong
+        error: String,
+    },
+}
+
+impl RunEvent {
+    /// Name of the event type (`progress`, `partial`, `done` or `error`)
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Progress { .. } => "progress",
+            Self::Partial { .. } => "partial",
+            Self::Done { .. } => "done",
+            Self::Error { .. } => "error",
+        }
+    }
+}
+
+/// Reports the progress of a node run
+///
+/// Reports are dropped when nobody is listening (e.g. a plain `/run`).
+#[derive(Debug, Clone, Default)]
+pub struct Progress {
+    tx: Option<mpsc::UnboundedSender<RunEvent>>,
+}
+
+impl Progress {
+    /// A handle whose reports go nowhere
+    pub fn discard() -> Self {
+        Self::default()
+    }
+
+    /// A handle sending its reports to `tx`
+    pub fn new(tx: mpsc::UnboundedSender<RunEvent>) -> Self {
+        Self { tx: Some(tx) }
+    }
+
+    /// Report completion (clamped to 0-100) and, unless empty, what the
+    /// node is doing
+    pub fn report(&self, percent: f32, message: impl Into<String>) {
+        let message = message.into();
+        self.send(RunEvent::Progress {
+            percent: percent.clamp(0.0, 100.0),
+            message: (!message.is_empty()).then_some(message),
+        });
+    }
+
+    /// Report outputs available before the run ends
+    pub fn partial(&self, outputs: HashMap<String, Value>) {
+        self.send(RunEvent::Partial { outputs });
+    }
+
+    fn send(&self, event: RunEvent) {
+        if let Some(tx) = &self.tx {
+            let _ = tx.send(event);
+        }
+    }
+}
+
+/// Encoding of a stream of events
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamFormat {
+    /// One JSON event per line (`application/x-ndjson`)
+    Ndjson,
+    /// Server-Sent Events (`text/event-stream`), named by event type
+    Sse,
+}
+
+impl StreamFormat {
+    /// `Content-Type` of streams in this format
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Ndjson => "application/x-ndjson",
+            Self::Sse => "text/event-stream",
+        }
+    }
+
+    /// The format of a `Content-Type`, ignoring its parameters
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let mime = content_type.split(';').next().unwrap_or("").trim();
+
+        match mime.to_ascii_lowercase().as_str() {
+            "application/x-ndjson" | "application/ndjson" => Some(Self::Ndjson),
+            "text/event-stream" => Some(Self::Sse),
+            _ => None,
+        }
+    }
+
+    /// The stream format a response is in, if it is a stream
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        Self::from_content_type(headers.get(header::CONTENT_TYPE)?.to_str().ok()?)
+    }
+
+    /// The first stream format a request's `Accept` lists, if any
+    pub fn from_accept(headers: &HeaderMap) -> Option<Self> {
+        headers
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .find_map(Self::from_content_type)
+    }
+
+    /// Encode one event
+    pub fn encode<T: Serialize>(self, kind: &str, event: &T) -> Bytes {
+        let json = serde_json::to_string(event).unwrap_or_else(|e| {
+            serde_json::json!({ "type": "error", "error": e.to_string() }).to_string()
+        });
+
+        match self {
+            Self::Ndjson => format!("{}\n", json).into(),
+            Self::Sse => format!("event: {}\ndata: {}\n\n", kind, json).into(),
+        }
+    }
+}
+
+/// Decodes the events of a stream as its chunks arrive
+#[derive(Debug)]
+pub struct EventDecoder {
+    format: StreamFormat,
+    buf: Vec<u8>,
+}
+
+impl EventDecoder {
+    /// A decoder for a stream in `format`
+    pub fn new(format: StreamFormat) -> Self {
+        Self {
+            format,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Feed a chunk and return the events it completes
+    ///
+    /// # Returns
+    ///
+    /// * `Err(AppError::BadRequest)` - An event is not valid JSON for `T`
+    pub fn push<T: DeserializeOwned>(&mut self, chunk: &[u8]) -> Result<Vec<T>, AppError> {
+        self.buf.extend_from_slice(chunk);
+        let separator: &[u8] = match self.format {
+            StreamFormat::Ndjson => b"\n",
+            StreamFormat::Sse => b"\n\n",
+        };
+
+        let mut events = Vec::new();
+        while let Some(end) = self
+            .buf
+            .windows(separator.len())
+            .position(|window| window == separator)
+        {
+            let frame: Vec<u8> = self.buf.drain(..end + separator.len()).collect();
+            let frame = String::from_utf8_lossy(&frame[..end]);
+
+            let json = match self.format {
+                StreamFormat::Ndjson => frame.trim().to_string(),
+                StreamFormat::Sse => frame
+                    .lines()
+                    .filter_map(|line| line.trim_end_matches('\r').strip_prefix("data:"))
+                    .map(|data| data.strip_prefix(' ').unwrap_or(data))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            };
+            if json.is_empty() {
+                continue;
+            }
+
+            let event = serde_json::from_str(&json)
+                .map_err(|e| AppError::BadRequest(format!("Invalid stream event: {}", e)))?;
+            events.push(event);
+        }
+
+        Ok(events)
+    }
+}
+
+/// Run a node in the background and stream its events
+///
+/// `run` gets the [`Progress`] handle to report through; its result becomes
+/// the final `done` or `error` event.
+pub fn stream_run<F, Fut>(format: StreamFormat, run: F) -> Response
+where
+    F: FnOnce(Progress) -> Fut,
+    Fut: Future<Output = Result<HashMap<String, Value>, AppError>> + Send + 'static,
+{
+    let (tx, rx) = mpsc::unbounded_channel();
+    let running = run(Progress::new(tx.clone()));
+
+    tokio::spawn(async move {
+        let event = match running.await {
+            Ok(outputs) => RunEvent::Done { outputs },
+            Err(e) => RunEvent::Error {
+                error: e.to_string(),
+            },
+        };
+        let _ = tx.send(event);
+    });
+
+    let events = futures_util::stream::unfold(rx, move |mut rx| async move {
+        let event = rx.recv().await?;
+        Some((Ok::<_, Infallible>(format.encode(event.kind(), &event)), rx))
+    });
+
+    (
+        [
+            (header::CONTENT_TYPE, format.content_type()),
+            (header::CACHE_CONTROL, "no-cache"),
+        ],
+        Body::from_stream(events),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_decoder_handles_split_frames() {
+        for format in [StreamFormat::Ndjson, StreamFormat::Sse] {
+            let events = vec![
+                RunEvent::Progress {
+                    percent: 50.0,
+                    message: Some("half".to_string()),
+                },
+                RunEvent::Done {
+                    outputs: HashMap::from([("out".to_string(), json!("a\nb"))]),
+                },
+            ];
+            let stream: Vec<u8> = events
+                .iter()
+                .flat_map(|event| format.encode(event.kind(), event).to_vec())
+                .collect();
+
+            // Feed the stream in chunks that cut through frames
+            let mut decoder = EventDecoder::new(format);
+            let mut decoded = Vec::new();
+            for chunk in stream.chunks(7) {
+                decoded.extend(decoder.push::<RunEvent>(chunk).unwrap());
+            }
+            assert_eq!(decoded, events, "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn test_accept_negotiation() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(StreamFormat::from_accept(&headers), None);
+
+        headers.insert(
+            header::ACCEPT,
+            "application/json, text/event-stream".parse().unwrap(),
+        );
+        assert_eq!(StreamFormat::from_accept(&headers), Some(StreamFormat::Sse));
+    }
+
+    #[tokio::test]
+    async fn test_stream_run_ends_with_result() {
+        let response = stream_run(StreamFormat::Ndjson, |progress| async move {
+            progress.report(150.0, "");
+            progress.partial(HashMap::from([("a".to_string(), json!(1))]));
+            Err(AppError::Internal("disk full".to_string()))
+        });
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "application/x-ndjson"
+        );
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let events: Vec<RunEvent> = EventDecoder::new(StreamFormat::Ndjson).push(&body).unwrap();
+
+        assert_eq!(
+            events,
+            vec![
+                RunEvent::Progress {
+                    percent: 100.0,
+                    message: None
+                },
+                RunEvent::Partial {
+                    outputs: HashMap::from([("a".to_string(), json!(1))])
+                },
+                RunEvent::Error {
+                    error: "Internal error: disk full".to_string()
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,101 @@
+//! `Range` requests for downloads.
+//!
+//! Brazil's blobs and Hermes' artifacts are served with single byte range
+//! support (`206 Partial Content`). Other or malformed `Range` headers are
+//! ignored and the whole content is served.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! let requested = parse_range(headers.get(header::RANGE), size)?;
+//! let range = requested.clone().unwrap_or(0..size);
+//! ```
+
+use axum::http::HeaderValue;
+use std::ops::Range;
+
+/// The requested range starts past the end of the content
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("Range not satisfiable for content of {size} bytes")]
+pub struct RangeNotSatisfiable {
+    /// Size of the content
+    pub size: u64,
+}
+
+impl RangeNotSatisfiable {
+    /// `Content-Range` of the `416 Range Not Satisfiable` response
+    pub fn content_range(&self) -> String {
+        format!("bytes */{}", self.size)
+    }
+}
+
+/// Parse a `Range` header against the size of the content
+///
+/// # Returns
+///
+/// * `Ok(Some(range))` - Bytes to serve
+/// * `Ok(None)` - Serve the whole content
+/// * `Err(RangeNotSatisfiable)` - The range starts past the end
+pub fn parse_range(
+    header: Option<&HeaderValue>,
+    size: u64,
+) -> Result<Option<Range<u64>>, RangeNotSatisfiable> {
+    let Some(spec) = header
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.trim().strip_prefix("bytes="))
+    else {
+        return Ok(None);
+    };
+
+    if spec.contains(',') {
+        return Ok(None);
+    }
+
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return Ok(None);
+    };
+    let unsatisfiable = RangeNotSatisfiable { size };
+
+    let range = match (start.parse::<u64>(), end.parse::<u64>()) {
+        // bytes=-<suffix length>
+        _ if start.is_empty() => match end.parse::<u64>() {
+            Ok(0) => return Err(unsatisfiable),
+            Ok(suffix) => size.saturating_sub(suffix)..size,
+            Err(_) => return Ok(None),
+        },
+        (Ok(start), _) if start >= size => return Err(unsatisfiable),
+        (Ok(start), Ok(end)) if start <= end => start..size.min(end + 1),
+        (Ok(start), _) if end.is_empty() => start..size,
+        _ => return Ok(None),
+    };
+
+    if range.is_empty() {
+        return Err(unsatisfiable);
+    }
+    Ok(Some(range))
+}
+
+/// `Content-Range` of a `206 Partial Content` response
+pub fn content_range(range: &Range<u64>, size: u64) -> String {
+    format!("bytes {}-{}/{}", range.start, range.end - 1, size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_range() {
+        let range = |h: &str| parse_range(Some(&HeaderValue::from_str(h).unwrap()), 10);
+
+        assert_eq!(parse_range(None, 10).unwrap(), None);
+        assert_eq!(range("bytes=2-4").unwrap(), Some(2..5));
+        assert_eq!(range("bytes=8-").unwrap(), Some(8..10));
+        assert_eq!(range("bytes=5-100").unwrap(), Some(5..10));
+        assert_eq!(range("bytes=-3").unwrap(), Some(7..10));
+        assert_eq!(range("bytes=0-1,4-5").unwrap(), None);
+        assert_eq!(range("items=0-1").unwrap(), None);
+        assert_eq!(range("bytes=10-"), Err(RangeNotSatisfiable { size: 10 }));
+        assert_eq!(content_range(&(2..5), 10), "bytes 2-4/10");
+    }
+}
@@ -0,0 +1,65 @@
+//! Graceful shutdown helpers.
+//!
+//! Every NDNM service waits on the same signals (Ctrl+C / SIGINT and, on Unix,
+//! SIGTERM) and uses the same drain deadline, so that stopping the system
+//! behaves consistently across Hermes, Brazil and the node harnesses.
+
+use std::time::Duration;
+
+/// Default time given to in-flight work to finish after a shutdown signal
+pub const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Environment variable used to override the drain deadline (in seconds)
+pub const DRAIN_TIMEOUT_ENV: &str = "SHUTDOWN_TIMEOUT_SECS";
+
+/// Wait until the process receives a shutdown signal.
+///
+/// Resolves on Ctrl+C (SIGINT) on every platform and on SIGTERM on Unix.
+/// Intended to be passed to `axum::serve(...).with_graceful_shutdown(...)`.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// axum::serve(listener, app)
+///     .with_graceful_shutdown(ndnm_libs::shutdown_signal())
+///     .await?;
+/// ```
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if tokio::signal::ctrl_c().await.is_err() {
+            // Without a Ctrl+C handler we can only rely on SIGTERM
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+/// Read the drain deadline from `SHUTDOWN_TIMEOUT_SECS`.
+///
+/// Falls back to [`DEFAULT_DRAIN_TIMEOUT`] when the variable is missing or
+/// not a valid number of seconds.
+pub fn drain_timeout_from_env() -> Duration {
+    std::env::var(DRAIN_TIMEOUT_ENV)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_DRAIN_TIMEOUT)
+}
+
@@ -0,0 +1,694 @@
+//! Service-to-service request signing.
+//!
+//! Hermes and the nodes bind every interface, so when a shared secret is
+//! configured (`NDNM_SERVICE_SECRET`, at least 32 bytes) internal calls are
+//! signed with HMAC-SHA256 and receivers reject anything else:
+//!
+//! - Brazil signs its calls to Hermes
+//! - Hermes signs its `/run` calls to nodes
+//!
+//! Each signed request carries three headers:
+//!
+//! - `x-ndnm-timestamp`: Unix time in seconds
+//! - `x-ndnm-nonce`: random value, unique per request
+//! - `x-ndnm-signature`: hex HMAC of the method, authority (`host:port`),
+//!   path and query, timestamp, nonce and the SHA-256 of the body, one per
+//!   line
+//!
+//! Receivers accept a timestamp within [`MAX_SIGNATURE_AGE`] of their own
+//! clock and remember nonces for that long, so a captured request cannot be
+//! replayed. They also reject requests signed for another port than the
+//! one they listen on, so a request captured on its way to one node cannot
+//! be replayed to another node or to Hermes either. Receivers reached
+//! through a port mapping must therefore be called on the port they listen
+//! on.
+//!
+//! The headers are checked before the body is read, and the body is
+//! buffered up to the verifier's limit ([`DEFAULT_SIGNED_BODY_LIMIT`] unless
+//! raised with [`Verifier::with_body_limit`] for routes taking large
+//! bodies).
+//!
+//! Only requests with an in-memory body can be signed; streaming bodies are
+//! refused with [`SendError::StreamingBody`].
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! // Sender
+//! let signer = Signer::from_env()?;
+//! let response = signing::send(client.post(&url).json(&body), signer.as_ref()).await?;
+//!
+//! // Receiver
+//! if let Some(verifier) = Verifier::from_env(port)? {
+//!     router = router.route_layer(middleware::from_fn_with_state(verifier, signing::verify_request));
+//! }
+//! ```
+
+use crate::error::AppError;
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{header, uri::Authority, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Environment variable holding the shared secret
+pub const SECRET_ENV: &str = "NDNM_SERVICE_SECRET";
+
+/// Header carrying the signing time
+pub const TIMESTAMP_HEADER: &str = "x-ndnm-timestamp";
+
+/// Header carrying the request nonce
+pub const NONCE_HEADER: &str = "x-ndnm-nonce";
+
+/// Header carrying the signature
+pub const SIGNATURE_HEADER: &str = "x-ndnm-signature";
+
+/// How far a request's timestamp may be from the receiver's clock
+pub const MAX_SIGNATURE_AGE: Duration = Duration::from_secs(60);
+
+/// Largest body buffered for verification by default (matches axum's
+/// default body limit)
+pub const DEFAULT_SIGNED_BODY_LIMIT: usize = 2 * 1024 * 1024;
+
+/// Shortest accepted secret, in bytes
+const MIN_SECRET_LEN: usize = 32;
+
+/// Length of a nonce, in bytes (sent hex encoded)
+const NONCE_LEN: usize = 16;
+
+/// Port of an authority without one (services are reached over plain HTTP)
+const DEFAULT_PORT: u16 = 80;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Error sending a signed request
+#[derive(Debug, thiserror::Error)]
+pub enum SendError {
+    /// The request could not be built or sent
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+
+    /// The body is a stream, whose bytes cannot be signed
+    #[error("Cannot sign a streaming request body")]
+    StreamingBody,
+}
+
+/// Signs outgoing requests with the shared secret
+#[derive(Clone)]
+pub struct Signer {
+    secret: Arc<[u8]>,
+}
+
+impl Signer {
+    /// Create a signer from a secret
+    ///
+    /// # Returns
+    ///
+    /// * `Err(AppError::ConfigError)` - The secret is shorter than 32 bytes
+    pub fn new(secret: impl AsRef<[u8]>) -> Result<Self, AppError> {
+        let secret = secret.as_ref();
+        if secret.len() < MIN_SECRET_LEN {
+            return Err(AppError::ConfigError(format!(
+                "{} must be at least {} bytes",
+                SECRET_ENV, MIN_SECRET_LEN
+            )));
+        }
+
+        Ok(Self {
+            secret: Arc::from(secret),
+        })
+    }
+
+    /// Create a signer from `NDNM_SERVICE_SECRET`
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(None)` - No secret is configured; requests go out unsigned
+    pub fn from_env() -> Result<Option<Self>, AppError> {
+        match std::env::var(SECRET_ENV) {
+            Ok(secret) if !secret.is_empty() => Self::new(secret).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Signature headers for a request
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - Request method
+    /// * `authority` - Receiver as `host:port`
+    /// * `path_and_query` - Request path, with the query string if any
+    /// * `body` - Request body
+    pub fn headers(
+        &self,
+        method: &Method,
+        authority: &str,
+        path_and_query: &str,
+        body: &[u8],
+    ) -> HeaderMap {
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let target = Target {
+            method,
+            authority,
+            path_and_query,
+        };
+        self.headers_at(&target, body, unix_now(), &to_hex(&nonce))
+    }
+
+    fn headers_at(&self, target: &Target, body: &[u8], timestamp: u64, nonce: &str) -> HeaderMap {
+        let signature = to_hex(&self.mac(target, body, timestamp, nonce).finalize().into_bytes());
+
+        let mut headers = HeaderMap::new();
+        for (name, value) in [
+            (TIMESTAMP_HEADER, timestamp.to_string()),
+            (NONCE_HEADER, nonce.to_string()),
+            (SIGNATURE_HEADER, signature),
+        ] {
+            if let Ok(value) = HeaderValue::from_str(&value) {
+                headers.insert(name, value);
+            }
+        }
+        headers
+    }
+
+    /// Sign a built request in place
+    ///
+    /// # Returns
+    ///
+    /// * `Err(SendError::StreamingBody)` - The body is a stream
+    pub fn sign(&self, request: &mut reqwest::Request) -> Result<(), SendError> {
+        let url = request.url();
+        let authority = format!(
+            "{}:{}",
+            url.host_str().unwrap_or_default(),
+            url.port_or_known_default().unwrap_or(DEFAULT_PORT)
+        );
+        let path_and_query = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+        let body = match request.body() {
+            Some(body) => body.as_bytes().ok_or(SendError::StreamingBody)?,
+            None => &[],
+        };
+
+        let headers = self.headers(request.method(), &authority, &path_and_query, body);
+        request.headers_mut().extend(headers);
+        Ok(())
+    }
+
+    /// HMAC over the canonical form of a request
+    fn mac(&self, target: &Target, body: &[u8], timestamp: u64, nonce: &str) -> HmacSha256 {
+        let body_hash = to_hex(&Sha256::digest(body));
+        let canonical = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            target.method,
+            target.authority.to_ascii_lowercase(),
+            target.path_and_query,
+            timestamp,
+            nonce,
+            body_hash
+        );
+
+        let mut mac =
+            HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts keys of any length");
+        mac.update(canonical.as_bytes());
+        mac
+    }
+}
+
+/// Send a request, signing it when a signer is configured
+///
+/// # Arguments
+///
+/// * `request` - Request to send
+/// * `signer` - Signer, or `None` to send the request unsigned
+pub async fn send(
+    request: reqwest::RequestBuilder,
+    signer: Option<&Signer>,
+) -> Result<reqwest::Response, SendError> {
+    let Some(signer) = signer else {
+        return Ok(request.send().await?);
+    };
+
+    let (client, request) = request.build_split();
+    let mut request = request?;
+    signer.sign(&mut request)?;
+    Ok(client.execute(request).await?)
+}
+
+/// What a request is sent to, as covered by its signature
+pub struct Target<'a> {
+    /// Request method
+    pub method: &'a Method,
+
+    /// Receiver as `host:port`
+    pub authority: &'a str,
+
+    /// Request path, with the query string if any
+    pub path_and_query: &'a str,
+}
+
+/// Signature headers of a request, checked but not yet verified
+struct SignatureHeaders<'a> {
+    timestamp: u64,
+    nonce: &'a str,
+    signature: Vec<u8>,
+}
+
+/// Verifies signed requests and rejects replays
+#[derive(Clone)]
+pub struct Verifier {
+    signer: Signer,
+    /// Port the receiver listens on, which requests must be signed for
+    port: u16,
+    max_age: Duration,
+    /// Largest body buffered by [`verify_request`]
+    body_limit: usize,
+    /// Nonces seen within the signature window, with their timestamps
+    seen: Arc<Mutex<HashMap<String, u64>>>,
+}
+
+impl Verifier {
+    /// Create a verifier for requests signed by `signer`'s secret
+    ///
+    /// # Arguments
+    ///
+    /// * `signer` - Signer holding the shared secret
+    /// * `port` - Port the receiver listens on
+    pub fn new(signer: Signer, port: u16) -> Self {
+        Self {
+            signer,
+            port,
+            max_age: MAX_SIGNATURE_AGE,
+            body_limit: DEFAULT_SIGNED_BODY_LIMIT,
+            seen: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// The same verifier, buffering bodies up to `limit` bytes
+    ///
+    /// Both verifiers share their nonces, so a request accepted by one is a
+    /// replay for the other.
+    pub fn with_body_limit(&self, limit: usize) -> Self {
+        Self {
+            body_limit: limit,
+            ..self.clone()
+        }
+    }
+
+    /// Create a verifier from `NDNM_SERVICE_SECRET`
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(None)` - No secret is configured; requests are not verified
+    pub fn from_env(port: u16) -> Result<Option<Self>, AppError> {
+        Ok(Signer::from_env()?.map(|signer| Self::new(signer, port)))
+    }
+
+    /// Check a request's signature, receiver, timestamp and nonce
+    ///
+    /// # Returns
+    ///
+    /// * `Err(AppError::Unauthorized)` - Missing, invalid, stale or replayed
+    ///   signature, or a request signed for another receiver
+    pub fn verify(
+        &self,
+        target: &Target,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<(), AppError> {
+        let signature = self.check_headers(headers)?;
+        self.verify_signature(target, &signature, body)
+    }
+
+    /// Check the signature headers that do not depend on the body: presence,
+    /// format and timestamp window
+    fn check_headers<'a>(&self, headers: &'a HeaderMap) -> Result<SignatureHeaders<'a>, AppError> {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .ok_or_else(|| AppError::Unauthorized(format!("Missing {} header", name)))
+        };
+
+        let timestamp: u64 = header(TIMESTAMP_HEADER)?
+            .parse()
+            .map_err(|_| AppError::Unauthorized("Invalid signature timestamp".to_string()))?;
+        let nonce = header(NONCE_HEADER)?;
+        if nonce.len() != NONCE_LEN * 2 || !nonce.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(AppError::Unauthorized("Invalid signature nonce".to_string()));
+        }
+        let signature = from_hex(header(SIGNATURE_HEADER)?)
+            .ok_or_else(|| AppError::Unauthorized("Invalid signature".to_string()))?;
+
+        if unix_now().abs_diff(timestamp) > self.max_age.as_secs() {
+            return Err(AppError::Unauthorized(
+                "Signature timestamp is outside the allowed window".to_string(),
+            ));
+        }
+
+        Ok(SignatureHeaders {
+            timestamp,
+            nonce,
+            signature,
+        })
+    }
+
+    /// Check the signature over the request and record its nonce
+    ///
+    /// The authority the request was sent to is part of the signature, and
+    /// must name the port this receiver listens on.
+    fn verify_signature(
+        &self,
+        target: &Target,
+        headers: &SignatureHeaders,
+        body: &[u8],
+    ) -> Result<(), AppError> {
+        let SignatureHeaders {
+            timestamp,
+            nonce,
+            signature,
+        } = headers;
+
+        let authority: Authority = target
+            .authority
+            .parse()
+            .map_err(|_| AppError::Unauthorized("Invalid request authority".to_string()))?;
+        if authority.port_u16() != Some(self.port) {
+            return Err(AppError::Unauthorized(
+                "Request was signed for another receiver".to_string(),
+            ));
+        }
+
+        self.signer
+            .mac(target, body, *timestamp, nonce)
+            .verify_slice(signature)
+            .map_err(|_| AppError::Unauthorized("Invalid signature".to_string()))?;
+
+        // Nonces are only recorded for genuine requests, and forgotten once
+        // their timestamp leaves the window
+        let mut seen = self.seen.lock().unwrap();
+        let now = unix_now();
+        let max_age = self.max_age.as_secs();
+        seen.retain(|_, &mut seen_at| now.abs_diff(seen_at) <= max_age);
+
+        if seen.insert(nonce.to_string(), *timestamp).is_some() {
+            return Err(AppError::Unauthorized("Replayed request".to_string()));
+        }
+
+        Ok(())
+    }
+}
+
+/// Middleware rejecting requests without a valid signature
+///
+/// Unsigned and stale requests are rejected before their body is read, as
+/// are bodies declaring a `Content-Length` above the verifier's limit
+/// (`413 Payload Too Large`); longer bodies are cut off at the limit.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let app = Router::new()
+///     .route("/run", post(run_node))
+///     .route_layer(middleware::from_fn_with_state(verifier, signing::verify_request));
+/// ```
+pub async fn verify_request(
+    State(verifier): State<Verifier>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let (parts, body) = request.into_parts();
+    let signature = verifier.check_headers(&parts.headers)?;
+
+    let declared_len = parts
+        .headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok()?.parse::<usize>().ok());
+    if declared_len.is_some_and(|len| len > verifier.body_limit) {
+        let message = format!("Request body exceeds {} bytes", verifier.body_limit);
+        return Ok((StatusCode::PAYLOAD_TOO_LARGE, message).into_response());
+    }
+
+    let body = axum::body::to_bytes(body, verifier.body_limit)
+        .await
+        .map_err(|e| AppError::BadRequest(format!("Failed to read request body: {}", e)))?;
+
+    // HTTP/2 requests carry the authority in the URI, HTTP/1.1 in `Host`
+    let authority = parts
+        .uri
+        .authority()
+        .map(|a| a.as_str())
+        .or_else(|| parts.headers.get(header::HOST)?.to_str().ok())
+        .ok_or_else(|| AppError::Unauthorized("Missing host header".to_string()))?
+        .parse::<Authority>()
+        .map_err(|_| AppError::Unauthorized("Invalid host header".to_string()))?;
+    let authority = format!(
+        "{}:{}",
+        authority.host(),
+        authority.port_u16().unwrap_or(DEFAULT_PORT)
+    );
+    let target = Target {
+        method: &parts.method,
+        authority: &authority,
+        path_and_query: parts
+            .uri
+            .path_and_query()
+            .map(|p| p.as_str())
+            .unwrap_or_else(|| parts.uri.path()),
+    };
+    verifier.verify_signature(&target, &signature, &body)?;
+
+    Ok(next.run(Request::from_parts(parts, Body::from(body))).await)
+}
+
+/// Current Unix time in seconds
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Lowercase hex encoding
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{:02x}", byte);
+        hex
+    })
+}
+
+/// Decode lowercase or uppercase hex
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware, routing::post, Router};
+
+    const SECRET: &str = "0123456789abcdef0123456789abcdef";
+
+    /// Port the verifiers under test listen on
+    const PORT: u16 = 3001;
+
+    fn target<'a>(method: &'a Method, path_and_query: &'a str) -> Target<'a> {
+        Target {
+            method,
+            authority: "127.0.0.1:3001",
+            path_and_query,
+        }
+    }
+
+    /// Serve a signature-checking echo of the request body on a free port
+    async fn serve(signer: &Signer, body_limit: usize) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let verifier = Verifier::new(signer.clone(), addr.port()).with_body_limit(body_limit);
+        let app = Router::new()
+            .route("/run", post(|body: String| async move { body }))
+            .route_layer(middleware::from_fn_with_state(verifier, verify_request));
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}", addr)
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let signer = Signer::new(SECRET).unwrap();
+        let verifier = Verifier::new(signer.clone(), PORT);
+        let body = br#"{"inputs":{}}"#;
+        let run = target(&Method::POST, "/run");
+
+        let headers = signer.headers(&Method::POST, run.authority, "/run", body);
+        assert!(verifier.verify(&run, &headers, body).is_ok());
+
+        // The same request cannot be replayed
+        assert!(matches!(
+            verifier.verify(&run, &headers, body),
+            Err(AppError::Unauthorized(_))
+        ));
+
+        // Anything covered by the signature must match
+        let headers = signer.headers(&Method::POST, run.authority, "/run", body);
+        assert!(verifier.verify(&run, &headers, b"{}").is_err());
+        assert!(verifier
+            .verify(&target(&Method::POST, "/run?x=1"), &headers, body)
+            .is_err());
+        assert!(verifier
+            .verify(&target(&Method::PUT, "/run"), &headers, body)
+            .is_err());
+        let other_host = Target {
+            authority: "10.0.0.2:3001",
+            ..target(&Method::POST, "/run")
+        };
+        assert!(verifier.verify(&other_host, &headers, body).is_err());
+
+        // Other secrets and unsigned requests are rejected
+        let other = Signer::new("another secret that is long enough").unwrap();
+        let headers = other.headers(&Method::POST, run.authority, "/run", body);
+        assert!(verifier.verify(&run, &headers, body).is_err());
+        assert!(verifier.verify(&run, &HeaderMap::new(), body).is_err());
+
+        assert!(Signer::new("short").is_err());
+    }
+
+    #[test]
+    fn test_other_receivers_reject_the_request() {
+        let signer = Signer::new(SECRET).unwrap();
+        let node = Verifier::new(signer.clone(), PORT);
+        let hermes = Verifier::new(signer.clone(), 3000);
+        let run = target(&Method::POST, "/run");
+
+        let headers = signer.headers(&Method::POST, run.authority, "/run", b"");
+        assert!(matches!(
+            hermes.verify(&run, &headers, b""),
+            Err(AppError::Unauthorized(_))
+        ));
+        assert!(node.verify(&run, &headers, b"").is_ok());
+    }
+
+    #[test]
+    fn test_stale_timestamp_is_rejected() {
+        let signer = Signer::new(SECRET).unwrap();
+        let verifier = Verifier::new(signer.clone(), PORT);
+        let list = target(&Method::GET, "/nexus/list");
+
+        let stale = unix_now() - MAX_SIGNATURE_AGE.as_secs() - 5;
+        let nonce = "0".repeat(NONCE_LEN * 2);
+        let headers = signer.headers_at(&list, b"", stale, &nonce);
+        assert!(matches!(
+            verifier.verify(&list, &headers, b""),
+            Err(AppError::Unauthorized(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_signed_requests_pass_middleware() {
+        let signer = Signer::new(SECRET).unwrap();
+        let url = format!("{}/run?mode=test", serve(&signer, DEFAULT_SIGNED_BODY_LIMIT).await);
+
+        let client = reqwest::Client::new();
+
+        let unsigned = send(client.post(&url).body("hello"), None).await.unwrap();
+        assert_eq!(unsigned.status(), 401);
+
+        let signed = send(client.post(&url).body("hello"), Some(&signer))
+            .await
+            .unwrap();
+        assert_eq!(signed.status(), 200);
+        assert_eq!(signed.text().await.unwrap(), "hello");
+    }
+
+    #[tokio::test]
+    async fn test_request_signed_for_another_authority_is_rejected() {
+        let signer = Signer::new(SECRET).unwrap();
+        let first = serve(&signer, DEFAULT_SIGNED_BODY_LIMIT).await;
+        let second = serve(&signer, DEFAULT_SIGNED_BODY_LIMIT).await;
+
+        // A request captured on its way to the first receiver, replayed to
+        // the second one with and without its original Host header
+        let client = reqwest::Client::new();
+        let captured = client
+            .post(format!("{}/run", first))
+            .body("hello")
+            .build()
+            .unwrap();
+        let mut signed = captured.try_clone().unwrap();
+        signer.sign(&mut signed).unwrap();
+        let headers = signed.headers().clone();
+        let first_host = first.trim_start_matches("http://").to_string();
+
+        for host in [None, Some(first_host)] {
+            let mut replay = client
+                .post(format!("{}/run", second))
+                .headers(headers.clone())
+                .body("hello");
+            if let Some(host) = host {
+                replay = replay.header(header::HOST, host);
+            }
+            assert_eq!(replay.send().await.unwrap().status(), 401);
+        }
+
+        assert_eq!(client.execute(signed).await.unwrap().status(), 200);
+    }
+
+    #[tokio::test]
+    async fn test_body_limit() {
+        let signer = Signer::new(SECRET).unwrap();
+        let url = format!("{}/run", serve(&signer, 1024).await);
+
+        let client = reqwest::Client::new();
+        let large = vec![b'x'; 2048];
+
+        // Unsigned requests are rejected before their body is considered
+        let unsigned = send(client.post(&url).body(large.clone()), None)
+            .await
+            .unwrap();
+        assert_eq!(unsigned.status(), 401);
+
+        let signed = send(client.post(&url).body(large), Some(&signer))
+            .await
+            .unwrap();
+        assert_eq!(signed.status(), 413);
+
+        let signed = send(client.post(&url).body(vec![b'x'; 1024]), Some(&signer))
+            .await
+            .unwrap();
+        assert_eq!(signed.text().await.unwrap().len(), 1024);
+    }
+
+    #[test]
+    fn test_malformed_nonce_is_rejected() {
+        let signer = Signer::new(SECRET).unwrap();
+        let verifier = Verifier::new(signer.clone(), PORT);
+        let root = target(&Method::GET, "/");
+
+        for nonce in ["abc", &"z".repeat(NONCE_LEN * 2)] {
+            let headers = signer.headers_at(&root, b"", unix_now(), nonce);
+            assert!(matches!(
+                verifier.verify(&root, &headers, b""),
+                Err(AppError::Unauthorized(_))
+            ));
+        }
+    }
+}
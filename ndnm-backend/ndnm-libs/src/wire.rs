@@ -0,0 +1,425 @@
+//! Wire formats for `/run` bodies.
+//!
+//! `/run` requests and responses are JSON by default. Hermes and the nodes
+//! can also exchange them as MessagePack or CBOR, chosen by `Content-Type`:
+//!
+//! | Format      | `Content-Type`        |
+//! |-------------|-----------------------|
+//! | JSON        | `application/json`    |
+//! | MessagePack | `application/msgpack` |
+//! | CBOR        | `application/cbor`    |
+//!
+//! A node answers in the format of the request. Nodes that only speak JSON
+//! reject the others with `415 Unsupported Media Type`, and Hermes falls
+//! back to JSON for them.
+//!
+//! Binary content is written as a bytes value:
+//!
+//! ```json
+//! { "$bytes": "iVBORw0KGgo..." }
+//! ```
+//!
+//! JSON carries it base64-encoded; MessagePack and CBOR carry the raw bytes,
+//! which saves the base64 overhead (a third of the content) and the cost of
+//! encoding it. Either way, node code sees the same `serde_json::Value`.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! async fn run_node(
+//!     State(node): State<MyNode>,
+//!     Wire(format, req): Wire<RunRequest>,
+//! ) -> Result<Wire<RunResponse>, AppError> {
+//!     let ctx = ExecutionContext::new(req.execution_id, req.instance_id);
+//!     let outputs = node.process(req.inputs, &ctx).await?;
+//!     Ok(Wire(format, RunResponse { outputs }))
+//! }
+//! ```
+
+use crate::error::AppError;
+use axum::{
+    async_trait,
+    body::Bytes,
+    extract::{FromRequest, Request},
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::de::{self, DeserializeOwned, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{SerializeMap, SerializeSeq, Serializer};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+use std::fmt;
+use std::str::FromStr;
+
+/// Key of a bytes value object
+pub const BYTES_KEY: &str = "$bytes";
+
+/// Encoding of a `/run` body
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum WireFormat {
+    /// JSON, with bytes values base64-encoded
+    #[default]
+    Json,
+    /// MessagePack, with bytes values as `bin`
+    MessagePack,
+    /// CBOR, with bytes values as byte strings
+    Cbor,
+}
+
+impl fmt::Display for WireFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Json => "json",
+            Self::MessagePack => "msgpack",
+            Self::Cbor => "cbor",
+        })
+    }
+}
+
+impl FromStr for WireFormat {
+    type Err = AppError;
+
+    /// Parse a format name (`json`, `msgpack` or `cbor`)
+    fn from_str(name: &str) -> Result<Self, AppError> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "msgpack" | "messagepack" => Ok(Self::MessagePack),
+            "cbor" => Ok(Self::Cbor),
+            _ => Err(AppError::ConfigError(format!(
+                "Unknown wire format '{}' (expected json, msgpack or cbor)",
+                name
+            ))),
+        }
+    }
+}
+
+impl WireFormat {
+    /// `Content-Type` of bodies in this format
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::MessagePack => "application/msgpack",
+            Self::Cbor => "application/cbor",
+        }
+    }
+
+    /// The format of a `Content-Type`, ignoring its parameters
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let mime = content_type.split(';').next().unwrap_or("").trim();
+
+        match mime.to_ascii_lowercase().as_str() {
+            "application/json" => Some(Self::Json),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => {
+                Some(Self::MessagePack)
+            }
+            "application/cbor" => Some(Self::Cbor),
+            _ => None,
+        }
+    }
+
+    /// The format of a body from its headers (JSON without `Content-Type`)
+    ///
+    /// # Returns
+    ///
+    /// * `Err(AppError::UnsupportedMediaType)` - Not one of the wire formats
+    pub fn from_headers(headers: &HeaderMap) -> Result<Self, AppError> {
+        let Some(content_type) = headers.get(header::CONTENT_TYPE) else {
+            return Ok(Self::Json);
+        };
+
+        content_type
+            .to_str()
+            .ok()
+            .and_then(Self::from_content_type)
+            .ok_or_else(|| {
+                AppError::UnsupportedMediaType(format!(
+                    "Unsupported Content-Type {:?} (expected application/json, \
+                     application/msgpack or application/cbor)",
+                    content_type
+                ))
+            })
+    }
+
+    /// Encode a body
+    pub fn encode<T: Serialize>(self, body: &T) -> Result<Vec<u8>, AppError> {
+        if self == Self::Json {
+            return Ok(serde_json::to_vec(body)?);
+        }
+
+        let value = serde_json::to_value(body)?;
+        let fail = |e: &dyn fmt::Display| {
+            AppError::Internal(format!("Failed to encode {} body: {}", self, e))
+        };
+
+        match self {
+            Self::MessagePack => rmp_serde::to_vec_named(&Binary(&value)).map_err(|e| fail(&e)),
+            _ => {
+                let mut buf = Vec::new();
+                ciborium::into_writer(&Binary(&value), &mut buf).map_err(|e| fail(&e))?;
+                Ok(buf)
+            }
+        }
+    }
+
+    /// Decode a body
+    ///
+    /// # Returns
+    ///
+    /// * `Err(AppError::BadRequest)` - The body is malformed or does not
+    ///   match `T`
+    pub fn decode<T: DeserializeOwned>(self, body: &[u8]) -> Result<T, AppError> {
+        let fail =
+            |e: &dyn fmt::Display| AppError::BadRequest(format!("Invalid {} body: {}", self, e));
+
+        let value = match self {
+            Self::Json => return serde_json::from_slice(body).map_err(|e| fail(&e)),
+            Self::MessagePack => rmp_serde::from_slice::<Decoded>(body).map_err(|e| fail(&e))?,
+            Self::Cbor => ciborium::from_reader::<Decoded, _>(body).map_err(|e| fail(&e))?,
+        };
+
+        serde_json::from_value(value.0).map_err(|e| fail(&e))
+    }
+}
+
+/// A bytes value (`{"$bytes": "<base64>"}`) holding `bytes`
+pub fn bytes_value(bytes: &[u8]) -> Value {
+    serde_json::json!({ BYTES_KEY: STANDARD.encode(bytes) })
+}
+
+/// The content of `value`, if it is a bytes value
+pub fn value_bytes(value: &Value) -> Option<Vec<u8>> {
+    let object = value.as_object()?;
+    if object.len() != 1 {
+        return None;
+    }
+
+    STANDARD.decode(object.get(BYTES_KEY)?.as_str()?).ok()
+}
+
+/// Serializes a JSON value with its bytes values as native bytes
+struct Binary<'a>(&'a Value);
+
+impl Serialize for Binary<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            Value::Null => serializer.serialize_unit(),
+            Value::Bool(b) => serializer.serialize_bool(*b),
+            Value::Number(n) => {
+                if let Some(n) = n.as_u64() {
+                    serializer.serialize_u64(n)
+                } else if let Some(n) = n.as_i64() {
+                    serializer.serialize_i64(n)
+                } else {
+                    serializer.serialize_f64(n.as_f64().unwrap_or(f64::NAN))
+                }
+            }
+            Value::String(s) => serializer.serialize_str(s),
+            Value::Array(items) => {
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for item in items {
+                    seq.serialize_element(&Binary(item))?;
+                }
+                seq.end()
+            }
+            Value::Object(object) => {
+                if let Some(bytes) = value_bytes(self.0) {
+                    return serializer.serialize_bytes(&bytes);
+                }
+
+                let mut map = serializer.serialize_map(Some(object.len()))?;
+                for (key, value) in object {
+                    map.serialize_entry(key, &Binary(value))?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+/// A JSON value decoded from a binary format, native bytes becoming bytes
+/// values
+struct Decoded(Value);
+
+impl<'de> Deserialize<'de> for Decoded {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(DecodedVisitor).map(Decoded)
+    }
+}
+
+struct DecodedVisitor;
+
+impl<'de> Visitor<'de> for DecodedVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a JSON-compatible value or bytes")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::Bool(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Value, E> {
+        Ok(Value::Number(v.into()))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Value, E> {
+        Ok(Value::Number(v.into()))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Value, E> {
+        Ok(Number::from_f64(v).map_or(Value::Null, Value::Number))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Value, E> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Value, E> {
+        Ok(Value::String(v))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Value, E> {
+        Ok(bytes_value(v))
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        Decoded::deserialize(deserializer).map(|d| d.0)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(Decoded(item)) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut object = Map::new();
+        while let Some((key, Decoded(value))) = map.next_entry::<String, Decoded>()? {
+            object.insert(key, value);
+        }
+        Ok(Value::Object(object))
+    }
+}
+
+/// A body in one of the wire formats
+///
+/// As an extractor, decodes the request body in the format of its
+/// `Content-Type` (JSON without one); as a response, encodes the body in
+/// the given format. Handlers answer in the format they were called with.
+#[derive(Debug, Clone)]
+pub struct Wire<T>(pub WireFormat, pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for Wire<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let format =
+            WireFormat::from_headers(req.headers()).map_err(IntoResponse::into_response)?;
+        let body = Bytes::from_request(req, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let value = format.decode(&body).map_err(IntoResponse::into_response)?;
+
+        Ok(Wire(format, value))
+    }
+}
+
+impl<T: Serialize> IntoResponse for Wire<T> {
+    fn into_response(self) -> Response {
+        let Wire(format, body) = self;
+
+        match format.encode(&body) {
+            Ok(bytes) => ([(header::CONTENT_TYPE, format.content_type())], bytes).into_response(),
+            Err(e) => e.into_response(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn payload() -> Value {
+        json!({
+            "inputs": {
+                "image": bytes_value(&[0u8, 159, 146, 150, 255].repeat(200)),
+                "caption": "a cat",
+                "nested": [{ "scale": 0.5, "count": -3, "big": u64::MAX }, null, true]
+            },
+            "target_directory": "./out"
+        })
+    }
+
+    #[test]
+    fn test_formats_round_trip() {
+        for format in [WireFormat::Json, WireFormat::MessagePack, WireFormat::Cbor] {
+            let encoded = format.encode(&payload()).unwrap();
+            let decoded: Value = format.decode(&encoded).unwrap();
+            assert_eq!(decoded, payload(), "{} round trip", format);
+        }
+    }
+
+    #[test]
+    fn test_binary_formats_carry_raw_bytes() {
+        let json = WireFormat::Json.encode(&payload()).unwrap();
+        let msgpack = WireFormat::MessagePack.encode(&payload()).unwrap();
+        let cbor = WireFormat::Cbor.encode(&payload()).unwrap();
+
+        // 1000 bytes of content take 1336 as base64
+        assert!(msgpack.len() + 300 < json.len());
+        assert!(cbor.len() + 300 < json.len());
+    }
+
+    #[test]
+    fn test_content_type_negotiation() {
+        assert_eq!(
+            WireFormat::from_content_type("application/msgpack"),
+            Some(WireFormat::MessagePack)
+        );
+        assert_eq!(
+            WireFormat::from_content_type("application/json; charset=utf-8"),
+            Some(WireFormat::Json)
+        );
+        assert_eq!(WireFormat::from_content_type("text/plain"), None);
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(
+            WireFormat::from_headers(&headers).unwrap(),
+            WireFormat::Json
+        );
+        headers.insert(header::CONTENT_TYPE, "application/cbor".parse().unwrap());
+        assert_eq!(
+            WireFormat::from_headers(&headers).unwrap(),
+            WireFormat::Cbor
+        );
+        headers.insert(header::CONTENT_TYPE, "text/plain".parse().unwrap());
+        assert!(matches!(
+            WireFormat::from_headers(&headers),
+            Err(AppError::UnsupportedMediaType(_))
+        ));
+
+        assert_eq!(
+            "MsgPack".parse::<WireFormat>().unwrap(),
+            WireFormat::MessagePack
+        );
+        assert!("xml".parse::<WireFormat>().is_err());
+    }
+}
@@ -11,22 +11,38 @@
 //! - Read existing files
 //! - Overwrite existing files
 //! - Configurable target directory
+//! - Reports progress per file when `/run` is called for a stream (NDJSON/SSE)
+//! - Binary files as bytes values (`{"$bytes": "<base64>"}`), sent raw when
+//!   Hermes calls `/run` with MessagePack or CBOR
+//! - Accepts file contents as artifact handles (`{"$ref": "sha256:..."}`),
+//!   fetched from Hermes' artifact store
+//! - Only accepts `/run` calls signed by Hermes when `NDNM_SERVICE_SECRET` is set
+//! - Stops between file operations when its execution is cancelled
+//!   (`POST /cancel/:execution_id`) or the run passes its deadline
 
 use anyhow::Result;
 use async_trait::async_trait;
 use axum::{
-    extract::State,
-    http::StatusCode,
+    extract::{Path as UrlPath, State},
+    http::{HeaderMap, StatusCode},
+    middleware,
+    response::{IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
-use ndnm_libs::{load_config, AppError, Node, NodeConfig};
+use ndnm_libs::progress::{self, Progress, StreamFormat};
+use ndnm_libs::wire::{self, Wire};
+use ndnm_libs::{
+    load_config, signing, AppError, ArtifactClient, ExecutionContext, Executions, Node,
+    NodeConfig, Verifier,
+};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
+use std::time::Duration;
 use tokio::net::TcpListener;
 use tokio::net::UdpSocket;
 use tracing::{error, info, warn};
@@ -39,6 +55,10 @@ struct FileBrowserNode {
     config: NodeConfig,
     /// Current target directory being managed
     target_directory: Arc<RwLock<PathBuf>>,
+    /// Client for Hermes' artifact store
+    artifacts: ArtifactClient,
+    /// Runs in flight, cancellable by execution
+    executions: Executions,
 }
 
 impl FileBrowserNode {
@@ -51,9 +71,17 @@ impl FileBrowserNode {
         Self {
             config,
             target_directory: Arc::new(RwLock::new(PathBuf::from("./managed_files"))),
+            artifacts: ArtifactClient::new("http://localhost:3000"),
+            executions: Executions::new(),
         }
     }
 
+    /// Use the given artifact store client
+    fn with_artifacts(mut self, artifacts: ArtifactClient) -> Self {
+        self.artifacts = artifacts;
+        self
+    }
+
     /// Set the target directory to manage
     ///
     /// # Arguments
@@ -122,13 +150,13 @@ impl FileBrowserNode {
     /// # Arguments
     ///
     /// * `filename` - Name for the new file
-    /// * `content` - File content (as bytes encoded in base64 or direct string)
+    /// * `content` - File content
     ///
     /// # Returns
     ///
     /// * `Ok(PathBuf)` - Path to the created file
     /// * `Err(AppError)` - Failed to write file
-    fn copy_file(&self, filename: &str, content: &str) -> Result<PathBuf, AppError> {
+    fn copy_file(&self, filename: &str, content: impl AsRef<[u8]>) -> Result<PathBuf, AppError> {
         let dir = self.target_directory.read().unwrap().clone();
         let file_path = dir.join(filename);
 
@@ -147,9 +175,9 @@ impl FileBrowserNode {
     ///
     /// # Returns
     ///
-    /// * `Ok(String)` - File contents
+    /// * `Ok(Value)` - File contents, as a string or, if not UTF-8, a bytes value
     /// * `Err(AppError)` - Failed to read file
-    fn read_file(&self, filename: &str) -> Result<String, AppError> {
+    fn read_file(&self, filename: &str) -> Result<Value, AppError> {
         let dir = self.target_directory.read().unwrap().clone();
         let file_path = dir.join(filename);
 
@@ -160,8 +188,13 @@ impl FileBrowserNode {
             )));
         }
 
-        fs::read_to_string(&file_path)
-            .map_err(|e| AppError::Internal(format!("Failed to read file: {}", e)))
+        let content = fs::read(&file_path)
+            .map_err(|e| AppError::Internal(format!("Failed to read file: {}", e)))?;
+
+        Ok(match String::from_utf8(content) {
+            Ok(text) => Value::String(text),
+            Err(e) => wire::bytes_value(e.as_bytes()),
+        })
     }
 
     /// Overwrite an existing file in the target directory
@@ -175,7 +208,7 @@ impl FileBrowserNode {
     ///
     /// * `Ok(())` if successful
     /// * `Err(AppError)` if file doesn't exist or write fails
-    fn overwrite_file(&self, filename: &str, content: &str) -> Result<(), AppError> {
+    fn overwrite_file(&self, filename: &str, content: impl AsRef<[u8]>) -> Result<(), AppError> {
         let dir = self.target_directory.read().unwrap().clone();
         let file_path = dir.join(filename);
 
@@ -194,6 +227,14 @@ impl FileBrowserNode {
     }
 }
 
+/// File content of an input, given as a string or a bytes value
+fn file_content(value: &Value) -> Option<Vec<u8>> {
+    match value {
+        Value::String(text) => Some(text.clone().into_bytes()),
+        _ => wire::value_bytes(value),
+    }
+}
+
 #[async_trait]
 impl Node for FileBrowserNode {
     /// Validate inputs before processing
@@ -214,10 +255,10 @@ impl Node for FileBrowserNode {
                     )));
                 }
             } else if key.starts_with("internal_input_") {
-                // Validate it's a string (file content)
-                if !value.is_string() {
+                // Validate it's a string or bytes value (file content)
+                if file_content(value).is_none() {
                     return Err(AppError::BadRequest(format!(
-                        "Input '{}' must be a string (file content)",
+                        "Input '{}' must be a string or bytes value (file content)",
                         key
                     )));
                 }
@@ -235,9 +276,31 @@ impl Node for FileBrowserNode {
     async fn process(
         &self,
         inputs: HashMap<String, Value>,
+        ctx: &ExecutionContext,
+    ) -> Result<HashMap<String, Value>, AppError> {
+        self.process_streaming(inputs, ctx, &Progress::discard()).await
+    }
+
+    /// Process node inputs, reporting progress per file
+    ///
+    /// Writes take the first half of the progress and reads the second, or
+    /// all of it when there is nothing to write. Stops before the next file
+    /// once `ctx` is cancelled; files already written stay written.
+    async fn process_streaming(
+        &self,
+        inputs: HashMap<String, Value>,
+        ctx: &ExecutionContext,
+        progress: &Progress,
     ) -> Result<HashMap<String, Value>, AppError> {
         let mut outputs = HashMap::new();
 
+        let writes = inputs
+            .keys()
+            .filter(|key| key.starts_with("copy_input_") || key.starts_with("internal_input_"))
+            .count();
+        let reads_from = if writes == 0 { 0.0 } else { 50.0 };
+        let mut written = 0;
+
         // Process copy_input_N handles (new files being added)
         for (key, value) in inputs.iter() {
             if key.starts_with("copy_input_") {
@@ -247,25 +310,37 @@ impl Node for FileBrowserNode {
                     .unwrap_or("0");
 
                 // Extract filename and content
-                let (filename, content) = if let Some(obj) = value.as_object() {
-                    let default_filename = format!("file_{}.txt", index);
+                let default_filename = format!("file_{}.txt", index);
+                let (filename, content) = if file_content(value).is_some() {
+                    (default_filename, value.clone())
+                } else if let Some(obj) = value.as_object() {
                     let filename = obj
                         .get("filename")
                         .and_then(|v| v.as_str())
                         .unwrap_or(&default_filename);
                     let content = obj
                         .get("content")
-                        .and_then(|v| v.as_str())
-                        .unwrap_or("");
-                    (filename.to_string(), content.to_string())
-                } else if let Some(content) = value.as_str() {
-                    (format!("file_{}.txt", index), content.to_string())
+                        .cloned()
+                        .unwrap_or_else(|| Value::String(String::new()));
+                    (filename.to_string(), content)
                 } else {
                     continue;
                 };
 
                 // Copy file
-                self.copy_file(&filename, &content)?;
+                let bytes = file_content(&content).ok_or_else(|| {
+                    AppError::BadRequest(format!(
+                        "Input '{}' content must be a string or bytes value",
+                        key
+                    ))
+                })?;
+                ctx.check()?;
+                self.copy_file(&filename, bytes)?;
+                written += 1;
+                progress.report(
+                    written as f32 / writes as f32 * reads_from,
+                    format!("Copied {}", filename),
+                );
 
                 // Generate corresponding output
                 let output_key = format!("copied_output_{}", index);
@@ -283,25 +358,37 @@ impl Node for FileBrowserNode {
                 // Extract filename from key
                 let filename = key.strip_prefix("internal_input_").unwrap_or("");
 
-                if let Some(content) = value.as_str() {
+                if let Some(content) = file_content(value) {
+                    ctx.check()?;
                     self.overwrite_file(filename, content)?;
+                    written += 1;
+                    progress.report(
+                        written as f32 / writes as f32 * reads_from,
+                        format!("Overwrote {}", filename),
+                    );
 
                     // Generate corresponding output (updated file content)
                     let output_key = format!("internal_output_{}", filename);
-                    outputs.insert(output_key, Value::String(content.to_string()));
+                    outputs.insert(output_key, value.clone());
                 }
             }
         }
 
         // Also add outputs for all existing files that weren't modified
         let files = self.list_files()?;
-        for filename in files {
+        let reads = files.len();
+        for (read, filename) in files.into_iter().enumerate() {
+            ctx.check()?;
+            progress.report(
+                reads_from + (read + 1) as f32 / reads as f32 * (100.0 - reads_from),
+                format!("Read {}", filename),
+            );
             let output_key = format!("internal_output_{}", filename);
 
             // Only add if not already in outputs (not overwritten)
             if !outputs.contains_key(&output_key) {
                 if let Ok(content) = self.read_file(&filename) {
-                    outputs.insert(output_key, Value::String(content));
+                    outputs.insert(output_key, content);
                 }
             }
         }
@@ -319,6 +406,15 @@ struct RunRequest {
     inputs: HashMap<String, Value>,
     /// Optional target directory override
     target_directory: Option<String>,
+    /// Execution the run belongs to, for `/cancel`
+    #[serde(default)]
+    execution_id: Option<String>,
+    /// Graph node instance being run
+    #[serde(default)]
+    instance_id: Option<String>,
+    /// Time the run may take, in milliseconds
+    #[serde(default)]
+    timeout_ms: Option<u64>,
 }
 
 /// Response body for the /run endpoint
@@ -328,6 +424,15 @@ struct RunResponse {
     outputs: HashMap<String, Value>,
 }
 
+/// Response body for the /cancel endpoint
+#[derive(Debug, Serialize)]
+struct CancelResponse {
+    /// Execution whose runs were cancelled
+    execution_id: String,
+    /// Always true; unknown executions are rejected
+    cancelled: bool,
+}
+
 /// Response body for the /list endpoint
 #[derive(Debug, Serialize)]
 struct ListResponse {
@@ -363,31 +468,86 @@ async fn list_files(
 }
 
 /// Handler for POST /run - Main node execution endpoint
+///
+/// Accepts JSON, MessagePack and CBOR bodies and answers in the same format,
+/// or with a stream of progress events when `Accept` asks for NDJSON or SSE.
 async fn run_node(
     State(node): State<FileBrowserNode>,
-    Json(req): Json<RunRequest>,
-) -> Result<Json<RunResponse>, AppError> {
+    headers: HeaderMap,
+    Wire(format, req): Wire<RunRequest>,
+) -> Result<Response, AppError> {
     // Update target directory if provided
     if let Some(dir) = req.target_directory {
         node.set_target_directory(&dir)?;
     }
 
+    // Fetch file contents passed as artifact handles
+    let mut inputs = req.inputs;
+    node.artifacts.resolve_inputs(&mut inputs).await?;
+
     // Validate inputs
-    node.validate(&req.inputs)?;
+    node.validate(&inputs)?;
+
+    // Register the run so /cancel can stop it; runs without an execution
+    // (e.g. called by hand) are not registered and only stop at their
+    // deadline
+    let execution_id = req.execution_id.unwrap_or_default();
+    let instance_id = req.instance_id.unwrap_or_default();
+    let timeout = req.timeout_ms.map(Duration::from_millis);
+    let (ctx, guard) = node.executions.start(&execution_id, &instance_id, timeout);
+
+    // Stream progress to callers that accept it
+    if let Some(stream) = StreamFormat::from_accept(&headers) {
+        return Ok(progress::stream_run(stream, move |progress| async move {
+            let _guard = guard;
+            node.process_streaming(inputs, &ctx, &progress).await
+        }));
+    }
 
     // Process
-    let outputs = node.process(req.inputs).await?;
+    let outputs = node.process(inputs, &ctx).await?;
+    drop(guard);
 
-    Ok(Json(RunResponse { outputs }))
+    Ok(Wire(format, RunResponse { outputs }).into_response())
+}
+
+/// Handler for POST /cancel/:execution_id - Stops the runs of an execution
+///
+/// Runs stop before their next file operation and answer
+/// `AppError::Cancelled`.
+async fn cancel_execution(
+    State(node): State<FileBrowserNode>,
+    UrlPath(execution_id): UrlPath<String>,
+) -> Result<Json<CancelResponse>, AppError> {
+    node.executions.cancel(&execution_id)?;
+    info!("Cancelled execution {}", execution_id);
+
+    Ok(Json(CancelResponse {
+        execution_id,
+        cancelled: true,
+    }))
 }
 
 /// Create the Axum router with all endpoints
-fn create_router(node: FileBrowserNode) -> Router {
+///
+/// With a verifier, `/run` and `/cancel` only accept requests signed by
+/// Hermes.
+fn create_router(node: FileBrowserNode, verifier: Option<Verifier>) -> Router {
+    let mut run = Router::new()
+        .route("/run", post(run_node))
+        .route("/cancel/:execution_id", post(cancel_execution));
+    if let Some(verifier) = verifier {
+        run = run.route_layer(middleware::from_fn_with_state(
+            verifier,
+            signing::verify_request,
+        ));
+    }
+
     Router::new()
         .route("/health", get(health_check))
         .route("/config", get(get_config))
         .route("/list", get(list_files))
-        .route("/run", post(run_node))
+        .merge(run)
         .with_state(node)
 }
 
@@ -423,30 +583,39 @@ async fn main() -> Result<()> {
     info!("Loaded node configuration: {}", config.label);
 
     // Create node instance
-    let node = FileBrowserNode::new(config);
+    let node = FileBrowserNode::new(config).with_artifacts(ArtifactClient::from_env()?);
 
     // Initialize default target directory
     node.set_target_directory("./managed_files")?;
 
-    // Build router
-    let app = create_router(node);
-
     // Get port from environment or use default
     let port = std::env::var("PORT")
         .ok()
         .and_then(|p| p.parse().ok())
         .unwrap_or(3001);
 
+    // Reject unsigned /run calls when a service secret is configured
+    let verifier = Verifier::from_env(port)?;
+    if verifier.is_none() {
+        warn!("NDNM_SERVICE_SECRET is not set; /run accepts unsigned requests");
+    }
+
+    // Build router
+    let app = create_router(node, verifier);
+
     let addr = format!("0.0.0.0:{}", port);
     info!("Starting node server on {}", addr);
 
     // Send startup log to Exdoida
     tokio::spawn(send_startup_log());
 
-    // Start server
+    // Start server; in-flight /run calls finish before the process exits
     let listener = TcpListener::bind(&addr).await?;
-    axum::serve(listener, app).await?;
+    axum::serve(listener, app)
+        .with_graceful_shutdown(ndnm_libs::shutdown_signal())
+        .await?;
 
+    info!("Node server stopped");
     Ok(())
 }
 
@@ -511,10 +680,125 @@ mod tests {
             json!({"filename": "test.txt", "content": "hello"}),
         );
 
-        let result = node.process(inputs).await;
+        let result = node.process(inputs, &ExecutionContext::new("test", "node")).await;
         assert!(result.is_ok());
 
         let outputs = result.unwrap();
         assert!(outputs.contains_key("copied_output_0"));
     }
+
+    #[tokio::test]
+    async fn test_process_binary_file() {
+        let config = create_test_config();
+        let node = FileBrowserNode::new(config);
+
+        let temp_dir = TempDir::new().unwrap();
+        node.set_target_directory(temp_dir.path()).unwrap();
+
+        let png = wire::bytes_value(&[0x89, b'P', b'N', b'G', 0xff, 0x00]);
+        let mut inputs = HashMap::new();
+        inputs.insert(
+            "copy_input_0".to_string(),
+            json!({"filename": "image.png", "content": png}),
+        );
+
+        let ctx = ExecutionContext::new("test", "node");
+        let outputs = node.process(inputs, &ctx).await.unwrap();
+        assert_eq!(
+            fs::read(temp_dir.path().join("image.png")).unwrap(),
+            [0x89, b'P', b'N', b'G', 0xff, 0x00]
+        );
+        assert_eq!(outputs["internal_output_image.png"], png);
+    }
+
+    #[tokio::test]
+    async fn test_process_streaming_reports_progress() {
+        let config = create_test_config();
+        let node = FileBrowserNode::new(config);
+
+        let temp_dir = TempDir::new().unwrap();
+        node.set_target_directory(temp_dir.path()).unwrap();
+        node.copy_file("existing.txt", "old").unwrap();
+
+        let mut inputs = HashMap::new();
+        inputs.insert("copy_input_0".to_string(), json!({"filename": "new.txt", "content": "hi"}));
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let ctx = ExecutionContext::new("test", "node");
+        node.process_streaming(inputs, &ctx, &Progress::new(tx)).await.unwrap();
+
+        let mut percents = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            if let ndnm_libs::RunEvent::Progress { percent, .. } = event {
+                percents.push(percent);
+            }
+        }
+        // One write, then two reads
+        assert_eq!(percents, vec![50.0, 75.0, 100.0]);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_stops_between_files() {
+        let config = create_test_config();
+        let node = FileBrowserNode::new(config);
+
+        let temp_dir = TempDir::new().unwrap();
+        node.set_target_directory(temp_dir.path()).unwrap();
+
+        let (ctx, _guard) = node.executions.start("exec-1", "browser", None);
+        let app = create_router(node.clone(), None);
+        let response = tower::ServiceExt::oneshot(
+            app,
+            axum::http::Request::post("/cancel/exec-1")
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let mut inputs = HashMap::new();
+        inputs.insert("copy_input_0".to_string(), json!({"filename": "a.txt", "content": "a"}));
+        let result = node.process(inputs, &ctx).await;
+
+        assert!(matches!(result, Err(AppError::Cancelled(_))));
+        assert!(!temp_dir.path().join("a.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_before_run() {
+        let node = FileBrowserNode::new(create_test_config());
+        let temp_dir = TempDir::new().unwrap();
+        node.set_target_directory(temp_dir.path()).unwrap();
+
+        // Hermes' cancel overtakes the /run it was meant for
+        let app = create_router(node.clone(), None);
+        let response = tower::ServiceExt::oneshot(
+            app.clone(),
+            axum::http::Request::post("/cancel/exec-1")
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = json!({
+            "execution_id": "exec-1",
+            "instance_id": "browser",
+            "inputs": { "copy_input_0": { "filename": "a.txt", "content": "a" } }
+        });
+        let response = tower::ServiceExt::oneshot(
+            app,
+            axum::http::Request::post("/run")
+                .header(axum::http::header::CONTENT_TYPE, "application/json")
+                .body(axum::body::Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+        // 499, AppError::Cancelled
+        assert_eq!(response.status().as_u16(), 499);
+        assert!(!temp_dir.path().join("a.txt").exists());
+    }
 }